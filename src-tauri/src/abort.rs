@@ -0,0 +1,58 @@
+use crate::live::{self, LiveDictation};
+use crate::local_task_handler::Task;
+use crate::notifications::{AppNotifications, Notification};
+use crate::prompts::PromptLibrary;
+use crate::session::{self, DictationSession};
+use crate::target_picker;
+use crate::transcribe_icon::{RecorderState, TranscribeIcon};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::sync::{mpsc, oneshot};
+
+/// Bumped by `hotkeys.panic`. Transcriptions and polishes compare it to the value from
+/// when they started, and drop their result if it moved.
+#[derive(Default)]
+pub struct AbortEpoch(AtomicU64);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Epoch(u64);
+
+pub fn epoch(app_handle: &AppHandle) -> Epoch {
+    Epoch(app_handle.state::<AbortEpoch>().0.load(Ordering::SeqCst))
+}
+
+pub fn aborted(app_handle: &AppHandle, epoch: Epoch) -> bool {
+    app_handle.state::<AbortEpoch>().0.load(Ordering::SeqCst) != epoch.0
+}
+
+/// The panic key: discards any recording, drops transcriptions and polishes in
+/// flight before they're delivered and puts the icon back to idle. Does nothing
+/// harmful when idle, so it can be pressed again to be sure. Text that is already
+/// being typed or pasted can't be taken back.
+pub fn abort_all(app_handle: &AppHandle) {
+    log::warn!("Aborting everything on the panic key");
+    app_handle.state::<AbortEpoch>().0.fetch_add(1, Ordering::SeqCst);
+    app_handle.state::<PromptLibrary>().take_pending();
+    target_picker::choose(app_handle, None);
+
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let (tx, rx) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::AbortAll(tx)).await.is_err() {
+            log::error!("Failed to send 'AbortAll' task to channel");
+        }
+        _ = rx.await;
+        // Their recordings are gone, this only resets their state
+        if app_handle.state::<LiveDictation>().is_active() {
+            live::stop(&app_handle, false);
+        }
+        if app_handle.state::<DictationSession>().is_active() {
+            session::toggle(&app_handle);
+        }
+        *app_handle.state::<Arc<Mutex<bool>>>().lock().unwrap() = false;
+        app_handle.state::<TranscribeIcon>().sync_to(RecorderState::Idle);
+        AppNotifications::new(&app_handle).notify(Notification::Aborted);
+    });
+}
@@ -0,0 +1,78 @@
+use crate::collect::Collector;
+use crate::settings::SettingsState;
+use crate::shutdown;
+use crate::transcribe_icon::TranscribeIcon;
+use crate::watch_folder::WatchFolder;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Remembers when the user last recorded or polished something
+pub struct Activity {
+    last: Mutex<Instant>,
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        Self {
+            last: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last.lock().unwrap().elapsed()
+    }
+}
+
+/// What keeps the app from counting as idle, if anything
+fn in_flight(app_handle: &AppHandle) -> Option<&'static str> {
+    let state = app_handle.state::<TranscribeIcon>().state();
+    if state.is_busy() {
+        Some("recording or waiting on the backend")
+    } else if state.armed {
+        Some("listening for the wake phrase")
+    } else if app_handle.state::<WatchFolder>().is_working() {
+        Some("transcribing a watched file")
+    } else if app_handle.state::<Collector>().is_active() {
+        Some("collecting transcripts")
+    } else {
+        None
+    }
+}
+
+/// Quits the app once it has been idle for `behavior.idle_quit_minutes`, through the
+/// graceful shutdown. Anything still running counts as activity, so a long recording
+/// is never cut off. The setting is read on every check so it can be changed while
+/// running.
+pub fn watch_idle(app_handle: AppHandle) {
+    spawn(async move {
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+
+            let minutes =
+                app_handle.state::<SettingsState>().get().behavior.idle_quit_minutes;
+            if minutes == 0 {
+                continue;
+            }
+
+            let activity = app_handle.state::<Activity>();
+            if let Some(what) = in_flight(&app_handle) {
+                log::trace!("Not idle, {what}");
+                activity.touch();
+                continue;
+            }
+            let idle_for = activity.idle_for();
+            if idle_for >= Duration::from_secs(minutes * 60) {
+                let reason = format!("after {} minutes idle", idle_for.as_secs() / 60);
+                shutdown::quit(&app_handle, &reason);
+                return;
+            }
+        }
+    });
+}
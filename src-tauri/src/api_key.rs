@@ -0,0 +1,101 @@
+use anyhow::{Result, anyhow, bail};
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chacha20poly1305::{
+    XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+
+pub const KEYRING_SERVICE: &str = "whistle";
+const KEYRING_USER: &str = "api-key";
+
+fn entry() -> Result<Entry> {
+    Ok(Entry::new(KEYRING_SERVICE, KEYRING_USER)?)
+}
+
+/// Reads the API key from the system keychain
+pub fn load() -> Option<String> {
+    match entry().and_then(|entry| Ok(entry.get_password()?)) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            log::debug!("No API key in keychain: {e}");
+            None
+        }
+    }
+}
+
+pub fn save(api_key: &str) -> Result<()> {
+    entry()?.set_password(api_key)?;
+    log::info!("Saved API key {} to keychain", mask(api_key));
+    Ok(())
+}
+
+pub fn clear() -> Result<()> {
+    match entry()?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(e.into()),
+    }
+    log::info!("Removed API key from keychain");
+    Ok(())
+}
+
+/// Keeps just enough of the key to tell keys apart, e.g. `sk-…a1b2`
+pub fn mask(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.chars().collect();
+    if chars.len() <= 8 {
+        return "…".into();
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+/// An API key encrypted with a key derived from a user passphrase, for settings exports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedKey {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypts with XChaCha20-Poly1305 under an Argon2id key from `passphrase`
+pub fn seal(api_key: &str, passphrase: &str) -> Result<SealedKey> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, api_key.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt the API key"))?;
+    Ok(SealedKey {
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+pub fn unseal(sealed: &SealedKey, passphrase: &str) -> Result<String> {
+    let salt = BASE64.decode(&sealed.salt)?;
+    let nonce = BASE64.decode(&sealed.nonce)?;
+    if nonce.len() != 24 {
+        bail!("The encrypted API key is damaged");
+    }
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            BASE64.decode(&sealed.ciphertext)?.as_ref(),
+        )
+        .map_err(|_| anyhow!("Wrong passphrase for the API key"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive a key from the passphrase: {e}"))?;
+    Ok(key)
+}
@@ -0,0 +1,99 @@
+use crate::audio_recorder;
+use crate::notifications::{AppNotifications, Notification};
+use crate::paths;
+use crate::settings::SettingsState;
+use anyhow::Result;
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager, async_runtime::spawn_blocking};
+
+/// Written to the data directory once the user has been told about Bluetooth
+/// microphones
+const BLUETOOTH_MARKER: &str = "bluetooth-mic-noticed";
+
+/// How a device is connected, from its CoreAudio transport type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    BuiltIn,
+    Bluetooth,
+    Other,
+}
+
+/// Transport types by device name. Asking CoreAudio takes long enough to hold up a
+/// recording, so they're asked again only when a device turns up that isn't known.
+#[derive(Default)]
+pub struct Transports(HashMap<String, Transport>);
+
+impl Transports {
+    pub fn get(&mut self, name: &str) -> Transport {
+        if !self.0.contains_key(name) {
+            match query() {
+                Ok(transports) => self.0 = transports,
+                Err(e) => log::warn!("Failed to read audio device transports: {e:#}"),
+            }
+        }
+        self.0.get(name).copied().unwrap_or(Transport::Other)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn query() -> Result<HashMap<String, Transport>> {
+    let output = std::process::Command::new("system_profiler")
+        .args(["SPAudioDataType", "-json"])
+        .output()?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let devices = json["SPAudioDataType"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|group| group["_items"].as_array().into_iter().flatten());
+    Ok(devices
+        .filter_map(|device| {
+            let name = device["_name"].as_str()?;
+            let transport = match device["coreaudio_device_transport"].as_str() {
+                Some("coreaudio_device_type_builtin") => Transport::BuiltIn,
+                Some("coreaudio_device_type_bluetooth")
+                | Some("coreaudio_device_type_bluetooth_le") => Transport::Bluetooth,
+                _ => Transport::Other,
+            };
+            Some((name.to_string(), transport))
+        })
+        .collect())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn query() -> Result<HashMap<String, Transport>> {
+    Ok(HashMap::new())
+}
+
+/// The first time a recording's input is a Bluetooth headset, explains that its
+/// microphone drops it into the low quality hands-free profile and what
+/// `audio.avoid_bluetooth_mic` does about it
+pub fn notice_bluetooth(app_handle: &AppHandle) {
+    let Ok(marker) = paths::data_file(app_handle, BLUETOOTH_MARKER) else {
+        return;
+    };
+    if marker.exists() {
+        return;
+    }
+    let app_handle = app_handle.clone();
+    let audio = app_handle.state::<SettingsState>().get().audio;
+    spawn_blocking(move || {
+        let Ok(name) = audio_recorder::device_name(&audio) else {
+            return;
+        };
+        let mut transports = Transports::default();
+        if transports.get(&name) != Transport::Bluetooth {
+            return;
+        }
+        let avoided = audio.avoid_bluetooth_mic
+            && audio_recorder::built_in_input(&mut transports).is_some();
+        log::info!("Input device {name} is a Bluetooth headset");
+        AppNotifications::new(&app_handle).notify(Notification::BluetoothMic {
+            device: name,
+            avoided,
+        });
+        if let Err(e) = std::fs::write(&marker, "") {
+            log::warn!("Failed to write {}: {e}", marker.display());
+        }
+    });
+}
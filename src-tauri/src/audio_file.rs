@@ -0,0 +1,316 @@
+use crate::activity::Activity;
+use crate::filler::{self, FillerOutcome};
+use crate::history::{EntryKind, NewEntry};
+use crate::hook::{self, HookPayload};
+use crate::notifications::{AppNotifications, Notification};
+use crate::quality;
+use crate::settings::SettingsState;
+use crate::sinks;
+use crate::snippets;
+use crate::stats;
+use crate::transcribe_client::{
+    QuotaExceeded, TranscribeClient, Transcription, Unauthorized,
+};
+use crate::transcribe_icon::{self, TranscribeIcon, Work};
+use crate::upload;
+use crate::voice_commands;
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+pub const TRANSCRIBE_FILE_ID: &str = "transcribe_file";
+const EXTENSIONS: &[&str] = &["wav", "mp3", "m4a", "aac", "flac", "ogg", "oga"];
+const MAX_FILE_BYTES: u64 = 500 * 1024 * 1024;
+
+/// Files opened before setup finished, e.g. dropped on the Dock icon of the closed app
+/// or passed as arguments
+pub struct PendingFiles(Mutex<Vec<PathBuf>>);
+
+impl PendingFiles {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self(Mutex::new(paths))
+    }
+}
+
+/// Transcribes files the system asked the app to open, from the Dock on macOS or
+/// "Open with" elsewhere. Kept until setup is done when the app is still starting.
+pub fn open(app_handle: &AppHandle, paths: Vec<PathBuf>) {
+    log::info!("Asked to open {paths:?}");
+    if app_handle.try_state::<TranscribeIcon>().is_none() {
+        app_handle.state::<PendingFiles>().0.lock().unwrap().extend(paths);
+        return;
+    }
+    transcribe(app_handle, paths);
+}
+
+/// Transcribes whatever was opened while the app was starting
+pub fn open_pending(app_handle: &AppHandle) {
+    let paths =
+        std::mem::take(&mut *app_handle.state::<PendingFiles>().0.lock().unwrap());
+    if !paths.is_empty() {
+        transcribe(app_handle, paths);
+    }
+}
+
+pub fn pick(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    app_handle
+        .dialog()
+        .file()
+        .add_filter("Audio", EXTENSIONS)
+        .pick_files(move |paths| {
+            let paths: Vec<PathBuf> = paths
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|path| path.into_path().ok())
+                .collect();
+            if !paths.is_empty() {
+                transcribe(&app_handle, paths);
+            }
+        });
+}
+
+/// Asks to transcribe a single copied audio file, anything else is explained
+pub fn offer(app_handle: &AppHandle, paths: Vec<PathBuf>) {
+    let [path] = paths.as_slice() else {
+        AppNotifications::new(app_handle)
+            .notify(Notification::ClipboardFiles { count: paths.len() });
+        return;
+    };
+    if !is_audio(path) {
+        AppNotifications::new(app_handle)
+            .notify(Notification::ClipboardFiles { count: 1 });
+        return;
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let app_handle_ = app_handle.clone();
+    app_handle
+        .dialog()
+        .message(format!("The clipboard holds {name} instead of text."))
+        .title("Transcribe this file?")
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Transcribe".into(),
+            "Cancel".into(),
+        ))
+        .show(move |confirmed| {
+            if confirmed {
+                transcribe(&app_handle_, paths);
+            }
+        });
+}
+
+/// Runs each file through the same pipeline as a recording, one after another, with a
+/// notification per file
+pub fn transcribe(app_handle: &AppHandle, paths: Vec<PathBuf>) {
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        for path in paths {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if app_handle.state::<TranscribeIcon>().is_recording() {
+                AppNotifications::new(&app_handle).notify(Notification::FileFailed {
+                    name,
+                    reason: "Finish the current recording first".into(),
+                });
+                continue;
+            }
+            log::info!("Transcribing audio file {name:?}");
+            app_handle.state::<Activity>().touch();
+            let busy = transcribe_icon::busy(&app_handle, Work::Transcribing);
+            let result = transcribe_file(&app_handle, &path).await;
+            drop(busy);
+
+            match result {
+                Ok(()) => {
+                    AppNotifications::new(&app_handle)
+                        .notify(Notification::FileTranscribed { name });
+                }
+                Err(e) => {
+                    log::error!("Failed to transcribe {name:?}: {e:#}");
+                    // These need the user to act on the account, not the file
+                    if e.is::<QuotaExceeded>() || e.is::<Unauthorized>() {
+                        crate::notify_api_error(&app_handle, &e);
+                        continue;
+                    }
+                    AppNotifications::new(&app_handle).notify(Notification::FileFailed {
+                        name,
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// What an audio file said, after filler removal
+pub struct FileTranscript {
+    pub text: String,
+    pub language: Option<String>,
+    pub audio_duration: Duration,
+}
+
+/// Whether `path` has one of the extensions that can be transcribed
+pub fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+async fn transcribe_file(app_handle: &AppHandle, path: &Path) -> Result<()> {
+    let FileTranscript {
+        text,
+        language,
+        audio_duration,
+    } = transcribe_audio(app_handle, path).await?;
+
+    stats::record(app_handle, &text);
+    crate::save_to_history(
+        app_handle,
+        NewEntry {
+            audio_duration_ms: Some(audio_duration.as_millis() as u64),
+            provider: Some(app_handle.state::<TranscribeClient>().provider()),
+            language: language.clone(),
+            ..NewEntry::new(EntryKind::Transcription, text.clone())
+        },
+    );
+
+    let settings = app_handle.state::<SettingsState>().get();
+    let text = voice_commands::apply(text, &settings.voice_commands);
+    let text = snippets::expand(text, &settings.snippets);
+    let payload = HookPayload {
+        duration_ms: Some(audio_duration.as_millis() as u64),
+        language,
+        ..HookPayload::new(EntryKind::Transcription, text.clone())
+    };
+    let report = sinks::deliver(app_handle, text, None, None, false).await;
+    hook::fire(app_handle, payload);
+    if !report.failures.is_empty() {
+        AppNotifications::new(app_handle)
+            .notify(Notification::DeliveryFailed(report.failures));
+    }
+    Ok(())
+}
+
+/// Decodes and transcribes a file, splitting it into several uploads when it's long
+pub async fn transcribe_audio(
+    app_handle: &AppHandle,
+    path: &Path,
+) -> Result<FileTranscript> {
+    if !is_audio(path) {
+        bail!("Only {} files can be transcribed", EXTENSIONS.join(", "));
+    }
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_FILE_BYTES {
+        bail!(
+            "The file is {} MB, over the {} MB limit",
+            size / 1024 / 1024,
+            MAX_FILE_BYTES / 1024 / 1024
+        );
+    }
+    let path_ = path.to_path_buf();
+    let (samples, sample_rate) =
+        tokio::task::spawn_blocking(move || decode(&path_)).await??;
+    if samples.is_empty() {
+        bail!("The file has no audio in it");
+    }
+    let audio_duration =
+        Duration::from_secs_f64(samples.len() as f64 / sample_rate as f64);
+
+    let settings = app_handle.state::<SettingsState>().get();
+    let plan = quality::choose(&settings, Some(audio_duration));
+    let model = plan.and_then(|plan| plan.model(&settings));
+    let transcribe_client = app_handle.state::<TranscribeClient>();
+    let transcription = upload::transcribe_samples(
+        &*transcribe_client,
+        &samples,
+        sample_rate,
+        model.as_deref(),
+        quality::upload(&settings, plan),
+    )
+    .await?;
+    let Transcription { text, language, .. } = transcription;
+
+    let FillerOutcome::Text(text) = filler::apply(text, &settings.filler) else {
+        bail!("No speech was found in it");
+    };
+    if text.trim().is_empty() {
+        bail!("No speech was found in it");
+    }
+    Ok(FileTranscript {
+        text,
+        language,
+        audio_duration,
+    })
+}
+
+/// Decodes any supported container and codec to 16-bit mono samples, which keeps
+/// uploads small and lets long files be split anywhere
+fn decode(path: &Path) -> Result<(Vec<i16>, u32)> {
+    let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()
+        .context("It isn't an audio format that can be read")?;
+    let mut format = probed.format;
+    let track = format.default_track().context("It has no audio track")?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .ok()
+        .context("Its audio codec isn't supported")?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet only loses a moment of audio
+            Err(SymphoniaError::DecodeError(e)) => {
+                log::warn!("Skipping an undecodable packet: {e}");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        sample_rate = Some(spec.rate);
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(buffer.samples().chunks(channels).map(|frame| {
+            (frame.iter().map(|&s| s as i32).sum::<i32>() / frame.len() as i32) as i16
+        }));
+    }
+    Ok((samples, sample_rate.context("Its sample rate is unknown")?))
+}
@@ -1,18 +1,71 @@
+use crate::audio_device::{Transport, Transports};
+use crate::dsp::Processing;
+use crate::settings::AudioSettings;
+use crate::wake_word::{Frame, FrameBuilder};
 use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
 use cpal::{
     Stream,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
 use hound::{WavSpec, WavWriter};
-use std::sync::{Arc, Mutex};
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
 use tempfile::NamedTempFile;
+use tokio::sync::oneshot;
+
+/// Audio kept in memory once a recording spills, so the level meter still has
+/// something to measure. Past that, audio spills this much at a time.
+const SPILL_KEEP: Duration = Duration::from_secs(1);
+/// Spilled audio is read back this many bytes at a time
+const SPILL_READ_CHUNK: usize = 1 << 16;
+
+/// What's known about a recording besides its audio, kept with its history entry
+#[derive(Debug, Clone)]
+pub struct RecordingInfo {
+    pub started_at: DateTime<Utc>,
+    pub duration: Option<Duration>,
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    /// Filled in once it's transcribed
+    pub language: Option<String>,
+    pub model: Option<String>,
+}
 
 pub struct AudioRecorder {
     stream: Option<Stream>,
+    /// Device, gain and input channel the open stream was built with, to tell if it
+    /// can be reused
+    stream_source: Option<(Option<String>, f32, Option<u16>)>,
+    /// Set by the stream's error callback, e.g. when the device was unplugged
+    stream_failed: Arc<AtomicBool>,
+    /// The stream may run while idle with `audio.keep_warm`, samples are only kept
+    /// while this is set
+    retaining: Arc<AtomicBool>,
+    /// Frames for the wake phrase listener, which keeps the stream open while set
+    wake_tap: Arc<Mutex<Option<mpsc::SyncSender<Frame>>>>,
+    /// Fired by the stream callback on the first buffer of a recording
+    live_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    live: Arc<AtomicBool>,
+    keep_warm: bool,
     sample_rate: Option<u32>,
     channels: Option<u16>,
     samples: Arc<Mutex<Vec<i16>>>,
+    /// Set while a recording moves its older audio to disk, shared with the callback
+    spill_sink: Arc<Mutex<Option<SpillSink>>>,
+    /// Samples moved out of `samples` into the spill file
+    spilled: Arc<AtomicUsize>,
+    spill: Option<Spill>,
+    /// `audio.spill_after_secs` as the recording started, zero keeps it in memory
+    spill_after: Duration,
+    started_at: Option<DateTime<Utc>>,
+    /// Filters for the recording's audio, taken from the settings it started with
+    processing: Processing,
+    transports: Transports,
     pub is_recording: bool,
 }
 
@@ -20,54 +73,243 @@ impl AudioRecorder {
     pub fn new() -> Self {
         Self {
             stream: None,
+            stream_source: None,
+            stream_failed: Arc::new(AtomicBool::new(false)),
+            retaining: Arc::new(AtomicBool::new(false)),
+            wake_tap: Arc::new(Mutex::new(None)),
+            live_tx: Arc::new(Mutex::new(None)),
+            live: Arc::new(AtomicBool::new(false)),
+            keep_warm: false,
             sample_rate: None,
             channels: None,
             samples: Arc::new(Mutex::new(Vec::new())),
+            spill_sink: Arc::new(Mutex::new(None)),
+            spilled: Arc::new(AtomicUsize::new(0)),
+            spill: None,
+            spill_after: Duration::ZERO,
+            started_at: None,
+            processing: Processing::default(),
+            transports: Transports::default(),
             is_recording: false,
         }
     }
 
     pub fn reset(&mut self) {
-        self.stream = None;
-        self.sample_rate = None;
-        self.channels = None;
+        self.close_stream();
+        self.end_spill();
         self.samples.lock().unwrap().clear();
         self.is_recording = false;
     }
 
-    pub fn start_recording(&mut self) -> Result<()> {
+    /// Opens or closes the idle stream to match `audio.keep_warm` and the wake phrase
+    /// listener. Does nothing while recording, the new settings apply once it stops.
+    pub fn apply_settings(&mut self, audio: &AudioSettings) {
+        self.keep_warm = audio.keep_warm;
         if self.is_recording {
-            bail!("'AudioRecorder' is already recording, skipping...");
+            return;
         }
+        if !self.stays_open() {
+            if self.stream.is_some() {
+                log::info!("Closing warm input stream");
+            }
+            self.close_stream();
+            return;
+        }
+        match self.capture_device(audio).and_then(|device| {
+            self.ensure_stream(device, audio.gain, audio.input_channel)
+        }) {
+            Ok(()) => log::info!("Keeping the input stream warm between recordings"),
+            Err(e) => log::error!("Failed to open warm input stream: {e:#}"),
+        }
+    }
 
-        let device = cpal::default_host()
-            .default_input_device()
-            .context("No input device available")?;
-        let config = device.default_input_config()?;
+    /// Hands frames to the wake phrase listener, or stops with `None`
+    pub fn set_wake_tap(
+        &mut self,
+        tap: Option<mpsc::SyncSender<Frame>>,
+        audio: &AudioSettings,
+    ) {
+        *self.wake_tap.lock().unwrap() = tap;
+        self.apply_settings(audio);
+    }
 
-        // Store audio format information
-        self.sample_rate = Some(config.sample_rate().0);
-        self.channels = Some(config.channels());
+    pub fn wake_tap(&self) -> Option<mpsc::SyncSender<Frame>> {
+        self.wake_tap.lock().unwrap().clone()
+    }
+
+    /// Whether the stream stays open between recordings
+    fn stays_open(&self) -> bool {
+        self.keep_warm || self.wake_tap.lock().unwrap().is_some()
+    }
+
+    /// Closes an idle stream before the system sleeps, so a warm stream isn't left
+    /// holding the device. A recording still running is left to its own stop.
+    pub fn before_sleep(&mut self) {
+        if self.is_recording {
+            return;
+        }
+        if self.stream.is_some() {
+            log::info!("Closing the input stream before sleep");
+        }
+        self.close_stream();
+    }
+
+    /// The stream doesn't survive system sleep. Marks it for rebuilding on the next
+    /// recording, or rebuilds it right away when kept warm, and checks that an input
+    /// device is still there.
+    pub fn after_wake(&mut self, audio: &AudioSettings) -> Result<String> {
+        self.stream_failed.store(true, Ordering::Relaxed);
+        if !self.is_recording {
+            self.apply_settings(audio);
+        }
+        let device = self.capture_device(audio)?;
+        Ok(device.name()?)
+    }
+
+    /// The returned receiver fires once audio is actually arriving, which can lag
+    /// behind this call while the device spins up
+    pub fn start_recording(
+        &mut self,
+        audio: &AudioSettings,
+    ) -> Result<oneshot::Receiver<()>> {
+        if self.is_recording {
+            bail!("'AudioRecorder' is already recording, skipping...");
+        }
+
+        self.keep_warm = audio.keep_warm;
+        let device = self.capture_device(audio)?;
+        self.ensure_stream(device, audio.gain, audio.input_channel)?;
 
         // Clear previous samples
         self.samples.lock().unwrap().clear();
+        let (live_tx, live_rx) = oneshot::channel();
+        *self.live_tx.lock().unwrap() = Some(live_tx);
+        self.live.store(false, Ordering::Relaxed);
+        self.retaining.store(true, Ordering::Relaxed);
 
-        // Create a samples buffer for the callback
-        let samples_for_callback = self.samples.clone();
-
+        self.started_at = Some(Utc::now());
+        self.processing = Processing::from_settings(audio);
+        self.spill_after = Duration::from_secs(audio.spill_after_secs);
+        self.begin_spill();
         self.is_recording = true;
         log::debug!(
             "'AudioRecorder' is recording: {} (should be true)",
             self.is_recording
         );
 
+        Ok(live_rx)
+    }
+
+    /// The device to record from. With `audio.avoid_bluetooth_mic` a Bluetooth headset
+    /// is passed over for the built-in microphone, so using its microphone doesn't
+    /// switch it to the low quality hands-free profile. Without a built-in microphone,
+    /// as on most desktops, the headset is used anyway.
+    fn capture_device(&mut self, audio: &AudioSettings) -> Result<cpal::Device> {
+        let device = input_device(audio.input_device.as_deref())?;
+        if !audio.avoid_bluetooth_mic {
+            return Ok(device);
+        }
+        let name = device.name()?;
+        if self.transports.get(&name) != Transport::Bluetooth {
+            return Ok(device);
+        }
+        match built_in_input(&mut self.transports) {
+            Some(built_in) => {
+                log::info!(
+                    "{name} is a Bluetooth headset, recording from {} instead",
+                    built_in.name().unwrap_or_default()
+                );
+                Ok(built_in)
+            }
+            None => {
+                log::info!(
+                    "{name} is a Bluetooth headset, but there's no built-in microphone"
+                );
+                Ok(device)
+            }
+        }
+    }
+
+    /// Reuses the open stream when it's healthy and was built for the same device,
+    /// gain and channel, which covers the default device changing while warm. With
+    /// `channel` only that input channel is kept, as a mono recording.
+    fn ensure_stream(
+        &mut self,
+        device: cpal::Device,
+        gain: f32,
+        channel: Option<u16>,
+    ) -> Result<()> {
+        let source = (device.name().ok(), gain, channel);
+        if self.stream.is_some()
+            && !self.stream_failed.load(Ordering::Relaxed)
+            && self.stream_source.as_ref() == Some(&source)
+        {
+            return Ok(());
+        }
+        self.close_stream();
+
+        let config = device.default_input_config()?;
+
+        // 1-based in the settings, past the device's channels records them all
+        let device_channels = config.channels();
+        let pick = match channel {
+            Some(channel) if (1..=device_channels).contains(&channel) => {
+                log::info!("Recording input channel {channel} of {device_channels}");
+                Some(channel as usize - 1)
+            }
+            Some(channel) => {
+                log::warn!(
+                    "`audio.input_channel` is {channel}, but the device has \
+                     {device_channels} channels. Recording all of them"
+                );
+                None
+            }
+            None => None,
+        };
+        let (skip, step) = pick.map_or((0, 1), |pick| (pick, device_channels as usize));
+        let mut frames = FrameBuilder::new(config.sample_rate().0);
+
+        // Store audio format information
+        self.sample_rate = Some(config.sample_rate().0);
+        self.channels = Some(if pick.is_some() { 1 } else { device_channels });
+
+        // Create a samples buffer for the callback
+        let samples_for_callback = self.samples.clone();
+        let retaining = self.retaining.clone();
+        let wake_tap = self.wake_tap.clone();
+        let live = self.live.clone();
+        let live_tx = self.live_tx.clone();
+        let stream_failed = self.stream_failed.clone();
+        stream_failed.store(false, Ordering::Relaxed);
+        let spill_sink = self.spill_sink.clone();
+        let spilled = self.spilled.clone();
+
         let stream = device.build_input_stream(
             &config.into(),
             move |data: &[f32], _| {
+                // The wake phrase listener hears one channel, recording or not
+                if let Some(tap) = wake_tap.lock().unwrap().as_ref() {
+                    let frame_step = device_channels as usize;
+                    for &sample in data.iter().skip(skip).step_by(frame_step) {
+                        if let Some(frame) = frames.push(sample * gain) {
+                            // Dropped if the listener falls behind, so frames never pile up
+                            _ = tap.try_send(frame);
+                        }
+                    }
+                }
+                if !retaining.load(Ordering::Relaxed) {
+                    return;
+                }
+                if !data.is_empty() && !live.swap(true, Ordering::Relaxed) {
+                    if let Some(tx) = live_tx.lock().unwrap().take() {
+                        _ = tx.send(());
+                    }
+                }
                 let mut samples = samples_for_callback.lock().unwrap();
-                for &sample in data {
-                    // Apply gain (increase volume) - adjust the multiplier as needed
-                    let amplified_sample = sample * 3.0;
+                // Frames are interleaved, one sample per channel
+                for &sample in data.iter().skip(skip).step_by(step) {
+                    // Apply gain (increase volume)
+                    let amplified_sample = sample * gain;
 
                     // Avoids distortion
                     let clamped_sample = amplified_sample.clamp(-1.0, 1.0);
@@ -76,18 +318,165 @@ impl AudioRecorder {
                     let sample = (clamped_sample * 32767.0) as i16;
                     samples.push(sample);
                 }
+                // Never waits on the sink, while it's being swapped the audio stays
+                // in memory and spills with the next buffer
+                if let Ok(sink) = spill_sink.try_lock() {
+                    if let Some(sink) = sink.as_ref() {
+                        sink.spill_older(&mut samples, &spilled);
+                    }
+                }
+            },
+            move |err| {
+                log::error!("An error occurred on the audio stream: {}", err);
+                stream_failed.store(true, Ordering::Relaxed);
             },
-            |err| log::error!("An error occurred on the audio stream: {}", err),
             None,
         )?;
 
         stream.play()?;
 
         self.stream = Some(stream);
+        self.stream_source = Some(source);
 
         Ok(())
     }
 
+    fn close_stream(&mut self) {
+        self.retaining.store(false, Ordering::Relaxed);
+        self.stream = None;
+        self.stream_source = None;
+        self.sample_rate = None;
+        self.channels = None;
+    }
+
+    /// Before quitting: throws away a recording still running and closes the stream,
+    /// so the device is released and no spill file is left behind
+    pub fn shut_down(&mut self) {
+        if self.is_recording {
+            self.discard();
+        }
+        self.close_stream();
+    }
+
+    /// Stops recording and throws the audio away
+    pub fn discard(&mut self) {
+        self.finish_recording();
+        self.end_spill();
+        self.samples.lock().unwrap().clear();
+    }
+
+    /// Starts moving audio older than `spill_after` to a temp file as the recording
+    /// runs. Keeps everything in memory when that's turned off or the file can't be
+    /// created.
+    fn begin_spill(&mut self) {
+        let (Some(sample_rate), Some(channels)) = (self.sample_rate, self.channels)
+        else {
+            return;
+        };
+        if self.spill_after.is_zero() {
+            return;
+        }
+        let per_second = sample_rate as f64 * channels as f64;
+        match Spill::create() {
+            Ok((spill, tx)) => {
+                self.spilled.store(0, Ordering::Relaxed);
+                *self.spill_sink.lock().unwrap() = Some(SpillSink {
+                    tx,
+                    after: (self.spill_after.as_secs_f64() * per_second) as usize,
+                    keep: (SPILL_KEEP.as_secs_f64() * per_second) as usize,
+                });
+                self.spill = Some(spill);
+            }
+            Err(e) => {
+                log::error!("Failed to create spill file, recording in memory: {e:#}")
+            }
+        }
+    }
+
+    /// Stops spilling and hands back the file once everything sent to it is written,
+    /// `None` if nothing was spilled
+    fn end_spill(&mut self) -> Option<NamedTempFile> {
+        // Dropping the sender ends the writer thread
+        self.spill_sink.lock().unwrap().take();
+        let spill = self.spill.take()?;
+        match spill.writer.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::error!("Failed to write spilled audio: {e}"),
+            Err(_) => log::error!("The spill writer panicked"),
+        }
+        let spilled = self.spilled.swap(0, Ordering::Relaxed);
+        (spilled > 0).then_some(spill.file)
+    }
+
+    /// Stops keeping samples, and drops the stream unless it should stay warm
+    fn finish_recording(&mut self) {
+        self.is_recording = false;
+        self.retaining.store(false, Ordering::Relaxed);
+        if !self.stays_open() {
+            self.stream = None;
+            self.stream_source = None;
+        }
+    }
+
+    /// Loudness of the last ~100ms of audio as RMS, from 0.0 to 1.0
+    pub fn level(&self) -> f32 {
+        let (Some(sample_rate), Some(channels)) = (self.sample_rate, self.channels)
+        else {
+            return 0.0;
+        };
+        let window = (sample_rate as usize * channels as usize) / 10;
+        let samples = self.samples.lock().unwrap();
+        let recent = &samples[samples.len().saturating_sub(window)..];
+        if recent.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = recent.iter().map(|&s| (s as f64 / 32768.0).powi(2)).sum();
+        (sum / recent.len() as f64).sqrt() as f32
+    }
+
+    /// Describes the current recording, call before stopping it while the stream's
+    /// device is still known
+    pub fn recording_info(&self) -> RecordingInfo {
+        let duration = match (self.sample_rate, self.channels) {
+            (Some(sample_rate), Some(channels)) if sample_rate > 0 && channels > 0 => {
+                let samples = self.samples.lock().unwrap().len()
+                    + self.spilled.load(Ordering::Relaxed);
+                Some(Duration::from_secs_f64(
+                    samples as f64 / (sample_rate as f64 * channels as f64),
+                ))
+            }
+            _ => None,
+        };
+        RecordingInfo {
+            started_at: self.started_at.unwrap_or_else(Utc::now),
+            duration,
+            device: self.stream_source.as_ref().and_then(|(name, _, _)| name.clone()),
+            sample_rate: self.sample_rate,
+            language: None,
+            model: None,
+        }
+    }
+
+    /// Stops the stream and hands back the raw samples along with their format
+    pub fn stop_recording_and_get_samples(&mut self) -> Option<(Vec<i16>, u32, u16)> {
+        if !self.is_recording {
+            return None;
+        }
+        self.finish_recording();
+
+        let mut samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        if let Some(spill) = self.end_spill() {
+            match read_spilled(&spill) {
+                Ok(mut older) => {
+                    older.append(&mut samples);
+                    samples = older;
+                }
+                Err(e) => log::error!("Failed to read spilled audio: {e:#}"),
+            }
+        }
+        Some((samples, self.sample_rate?, self.channels?))
+    }
+
     pub fn stop_recording_and_get_bytes(&mut self) -> Option<Vec<u8>> {
         if !self.is_recording {
             return None;
@@ -98,17 +487,111 @@ impl AudioRecorder {
             self.is_recording,
             false
         );
-        self.is_recording = false;
-
-        // Drop the stream to stop recording
-        self.stream = None;
+        self.finish_recording();
 
         // Get the recorded samples
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        let spill = self.end_spill();
+        // The spill file is deleted when it drops, right after it's been read back
+        self.wav_bytes(spill.as_ref(), &samples)
+    }
+
+    /// Hands back the audio captured since the last segment and keeps recording
+    pub fn take_segment(&mut self, overlap: Duration) -> Option<Vec<u8>> {
+        if !self.is_recording {
+            return None;
+        }
+        let spill = self.end_spill();
+        let mut samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        let bytes = self.wav_bytes(spill.as_ref(), &samples);
+        self.begin_spill();
+        // The last `overlap` also starts the next segment, so a word cut in two is
+        // heard whole in one of them
+        if let (Some(sample_rate), Some(channels)) = (self.sample_rate, self.channels) {
+            let keep =
+                (overlap.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+            let tail = samples.split_off(samples.len().saturating_sub(keep));
+            self.samples.lock().unwrap().splice(0..0, tail);
+        }
+        bytes
+    }
+
+    /// The audio captured so far, leaving the recording running untouched. Audio
+    /// being spilled at that moment may be missing.
+    pub fn snapshot(&self) -> Option<Vec<u8>> {
+        if !self.is_recording {
+            return None;
+        }
         let samples = self.samples.lock().unwrap().clone();
+        let spill = self.spill.as_ref().map(|spill| &spill.file);
+        self.wav_bytes(spill, &samples)
+    }
+
+    /// Drops all but the last `keep` of the captured audio
+    pub fn trim(&mut self, keep: Duration) {
+        let (Some(sample_rate), Some(channels)) = (self.sample_rate, self.channels)
+        else {
+            return;
+        };
+        let keep = (keep.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+        let fits_in_memory = self.samples.lock().unwrap().len() >= keep;
+        // Spilled audio is only ever dropped whole
+        if fits_in_memory && self.spill.is_some() {
+            self.end_spill();
+            self.begin_spill();
+        }
+        let mut samples = self.samples.lock().unwrap();
+        let excess = samples.len().saturating_sub(keep);
+        samples.drain(..excess);
+    }
 
-        if samples.is_empty() || self.sample_rate.is_none() || self.channels.is_none() {
+    /// Runs the enabled filters first, the buffer itself stays raw. `spilled` holds
+    /// the audio before `samples`.
+    fn wav_bytes(
+        &self,
+        spilled: Option<&NamedTempFile>,
+        samples: &[i16],
+    ) -> Option<Vec<u8>> {
+        if (samples.is_empty() && spilled.is_none())
+            || self.sample_rate.is_none()
+            || self.channels.is_none()
+        {
             return None;
         }
+        // The filters need the whole recording at once
+        let (to_copy, processed);
+        let samples = match (self.processing.is_enabled(), spilled) {
+            (true, Some(file)) => {
+                let mut all = match read_spilled(file) {
+                    Ok(all) => all,
+                    Err(e) => {
+                        log::error!("Failed to read spilled audio: {e:#}");
+                        return None;
+                    }
+                };
+                all.extend_from_slice(samples);
+                processed = self.processing.apply(
+                    &all,
+                    self.sample_rate.unwrap(),
+                    self.channels.unwrap(),
+                );
+                to_copy = None;
+                &processed[..]
+            }
+            (true, None) => {
+                processed = self.processing.apply(
+                    samples,
+                    self.sample_rate.unwrap(),
+                    self.channels.unwrap(),
+                );
+                to_copy = None;
+                &processed[..]
+            }
+            (false, file) => {
+                to_copy = file;
+                samples
+            }
+        };
 
         // Create a temporary file for the WAV
         let temp_file = match NamedTempFile::new() {
@@ -138,8 +621,22 @@ impl AudioRecorder {
             }
         };
 
+        // Write the spilled samples first, a chunk at a time
+        if let Some(file) = to_copy {
+            let written = for_each_spilled(file, |chunk| {
+                for &sample in chunk {
+                    writer.write_sample(sample)?;
+                }
+                Ok(())
+            });
+            if let Err(e) = written {
+                log::error!("Error writing spilled samples: {e:#}");
+                return None;
+            }
+        }
+
         // Write all samples
-        for &sample in &samples {
+        for &sample in samples {
             if let Err(e) = writer.write_sample(sample) {
                 eprintln!("Error writing sample: {}", e);
                 return None;
@@ -167,3 +664,255 @@ impl AudioRecorder {
         }
     }
 }
+
+/// Where the stream callback sends audio once the recording is over `after` samples,
+/// keeping the last `keep` in memory
+struct SpillSink {
+    tx: mpsc::Sender<Vec<i16>>,
+    after: usize,
+    keep: usize,
+}
+
+impl SpillSink {
+    /// Sends all but the last `keep` samples to the writer once there are `after` of
+    /// them, then again every `keep` samples. Only the kept tail is copied, the older
+    /// audio moves to the writer thread as is and is freed there.
+    fn spill_older(&self, samples: &mut Vec<i16>, spilled: &AtomicUsize) {
+        let hold = if spilled.load(Ordering::Relaxed) == 0 {
+            self.after
+        } else {
+            self.keep
+        };
+        if samples.len() < hold + self.keep {
+            return;
+        }
+        let recent = samples.split_off(samples.len() - self.keep);
+        let older = std::mem::replace(samples, recent);
+        spilled.fetch_add(older.len(), Ordering::Relaxed);
+        _ = self.tx.send(older);
+    }
+}
+
+/// A temp file of raw little-endian samples, written by its own thread so the stream
+/// callback never waits on the disk
+struct Spill {
+    file: NamedTempFile,
+    writer: JoinHandle<std::io::Result<()>>,
+}
+
+impl Spill {
+    fn create() -> Result<(Self, mpsc::Sender<Vec<i16>>)> {
+        let file = NamedTempFile::new().context("Failed to create temp file")?;
+        let mut out =
+            BufWriter::with_capacity(SPILL_READ_CHUNK, file.as_file().try_clone()?);
+        let (tx, rx) = mpsc::channel::<Vec<i16>>();
+        let writer = std::thread::spawn(move || {
+            for chunk in rx {
+                for sample in chunk {
+                    out.write_all(&sample.to_le_bytes())?;
+                }
+                // Whole chunks reach the file, for snapshots read while recording
+                out.flush()?;
+            }
+            Ok(())
+        });
+        log::debug!("Spilling the recording to {}", file.path().display());
+        Ok((Self { file, writer }, tx))
+    }
+}
+
+/// Hands the spilled samples to `each` a chunk at a time. Only whole samples are
+/// read, so a chunk still being written is cut short rather than misread.
+fn for_each_spilled(
+    file: &NamedTempFile,
+    mut each: impl FnMut(&[i16]) -> Result<()>,
+) -> Result<()> {
+    let mut reader = BufReader::new(file.reopen()?);
+    let mut remaining = (file.as_file().metadata()?.len() as usize) & !1;
+    let mut bytes = vec![0; SPILL_READ_CHUNK];
+    let mut samples = Vec::with_capacity(SPILL_READ_CHUNK / 2);
+    while remaining > 0 {
+        let len = remaining.min(SPILL_READ_CHUNK);
+        reader.read_exact(&mut bytes[..len])?;
+        samples.clear();
+        samples.extend(
+            bytes[..len]
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]])),
+        );
+        each(&samples)?;
+        remaining -= len;
+    }
+    Ok(())
+}
+
+fn read_spilled(file: &NamedTempFile) -> Result<Vec<i16>> {
+    let mut all = vec![];
+    for_each_spilled(file, |chunk| {
+        all.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    Ok(all)
+}
+
+/// Name of the device these settings select, before `audio.avoid_bluetooth_mic` may
+/// pass it over
+pub fn device_name(audio: &AudioSettings) -> Result<String> {
+    Ok(input_device(audio.input_device.as_deref())?.name()?)
+}
+
+/// The built-in microphone, if this machine has one
+pub fn built_in_input(transports: &mut Transports) -> Option<cpal::Device> {
+    cpal::default_host().input_devices().ok()?.find(|device| {
+        device
+            .name()
+            .is_ok_and(|name| transports.get(&name) == Transport::BuiltIn)
+    })
+}
+
+/// Finds the input device by name, falling back to the system default
+fn input_device(name: Option<&str>) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+
+    if let Some(name) = name {
+        let device = host
+            .input_devices()?
+            .find(|device| device.name().is_ok_and(|n| n == name));
+        if let Some(device) = device {
+            return Ok(device);
+        }
+        log::warn!("Input device {name:?} not found, using the default device");
+    }
+
+    host.default_input_device().context("No input device available")
+}
+
+/// 16-bit PCM WAV in memory
+pub fn encode_wav(samples: &[i16], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut bytes, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(bytes.into_inner())
+}
+
+/// The samples of a 16-bit WAV file, with its channels averaged into one
+pub fn wav_mono(bytes: &[u8]) -> Result<(Vec<i16>, u32)> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+    let spec = reader.spec();
+    if spec.bits_per_sample != 16 || spec.sample_format != hound::SampleFormat::Int {
+        bail!("Only 16-bit WAV recordings can be compressed");
+    }
+    let samples = reader.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| {
+            (frame.iter().map(|&s| s as i32).sum::<i32>() / frame.len() as i32) as i16
+        })
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+/// Length of the audio in a WAV file, read from its header
+pub fn wav_duration(bytes: &[u8]) -> Option<Duration> {
+    let reader = hound::WavReader::new(Cursor::new(bytes)).ok()?;
+    let sample_rate = reader.spec().sample_rate;
+    if sample_rate == 0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(reader.duration() as f64 / sample_rate as f64))
+}
+
+/// Loudness of a whole WAV file as RMS, from 0.0 to 1.0
+pub fn wav_level(bytes: &[u8]) -> Option<f32> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes)).ok()?;
+    let (sum, count) = reader
+        .samples::<i16>()
+        .filter_map(Result::ok)
+        .fold((0.0, 0usize), |(sum, count), s| {
+            (sum + (s as f64 / 32768.0).powi(2), count + 1)
+        });
+    (count > 0).then(|| (sum / count as f64).sqrt() as f32)
+}
+
+/// Plays 16-bit samples on the default output device. Playback stops when the
+/// returned stream is dropped.
+pub fn play_samples(
+    samples: Vec<i16>,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<Stream> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .context("No output device available")?;
+    let config = cpal::StreamConfig {
+        channels,
+        sample_rate: cpal::SampleRate(sample_rate),
+        buffer_size: cpal::BufferSize::Default,
+    };
+
+    let mut samples = samples.into_iter();
+    let stream = device.build_output_stream(
+        &config,
+        move |data: &mut [f32], _| {
+            for out in data.iter_mut() {
+                *out = samples.next().map_or(0.0, |s| s as f32 / 32768.0);
+            }
+        },
+        |err| log::error!("An error occurred on the playback stream: {}", err),
+        None,
+    )?;
+    stream.play()?;
+
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spills_once_past_after_then_in_steady_chunks() {
+        let (spill, tx) = Spill::create().unwrap();
+        let sink = SpillSink {
+            tx,
+            after: 10,
+            keep: 4,
+        };
+        let spilled = AtomicUsize::new(0);
+        let mut samples: Vec<i16> = (0..13).collect();
+        sink.spill_older(&mut samples, &spilled);
+        assert_eq!(samples.len(), 13, "kept in memory until `after` is passed");
+
+        samples.push(13);
+        sink.spill_older(&mut samples, &spilled);
+        assert_eq!(samples, [10, 11, 12, 13]);
+        assert_eq!(spilled.load(Ordering::Relaxed), 10);
+
+        samples.extend(14..20);
+        sink.spill_older(&mut samples, &spilled);
+        assert_eq!(samples, [16, 17, 18, 19]);
+        assert_eq!(spilled.load(Ordering::Relaxed), 16);
+
+        drop(sink);
+        spill.writer.join().unwrap().unwrap();
+        assert_eq!(read_spilled(&spill.file).unwrap(), (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn averages_wav_channels_into_one() {
+        let stereo = encode_wav(&[100, 300, -50, -150, 7, 7], 44100, 2).unwrap();
+        assert_eq!(wav_mono(&stereo).unwrap(), (vec![200, -100, 7], 44100));
+        let mono = encode_wav(&[1, 2, 3], 16000, 1).unwrap();
+        assert_eq!(wav_mono(&mono).unwrap(), (vec![1, 2, 3], 16000));
+    }
+}
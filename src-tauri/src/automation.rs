@@ -0,0 +1,217 @@
+use crate::history::History;
+use crate::history_cipher::Locked;
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::SettingsState;
+use crate::{cli, deep_link, privacy};
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Url, async_runtime::spawn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+/// Requests are a request line and a few headers, nothing needs a body
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The local HTTP server for Shortcuts, Automator and scripts. Every action runs the
+/// same function as its tray item, so the two can't behave differently.
+#[derive(Default)]
+pub struct AutomationServer {
+    stop: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+/// Stops the running server and starts a new one if `automation.enabled` is on
+pub fn apply(app_handle: &AppHandle) {
+    let server = app_handle.state::<AutomationServer>();
+    if let Some(stop) = server.stop.lock().unwrap().take() {
+        _ = stop.send(());
+    }
+    let automation = app_handle.state::<SettingsState>().get().automation.clone();
+    if !automation.enabled {
+        return;
+    }
+
+    let (tx_stop, mut rx_stop) = oneshot::channel();
+    *server.stop.lock().unwrap() = Some(tx_stop);
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        // Loopback only, nothing off this machine can connect
+        let listener =
+            match TcpListener::bind((Ipv4Addr::LOCALHOST, automation.port)).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!(
+                        "Failed to start automation on port {}: {e}",
+                        automation.port
+                    );
+                    AppNotifications::new(&app_handle)
+                        .notify(Notification::AutomationUnavailable(e.to_string()));
+                    return;
+                }
+            };
+        log::info!("Automation listening on 127.0.0.1:{}", automation.port);
+        loop {
+            let stream = tokio::select! {
+                _ = &mut rx_stop => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log::warn!("Failed to accept an automation request: {e}");
+                        continue;
+                    }
+                },
+            };
+            let app_handle = app_handle.clone();
+            spawn(async move {
+                if let Err(e) = handle(&app_handle, stream).await {
+                    log::warn!("Failed to answer an automation request: {e:#}");
+                }
+            });
+        }
+        log::info!("Automation stopped");
+    });
+}
+
+async fn handle(app_handle: &AppHandle, mut stream: TcpStream) -> Result<()> {
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .context("Timed out reading the request")?;
+    let (status, body) = match request {
+        Ok(request) => respond(app_handle, request).await,
+        Err(e) => (400, json!({ "error": e.to_string() })),
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        reason(status),
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    url: Url,
+    token: Option<String>,
+}
+
+/// Reads the request line and headers, the only parts any endpoint looks at
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 1024];
+    while !buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            bail!("The request ended early");
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if buffer.len() > MAX_REQUEST_BYTES {
+            bail!("The request is too large");
+        }
+    }
+    let head = String::from_utf8_lossy(&buffer);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        bail!("Malformed request line");
+    };
+    let token = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.trim().strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    Ok(Request {
+        method: method.to_string(),
+        url: Url::parse("http://127.0.0.1")?.join(target)?,
+        token,
+    })
+}
+
+async fn respond(app_handle: &AppHandle, request: Request) -> (u16, Value) {
+    let expected = app_handle.state::<SettingsState>().get().automation.token.clone();
+    let authorized = request
+        .token
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()));
+    if !authorized {
+        log::warn!("Rejected an automation request without a valid token");
+        return (401, json!({ "error": "Missing or wrong token" }));
+    }
+    if request.method != "GET" && request.method != "POST" {
+        return (405, json!({ "error": "Use GET or POST" }));
+    }
+
+    // Query values may carry the user's own text, only the path is logged
+    log::info!("Automation request for {}", request.url.path());
+    match request.url.path() {
+        "/toggle" => {
+            crate::toggle_recording(app_handle.clone(), None);
+            (200, json!({ "ok": true }))
+        }
+        "/polish" => match deep_link::polish_overrides(&request.url) {
+            Ok(overrides) => {
+                crate::cleanse_clipboard(app_handle.clone(), None, overrides);
+                (200, json!({ "ok": true }))
+            }
+            Err(e) => (400, json!({ "error": e.to_string() })),
+        },
+        "/cancel" => {
+            crate::cancel_recording(app_handle.clone());
+            (200, json!({ "ok": true }))
+        }
+        "/status" => (200, json!(cli::status(app_handle))),
+        "/last-transcript" => last_transcript(app_handle).await,
+        _ => (404, json!({ "error": "Unknown endpoint" })),
+    }
+}
+
+async fn last_transcript(app_handle: &AppHandle) -> (u16, Value) {
+    if privacy::enabled(app_handle) {
+        return (403, json!({ "error": "Privacy mode is on" }));
+    }
+    let history = app_handle.state::<History>().inner().clone();
+    match history.recent(1).await {
+        Ok(entries) => match entries.into_iter().next() {
+            Some(entry) => (
+                200,
+                json!({
+                    "text": entry.text,
+                    "kind": entry.kind,
+                    "created_at": entry.created_at,
+                    "language": entry.language,
+                }),
+            ),
+            None => (200, json!({ "text": null })),
+        },
+        Err(e) if e.is::<Locked>() => (423, json!({ "error": "History is locked" })),
+        Err(e) => {
+            log::error!("Failed to load the latest history entry: {e:#}");
+            (500, json!({ "error": "History couldn't be read" }))
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        423 => "Locked",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Takes as long whatever the mismatch, so the token can't be guessed byte by byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,46 @@
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::{self, SettingsState};
+use anyhow::{Result, bail};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_autostart::ManagerExt;
+
+/// Registers or removes the login item to match `behavior.launch_at_login`. When the
+/// OS refuses, the user is told and the setting is put back to what's really in place.
+pub fn apply(app_handle: &AppHandle, enabled: bool) {
+    if let Err(e) = apply_(app_handle, enabled) {
+        log::error!("Failed to change launch at login: {e:#}");
+        AppNotifications::new(app_handle)
+            .notify(Notification::AutostartFailed(e.to_string()));
+        let actual = app_handle.autolaunch().is_enabled().unwrap_or(false);
+        if let Err(e) = settings::update(app_handle, |settings| {
+            settings.behavior.launch_at_login = actual
+        }) {
+            log::error!("Failed to save launch at login: {e:#}");
+        }
+    }
+}
+
+fn apply_(app_handle: &AppHandle, enabled: bool) -> Result<()> {
+    let autolaunch = app_handle.autolaunch();
+    if autolaunch.is_enabled()? == enabled {
+        return Ok(());
+    }
+    if enabled {
+        autolaunch.enable()?;
+    } else {
+        autolaunch.disable()?;
+    }
+    // Some systems report success without registering anything
+    if autolaunch.is_enabled()? != enabled {
+        bail!("The system didn't accept the change");
+    }
+    log::info!("Launch at login {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
+
+/// Brings the login item in line with the settings file at startup, e.g. after the
+/// user removed it in the OS settings or edited the file while the app was closed
+pub fn sync(app_handle: &AppHandle) {
+    let enabled = app_handle.state::<SettingsState>().get().behavior.launch_at_login;
+    apply(app_handle, enabled);
+}
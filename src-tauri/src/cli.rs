@@ -0,0 +1,314 @@
+use crate::deep_link;
+use crate::paths::config_dir;
+use crate::transcribe_icon::{Icon, TranscribeIcon};
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+pub const USAGE: &str = "\
+Usage: whistle [COMMAND]
+
+Commands, run in the already open app if there is one:
+  --toggle-recording  Start or stop recording, starting the app if needed
+  --polish-clipboard  Polish the text in the clipboard
+  --cancel            Stop the current recording without transcribing it
+  --status            Print the app's current state as JSON
+
+Exit codes:
+  0  Done, or handed to the running app
+  2  Unknown or repeated command
+  3  --status found no running app";
+
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_RUNNING: i32 = 3;
+
+/// Holds the port the running instance answers `--status` on
+const STATUS_PORT_FILE: &str = "instance.port";
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    ToggleRecording,
+    PolishClipboard,
+    Cancel,
+    Status,
+}
+
+impl Command {
+    const ALL: [Command; 4] = [
+        Command::ToggleRecording,
+        Command::PolishClipboard,
+        Command::Cancel,
+        Command::Status,
+    ];
+
+    pub fn flag(self) -> &'static str {
+        match self {
+            Command::ToggleRecording => "--toggle-recording",
+            Command::PolishClipboard => "--polish-clipboard",
+            Command::Cancel => "--cancel",
+            Command::Status => "--status",
+        }
+    }
+
+    fn from_flag(flag: &str) -> Option<Self> {
+        // `--toggle` predates the other commands
+        if flag == "--toggle" {
+            return Some(Command::ToggleRecording);
+        }
+        Self::ALL.into_iter().find(|command| command.flag() == flag)
+    }
+}
+
+/// Reads the command from the process arguments, skipping the binary name. The same
+/// arguments reach the running instance when a second launch is redirected.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Command>> {
+    let mut command = None;
+    for arg in args.into_iter().skip(1) {
+        // macOS adds this when launched from Finder, and deep links arrive as
+        // arguments on Windows and Linux. Those are handled by the deep link plugin,
+        // and files opened with the app by `files`.
+        if arg.starts_with("-psn_")
+            || arg.starts_with(&format!("{}://", deep_link::SCHEME))
+            || Path::new(&arg).is_file()
+        {
+            continue;
+        }
+        let Some(parsed) = Command::from_flag(&arg) else {
+            bail!("Unknown argument {arg:?}");
+        };
+        if command.replace(parsed).is_some() {
+            bail!("Only one command can be given at a time");
+        }
+    }
+    Ok(command)
+}
+
+/// Files passed as arguments, by "Open with" on Windows and Linux or by a script
+pub fn files(args: impl IntoIterator<Item = String>) -> Vec<PathBuf> {
+    args.into_iter()
+        .skip(1)
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Carries out a command in the running instance
+pub fn run(app_handle: &AppHandle, command: Command) {
+    log::info!("Running {} from the command line", command.flag());
+    match command {
+        Command::ToggleRecording => crate::toggle_recording(app_handle.clone(), None),
+        Command::PolishClipboard => crate::cleanse_clipboard(
+            app_handle.clone(),
+            None,
+            crate::PolishOverrides::default(),
+        ),
+        Command::Cancel => crate::cancel_recording(app_handle.clone()),
+        // Answered over the status port without reaching the running instance's args
+        Command::Status => {}
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub running: bool,
+    /// "idle", "starting", "recording", "transcribing", "polishing", "session" or "live"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+pub fn state_name(icon: Icon) -> &'static str {
+    match icon {
+        Icon::Default => "idle",
+        Icon::Starting => "starting",
+        Icon::Recording => "recording",
+        Icon::Transcribing => "transcribing",
+        Icon::Cleansing => "polishing",
+        Icon::Session => "session",
+        Icon::Live => "live",
+    }
+}
+
+/// The running instance's own status
+pub fn status(app_handle: &AppHandle) -> Status {
+    Status {
+        running: true,
+        state: app_handle
+            .try_state::<TranscribeIcon>()
+            .map(|icon| state_name(icon.current())),
+        version: Some(app_handle.package_info().version.to_string()),
+    }
+}
+
+fn status_port_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(STATUS_PORT_FILE))
+}
+
+/// Answers `--status` from later launches with one JSON line per connection. Listens
+/// on loopback only, and a port file left behind by a crash simply fails to connect.
+pub fn serve_status(app_handle: AppHandle) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    std::fs::write(status_port_path()?, listener.local_addr()?.port().to_string())?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let status = status(&app_handle);
+            let result = stream.and_then(|mut stream| write_status(&mut stream, &status));
+            if let Err(e) = result {
+                log::error!("Failed to answer a status request: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn write_status(stream: &mut impl Write, status: &Status) -> std::io::Result<()> {
+    writeln!(stream, "{}", serde_json::to_string(status)?)
+}
+
+/// Prints the running instance's status as JSON for `--status` and returns the exit code
+pub fn print_status() -> i32 {
+    match query_status() {
+        Ok(json) => {
+            println!("{}", json.trim());
+            0
+        }
+        Err(_) => {
+            let status = Status {
+                running: false,
+                state: None,
+                version: None,
+            };
+            println!("{}", serde_json::to_string(&status).unwrap_or_default());
+            EXIT_NOT_RUNNING
+        }
+    }
+}
+
+fn query_status() -> Result<String> {
+    let port: u16 = std::fs::read_to_string(status_port_path()?)?.trim().parse()?;
+    read_status(port)
+}
+
+fn read_status(port: u16) -> Result<String> {
+    let mut stream =
+        TcpStream::connect_timeout(&(Ipv4Addr::LOCALHOST, port).into(), STATUS_TIMEOUT)?;
+    stream.set_read_timeout(Some(STATUS_TIMEOUT))?;
+    let mut json = String::new();
+    stream.read_to_string(&mut json)?;
+    // Something else may have taken over the port since
+    serde_json::from_str::<serde_json::Value>(&json)?;
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("whistle")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn parses_each_command() {
+        for command in Command::ALL {
+            assert_eq!(parse(args(&[command.flag()])).unwrap(), Some(command));
+        }
+        assert_eq!(parse(args(&["--toggle"])).unwrap(), Some(Command::ToggleRecording));
+    }
+
+    #[test]
+    fn no_arguments_is_no_command() {
+        assert_eq!(parse(args(&[])).unwrap(), None);
+        assert_eq!(parse(Vec::new()).unwrap(), None);
+    }
+
+    #[test]
+    fn skips_finder_and_deep_link_arguments() {
+        let deep_link = format!("{}://toggle", deep_link::SCHEME);
+        assert_eq!(parse(args(&["-psn_0_12345", &deep_link])).unwrap(), None);
+        assert_eq!(
+            parse(args(&["-psn_0_12345", "--cancel"])).unwrap(),
+            Some(Command::Cancel)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_repeated_commands() {
+        assert!(parse(args(&["--record"])).is_err());
+        assert!(parse(args(&["toggle-recording"])).is_err());
+        assert!(parse(args(&["--cancel", "--cancel"])).is_err());
+        assert!(parse(args(&["--cancel", "--status"])).is_err());
+    }
+
+    /// The single instance plugin hands the running app the second launch's argv as is
+    #[test]
+    fn forwarded_arguments_parse_to_the_same_command() {
+        for command in Command::ALL {
+            let forwarded = vec![
+                "/Applications/Whistle.app/Contents/MacOS/whistle".to_string(),
+                command.flag().to_string(),
+            ];
+            assert_eq!(parse(forwarded).unwrap(), Some(command));
+        }
+    }
+
+    #[test]
+    fn status_serialization() {
+        let running = Status {
+            running: true,
+            state: Some("recording"),
+            version: Some("1.2.3".into()),
+        };
+        assert_eq!(
+            serde_json::to_string(&running).unwrap(),
+            r#"{"running":true,"state":"recording","version":"1.2.3"}"#
+        );
+        let not_running = Status {
+            running: false,
+            state: None,
+            version: None,
+        };
+        assert_eq!(serde_json::to_string(&not_running).unwrap(), r#"{"running":false}"#);
+    }
+
+    #[test]
+    fn status_round_trips_over_the_status_port() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let status = Status {
+                running: true,
+                state: Some(state_name(Icon::Transcribing)),
+                version: Some("1.2.3".into()),
+            };
+            write_status(&mut stream, &status).unwrap();
+        });
+        let json = read_status(port).unwrap();
+        server.join().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["running"], true);
+        assert_eq!(json["state"], "transcribing");
+    }
+
+    #[test]
+    fn a_port_answering_something_else_is_not_a_status() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").unwrap();
+        });
+        assert!(read_status(port).is_err());
+        server.join().unwrap();
+    }
+}
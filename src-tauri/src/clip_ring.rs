@@ -0,0 +1,156 @@
+use crate::notifications::{AppNotifications, Notification};
+use crate::transcribe_icon::TranscribeIcon;
+use crate::{privacy, recent_menu, transcribe_app_logger};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{
+    AppHandle, Manager, Wry,
+    menu::{MenuItem, PredefinedMenuItem, Submenu},
+};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+pub const RING_LEN: usize = 5;
+pub const RING_ID_PREFIX: &str = "ring:";
+pub const RING_CYCLE_ID: &str = "ring_cycle";
+/// After this long without cycling, the next press starts from the newest item again
+const CURSOR_RESET: Duration = Duration::from_secs(4);
+
+/// The last few transcripts and polishes, newest first, with the tray's
+/// "Clipboard ring" submenu mirroring them
+pub struct ClipRing {
+    ring: Mutex<Ring>,
+    menu: Submenu<Wry>,
+}
+
+#[derive(Default)]
+struct Ring {
+    items: VecDeque<String>,
+    cursor: usize,
+    last_cycle: Option<Instant>,
+}
+
+impl ClipRing {
+    pub fn new(app_handle: &AppHandle, menu: Submenu<Wry>) -> Self {
+        let ring = Self {
+            ring: Mutex::default(),
+            menu,
+        };
+        ring.refresh_menu(app_handle);
+        ring
+    }
+
+    pub fn push(&self, app_handle: &AppHandle, text: String) {
+        {
+            let mut ring = self.ring.lock().unwrap();
+            ring.items.push_front(text);
+            ring.items.truncate(RING_LEN);
+            ring.last_cycle = None;
+        }
+        self.refresh_menu(app_handle);
+    }
+
+    pub fn clear(&self, app_handle: &AppHandle) {
+        *self.ring.lock().unwrap() = Ring::default();
+        self.refresh_menu(app_handle);
+    }
+
+    /// Moves one item further back, returning it with its 0-based position
+    fn step_back(&self) -> Option<(String, usize, usize)> {
+        let mut ring = self.ring.lock().unwrap();
+        if ring.items.is_empty() {
+            return None;
+        }
+        let resumed = matches!(ring.last_cycle, Some(t) if t.elapsed() < CURSOR_RESET);
+        ring.cursor = if resumed {
+            (ring.cursor + 1) % ring.items.len()
+        } else {
+            0
+        };
+        ring.last_cycle = Some(Instant::now());
+        Some((ring.items[ring.cursor].clone(), ring.cursor, ring.items.len()))
+    }
+
+    fn get(&self, index: usize) -> Option<(String, usize, usize)> {
+        let ring = self.ring.lock().unwrap();
+        let text = ring.items.get(index)?.clone();
+        Some((text, index, ring.items.len()))
+    }
+
+    fn refresh_menu(&self, app_handle: &AppHandle) {
+        if let Err(e) = self.refresh_menu_(app_handle) {
+            log::error!("Unable to refresh clipboard ring menu: {e}");
+        }
+    }
+
+    fn refresh_menu_(&self, app_handle: &AppHandle) -> Result<()> {
+        for item in self.menu.items()? {
+            self.menu.remove(&item)?;
+        }
+
+        let ring = self.ring.lock().unwrap();
+        self.menu.append(&MenuItem::with_id(
+            app_handle,
+            RING_CYCLE_ID,
+            "Copy previous",
+            !ring.items.is_empty(),
+            None::<&str>,
+        )?)?;
+        if ring.items.is_empty() {
+            return Ok(());
+        }
+
+        self.menu.append(&PredefinedMenuItem::separator(app_handle)?)?;
+        for (i, text) in ring.items.iter().enumerate() {
+            self.menu.append(&MenuItem::with_id(
+                app_handle,
+                format!("{RING_ID_PREFIX}{i}"),
+                format!("{}/{}  {}", i + 1, ring.items.len(), recent_menu::label(text)),
+                true,
+                None::<&str>,
+            )?)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies the item before the one copied last, ignored while recording
+pub fn cycle(app_handle: &AppHandle) {
+    if app_handle.state::<TranscribeIcon>().is_recording() {
+        log::info!("Ignoring clipboard ring while recording");
+        return;
+    }
+    let Some(item) = app_handle.state::<ClipRing>().step_back() else {
+        log::info!("Clipboard ring is empty");
+        return;
+    };
+    copy(app_handle, item);
+}
+
+/// Copies the item at `index` (0 is the newest), as picked from the tray
+pub fn copy_index(app_handle: &AppHandle, index: usize) {
+    let Some(item) = app_handle.state::<ClipRing>().get(index) else {
+        log::warn!("Clipboard ring has no item {index}");
+        return;
+    };
+    copy(app_handle, item);
+}
+
+fn copy(app_handle: &AppHandle, (text, index, len): (String, usize, usize)) {
+    let preview = match privacy::enabled(app_handle) {
+        true => transcribe_app_logger::redact(&text),
+        false => recent_menu::label(&text),
+    };
+    if let Err(e) = app_handle.clipboard().write_text(text) {
+        log::error!("Failed to copy clipboard ring item: {e}");
+        return;
+    }
+    log::info!("Copied clipboard ring item {}/{len}", index + 1);
+    AppNotifications::new(app_handle).notify(Notification::ClipRingItem {
+        preview,
+        position: index + 1,
+        len,
+    });
+}
@@ -0,0 +1,296 @@
+use crate::history::{EntryKind, NewEntry};
+use crate::hook::{self, HookPayload};
+use crate::length_limit::{self, Limited};
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::SettingsState;
+use crate::sounds::{self, Cue};
+use crate::transcribe_client::TranscribeClient;
+use crate::transcribe_icon::{self, Work};
+use crate::{frontmost_app, paths, privacy, sinks, stats, text_format};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Wry, async_runtime::spawn, menu::MenuItem};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+pub const COLLECT_MENU_ID: &str = "collect";
+pub const DISCARD_COLLECTED_ID: &str = "discard_collected";
+const START_TEXT: &str = "Start collecting";
+const FINISH_TEXT: &str = "Finish collecting";
+/// Holds the collected transcripts in the data folder, so a crash doesn't lose them
+const BUFFER_FILE: &str = "collected.json";
+
+/// Collecting gathers the transcripts of several recordings instead of delivering
+/// each one, then delivers them as one text when it's finished
+pub struct Collector {
+    item: MenuItem<Wry>,
+    discard_item: MenuItem<Wry>,
+    segments: Mutex<Option<Vec<String>>>,
+    buffer_file: Option<PathBuf>,
+}
+
+impl Collector {
+    /// Picks up transcripts left behind by a crash while collecting
+    pub fn new(
+        app_handle: &AppHandle,
+        item: MenuItem<Wry>,
+        discard_item: MenuItem<Wry>,
+    ) -> Self {
+        let buffer_file = paths::data_file(app_handle, BUFFER_FILE).ok();
+        let restored = buffer_file.as_ref().and_then(|path| match read_buffer(path) {
+            Ok(segments) => segments,
+            Err(e) => {
+                log::error!("Failed to restore collected transcripts: {e:#}");
+                None
+            }
+        });
+        let collector = Self {
+            item,
+            discard_item,
+            segments: Mutex::new(None),
+            buffer_file,
+        };
+        if let Some(segments) = restored {
+            log::info!("Restored {} collected transcripts", segments.len());
+            AppNotifications::new(app_handle).notify(Notification::CollectRestored {
+                segments: segments.len(),
+            });
+            collector.set_segments(Some(segments));
+        }
+        collector
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.segments.lock().unwrap().is_some()
+    }
+
+    /// "Collecting: 3 segments, 148 words" while collecting
+    pub fn status(&self) -> Option<String> {
+        let segments = self.segments.lock().unwrap();
+        let segments = segments.as_ref()?;
+        let words: usize = segments
+            .iter()
+            .map(|segment| segment.split_whitespace().count())
+            .sum();
+        Some(format!(
+            "Collecting: {} {}, {words} words",
+            segments.len(),
+            if segments.len() == 1 {
+                "segment"
+            } else {
+                "segments"
+            }
+        ))
+    }
+
+    fn set_segments(&self, segments: Option<Vec<String>>) {
+        let active = segments.is_some();
+        *self.segments.lock().unwrap() = segments;
+        let text = if active { FINISH_TEXT } else { START_TEXT };
+        if let Err(e) = self
+            .item
+            .set_text(text)
+            .and_then(|_| self.discard_item.set_enabled(active))
+        {
+            log::error!("Unable to update collect menu items: {e}");
+        }
+    }
+
+    /// Keeps the buffer file in step with the segments. Nothing is written in privacy
+    /// mode, so a crash then loses what was collected.
+    fn save(&self, app_handle: &AppHandle) {
+        let Some(path) = &self.buffer_file else {
+            return;
+        };
+        let segments = self.segments.lock().unwrap().clone();
+        let result = match segments {
+            Some(segments) if !privacy::enabled(app_handle) => {
+                serde_json::to_vec(&segments)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| Ok(std::fs::write(path, json)?))
+            }
+            _ if path.exists() => std::fs::remove_file(path).map_err(Into::into),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to update {}: {e:#}", path.display());
+        }
+    }
+}
+
+fn read_buffer(path: &Path) -> Result<Option<Vec<String>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let segments: Vec<String> = serde_json::from_slice(&std::fs::read(path)?)?;
+    Ok(Some(segments))
+}
+
+/// The tray item and `hotkeys.collect`: starts collecting, or delivers what was
+/// collected
+pub fn toggle(app_handle: &AppHandle) {
+    let collector = app_handle.state::<Collector>();
+    if collector.is_active() {
+        finish(app_handle);
+        return;
+    }
+    collector.set_segments(Some(vec![]));
+    collector.save(app_handle);
+    log::info!("Started collecting transcripts");
+    stats::refresh_tooltip(app_handle.clone());
+}
+
+/// Adds a finished transcript instead of delivering it. Returns false when not
+/// collecting, so the caller delivers it as usual.
+pub fn add(app_handle: &AppHandle, text: &str) -> bool {
+    let collector = app_handle.state::<Collector>();
+    {
+        let mut segments = collector.segments.lock().unwrap();
+        let Some(segments) = segments.as_mut() else {
+            return false;
+        };
+        segments.push(text.trim().to_string());
+        log::info!("Collected transcript {}", segments.len());
+    }
+    collector.save(app_handle);
+    stats::refresh_tooltip(app_handle.clone());
+    true
+}
+
+/// Joins the collected transcripts, polishes them once if `collect.polish` is set and
+/// delivers the result like a single transcription
+fn finish(app_handle: &AppHandle) {
+    let collector = app_handle.state::<Collector>();
+    let Some(segments) = collector.segments.lock().unwrap().take() else {
+        return;
+    };
+    collector.set_segments(None);
+    stats::refresh_tooltip(app_handle.clone());
+    if segments.is_empty() {
+        collector.save(app_handle);
+        log::info!("Stopped collecting, nothing was collected");
+        return;
+    }
+    log::info!("Delivering {} collected transcripts", segments.len());
+
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let settings = app_handle.state::<SettingsState>().get();
+        let mut kind = EntryKind::Transcription;
+        let mut text = segments.join(&settings.collect.separator);
+
+        if settings.collect.polish {
+            let client = app_handle.state::<TranscribeClient>();
+            let app_id = frontmost_app::frontmost_app();
+            let output = settings.resolve_output(app_id.as_deref());
+            let format = text_format::for_polish(&settings.behavior, &text);
+            let busy = transcribe_icon::busy(&app_handle, Work::Polishing);
+            let result = client
+                .clean_transcription(
+                    text.clone(),
+                    &output.polish_style,
+                    None,
+                    None,
+                    format,
+                )
+                .await;
+            drop(busy);
+            match result {
+                Ok(polished) if !polished.trim().is_empty() => {
+                    kind = EntryKind::Polish;
+                    crate::save_to_history(
+                        &app_handle,
+                        NewEntry {
+                            provider: Some(client.provider()),
+                            source_text: Some(text),
+                            ..NewEntry::new(EntryKind::Polish, polished.clone())
+                        },
+                    );
+                    text = polished;
+                }
+                Ok(_) => log::warn!("Polishing the collected text returned nothing"),
+                Err(e) => {
+                    // The joined transcripts are still worth delivering
+                    log::error!("Failed to polish the collected text: {e:#}");
+                    crate::notify_api_error(&app_handle, &e);
+                }
+            }
+        }
+
+        let text = match length_limit::apply(&app_handle, text, &settings.behavior) {
+            Limited::Unchanged(text) => text,
+            Limited::Truncated { text, full_text } => {
+                AppNotifications::new(&app_handle).notify(
+                    Notification::OutputTruncated {
+                        max: settings.behavior.max_output_chars,
+                        full_text,
+                    },
+                );
+                text
+            }
+            Limited::Refused { chars } => {
+                // Kept so the collected text isn't lost
+                let collector = app_handle.state::<Collector>();
+                collector.set_segments(Some(segments));
+                AppNotifications::new(&app_handle).notify(Notification::OutputRefused {
+                    chars,
+                    max: settings.behavior.max_output_chars,
+                });
+                return;
+            }
+        };
+
+        let payload = HookPayload::new(kind, text.clone());
+        let rich_text = kind == EntryKind::Polish && settings.behavior.rich_text;
+        let report = sinks::deliver(&app_handle, text, None, None, rich_text).await;
+        hook::fire(&app_handle, payload);
+        app_handle.state::<Collector>().save(&app_handle);
+        if !report.failures.is_empty() {
+            sounds::play(&app_handle, Cue::Error);
+            AppNotifications::new(&app_handle)
+                .notify(Notification::DeliveryFailed(report.failures));
+            return;
+        }
+        sounds::play(&app_handle, Cue::Success);
+        AppNotifications::new(&app_handle).notify(Notification::CollectDelivered {
+            segments: segments.len(),
+        });
+    });
+}
+
+/// Asks before throwing the collected transcripts away. They stay in history.
+pub fn discard(app_handle: &AppHandle) {
+    let Some(count) = app_handle
+        .state::<Collector>()
+        .segments
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(Vec::len)
+    else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+    app_handle
+        .dialog()
+        .message(format!(
+            "The {count} collected transcripts won't be delivered. They stay in history."
+        ))
+        .title("Discard collected text?")
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Discard".into(),
+            "Keep collecting".into(),
+        ))
+        .show(move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            let collector = app_handle.state::<Collector>();
+            collector.set_segments(None);
+            collector.save(&app_handle);
+            stats::refresh_tooltip(app_handle.clone());
+            log::info!("Discarded {count} collected transcripts");
+            AppNotifications::new(&app_handle)
+                .notify(Notification::CollectDiscarded { segments: count });
+        });
+}
@@ -0,0 +1,829 @@
+use crate::collect::Collector;
+use crate::history::{EncryptionStatus, History, HistoryEntry};
+use crate::history_cipher::EncryptionMode;
+use crate::history_export::{self, ExportFormat, ExportHeader};
+use crate::live::LiveDictation;
+use crate::local_task_handler::{MicTestReport, Task};
+use crate::log_viewer::{self, LogChunk};
+use crate::polish_diff::{self, DiffOp};
+use crate::session::DictationSession;
+use crate::session_window::{self, SessionTranscript};
+use crate::settings::{self, AppOverride, Settings, SettingsState};
+use crate::stats::{self, Stats};
+use crate::target_picker::{self, TargetPicker};
+use crate::transcribe_client::{
+    Capabilities, TranscribeClient, TranscriptionTiming, Unauthorized,
+};
+use crate::transcribe_icon::TranscribeIcon;
+use crate::{
+    PolishOverrides, ShortcutsConfig, api_key, cli, deep_link, disk_budget,
+    frontmost_app, hook, on_settings_changed, optional_shortcut, privacy, quota,
+    refresh_history_menus, reinit_audio, set_entry_pinned,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+use tokio::sync::{mpsc, oneshot};
+
+/// What the app is doing, for a window to show instead of the tray icon
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineState {
+    /// "idle", "starting", "recording", "transcribing", "polishing", "session" or "live"
+    pub state: &'static str,
+    pub recording: bool,
+    pub polishing: bool,
+    pub session: bool,
+    pub live: bool,
+    /// "Collecting: 3 segments, 148 words" while collecting
+    pub collecting: Option<String>,
+    pub privacy_mode: bool,
+    pub quota_paused: bool,
+}
+
+/// Starts or stops a recording, like the tray's "Start recording"
+#[tauri::command]
+pub fn toggle_recording_cmd(app_handle: AppHandle) {
+    crate::toggle_recording(app_handle, None);
+}
+
+/// Stops the current recording without transcribing it
+#[tauri::command]
+pub fn cancel_cmd(app_handle: AppHandle) {
+    crate::cancel_recording(app_handle);
+}
+
+/// Polishes the clipboard, in `style` instead of the resolved one when given
+#[tauri::command]
+pub fn polish_clipboard_cmd(
+    app_handle: AppHandle,
+    style: Option<String>,
+) -> Result<(), String> {
+    let style = style
+        .map(|style| deep_link::limited("style", &style, deep_link::MAX_STYLE_CHARS))
+        .transpose()
+        .map_err(|e| format!("{e:#}"))?;
+    crate::cleanse_clipboard(
+        app_handle,
+        None,
+        PolishOverrides {
+            style,
+            prompt: None,
+        },
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_pipeline_state(app_handle: AppHandle) -> PipelineState {
+    let icon = app_handle.state::<TranscribeIcon>();
+    PipelineState {
+        state: cli::state_name(icon.current()),
+        recording: icon.is_recording(),
+        polishing: *app_handle.state::<Arc<Mutex<bool>>>().lock().unwrap(),
+        session: app_handle.state::<DictationSession>().is_active(),
+        live: app_handle.state::<LiveDictation>().is_active(),
+        collecting: app_handle.state::<Collector>().status(),
+        privacy_mode: privacy::enabled(&app_handle),
+        quota_paused: quota::paused(&app_handle),
+    }
+}
+
+#[tauri::command]
+pub fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[tauri::command]
+pub fn assign_shortcut(app_handle: AppHandle, name: &str, shortcut: &str) -> String {
+    if name != "toggle-recording" && name != "cleanse-clipboard" {
+        return "Invalid shortcut name".into();
+    }
+
+    let Ok(new_shortcut) = Shortcut::from_str(shortcut) else {
+        return "Invalid shortcut".into();
+    };
+
+    let set_hotkey = |settings: &mut Settings| {
+        if name == "toggle-recording" {
+            settings.hotkeys.toggle_recording = shortcut.into();
+        } else {
+            settings.hotkeys.cleanse_clipboard = shortcut.into();
+        }
+    };
+
+    // Nothing is registered or changed for a shortcut the settings would refuse
+    let mut candidate = (*app_handle.state::<SettingsState>().get()).clone();
+    set_hotkey(&mut candidate);
+    if let Err(e) = candidate.validate() {
+        return format!("{e:#}");
+    }
+
+    let shortcuts_config = app_handle.state::<Mutex<ShortcutsConfig>>();
+    let mut shortcuts_config = shortcuts_config.lock().unwrap();
+
+    let old_shortcut = if name == "toggle-recording" {
+        shortcuts_config.toggle_recording
+    } else {
+        shortcuts_config.cleanse_clipboard
+    };
+    let global_shortcut = app_handle.global_shortcut();
+    _ = global_shortcut.unregister(old_shortcut);
+    if let Err(e) = global_shortcut.register(new_shortcut) {
+        log::error!("Failed to register {shortcut}: {e}");
+        _ = global_shortcut.register(old_shortcut);
+        return "Could not register the shortcut, another app may be using it".into();
+    }
+
+    let settings = match settings::update(&app_handle, set_hotkey) {
+        Ok(settings) => settings,
+        Err(e) => {
+            log::error!("Failed to save shortcut: {e:#}");
+            _ = global_shortcut.unregister(new_shortcut);
+            _ = global_shortcut.register(old_shortcut);
+            return format!("Failed to save settings: {e:#}");
+        }
+    };
+
+    if name == "toggle-recording" {
+        shortcuts_config.toggle_recording = new_shortcut;
+    } else {
+        shortcuts_config.cleanse_clipboard = new_shortcut;
+    }
+
+    // The type boost is toggle_recording plus a modifier, so it moves along with it
+    if name == "toggle-recording" {
+        if let Some(old_boost) = shortcuts_config.type_boost {
+            _ = app_handle.global_shortcut().unregister(old_boost);
+        }
+        shortcuts_config.type_boost =
+            optional_shortcut(&settings.hotkeys.type_boost().unwrap_or_default())
+                .ok()
+                .flatten();
+        if let Some(new_boost) = shortcuts_config.type_boost {
+            _ = app_handle.global_shortcut().register(new_boost);
+        }
+    }
+
+    "".into()
+}
+
+#[tauri::command]
+pub fn get_shortcuts(app_handle: AppHandle) -> HashMap<String, String> {
+    let settings = app_handle.state::<SettingsState>().get();
+    HashMap::from([
+        ("toggle_recording".into(), settings.hotkeys.toggle_recording.clone()),
+        ("cleanse_clipboard".into(), settings.hotkeys.cleanse_clipboard.clone()),
+        ("push_to_talk".into(), settings.hotkeys.push_to_talk.clone()),
+        ("cycle_clipboard".into(), settings.hotkeys.cycle_clipboard.clone()),
+        ("discard_recording".into(), settings.hotkeys.discard_recording.clone()),
+        ("panic".into(), settings.hotkeys.panic.clone()),
+        ("repeat_last".into(), settings.hotkeys.repeat_last.clone()),
+        ("quick_note".into(), settings.hotkeys.quick_note.clone()),
+        ("collect".into(), settings.hotkeys.collect.clone()),
+        ("type_boost".into(), settings.hotkeys.type_boost().unwrap_or_default()),
+    ])
+}
+
+#[tauri::command]
+pub async fn get_recent_transcripts(
+    app_handle: AppHandle,
+    limit: u32,
+) -> Result<Vec<HistoryEntry>, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.recent(limit).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_history_encryption(
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.encryption().await.map_err(|e| format!("{e:#}"))
+}
+
+/// Fails with a message to retry on a wrong passphrase
+#[tauri::command]
+pub async fn unlock_history(
+    app_handle: AppHandle,
+    passphrase: String,
+) -> Result<(), String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.unlock(passphrase).await.map_err(|e| format!("{e:#}"))?;
+    refresh_history_menus(app_handle);
+    Ok(())
+}
+
+/// Migrates existing entries to the new mode. `passphrase` is only used for
+/// `EncryptionMode::Passphrase`.
+#[tauri::command]
+pub async fn set_history_encryption(
+    app_handle: AppHandle,
+    mode: EncryptionMode,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history
+        .set_encryption(mode, passphrase)
+        .await
+        .map_err(|e| format!("{e:#}"))?;
+    refresh_history_menus(app_handle);
+    Ok(())
+}
+
+/// Word-level diff of what a polish changed, plus the same diff as annotated text
+#[derive(Debug, Serialize)]
+pub struct PolishDiff {
+    ops: Vec<DiffOp>,
+    annotated: String,
+}
+
+#[tauri::command]
+pub async fn get_polish_diff(
+    app_handle: AppHandle,
+    entry_id: i64,
+) -> Result<PolishDiff, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    let entry = history
+        .get(entry_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("History entry not found")?;
+    let source_text = entry
+        .source_text
+        .ok_or("This entry has no original text to compare")?;
+    let ops = polish_diff::diff_words(&source_text, &entry.text);
+    Ok(PolishDiff {
+        annotated: polish_diff::annotate(&ops),
+        ops,
+    })
+}
+
+#[tauri::command]
+pub async fn copy_polish_diff(
+    app_handle: AppHandle,
+    entry_id: i64,
+) -> Result<(), String> {
+    let diff = get_polish_diff(app_handle.clone(), entry_id).await?;
+    app_handle
+        .clipboard()
+        .write_text(diff.annotated)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_disk_usage(
+    app_handle: AppHandle,
+) -> Result<disk_budget::DiskUsage, String> {
+    disk_budget::usage(&app_handle).await.map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+pub async fn get_stats(app_handle: AppHandle) -> Result<Stats, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    stats::load(&history).await.map_err(|e| e.to_string())
+}
+
+/// Where the time of the last transcription went, `None` before the first one
+#[tauri::command]
+pub fn get_transcription_timing(app_handle: AppHandle) -> Option<TranscriptionTiming> {
+    app_handle.state::<TranscribeClient>().last_timing()
+}
+
+#[tauri::command]
+pub async fn reset_stats(app_handle: AppHandle) -> Result<(), String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.reset_stats().await.map_err(|e| e.to_string())?;
+    stats::refresh_tooltip(app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_pinned_history(
+    app_handle: AppHandle,
+) -> Result<Vec<HistoryEntry>, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.pinned().await.map_err(|e| e.to_string())
+}
+
+/// An empty `title` falls back to a preview of the text
+#[tauri::command]
+pub async fn pin_history_entry(
+    app_handle: AppHandle,
+    entry_id: i64,
+    title: Option<String>,
+    auto_paste: bool,
+) -> Result<(), String> {
+    set_entry_pinned(&app_handle, entry_id, Some((title, auto_paste)))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn unpin_history_entry(
+    app_handle: AppHandle,
+    entry_id: i64,
+) -> Result<(), String> {
+    set_entry_pinned(&app_handle, entry_id, None)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// `tag` narrows the results to entries with that tag
+#[tauri::command]
+pub async fn search_history(
+    app_handle: AppHandle,
+    query: String,
+    tag: Option<String>,
+    limit: u32,
+) -> Result<Vec<HistoryEntry>, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history
+        .search(&query, tag.as_deref(), limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_history_tags(
+    app_handle: AppHandle,
+    entry_id: i64,
+    tags: Vec<String>,
+) -> Result<(), String> {
+    let history = app_handle.state::<History>().inner().clone();
+    match history.set_tags(entry_id, tags).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("History entry not found".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Every tag in use with its number of entries
+#[tauri::command]
+pub async fn get_history_tags(
+    app_handle: AppHandle,
+) -> Result<Vec<(String, u32)>, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.tags().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_history_range(
+    app_handle: AppHandle,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let history = app_handle.state::<History>().inner().clone();
+    history.range(from, to).await.map_err(|e| e.to_string())
+}
+
+/// Checks the key against the backend before storing it in the keychain
+#[tauri::command]
+pub async fn set_api_key(app_handle: AppHandle, api_key: String) -> Result<(), String> {
+    let api_key = api_key.trim();
+    let client = app_handle.state::<TranscribeClient>();
+    if let Err(e) = client.validate_api_key(api_key).await {
+        log::warn!("Rejected API key {}: {e:#}", api_key::mask(api_key));
+        return Err(if e.is::<Unauthorized>() {
+            "That API key was rejected".into()
+        } else {
+            format!("Couldn't verify the API key: {e:#}")
+        });
+    }
+    api_key::save(api_key).map_err(|e| format!("{e:#}"))?;
+    client.set_api_key(Some(api_key));
+    // The new key may belong to an account with credit
+    quota::resume(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn clear_api_key(app_handle: AppHandle) -> Result<(), String> {
+    api_key::clear().map_err(|e| format!("{e:#}"))?;
+    app_handle.state::<TranscribeClient>().set_api_key(None);
+    Ok(())
+}
+
+/// Records from the configured mic for `duration_secs` without transcribing.
+/// The live level is emitted as `mic-level` events while it runs.
+#[tauri::command]
+pub async fn test_microphone(
+    app_handle: AppHandle,
+    duration_secs: u64,
+    playback: bool,
+) -> Result<MicTestReport, String> {
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let (tx, rx) = oneshot::channel();
+    let task = Task::MicTest {
+        duration: Duration::from_secs(duration_secs.clamp(1, 30)),
+        playback,
+        tx,
+    };
+    tx_task.send(task).await.map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?.map_err(|e| format!("{e:#}"))
+}
+
+/// Rebuilds the audio recorder, answering with the device it now records from
+#[tauri::command]
+pub async fn reset_audio(app_handle: AppHandle) -> Result<String, String> {
+    reinit_audio(&app_handle).await.map_err(|e| format!("{e:#}"))
+}
+
+/// What the configured backend supports, for greying out options it ignores
+#[tauri::command]
+pub fn get_capabilities(app_handle: AppHandle) -> Capabilities {
+    app_handle.state::<TranscribeClient>().capabilities()
+}
+
+/// Sends a sample payload through the configured hook
+#[tauri::command]
+pub async fn test_hook(app_handle: AppHandle) -> Result<(), String> {
+    hook::test(&app_handle).await.map_err(|e| format!("{e:#}"))
+}
+
+/// Exports history between the optional bounds, returning the number of entries written
+#[tauri::command]
+pub async fn export_history(
+    app_handle: AppHandle,
+    path: PathBuf,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<usize, String> {
+    export_history_to(&app_handle, path, from, to)
+        .await
+        .map_err(|e| format!("{e:#}"))
+}
+
+pub async fn export_history_to(
+    app_handle: &AppHandle,
+    path: PathBuf,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<usize> {
+    let format = ExportFormat::from_path(&path)?;
+    let history = app_handle.state::<History>().inner().clone();
+    let header = ExportHeader {
+        app_version: app_handle.package_info().version.to_string(),
+        settings: (*app_handle.state::<SettingsState>().get()).clone(),
+    };
+    history_export::export(history, path, format, from, to, header).await
+}
+
+/// The last lines of the log file, the viewer then receives new ones as events
+#[tauri::command]
+pub fn read_log_tail(
+    app_handle: AppHandle,
+    max_lines: usize,
+) -> Result<LogChunk, String> {
+    log_viewer::tail(&app_handle, max_lines).map_err(|e| format!("{e:#}"))
+}
+
+/// What the running dictation session transcribed so far, for its window
+#[tauri::command]
+pub fn get_session_transcript(app_handle: AppHandle) -> String {
+    app_handle.state::<SessionTranscript>().text()
+}
+
+#[tauri::command]
+pub fn clear_session_transcript(app_handle: AppHandle) {
+    session_window::clear(&app_handle);
+}
+
+/// The apps the target picker offers, the one in front when recording stopped first
+#[tauri::command]
+pub fn list_targets(app_handle: AppHandle) -> Vec<frontmost_app::RunningApp> {
+    app_handle.state::<TargetPicker>().apps()
+}
+
+/// Answers the target picker, `None` delivers to the clipboard only
+#[tauri::command]
+pub fn choose_target(app_handle: AppHandle, id: Option<String>) {
+    target_picker::choose(&app_handle, id);
+}
+
+/// Copies the last lines of the log with app and OS details, ready for an issue
+#[tauri::command]
+pub fn copy_log_report(app_handle: AppHandle) -> Result<(), String> {
+    let report = log_viewer::report(&app_handle).map_err(|e| format!("{e:#}"))?;
+    app_handle.clipboard().write_text(report).map_err(|e| e.to_string())
+}
+
+/// The current settings, as saved in `settings.toml`
+#[tauri::command]
+pub fn get_settings(app_handle: AppHandle) -> Settings {
+    (*app_handle.state::<SettingsState>().get()).clone()
+}
+
+/// Merges `partial` into the settings, saves them and applies what can change live.
+/// Errors name the offending key in backticks for the UI to show next to it.
+#[tauri::command]
+pub fn set_settings(
+    app_handle: AppHandle,
+    partial: serde_json::Value,
+) -> Result<Settings, String> {
+    let old = app_handle.state::<SettingsState>().get();
+    let merged = settings::merge_json(&old, partial).map_err(|e| format!("{e:#}"))?;
+    let new = settings::update(&app_handle, |settings| *settings = merged)
+        .map_err(|e| format!("{e:#}"))?;
+    on_settings_changed(&app_handle, &old, &new);
+    // Applying can put values back, e.g. a refused launch at login
+    Ok((*app_handle.state::<SettingsState>().get()).clone())
+}
+
+/// Sets `behavior.polish_style`, for the settings window's style picker. A style that
+/// isn't built in or a prompt is kept with a warning, see `Settings::warn_unknown_style`.
+#[tauri::command]
+pub fn set_polish_style(app_handle: AppHandle, style: String) -> Result<(), String> {
+    app_handle.state::<SettingsState>().get().warn_unknown_style(&style);
+    set_behavior(&app_handle, |behavior| behavior.polish_style = style)
+}
+
+/// Sets `behavior.polish_language`, `None` goes back to the detected language or English
+#[tauri::command]
+pub fn set_polish_language(
+    app_handle: AppHandle,
+    language: Option<String>,
+) -> Result<(), String> {
+    let language = language.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+    set_behavior(&app_handle, |behavior| behavior.polish_language = language)
+}
+
+/// The one path the polish pickers change settings through, validated like `set_settings`
+fn set_behavior(
+    app_handle: &AppHandle,
+    f: impl FnOnce(&mut settings::BehaviorSettings),
+) -> Result<(), String> {
+    let old = app_handle.state::<SettingsState>().get();
+    let new = settings::update(app_handle, |settings| f(&mut settings.behavior))
+        .map_err(|e| format!("{e:#}"))?;
+    on_settings_changed(app_handle, &old, &new);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_app_overrides(app_handle: AppHandle) -> BTreeMap<String, AppOverride> {
+    app_handle.state::<SettingsState>().get().app_overrides.clone()
+}
+
+#[tauri::command]
+pub fn set_app_override(
+    app_handle: AppHandle,
+    app_id: String,
+    app_override: AppOverride,
+) -> Result<(), String> {
+    settings::update(&app_handle, |settings| {
+        settings.app_overrides.insert(app_id, app_override);
+    })
+    .map(|_| ())
+    .map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+pub fn remove_app_override(app_handle: AppHandle, app_id: String) -> Result<(), String> {
+    settings::update(&app_handle, |settings| {
+        settings.app_overrides.remove(&app_id);
+    })
+    .map(|_| ())
+    .map_err(|e| format!("{e:#}"))
+}
+
+/// Bundle id of the focused app, so the settings window can offer it as an override key
+#[tauri::command]
+pub fn get_frontmost_app() -> Option<String> {
+    frontmost_app::frontmost_app()
+}
+
+/// The API key is only included, encrypted, when a `passphrase` is given
+#[tauri::command]
+pub fn export_settings(
+    app_handle: AppHandle,
+    path: PathBuf,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    export_settings_to(&app_handle, &path, passphrase.as_deref())
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Dotted paths of the settings an import would change, without applying it
+#[tauri::command]
+pub fn preview_settings_import(
+    app_handle: AppHandle,
+    path: PathBuf,
+) -> Result<Vec<String>, String> {
+    preview_settings_import_from(&app_handle, &path).map_err(|e| format!("{e:#}"))
+}
+
+/// Returns the dotted paths of the settings that changed. The API key in the export,
+/// if any, is only imported when a `passphrase` is given.
+#[tauri::command]
+pub fn import_settings(
+    app_handle: AppHandle,
+    path: PathBuf,
+    passphrase: Option<String>,
+) -> Result<Vec<String>, String> {
+    import_settings_from(&app_handle, &path, passphrase.as_deref())
+        .map_err(|e| format!("{e:#}"))
+}
+
+pub fn export_settings_to(
+    app_handle: &AppHandle,
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let settings = app_handle.state::<SettingsState>().get();
+    let version = app_handle.package_info().version.to_string();
+    let api_key = match passphrase {
+        Some(passphrase) => {
+            let api_key = api_key::load().context("There is no API key to export")?;
+            Some(api_key::seal(&api_key, passphrase)?)
+        }
+        None => None,
+    };
+    let with_key = api_key.is_some();
+    std::fs::write(path, settings::export_json(&settings, &version, api_key)?)?;
+    log::info!(
+        "Exported settings to {}{}",
+        path.display(),
+        if with_key { " with the API key" } else { "" }
+    );
+    Ok(())
+}
+
+pub fn preview_settings_import_from(
+    app_handle: &AppHandle,
+    path: &Path,
+) -> Result<Vec<String>> {
+    let current = app_handle.state::<SettingsState>().get();
+    let (imported, _) = settings::import_json(&std::fs::read_to_string(path)?, &current)?;
+    Ok(settings::diff(&current, &imported))
+}
+
+/// Everything that can fail is checked before anything is applied, so a failed
+/// import leaves the current settings and API key alone
+pub fn import_settings_from(
+    app_handle: &AppHandle,
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)?;
+    let old = app_handle.state::<SettingsState>().get();
+    let (imported, sealed_key) = settings::import_json(&contents, &old)?;
+    let api_key = match (sealed_key, passphrase) {
+        (Some(sealed), Some(passphrase)) => Some(api_key::unseal(&sealed, passphrase)?),
+        (Some(_), None) => {
+            log::info!("Export contains an API key, skipping it without a passphrase");
+            None
+        }
+        (None, _) => None,
+    };
+
+    let changed = settings::diff(&old, &imported);
+    let new = settings::update(app_handle, |settings| *settings = imported)?;
+    on_settings_changed(app_handle, &old, &new);
+
+    if let Some(api_key) = api_key {
+        api_key::save(&api_key)?;
+        app_handle.state::<TranscribeClient>().set_api_key(Some(&api_key));
+    }
+
+    log::info!(
+        "Imported settings from {} ({} changed)",
+        path.display(),
+        changed.len()
+    );
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EntryKind;
+    use crate::polish_diff::DiffKind;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    #[test]
+    fn pipeline_state_serializes_stably() {
+        let state = PipelineState {
+            state: "recording",
+            recording: true,
+            polishing: false,
+            session: false,
+            live: false,
+            collecting: Some("Collecting: 3 segments, 148 words".into()),
+            privacy_mode: true,
+            quota_paused: false,
+        };
+        assert_eq!(
+            serde_json::to_value(state).unwrap(),
+            json!({
+                "state": "recording",
+                "recording": true,
+                "polishing": false,
+                "session": false,
+                "live": false,
+                "collecting": "Collecting: 3 segments, 148 words",
+                "privacy_mode": true,
+                "quota_paused": false,
+            })
+        );
+    }
+
+    #[test]
+    fn polish_diff_serializes_stably() {
+        let diff = PolishDiff {
+            ops: vec![
+                DiffOp {
+                    kind: DiffKind::Equal,
+                    text: "see you".into(),
+                },
+                DiffOp {
+                    kind: DiffKind::Delete,
+                    text: "tmrw".into(),
+                },
+                DiffOp {
+                    kind: DiffKind::Insert,
+                    text: "tomorrow".into(),
+                },
+            ],
+            annotated: "see you [-tmrw-]{+tomorrow+}".into(),
+        };
+        assert_eq!(
+            serde_json::to_value(diff).unwrap(),
+            json!({
+                "ops": [
+                    { "kind": "equal", "text": "see you" },
+                    { "kind": "delete", "text": "tmrw" },
+                    { "kind": "insert", "text": "tomorrow" },
+                ],
+                "annotated": "see you [-tmrw-]{+tomorrow+}",
+            })
+        );
+    }
+
+    #[test]
+    fn history_entry_serializes_stably() {
+        let created_at = Utc.with_ymd_and_hms(2024, 1, 31, 10, 0, 0).unwrap();
+        let entry = HistoryEntry {
+            id: 7,
+            created_at,
+            kind: EntryKind::Polish,
+            text: "Hello there.".into(),
+            audio_duration_ms: None,
+            word_count: 2,
+            provider: Some("openai".into()),
+            model: None,
+            pinned: false,
+            source_text: Some("hello there".into()),
+            pin_title: None,
+            pin_auto_paste: false,
+            tags: vec!["work".into()],
+            language: Some("en".into()),
+            recorded_at: None,
+            device: None,
+            sample_rate: None,
+            partial: false,
+        };
+        assert_eq!(
+            serde_json::to_value(entry).unwrap(),
+            json!({
+                "id": 7,
+                "created_at": "2024-01-31T10:00:00Z",
+                "kind": "polish",
+                "text": "Hello there.",
+                "audio_duration_ms": null,
+                "word_count": 2,
+                "provider": "openai",
+                "model": null,
+                "pinned": false,
+                "source_text": "hello there",
+                "pin_title": null,
+                "pin_auto_paste": false,
+                "tags": ["work"],
+                "language": "en",
+                "recorded_at": null,
+                "device": null,
+                "sample_rate": null,
+                "partial": false,
+            })
+        );
+    }
+
+    #[test]
+    fn encryption_status_serializes_stably() {
+        let status = EncryptionStatus {
+            mode: EncryptionMode::Passphrase,
+            locked: true,
+        };
+        assert_eq!(
+            serde_json::to_value(status).unwrap(),
+            json!({ "mode": "passphrase", "locked": true })
+        );
+    }
+}
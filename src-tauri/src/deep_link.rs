@@ -0,0 +1,92 @@
+use crate::PolishOverrides;
+use crate::notifications::{AppNotifications, Notification};
+use anyhow::{Result, bail};
+use tauri::{AppHandle, Url};
+
+/// Registered for the app in `tauri.conf.json`, e.g. `transcribe://toggle`
+pub const SCHEME: &str = "transcribe";
+pub const MAX_STYLE_CHARS: usize = 64;
+const MAX_PROMPT_CHARS: usize = 2000;
+
+/// What a deep link asks for. The action is the URL's host.
+#[derive(Debug)]
+pub enum Action {
+    /// `transcribe://toggle`
+    Toggle,
+    /// `transcribe://polish?style=formal` or `transcribe://polish?prompt=...`
+    Polish(PolishOverrides),
+    /// `transcribe://cancel`
+    Cancel,
+    /// `transcribe://paste-last`
+    PasteLast,
+}
+
+/// Only the scheme and action make it into logs and notifications, query values may
+/// carry the user's own text
+pub fn open_urls(app_handle: &AppHandle, urls: Vec<Url>) {
+    for url in urls {
+        let action = url.host_str().unwrap_or_default();
+        log::info!("Opening {SCHEME}://{}", truncate(action, MAX_STYLE_CHARS));
+        match parse(&url) {
+            Ok(action) => run(app_handle, action),
+            Err(e) => {
+                log::warn!("Ignoring deep link: {e:#}");
+                AppNotifications::new(app_handle)
+                    .notify(Notification::DeepLinkFailed(e.to_string()));
+            }
+        }
+    }
+}
+
+pub fn parse(url: &Url) -> Result<Action> {
+    if url.scheme() != SCHEME {
+        bail!("Not a {SCHEME}:// link");
+    }
+    let action = url.host_str().unwrap_or_default();
+    match action {
+        "toggle" => Ok(Action::Toggle),
+        "cancel" => Ok(Action::Cancel),
+        "paste-last" => Ok(Action::PasteLast),
+        "polish" => Ok(Action::Polish(polish_overrides(url)?)),
+        _ => bail!("Unknown action {:?}", truncate(action, MAX_STYLE_CHARS)),
+    }
+}
+
+/// Reads `style` or `prompt` from the query, shared with the automation server
+pub fn polish_overrides(url: &Url) -> Result<PolishOverrides> {
+    let mut overrides = PolishOverrides::default();
+    // Values come percent-decoded
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "style" => overrides.style = Some(limited("style", &value, MAX_STYLE_CHARS)?),
+            "prompt" => {
+                overrides.prompt = Some(limited("prompt", &value, MAX_PROMPT_CHARS)?)
+            }
+            _ => bail!("Unknown parameter {:?}", truncate(&key, MAX_STYLE_CHARS)),
+        }
+    }
+    Ok(overrides)
+}
+
+fn run(app_handle: &AppHandle, action: Action) {
+    match action {
+        Action::Toggle => crate::toggle_recording(app_handle.clone(), None),
+        Action::Polish(overrides) => {
+            crate::cleanse_clipboard(app_handle.clone(), None, overrides)
+        }
+        Action::Cancel => crate::cancel_recording(app_handle.clone()),
+        Action::PasteLast => crate::paste_latest_entry(app_handle.clone()),
+    }
+}
+
+/// Rejects values past `max` chars without echoing them
+pub fn limited(name: &str, value: &str, max: usize) -> Result<String> {
+    if value.chars().count() > max {
+        bail!("`{name}` is longer than {max} characters");
+    }
+    Ok(value.to_string())
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
@@ -0,0 +1,124 @@
+use crate::notifications::{AppNotifications, Notification};
+use crate::paths::recordings_dir;
+use crate::settings::SettingsState;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager, async_runtime::spawn_blocking};
+
+const MB: u64 = 1024 * 1024;
+
+/// Space taken by saved recordings, against `retention.disk_budget_mb`
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskUsage {
+    pub used_bytes: u64,
+    /// 0 when there's no budget
+    pub budget_bytes: u64,
+}
+
+struct Artifact {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+pub async fn usage(app_handle: &AppHandle) -> Result<DiskUsage> {
+    let recordings = recordings_dir(app_handle)?;
+    let budget_bytes = budget(app_handle);
+    spawn_blocking(move || {
+        let used_bytes = artifacts(&recordings)?.iter().map(|a| a.size).sum();
+        Ok(DiskUsage {
+            used_bytes,
+            budget_bytes,
+        })
+    })
+    .await?
+}
+
+/// Deletes the oldest recordings until usage fits the budget, in the background. If
+/// some can't be deleted and it still doesn't fit, the user is told.
+pub fn enforce(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        match enforce_(&app_handle).await {
+            Ok(usage)
+                if usage.budget_bytes > 0 && usage.used_bytes > usage.budget_bytes =>
+            {
+                log::warn!(
+                    "Still using {} MB, over the {} MB disk budget",
+                    usage.used_bytes / MB,
+                    usage.budget_bytes / MB
+                );
+                AppNotifications::new(&app_handle).notify(
+                    Notification::DiskBudgetExceeded {
+                        used_mb: usage.used_bytes / MB,
+                        budget_mb: usage.budget_bytes / MB,
+                    },
+                );
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Disk budget cleanup failed: {e:#}"),
+        }
+    });
+}
+
+async fn enforce_(app_handle: &AppHandle) -> Result<DiskUsage> {
+    let recordings = recordings_dir(app_handle)?;
+    let budget_bytes = budget(app_handle);
+    spawn_blocking(move || {
+        let mut deletable = artifacts(&recordings)?;
+        let mut used_bytes = deletable.iter().map(|a| a.size).sum::<u64>();
+
+        if budget_bytes > 0 && used_bytes > budget_bytes {
+            deletable.sort_by_key(|a| a.modified);
+            for artifact in deletable {
+                if used_bytes <= budget_bytes {
+                    break;
+                }
+                match std::fs::remove_file(&artifact.path) {
+                    Ok(()) => {
+                        used_bytes -= artifact.size;
+                        log::info!(
+                            "Removed {} ({} KB) to stay within the disk budget",
+                            artifact.path.display(),
+                            artifact.size / 1024
+                        );
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to delete {}: {}", artifact.path.display(), e)
+                    }
+                }
+            }
+        }
+
+        Ok(DiskUsage {
+            used_bytes,
+            budget_bytes,
+        })
+    })
+    .await?
+}
+
+fn budget(app_handle: &AppHandle) -> u64 {
+    app_handle.state::<SettingsState>().get().retention.disk_budget_mb * MB
+}
+
+fn artifacts(dir: &Path) -> Result<Vec<Artifact>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut artifacts = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        artifacts.push(Artifact {
+            path: entry.path(),
+            size: metadata.len(),
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+    Ok(artifacts)
+}
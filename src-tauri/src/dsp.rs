@@ -0,0 +1,116 @@
+use crate::settings::AudioSettings;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// Noise suppression looks at the audio in frames this long
+const FRAME_MS: u32 = 20;
+/// The noise floor is taken from the quietest tenth of frames
+const FLOOR_PERCENTILE: f32 = 0.1;
+/// Frames less than this far above the floor count as noise (about 6 dB)
+const OPEN_RATIO: f32 = 2.0;
+/// Noise is turned down by about 20 dB rather than muted, which sounds less choppy
+const NOISE_GAIN: f32 = 0.1;
+/// Frames kept open after speech so word endings aren't cut
+const HOLD_FRAMES: usize = 5;
+
+/// Filters run over a finished recording before it's encoded. Each is off unless
+/// turned on in `[audio]`, so the raw capture is sent by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Processing {
+    pub high_pass_hz: Option<f32>,
+    pub noise_suppression: bool,
+}
+
+impl Processing {
+    pub fn from_settings(audio: &AudioSettings) -> Self {
+        Self {
+            high_pass_hz: audio.high_pass.then_some(audio.high_pass_hz),
+            noise_suppression: audio.noise_suppression,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.high_pass_hz.is_some() || self.noise_suppression
+    }
+
+    /// `samples` are interleaved across `channels`
+    pub fn apply(&self, samples: &[i16], sample_rate: u32, channels: u16) -> Vec<i16> {
+        let mut audio: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+        let channels = channels.max(1) as usize;
+        if let Some(cutoff) = self.high_pass_hz {
+            high_pass(&mut audio, sample_rate, channels, cutoff);
+        }
+        if self.noise_suppression {
+            suppress_noise(&mut audio, sample_rate, channels);
+        }
+        audio
+            .into_iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * 32767.0) as i16)
+            .collect()
+    }
+}
+
+/// Second-order high-pass from the RBJ audio EQ cookbook, with Q = 1/√2 for a flat
+/// passband. Cuts rumble and handling noise below `cutoff` Hz.
+fn high_pass(audio: &mut [f32], sample_rate: u32, channels: usize, cutoff: f32) {
+    let cutoff = cutoff.min(sample_rate as f32 * 0.45);
+    let w0 = 2.0 * PI * cutoff / sample_rate as f32;
+    let alpha = w0.sin() / (2.0 * FRAC_1_SQRT_2);
+    let cos = w0.cos();
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos) / 2.0 / a0;
+    let b1 = -(1.0 + cos) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    for channel in 0..channels {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        for sample in audio.iter_mut().skip(channel).step_by(channels) {
+            let x = *sample;
+            let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+            (x2, x1) = (x1, x);
+            (y2, y1) = (y1, y);
+            *sample = y;
+        }
+    }
+}
+
+/// A gate that learns the noise floor from the quietest frames and turns down frames
+/// close to it, like fan hum between sentences. Gain changes are ramped over a frame
+/// so they don't click.
+fn suppress_noise(audio: &mut [f32], sample_rate: u32, channels: usize) {
+    let frame_len = (sample_rate * FRAME_MS / 1000) as usize * channels;
+    if frame_len == 0 || audio.len() < frame_len * 2 {
+        return;
+    }
+    let levels: Vec<f32> = audio
+        .chunks(frame_len)
+        .map(|frame| {
+            (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt()
+        })
+        .collect();
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let floor = sorted[((sorted.len() - 1) as f32 * FLOOR_PERCENTILE) as usize];
+    let threshold = floor * OPEN_RATIO;
+
+    let mut held = 0;
+    let mut gain = 1.0;
+    for (frame, level) in audio.chunks_mut(frame_len).zip(levels) {
+        let target = if level > threshold {
+            held = HOLD_FRAMES;
+            1.0
+        } else if held > 0 {
+            held -= 1;
+            1.0
+        } else {
+            NOISE_GAIN
+        };
+        let step = (target - gain) / frame.len() as f32;
+        for sample in frame {
+            gain += step;
+            *sample *= gain;
+        }
+        gain = target;
+    }
+}
@@ -1,5 +1,32 @@
 use anyhow::Result;
 use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::time::Duration;
+
+/// The physical V key, so the chord still reads as paste on AZERTY or Dvorak where
+/// the character 'v' sits elsewhere or needs a different key
+#[cfg(target_os = "macos")]
+const V_KEY: Key = Key::Other(0x09); // kVK_ANSI_V
+#[cfg(windows)]
+const V_KEY: Key = Key::Other(0x56); // VK_V
+/// X11 and Wayland shortcuts match on the keysym, which every layout maps
+#[cfg(not(any(target_os = "macos", windows)))]
+const V_KEY: Key = Key::Other(0x0076); // XK_v
+#[cfg(target_os = "macos")]
+const Z_KEY: Key = Key::Other(0x06); // kVK_ANSI_Z
+#[cfg(windows)]
+const Z_KEY: Key = Key::Other(0x5A); // VK_Z
+#[cfg(not(any(target_os = "macos", windows)))]
+const Z_KEY: Key = Key::Other(0x007a); // XK_z
+
+#[cfg(target_os = "macos")]
+const SHORTCUT_MODIFIER: Key = Key::Meta;
+#[cfg(not(target_os = "macos"))]
+const SHORTCUT_MODIFIER: Key = Key::Control;
+
+/// Modifiers from the hotkey that may still be held when the chord is sent
+const MODIFIERS: [Key; 4] = [Key::Shift, Key::Alt, Key::Control, Key::Meta];
+/// Lets the target app see the modifiers go up before the chord
+const CHORD_DELAY: Duration = Duration::from_millis(20);
 
 pub struct EnigoInstance {
     enigo: Enigo,
@@ -13,16 +40,166 @@ impl EnigoInstance {
     }
 
     pub fn paste_from_clipboard(&mut self) -> Result<()> {
-        self.enigo.key(Key::Meta, Direction::Press)?;
-        self.enigo.key(Key::Unicode('v'), Direction::Click)?;
-        self.enigo.key(Key::Meta, Direction::Release)?;
+        self.chord(V_KEY)
+    }
+
+    /// Presses Cmd/Ctrl+Z `count` times
+    pub fn undo(&mut self, count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.chord(Z_KEY)?;
+        }
         Ok(())
     }
 
-    pub fn undo_text(&mut self) -> Result<()> {
-        self.enigo.key(Key::Meta, Direction::Press)?;
-        self.enigo.key(Key::Unicode('z'), Direction::Click)?;
-        self.enigo.key(Key::Meta, Direction::Release)?;
+    pub fn new_line(&mut self) -> Result<()> {
+        self.enigo.key(Key::Return, Direction::Click)?;
         Ok(())
     }
+
+    /// Sends Cmd/Ctrl + `key` with no other modifiers held, so Cmd+Option+R still
+    /// being down doesn't turn paste into Cmd+Option+V
+    fn chord(&mut self, key: Key) -> Result<()> {
+        // An input method mid-composition can swallow the chord into its candidates
+        #[cfg(target_os = "macos")]
+        let _ascii_input = ime::AsciiInput::select();
+        for modifier in MODIFIERS {
+            self.enigo.key(modifier, Direction::Release)?;
+        }
+        std::thread::sleep(CHORD_DELAY);
+        self.enigo.key(SHORTCUT_MODIFIER, Direction::Press)?;
+        let result = self.enigo.key(key, Direction::Click);
+        // Never leave the modifier stuck down
+        self.enigo.key(SHORTCUT_MODIFIER, Direction::Release)?;
+        result?;
+        Ok(())
+    }
+
+    /// Presses backspace `count` times
+    pub fn erase(&mut self, count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.enigo.key(Key::Backspace, Direction::Click)?;
+        }
+        Ok(())
+    }
+
+    /// Types `text` into the focused app as individual keystrokes
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        self.enigo.text(text)?;
+        Ok(())
+    }
+}
+
+/// Japanese, Chinese and Korean input methods are swapped for the user's ASCII layout
+/// while a chord is sent. Switching input source ends any composition in progress.
+/// Text Input Sources has no Objective-C API, so it's called directly.
+#[cfg(target_os = "macos")]
+mod ime {
+    use std::ffi::c_void;
+
+    type CFTypeRef = *const c_void;
+
+    #[repr(C)]
+    struct DispatchQueue {
+        _private: [u8; 0],
+    }
+
+    #[link(name = "Carbon", kind = "framework")]
+    unsafe extern "C" {
+        static kTISPropertyInputSourceType: CFTypeRef;
+        static kTISTypeKeyboardInputMode: CFTypeRef;
+        fn TISCopyCurrentKeyboardInputSource() -> CFTypeRef;
+        fn TISCopyCurrentASCIICapableKeyboardInputSource() -> CFTypeRef;
+        fn TISGetInputSourceProperty(source: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        fn TISSelectInputSource(source: CFTypeRef) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFEqual(a: CFTypeRef, b: CFTypeRef) -> u8;
+        fn CFRelease(cf: CFTypeRef);
+    }
+
+    unsafe extern "C" {
+        static _dispatch_main_q: DispatchQueue;
+        fn dispatch_sync_f(
+            queue: *const DispatchQueue,
+            context: *mut c_void,
+            work: extern "C" fn(*mut c_void),
+        );
+        fn pthread_main_np() -> i32;
+    }
+
+    /// The input method that was active, selected again on drop
+    pub struct AsciiInput {
+        previous: CFTypeRef,
+    }
+
+    impl AsciiInput {
+        /// `None` when the current source is already a plain keyboard layout
+        pub fn select() -> Option<Self> {
+            // SAFETY: every copied source is released or kept in the returned value,
+            // and the TIS calls run on the main thread as macOS requires
+            on_main_thread(|| unsafe {
+                let current = TISCopyCurrentKeyboardInputSource();
+                if current.is_null() {
+                    return None;
+                }
+                let kind =
+                    TISGetInputSourceProperty(current, kTISPropertyInputSourceType);
+                if kind.is_null() || CFEqual(kind, kTISTypeKeyboardInputMode) == 0 {
+                    CFRelease(current);
+                    return None;
+                }
+                let ascii = TISCopyCurrentASCIICapableKeyboardInputSource();
+                if ascii.is_null() {
+                    CFRelease(current);
+                    return None;
+                }
+                let status = TISSelectInputSource(ascii);
+                CFRelease(ascii);
+                if status != 0 {
+                    log::warn!("Failed to switch away from the input method ({status})");
+                    CFRelease(current);
+                    return None;
+                }
+                Some(Self { previous: current })
+            })
+        }
+    }
+
+    impl Drop for AsciiInput {
+        fn drop(&mut self) {
+            let previous = self.previous;
+            // SAFETY: `previous` was copied in `select` and is released once, here
+            on_main_thread(|| unsafe {
+                TISSelectInputSource(previous);
+                CFRelease(previous);
+            });
+        }
+    }
+
+    /// Runs `f` on the main thread and waits for it. The main thread is never blocked
+    /// on the task handler, so this can't deadlock.
+    fn on_main_thread<F: FnOnce() -> T, T>(f: F) -> T {
+        extern "C" fn trampoline<F: FnOnce() -> T, T>(context: *mut c_void) {
+            // SAFETY: `context` points at the tuple below, alive until dispatch returns
+            let (f, out) = unsafe { &mut *(context as *mut (Option<F>, Option<T>)) };
+            *out = f.take().map(|f| f());
+        }
+
+        // SAFETY: no preconditions
+        if unsafe { pthread_main_np() } != 0 {
+            return f();
+        }
+        let mut context = (Some(f), None);
+        // SAFETY: dispatch_sync_f returns only after `trampoline` ran on `context`
+        unsafe {
+            dispatch_sync_f(
+                &raw const _dispatch_main_q,
+                &mut context as *mut _ as *mut c_void,
+                trampoline::<F, T>,
+            );
+        }
+        context.1.expect("ran on the main thread")
+    }
 }
@@ -0,0 +1,62 @@
+use crate::settings::FillerSettings;
+
+/// What's left of a transcription after the filler filter ran
+#[derive(Debug, PartialEq)]
+pub enum FillerOutcome {
+    /// Nothing but filler words and whitespace
+    NoSpeech,
+    Text(String),
+}
+
+pub fn apply(text: String, filler: &FillerSettings) -> FillerOutcome {
+    if !filler.drop_filler_only && !filler.strip_edges {
+        return FillerOutcome::Text(text);
+    }
+
+    let is_filler = |token: &str| {
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+        word.is_empty() || filler.words.iter().any(|f| f.eq_ignore_ascii_case(word))
+    };
+
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    if tokens.iter().all(|t| is_filler(t)) {
+        if filler.drop_filler_only {
+            return FillerOutcome::NoSpeech;
+        }
+        return FillerOutcome::Text(text);
+    }
+
+    if !filler.strip_edges {
+        return FillerOutcome::Text(text);
+    }
+
+    let start = tokens.iter().position(|t| !is_filler(t)).unwrap_or(0);
+    let end = tokens
+        .iter()
+        .rposition(|t| !is_filler(t))
+        .map_or(tokens.len(), |i| i + 1);
+    if start == 0 && end == tokens.len() {
+        return FillerOutcome::Text(text);
+    }
+
+    let mut stripped = tokens[start..end].join(" ");
+    if end < tokens.len() {
+        // "Thanks, um." -> "Thanks."
+        let ending =
+            tokens[tokens.len() - 1].chars().last().filter(|c| ".!?".contains(*c));
+        stripped = stripped.trim_end_matches([',', ';']).to_string();
+        if let Some(ending) = ending.filter(|_| !stripped.ends_with(['.', '!', '?'])) {
+            stripped.push(ending);
+        }
+    }
+    if start > 0 {
+        // "Um, so what" -> "So what"
+        let mut chars = stripped.chars();
+        if let Some(first) = chars.next() {
+            stripped = first.to_uppercase().chain(chars).collect();
+        }
+    }
+
+    log::debug!("Stripped filler words from the transcription edges");
+    FillerOutcome::Text(stripped)
+}
@@ -0,0 +1,248 @@
+use anyhow::{Result, bail};
+
+/// Identifies the focused application: its bundle id on macOS.
+/// Returns `None` where this can't be determined.
+pub fn frontmost_app() -> Option<String> {
+    match frontmost_app_() {
+        Ok(app) => Some(app),
+        Err(e) => {
+            log::debug!("Could not determine the frontmost app: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn frontmost_app_() -> Result<String> {
+    let output = std::process::Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to get bundle identifier of first \
+             application process whose frontmost is true",
+        ])
+        .output()?;
+    let app = String::from_utf8(output.stdout)?.trim().to_string();
+    if app.is_empty() {
+        bail!("osascript returned no bundle identifier");
+    }
+    Ok(app)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn frontmost_app_() -> Result<String> {
+    bail!("Not supported on this platform")
+}
+
+/// Whether the keyboard focus is in a password field. Only a field that reports
+/// itself as secure text counts, so one that can't be checked is taken as ordinary.
+pub fn secure_field_focused() -> bool {
+    match secure_field_focused_() {
+        Ok(secure) => secure,
+        Err(e) => {
+            log::debug!("Could not check the focused field: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn secure_field_focused_() -> Result<bool> {
+    let output = std::process::Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to get subrole of (value of attribute \
+             \"AXFocusedUIElement\" of first application process whose frontmost is true)",
+        ])
+        .output()?;
+    Ok(String::from_utf8(output.stdout)?.trim() == "AXSecureTextField")
+}
+
+#[cfg(not(target_os = "macos"))]
+fn secure_field_focused_() -> Result<bool> {
+    bail!("Not supported on this platform")
+}
+
+/// An app with a window the user could send text to. The id is a bundle id on macOS,
+/// a process id on Windows and an X11 window id on Linux.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RunningApp {
+    pub id: String,
+    pub name: String,
+}
+
+/// Apps that show up in the Dock and app switcher, or on Windows and Linux the
+/// windows in the taskbar. Never this app's own. Empty where this can't be determined,
+/// e.g. on Wayland or without `wmctrl`.
+pub fn running_apps() -> Vec<RunningApp> {
+    match running_apps_() {
+        Ok(apps) => apps,
+        Err(e) => {
+            log::debug!("Could not list running apps: {e}");
+            vec![]
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn running_apps_() -> Result<Vec<RunningApp>> {
+    let output = std::process::Command::new("osascript")
+        .args([
+            "-e",
+            "set out to \"\"\n\
+             tell application \"System Events\"\n\
+             repeat with p in (every application process whose background only is false)\n\
+             set out to out & (bundle identifier of p) & tab & (name of p) & linefeed\n\
+             end repeat\n\
+             end tell\n\
+             return out",
+        ])
+        .output()?;
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(|line| {
+            let (id, name) = line.split_once('\t')?;
+            (id != "missing value").then(|| RunningApp {
+                id: id.into(),
+                name: name.into(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+fn running_apps_() -> Result<Vec<RunningApp>> {
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "[Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+             Get-Process | Where-Object { $_.MainWindowTitle } | \
+             ForEach-Object { \"$($_.Id)`t$($_.MainWindowTitle)\" }",
+        ])
+        .output()?;
+    let own_pid = std::process::id().to_string();
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (id, name) = line.split_once('\t')?;
+            (id != own_pid).then(|| RunningApp {
+                id: id.into(),
+                name: name.into(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn running_apps_() -> Result<Vec<RunningApp>> {
+    let output = std::process::Command::new("wmctrl").arg("-lp").output()?;
+    if !output.status.success() {
+        bail!("wmctrl failed, the window manager may not support it");
+    }
+    let own_pid = std::process::id().to_string();
+    Ok(wmctrl_windows(&String::from_utf8_lossy(&output.stdout), &own_pid))
+}
+
+/// Windows listed by `wmctrl -lp`, one per line: id, desktop, pid, host, then the
+/// title. Columns are padded, and the title may hold any whitespace.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn wmctrl_windows(output: &str, own_pid: &str) -> Vec<RunningApp> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut rest = line;
+            let mut fields = [""; 4];
+            for field in &mut fields {
+                rest = rest.trim_start();
+                let end = rest.find(char::is_whitespace)?;
+                (*field, rest) = rest.split_at(end);
+            }
+            let [id, desktop, pid, _host] = fields;
+            let name = rest.trim();
+            // Desktop -1 holds panels and docks, which show on every desktop
+            (desktop != "-1" && pid != own_pid && !name.is_empty()).then(|| RunningApp {
+                id: id.into(),
+                name: name.into(),
+            })
+        })
+        .collect()
+}
+
+/// Brings the app with this bundle id to the front
+#[cfg(target_os = "macos")]
+pub fn activate(id: &str) -> Result<()> {
+    // Interpolated into the script, so only bundle id characters are let through
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+    {
+        bail!("{id:?} is not a bundle identifier");
+    }
+    let status = std::process::Command::new("osascript")
+        .args(["-e", &format!("tell application id \"{id}\" to activate")])
+        .status()?;
+    if !status.success() {
+        bail!("osascript failed to activate {id}");
+    }
+    Ok(())
+}
+
+/// Brings the main window of the process with this id to the front
+#[cfg(windows)]
+pub fn activate(id: &str) -> Result<()> {
+    let Ok(pid) = id.parse::<u32>() else {
+        bail!("{id:?} is not a process id");
+    };
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("(New-Object -ComObject WScript.Shell).AppActivate({pid})"),
+        ])
+        .output()?;
+    if String::from_utf8_lossy(&output.stdout).trim() != "True" {
+        bail!("Failed to activate process {pid}");
+    }
+    Ok(())
+}
+
+/// Raises and focuses the X11 window with this id
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn activate(id: &str) -> Result<()> {
+    if !id.starts_with("0x") || !id[2..].chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("{id:?} is not a window id");
+    }
+    let status = std::process::Command::new("wmctrl").args(["-ia", id]).status()?;
+    if !status.success() {
+        bail!("wmctrl failed to activate {id}");
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(any(target_os = "macos", windows))))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_wmctrl_windows() {
+        let output = "\
+0x02e00003 -1 1201   laptop Top Panel
+0x03a00003  0 2233   laptop Inbox  -  Mozilla Thunderbird
+0x04c0000a  1 4455   laptop notes.md — Text Editor
+0x05000001  0 999    laptop whisper-app
+0x06000002  0 3000   laptop
+";
+        let windows = wmctrl_windows(output, "999");
+        let listed: Vec<(&str, &str)> = windows
+            .iter()
+            .map(|app| (app.id.as_str(), app.name.as_str()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("0x03a00003", "Inbox  -  Mozilla Thunderbird"),
+                ("0x04c0000a", "notes.md — Text Editor"),
+            ]
+        );
+    }
+}
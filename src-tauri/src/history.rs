@@ -0,0 +1,917 @@
+use crate::audio_recorder::RecordingInfo;
+use crate::history_cipher::{
+    self, EncryptionMode, Locked, RowCipher, WrongPassphrase, is_encrypted,
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params, types::Value};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::async_runtime::spawn_blocking;
+
+const DB_FILE: &str = "history.sqlite3";
+/// Separates tags in the aggregated `tags` column, never part of a tag itself
+const TAG_SEPARATOR: char = '\u{1f}';
+const TAG_MAX_CHARS: usize = 32;
+
+/// Each entry upgrades the schema by one version (tracked in `PRAGMA user_version`).
+/// Never edit an existing entry, append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        created_at TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        text TEXT NOT NULL,
+        audio_duration_ms INTEGER,
+        word_count INTEGER NOT NULL,
+        provider TEXT,
+        model TEXT
+    );
+    CREATE INDEX entries_created_at ON entries (created_at);",
+    "ALTER TABLE entries ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;",
+    "ALTER TABLE entries ADD COLUMN source_text TEXT;",
+    "CREATE TABLE daily_words (
+        day TEXT PRIMARY KEY,
+        words INTEGER NOT NULL
+    );",
+    "ALTER TABLE entries ADD COLUMN pin_title TEXT;
+    ALTER TABLE entries ADD COLUMN pin_auto_paste INTEGER NOT NULL DEFAULT 0;",
+    "CREATE TABLE entry_tags (
+        entry_id INTEGER NOT NULL REFERENCES entries (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (entry_id, tag)
+    );
+    CREATE INDEX entry_tags_tag ON entry_tags (tag);",
+    "CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    "ALTER TABLE entries ADD COLUMN language TEXT;",
+    "ALTER TABLE entries ADD COLUMN recorded_at TEXT;
+    ALTER TABLE entries ADD COLUMN device TEXT;
+    ALTER TABLE entries ADD COLUMN sample_rate INTEGER;",
+    "ALTER TABLE entries ADD COLUMN partial INTEGER NOT NULL DEFAULT 0;",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Transcription,
+    Polish,
+    Summary,
+    Translation,
+}
+
+impl EntryKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::Transcription => "transcription",
+            EntryKind::Polish => "polish",
+            EntryKind::Summary => "summary",
+            EntryKind::Translation => "translation",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "transcription" => EntryKind::Transcription,
+            "polish" => EntryKind::Polish,
+            "summary" => EntryKind::Summary,
+            "translation" => EntryKind::Translation,
+            other => bail!("Unknown history entry kind: {other}"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub kind: EntryKind,
+    pub text: String,
+    pub audio_duration_ms: Option<u64>,
+    pub word_count: u32,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Pinned entries are kept by the retention policy
+    pub pinned: bool,
+    /// The text a polish started from, used to show what it changed
+    pub source_text: Option<String>,
+    /// Shown in the "Pinned" menu instead of the text
+    pub pin_title: Option<String>,
+    /// Paste into the focused app when picked from the "Pinned" menu
+    pub pin_auto_paste: bool,
+    pub tags: Vec<String>,
+    /// Detected by the transcription model, when the backend reports it
+    pub language: Option<String>,
+    /// When recording started, for entries transcribed from the microphone
+    pub recorded_at: Option<DateTime<Utc>>,
+    /// Input device the recording came from
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    /// What was transcribed before a dictation was cancelled, not the whole of it
+    pub partial: bool,
+}
+
+impl HistoryEntry {
+    /// The recording it was transcribed from, for entries that came from the microphone
+    pub fn recording_info(&self) -> Option<RecordingInfo> {
+        Some(RecordingInfo {
+            started_at: self.recorded_at?,
+            duration: self.audio_duration_ms.map(std::time::Duration::from_millis),
+            device: self.device.clone(),
+            sample_rate: self.sample_rate,
+            language: self.language.clone(),
+            model: self.model.clone(),
+        })
+    }
+}
+
+/// An entry about to be written. `id`, `created_at` and `word_count` are filled in on insert.
+#[derive(Debug, Clone)]
+pub struct NewEntry {
+    pub kind: EntryKind,
+    pub text: String,
+    pub audio_duration_ms: Option<u64>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub source_text: Option<String>,
+    pub language: Option<String>,
+    pub recorded_at: Option<DateTime<Utc>>,
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub partial: bool,
+}
+
+impl NewEntry {
+    pub fn new(kind: EntryKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+            audio_duration_ms: None,
+            provider: None,
+            model: None,
+            source_text: None,
+            language: None,
+            recorded_at: None,
+            device: None,
+            sample_rate: None,
+            partial: false,
+        }
+    }
+
+    /// Fills in what's known about the recording the text was transcribed from
+    pub fn with_recording(self, info: &RecordingInfo) -> Self {
+        Self {
+            audio_duration_ms: info.duration.map(|d| d.as_millis() as u64),
+            model: info.model.clone(),
+            language: info.language.clone(),
+            recorded_at: Some(info.started_at),
+            device: info.device.clone(),
+            sample_rate: info.sample_rate,
+            ..self
+        }
+    }
+}
+
+/// Whether history is encrypted, and if so whether this session can read it
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub mode: EncryptionMode,
+    pub locked: bool,
+}
+
+/// The key for this session, worked out on the first access that needs it
+enum KeyState {
+    Unknown,
+    Plain,
+    Unlocked(Arc<RowCipher>),
+}
+
+/// Transcription history backed by SQLite. All queries run on the blocking pool.
+///
+/// Entry text can be encrypted per row. Only `text`, `source_text` and `pin_title`
+/// are encrypted, timestamps, counts and tags stay readable for sorting and stats.
+#[derive(Clone)]
+pub struct History {
+    conn: Arc<Mutex<Connection>>,
+    key: Arc<Mutex<KeyState>>,
+    /// Entries saved while locked, written once history is unlocked
+    pending: Arc<Mutex<Vec<NewEntry>>>,
+}
+
+impl History {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(DB_FILE);
+        let mut conn = Connection::open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        // Needed for tags to be deleted along with their entry
+        conn.pragma_update(None, "foreign_keys", true)?;
+        // Deleted and overwritten text is zeroed instead of lingering in free pages
+        conn.pragma_update(None, "secure_delete", true)?;
+        migrate(&mut conn)?;
+        log::info!("Opened history at {}", path.display());
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            key: Arc::new(Mutex::new(KeyState::Unknown)),
+            pending: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Like `with_conn`, also handing over the cipher if history is encrypted.
+    /// Fails with `Locked` until the passphrase has been entered.
+    async fn with_cipher<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection, Option<&RowCipher>) -> Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        let key = Arc::clone(&self.key);
+        spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap();
+            let cipher = load_cipher(&conn, &mut key.lock().unwrap())?;
+            f(&mut conn, cipher.as_deref())
+        })
+        .await?
+    }
+
+    pub async fn encryption(&self) -> Result<EncryptionStatus> {
+        let key = Arc::clone(&self.key);
+        self.with_conn(move |conn| {
+            let mode = read_mode(conn)?;
+            let locked = match load_cipher(conn, &mut key.lock().unwrap()) {
+                Ok(_) => false,
+                Err(e) if e.is::<Locked>() => true,
+                Err(e) => return Err(e),
+            };
+            Ok(EncryptionStatus { mode, locked })
+        })
+        .await
+    }
+
+    /// Checks the passphrase and keeps the key for the rest of the session, then
+    /// writes any entries saved while locked
+    pub async fn unlock(&self, passphrase: String) -> Result<()> {
+        let key = Arc::clone(&self.key);
+        self.with_conn(move |conn| {
+            if read_mode(conn)? != EncryptionMode::Passphrase {
+                return Ok(());
+            }
+            let salt = read_meta(conn, "salt")?.context("History salt is missing")?;
+            let verifier =
+                read_meta(conn, "verifier")?.context("History verifier is missing")?;
+            let cipher =
+                RowCipher::new(&history_cipher::passphrase_key(&passphrase, &salt)?);
+            if !cipher.verify(&verifier) {
+                bail!(WrongPassphrase);
+            }
+            *key.lock().unwrap() = KeyState::Unlocked(Arc::new(cipher));
+            log::info!("Unlocked history");
+            Ok(())
+        })
+        .await?;
+
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for entry in pending {
+            self.insert(entry).await?;
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts every entry under the new mode, or decrypts them for `Off`, in
+    /// one transaction. Needs history unlocked first when it uses a passphrase.
+    pub async fn set_encryption(
+        &self,
+        mode: EncryptionMode,
+        passphrase: Option<String>,
+    ) -> Result<()> {
+        let key = Arc::clone(&self.key);
+        self.with_conn(move |conn| {
+            let old_mode = read_mode(conn)?;
+            let old = load_cipher(conn, &mut key.lock().unwrap())?;
+
+            let mut salt = None;
+            let new = match mode {
+                EncryptionMode::Off => None,
+                EncryptionMode::Passphrase => {
+                    let passphrase = passphrase
+                        .filter(|p| !p.is_empty())
+                        .context("Enter a passphrase to encrypt history with")?;
+                    let new_salt = history_cipher::new_salt();
+                    let derived = history_cipher::passphrase_key(&passphrase, &new_salt)?;
+                    salt = Some(new_salt);
+                    Some(Arc::new(RowCipher::new(&derived)))
+                }
+                EncryptionMode::Keychain if old_mode == EncryptionMode::Keychain => {
+                    old.clone()
+                }
+                EncryptionMode::Keychain => Some(Arc::new(RowCipher::new(
+                    &history_cipher::create_keychain_key()?,
+                ))),
+            };
+
+            let tx = conn.transaction()?;
+            let rows = {
+                let mut stmt =
+                    tx.prepare("SELECT id, text, source_text, pin_title FROM entries")?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()?
+            };
+            for (id, text, source_text, pin_title) in rows {
+                let reseal =
+                    |value: String| seal(new.as_deref(), open(old.as_deref(), value)?);
+                tx.execute(
+                    "UPDATE entries SET text = ?2, source_text = ?3, pin_title = ?4
+                     WHERE id = ?1",
+                    params![
+                        id,
+                        reseal(text)?,
+                        source_text.map(reseal).transpose()?,
+                        pin_title.map(reseal).transpose()?,
+                    ],
+                )?;
+            }
+            write_meta(&tx, "encryption", Some(mode.as_str()))?;
+            write_meta(&tx, "salt", salt.as_deref())?;
+            let verifier = new.as_deref().map(RowCipher::verifier).transpose()?;
+            write_meta(&tx, "verifier", verifier.as_deref())?;
+            tx.commit()?;
+            scrub(conn)?;
+
+            if old_mode == EncryptionMode::Keychain && mode != EncryptionMode::Keychain {
+                history_cipher::clear_keychain_key()?;
+            }
+            *key.lock().unwrap() = match new {
+                Some(cipher) => KeyState::Unlocked(cipher),
+                None => KeyState::Plain,
+            };
+            log::info!("History encryption is now {}", mode.as_str());
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns once writes that already hold or wait for the connection are done
+    pub async fn flush(&self) -> Result<()> {
+        self.with_conn(|_| Ok(())).await
+    }
+
+    /// Entries saved while history is locked are kept in memory until it's unlocked
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        spawn_blocking(move || f(&mut conn.lock().unwrap())).await?
+    }
+
+    /// Fails with `Locked` while the passphrase hasn't been entered, the entry is
+    /// then kept and written on `unlock`
+    pub async fn insert(&self, entry: NewEntry) -> Result<HistoryEntry> {
+        let result = self.insert_(entry.clone()).await;
+        if result.as_ref().is_err_and(|e| e.is::<Locked>()) {
+            self.pending.lock().unwrap().push(entry);
+        }
+        result
+    }
+
+    async fn insert_(&self, entry: NewEntry) -> Result<HistoryEntry> {
+        self.with_cipher(move |conn, cipher| {
+            let created_at = Utc::now();
+            let word_count = entry.text.split_whitespace().count() as u32;
+            conn.execute(
+                "INSERT INTO entries
+                    (created_at, kind, text, audio_duration_ms, word_count, provider, model,
+                     source_text, language, recorded_at, device, sample_rate, partial)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    to_sql_time(&created_at),
+                    entry.kind.as_str(),
+                    seal(cipher, entry.text.clone())?,
+                    entry.audio_duration_ms,
+                    word_count,
+                    entry.provider,
+                    entry.model,
+                    entry
+                        .source_text
+                        .clone()
+                        .map(|text| seal(cipher, text))
+                        .transpose()?,
+                    entry.language,
+                    entry.recorded_at.as_ref().map(to_sql_time),
+                    entry.device,
+                    entry.sample_rate,
+                    entry.partial,
+                ],
+            )?;
+            Ok(HistoryEntry {
+                id: conn.last_insert_rowid(),
+                created_at,
+                kind: entry.kind,
+                text: entry.text,
+                audio_duration_ms: entry.audio_duration_ms,
+                word_count,
+                provider: entry.provider,
+                model: entry.model,
+                pinned: false,
+                source_text: entry.source_text,
+                pin_title: None,
+                pin_auto_paste: false,
+                tags: vec![],
+                language: entry.language,
+                recorded_at: entry.recorded_at,
+                device: entry.device,
+                sample_rate: entry.sample_rate,
+                partial: entry.partial,
+            })
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<HistoryEntry>> {
+        self.with_cipher(move |conn, cipher| {
+            conn.query_row(&format!("{SELECT_ENTRIES} WHERE id = ?1"), [id], from_row)
+                .optional()?
+                .map(|entry| open_entry(cipher, entry?))
+                .transpose()
+        })
+        .await
+    }
+
+    pub async fn recent(&self, limit: u32) -> Result<Vec<HistoryEntry>> {
+        self.query(
+            format!("{SELECT_ENTRIES} ORDER BY created_at DESC LIMIT ?1"),
+            vec![Value::Integer(limit.into())],
+        )
+        .await
+    }
+
+    /// Entries containing `needle`, optionally only those tagged `tag`.
+    /// Encrypted text can't be matched in SQL, so it's decrypted and filtered here.
+    pub async fn search(
+        &self,
+        needle: &str,
+        tag: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<HistoryEntry>> {
+        let needle = needle.to_lowercase();
+        let tag = tag.map(normalize_tag);
+        self.with_cipher(move |conn, cipher| {
+            let mut stmt = conn.prepare(&format!(
+                "{SELECT_ENTRIES}
+                 WHERE ?1 IS NULL OR id IN (SELECT entry_id FROM entry_tags WHERE tag = ?1)
+                 ORDER BY created_at DESC"
+            ))?;
+            let mut entries = Vec::new();
+            for row in stmt.query_map([tag], from_row)? {
+                if entries.len() >= limit as usize {
+                    break;
+                }
+                let entry = open_entry(cipher, row??)?;
+                if entry.text.to_lowercase().contains(&needle) {
+                    entries.push(entry);
+                }
+            }
+            Ok(entries)
+        })
+        .await
+    }
+
+    /// Replaces the tags of an entry, returning false if it doesn't exist
+    pub async fn set_tags(&self, id: i64, tags: Vec<String>) -> Result<bool> {
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            let exists = tx
+                .query_row("SELECT 1 FROM entries WHERE id = ?1", [id], |_| Ok(()))
+                .optional()?
+                .is_some();
+            if !exists {
+                return Ok(false);
+            }
+            tx.execute("DELETE FROM entry_tags WHERE entry_id = ?1", [id])?;
+            for tag in tags.iter().map(|tag| normalize_tag(tag)) {
+                if tag.is_empty() {
+                    continue;
+                }
+                tx.execute(
+                    "INSERT OR IGNORE INTO entry_tags (entry_id, tag) VALUES (?1, ?2)",
+                    params![id, tag],
+                )?;
+            }
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Adds one tag to an entry, keeping its others
+    pub async fn add_tag(&self, id: i64, tag: &str) -> Result<()> {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() {
+            bail!("Tags can't be empty");
+        }
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO entry_tags (entry_id, tag) VALUES (?1, ?2)",
+                params![id, tag],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every tag in use with how many entries have it, most used first
+    pub async fn tags(&self) -> Result<Vec<(String, u32)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT tag, COUNT(*) FROM entry_tags GROUP BY tag
+                 ORDER BY COUNT(*) DESC, tag",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        })
+        .await
+    }
+
+    pub async fn range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<HistoryEntry>> {
+        self.query(
+            format!(
+                "{SELECT_ENTRIES} WHERE created_at >= ?1 AND created_at < ?2
+                 ORDER BY created_at ASC"
+            ),
+            vec![
+                Value::Text(to_sql_time(&from)),
+                Value::Text(to_sql_time(&to)),
+            ],
+        )
+        .await
+    }
+
+    /// Sorted by title, or text when untitled. Done after decrypting so it also
+    /// works on encrypted history.
+    pub async fn pinned(&self) -> Result<Vec<HistoryEntry>> {
+        let mut entries = self
+            .query(format!("{SELECT_ENTRIES} WHERE pinned = 1"), vec![])
+            .await?;
+        entries.sort_by_cached_key(|entry| {
+            entry.pin_title.as_ref().unwrap_or(&entry.text).to_lowercase()
+        });
+        Ok(entries)
+    }
+
+    /// Pins an entry with an optional title, returning false if it doesn't exist
+    pub async fn pin(
+        &self,
+        id: i64,
+        title: Option<String>,
+        auto_paste: bool,
+    ) -> Result<bool> {
+        self.with_cipher(move |conn, cipher| {
+            let title = title
+                .filter(|title| !title.trim().is_empty())
+                .map(|title| seal(cipher, title))
+                .transpose()?;
+            let changed = conn.execute(
+                "UPDATE entries SET pinned = 1, pin_title = ?2, pin_auto_paste = ?3
+                 WHERE id = ?1",
+                params![id, title, auto_paste],
+            )?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    pub async fn unpin(&self, id: i64) -> Result<bool> {
+        self.with_conn(move |conn| {
+            let changed = conn.execute(
+                "UPDATE entries SET pinned = 0, pin_title = NULL, pin_auto_paste = 0
+                 WHERE id = ?1",
+                [id],
+            )?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    /// Deletes unpinned entries created before `cutoff`, returning how many were removed
+    pub async fn prune_before(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        self.with_conn(move |conn| {
+            let removed = conn.execute(
+                "DELETE FROM entries WHERE created_at < ?1 AND pinned = 0",
+                [to_sql_time(&cutoff)],
+            )?;
+            if removed > 0 {
+                scrub(conn)?;
+            }
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Deletes every entry, pinned ones included
+    pub async fn clear(&self) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute("DELETE FROM entries", [])?;
+            scrub(conn)?;
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Adds to the words counted for a local day. Kept apart from the entries so
+    /// clearing or pruning history leaves the totals alone.
+    pub async fn add_words(&self, day: NaiveDate, words: u64) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO daily_words (day, words) VALUES (?1, ?2)
+                 ON CONFLICT (day) DO UPDATE SET words = words + excluded.words",
+                params![day.to_string(), words],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Words counted per local day, newest first
+    pub async fn word_days(&self) -> Result<Vec<(NaiveDate, u64)>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT day, words FROM daily_words ORDER BY day DESC")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+            })?;
+            rows.map(|row| {
+                let (day, words) = row?;
+                Ok((day.parse()?, words))
+            })
+            .collect()
+        })
+        .await
+    }
+
+    pub async fn reset_stats(&self) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM daily_words", [])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Visits entries oldest first without loading them all into memory.
+    /// Either bound may be omitted to leave that side of the range open.
+    /// Blocks, so call it from the blocking pool.
+    pub fn for_each_blocking(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        mut f: impl FnMut(HistoryEntry) -> Result<()>,
+    ) -> Result<()> {
+        let from = from.map(|t| to_sql_time(&t)).unwrap_or_default();
+        let to = to.map(|t| to_sql_time(&t)).unwrap_or_else(|| "9999".into());
+        let conn = self.conn.lock().unwrap();
+        let cipher = load_cipher(&conn, &mut self.key.lock().unwrap())?;
+        let mut stmt = conn.prepare(&format!(
+            "{SELECT_ENTRIES} WHERE created_at >= ?1 AND created_at < ?2
+             ORDER BY created_at ASC"
+        ))?;
+        for row in stmt.query_map([from, to], from_row)? {
+            f(open_entry(cipher.as_deref(), row??)?)?;
+        }
+        Ok(())
+    }
+
+    async fn query(&self, sql: String, args: Vec<Value>) -> Result<Vec<HistoryEntry>> {
+        self.with_cipher(move |conn, cipher| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(args), from_row)?;
+            rows.map(|row| open_entry(cipher, row??)).collect()
+        })
+        .await
+    }
+}
+
+/// Works out the session key on first use. Keychain keys load without asking,
+/// passphrase ones wait for `History::unlock`.
+fn load_cipher(conn: &Connection, key: &mut KeyState) -> Result<Option<Arc<RowCipher>>> {
+    match key {
+        KeyState::Plain => return Ok(None),
+        KeyState::Unlocked(cipher) => return Ok(Some(Arc::clone(cipher))),
+        KeyState::Unknown => {}
+    }
+    match read_mode(conn)? {
+        EncryptionMode::Off => {
+            *key = KeyState::Plain;
+            Ok(None)
+        }
+        EncryptionMode::Passphrase => bail!(Locked),
+        EncryptionMode::Keychain => {
+            let cipher = RowCipher::new(&history_cipher::keychain_key()?);
+            let verifier =
+                read_meta(conn, "verifier")?.context("History verifier is missing")?;
+            if !cipher.verify(&verifier) {
+                bail!("The history key in the keychain doesn't match this history");
+            }
+            let cipher = Arc::new(cipher);
+            *key = KeyState::Unlocked(Arc::clone(&cipher));
+            Ok(Some(cipher))
+        }
+    }
+}
+
+fn read_mode(conn: &Connection) -> Result<EncryptionMode> {
+    read_meta(conn, "encryption")?
+        .map_or(Ok(EncryptionMode::Off), |mode| EncryptionMode::parse(&mode))
+}
+
+fn read_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+        .optional()?)
+}
+
+/// `None` removes the key
+fn write_meta(conn: &Connection, key: &str, value: Option<&str>) -> Result<()> {
+    match value {
+        Some(value) => conn.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?,
+        None => conn.execute("DELETE FROM meta WHERE key = ?1", [key])?,
+    };
+    Ok(())
+}
+
+/// Encrypts a column value when history is encrypted
+fn seal(cipher: Option<&RowCipher>, value: String) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.encrypt(&value),
+        None => Ok(value),
+    }
+}
+
+/// Decrypts a column value, passing plaintext through
+fn open(cipher: Option<&RowCipher>, value: String) -> Result<String> {
+    if !is_encrypted(&value) {
+        return Ok(value);
+    }
+    cipher
+        .context("History entry is encrypted but no key is loaded")?
+        .decrypt(&value)
+}
+
+fn open_entry(
+    cipher: Option<&RowCipher>,
+    mut entry: HistoryEntry,
+) -> Result<HistoryEntry> {
+    entry.text = open(cipher, entry.text)?;
+    entry.source_text = entry.source_text.map(|t| open(cipher, t)).transpose()?;
+    entry.pin_title = entry.pin_title.map(|t| open(cipher, t)).transpose()?;
+    Ok(entry)
+}
+
+const SELECT_ENTRIES: &str = "SELECT id, created_at, kind, text, audio_duration_ms,
+    word_count, provider, model, pinned, source_text, pin_title, pin_auto_paste,
+    (SELECT group_concat(tag, char(31)) FROM entry_tags WHERE entry_id = entries.id),
+    language, recorded_at, device, sample_rate, partial
+    FROM entries";
+
+/// Fixed-width UTC timestamps so they sort and compare correctly as text
+fn to_sql_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Lowercase, trimmed and without the separator, so "Meeting " and "meeting" match
+fn normalize_tag(tag: &str) -> String {
+    tag.trim()
+        .to_lowercase()
+        .replace(TAG_SEPARATOR, "")
+        .chars()
+        .take(TAG_MAX_CHARS)
+        .collect()
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Result<HistoryEntry>> {
+    let created_at: String = row.get(1)?;
+    let kind: String = row.get(2)?;
+    let tags: Option<String> = row.get(12)?;
+    let recorded_at: Option<String> = row.get(14)?;
+    Ok((|| {
+        Ok(HistoryEntry {
+            id: row.get(0)?,
+            created_at: DateTime::parse_from_rfc3339(&created_at)?.with_timezone(&Utc),
+            kind: EntryKind::parse(&kind)?,
+            text: row.get(3)?,
+            audio_duration_ms: row.get(4)?,
+            word_count: row.get(5)?,
+            provider: row.get(6)?,
+            model: row.get(7)?,
+            pinned: row.get(8)?,
+            source_text: row.get(9)?,
+            pin_title: row.get(10)?,
+            pin_auto_paste: row.get(11)?,
+            tags: tags
+                .map(|tags| tags.split(TAG_SEPARATOR).map(String::from).collect())
+                .unwrap_or_default(),
+            language: row.get(13)?,
+            recorded_at: recorded_at
+                .map(|time| DateTime::parse_from_rfc3339(&time))
+                .transpose()?
+                .map(|time| time.with_timezone(&Utc)),
+            device: row.get(15)?,
+            sample_rate: row.get(16)?,
+            partial: row.get(17)?,
+        })
+    })())
+}
+
+/// Rewrites the database file after text was removed or re-encrypted, so nothing of
+/// the old text is left in free pages or a write-ahead log. `secure_delete` already
+/// zeroes what's freed, this also shrinks the file and drops leftover journal content.
+fn scrub(conn: &Connection) -> Result<()> {
+    conn.execute_batch("VACUUM")?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    if version > MIGRATIONS.len() {
+        bail!(
+            "History database version {version} is newer than this app supports ({})",
+            MIGRATIONS.len()
+        );
+    }
+
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+        log::info!("Migrated history database to version {}", i + 1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "the launch code is 0451";
+
+    fn contains_secret(dir: &Path) -> bool {
+        let bytes = std::fs::read(dir.join(DB_FILE)).unwrap();
+        bytes.windows(SECRET.len()).any(|w| w == SECRET.as_bytes())
+    }
+
+    #[tokio::test]
+    async fn encrypting_leaves_no_plaintext_in_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::open(dir.path()).unwrap();
+        history
+            .insert(NewEntry::new(EntryKind::Transcription, SECRET))
+            .await
+            .unwrap();
+        assert!(contains_secret(dir.path()));
+
+        history
+            .set_encryption(EncryptionMode::Passphrase, Some("hunter2".into()))
+            .await
+            .unwrap();
+        drop(history);
+        assert!(!contains_secret(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn clearing_leaves_no_plaintext_in_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::open(dir.path()).unwrap();
+        history
+            .insert(NewEntry::new(EntryKind::Transcription, SECRET))
+            .await
+            .unwrap();
+        assert_eq!(history.clear().await.unwrap(), 1);
+        drop(history);
+        assert!(!contains_secret(dir.path()));
+    }
+}
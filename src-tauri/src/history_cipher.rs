@@ -0,0 +1,159 @@
+use crate::api_key::{self, KEYRING_SERVICE};
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chacha20poly1305::{
+    XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+
+const KEYRING_USER: &str = "history-key";
+/// Marks encrypted column values, anything without it is plaintext
+const ENCRYPTED_PREFIX: &str = "enc1:";
+/// Encrypted and stored alongside the data to tell a wrong key from a damaged row
+const VERIFIER: &str = "whistle history";
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionMode {
+    Off,
+    /// Key derived from a passphrase entered once per session
+    Passphrase,
+    /// Random key kept in the system keychain
+    Keychain,
+}
+
+impl EncryptionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EncryptionMode::Off => "off",
+            EncryptionMode::Passphrase => "passphrase",
+            EncryptionMode::Keychain => "keychain",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "off" => EncryptionMode::Off,
+            "passphrase" => EncryptionMode::Passphrase,
+            "keychain" => EncryptionMode::Keychain,
+            other => bail!("Unknown history encryption mode: {other}"),
+        })
+    }
+}
+
+/// History is encrypted with a passphrase that hasn't been entered this session
+#[derive(Debug)]
+pub struct Locked;
+
+impl std::fmt::Display for Locked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "History is locked, enter its passphrase to unlock it")
+    }
+}
+
+impl std::error::Error for Locked {}
+
+#[derive(Debug)]
+pub struct WrongPassphrase;
+
+impl std::fmt::Display for WrongPassphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Wrong passphrase, try again")
+    }
+}
+
+impl std::error::Error for WrongPassphrase {}
+
+/// Encrypts single column values with XChaCha20-Poly1305 and a fresh nonce each
+pub struct RowCipher(XChaCha20Poly1305);
+
+impl RowCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self(XChaCha20Poly1305::new(key.into()))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow!("Failed to encrypt a history entry"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!("{ENCRYPTED_PREFIX}{}", BASE64.encode(sealed)))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let sealed = BASE64.decode(
+            value
+                .strip_prefix(ENCRYPTED_PREFIX)
+                .context("History value isn't encrypted")?,
+        )?;
+        if sealed.len() < NONCE_LEN {
+            bail!("An encrypted history entry is damaged");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .0
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt a history entry"))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+
+    pub fn verifier(&self) -> Result<String> {
+        self.encrypt(VERIFIER)
+    }
+
+    pub fn verify(&self, verifier: &str) -> bool {
+        self.decrypt(verifier).is_ok_and(|v| v == VERIFIER)
+    }
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+pub fn new_salt() -> String {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    BASE64.encode(salt)
+}
+
+pub fn passphrase_key(passphrase: &str, salt: &str) -> Result<[u8; 32]> {
+    api_key::derive_key(passphrase, &BASE64.decode(salt)?)
+}
+
+fn entry() -> Result<Entry> {
+    Ok(Entry::new(KEYRING_SERVICE, KEYRING_USER)?)
+}
+
+/// Generates a random key and stores it in the keychain
+pub fn create_keychain_key() -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    entry()?.set_password(&BASE64.encode(key))?;
+    log::info!("Saved history key to keychain");
+    Ok(key)
+}
+
+pub fn keychain_key() -> Result<[u8; 32]> {
+    let key = entry()?
+        .get_password()
+        .context("The history key is missing from the keychain")?;
+    BASE64
+        .decode(key)?
+        .try_into()
+        .map_err(|_| anyhow!("The history key in the keychain is damaged"))
+}
+
+pub fn clear_keychain_key() -> Result<()> {
+    match entry()?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(e.into()),
+    }
+    log::info!("Removed history key from keychain");
+    Ok(())
+}
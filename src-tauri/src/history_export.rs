@@ -0,0 +1,175 @@
+use crate::history::{History, HistoryEntry};
+use crate::settings::{Settings, TranscriptHeaderSettings};
+use crate::transcript_header;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::async_runtime::spawn_blocking;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+}
+
+impl ExportFormat {
+    /// Picks the format from the file extension chosen in the save dialog
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(ExportFormat::Json),
+            Some("md" | "markdown") => Ok(ExportFormat::Markdown),
+            _ => bail!("Export file must end in .json or .md"),
+        }
+    }
+}
+
+/// What goes at the top of every export
+pub struct ExportHeader {
+    pub app_version: String,
+    pub settings: Settings,
+}
+
+/// Streams history entries within the optional range to `path`.
+/// Returns how many entries were written.
+pub async fn export(
+    history: History,
+    path: PathBuf,
+    format: ExportFormat,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    header: ExportHeader,
+) -> Result<usize> {
+    spawn_blocking(move || {
+        let mut out = BufWriter::new(File::create(&path)?);
+        let mut count = 0;
+        let mut day = None;
+
+        write_header(&mut out, format, &header)?;
+        history.for_each_blocking(from, to, |entry| {
+            match format {
+                ExportFormat::Json => write_json_entry(&mut out, &entry, count == 0)?,
+                ExportFormat::Markdown => write_markdown_entry(
+                    &mut out,
+                    &entry,
+                    &mut day,
+                    &header.settings.transcript_header,
+                )?,
+            }
+            count += 1;
+            Ok(())
+        })?;
+        write_footer(&mut out, format)?;
+        out.flush()?;
+
+        log::info!("Exported {count} history entries to {}", path.display());
+        Ok(count)
+    })
+    .await?
+}
+
+fn write_header(
+    out: &mut impl Write,
+    format: ExportFormat,
+    header: &ExportHeader,
+) -> Result<()> {
+    let exported_at = Utc::now();
+    match format {
+        ExportFormat::Json => {
+            writeln!(out, "{{")?;
+            writeln!(
+                out,
+                "  \"app_version\": {},",
+                serde_json::to_string(&header.app_version)?
+            )?;
+            writeln!(
+                out,
+                "  \"exported_at\": {},",
+                serde_json::to_string(&exported_at)?
+            )?;
+            writeln!(
+                out,
+                "  \"settings\": {},",
+                serde_json::to_string(&header.settings)?
+            )?;
+            write!(out, "  \"entries\": [")?;
+        }
+        ExportFormat::Markdown => {
+            writeln!(out, "# Whistle history")?;
+            writeln!(out)?;
+            writeln!(out, "- App version: {}", header.app_version)?;
+            writeln!(
+                out,
+                "- Exported at: {}",
+                exported_at.with_timezone(&Local).to_rfc2822()
+            )?;
+            writeln!(out)?;
+            writeln!(out, "<details><summary>Settings</summary>")?;
+            writeln!(out)?;
+            writeln!(out, "```toml")?;
+            write!(out, "{}", header.settings.to_commented_toml()?)?;
+            writeln!(out, "```")?;
+            writeln!(out)?;
+            writeln!(out, "</details>")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_footer(out: &mut impl Write, format: ExportFormat) -> Result<()> {
+    match format {
+        ExportFormat::Json => writeln!(out, "\n  ]\n}}")?,
+        ExportFormat::Markdown => {}
+    }
+    Ok(())
+}
+
+fn write_json_entry(
+    out: &mut impl Write,
+    entry: &HistoryEntry,
+    first: bool,
+) -> Result<()> {
+    if !first {
+        write!(out, ",")?;
+    }
+    write!(out, "\n    {}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Starts a new `##` heading whenever the local date changes
+fn write_markdown_entry(
+    out: &mut impl Write,
+    entry: &HistoryEntry,
+    day: &mut Option<NaiveDate>,
+    transcript_header: &TranscriptHeaderSettings,
+) -> Result<()> {
+    let created_at = entry.created_at.with_timezone(&Local);
+    if *day != Some(created_at.date_naive()) {
+        *day = Some(created_at.date_naive());
+        writeln!(out)?;
+        writeln!(out, "## {}", created_at.format("%A, %B %-d, %Y"))?;
+    }
+
+    writeln!(out)?;
+    let partial = match entry.partial {
+        true => " · partial",
+        false => "",
+    };
+    writeln!(
+        out,
+        "### {} · {:?}{partial}",
+        created_at.format("%H:%M:%S"),
+        entry.kind
+    )?;
+    writeln!(out)?;
+    if let Some(info) =
+        entry.recording_info().filter(|_| transcript_header.history_export)
+    {
+        writeln!(out, "{}", transcript_header::render(&transcript_header.format, &info))?;
+    }
+    writeln!(out, "{}", entry.text.trim())?;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use crate::history::EntryKind;
+use crate::notifications::{AppNotifications, Notification};
+use crate::privacy;
+use crate::settings::{HookKind, HookSettings, SettingsState};
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::io::AsyncWriteExt;
+
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const SAMPLE_TEXT: &str = "This is a test of the transcription hook.";
+
+/// What the hook receives, as JSON for `http` and only `text` on stdin for `command`
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPayload {
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+    pub duration_ms: Option<u64>,
+    pub language: Option<String>,
+    pub kind: EntryKind,
+}
+
+impl HookPayload {
+    pub fn new(kind: EntryKind, text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            timestamp: Utc::now(),
+            duration_ms: None,
+            language: None,
+            kind,
+        }
+    }
+}
+
+/// Runs the configured hook in the background. The caller never waits for it, and a
+/// failure after every retry is logged and notified once.
+pub fn fire(app_handle: &AppHandle, payload: HookPayload) {
+    let hook = app_handle.state::<SettingsState>().get().hook.clone();
+    if hook.kind == HookKind::Off {
+        return;
+    }
+    if privacy::enabled(app_handle) {
+        log::debug!("Skipping the hook in privacy mode");
+        return;
+    }
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        if let Err(e) = run(&hook, &payload).await {
+            log::error!("{:?} hook failed: {e:#}", hook.kind);
+            AppNotifications::new(&app_handle)
+                .notify(Notification::HookFailed(format!("{e:#}")));
+        }
+    });
+}
+
+/// Sends a sample payload through the hook as configured, reporting the outcome
+/// instead of notifying
+pub async fn test(app_handle: &AppHandle) -> Result<()> {
+    let hook = app_handle.state::<SettingsState>().get().hook.clone();
+    if hook.kind == HookKind::Off {
+        bail!("No hook is configured, set `hook.kind` in settings");
+    }
+    let payload = HookPayload {
+        duration_ms: Some(3000),
+        language: Some("en".into()),
+        ..HookPayload::new(EntryKind::Transcription, SAMPLE_TEXT)
+    };
+    run(&hook, &payload).await
+}
+
+async fn run(hook: &HookSettings, payload: &HookPayload) -> Result<()> {
+    let timeout = Duration::from_secs(hook.timeout_secs);
+    let mut attempt = 0;
+    loop {
+        let result = match tokio::time::timeout(timeout, once(hook, payload)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!("Timed out after {}s", hook.timeout_secs)),
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < hook.retries => {
+                attempt += 1;
+                log::warn!("{:?} hook failed, retrying ({attempt}): {e:#}", hook.kind);
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn once(hook: &HookSettings, payload: &HookPayload) -> Result<()> {
+    match hook.kind {
+        HookKind::Off => Ok(()),
+        HookKind::Http => {
+            reqwest::Client::new()
+                .post(&hook.url)
+                .json(payload)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+        HookKind::Command => run_command(&hook.command, &payload.text).await,
+    }
+}
+
+async fn run_command(command: &str, text: &str) -> Result<()> {
+    #[cfg(windows)]
+    let mut command_ = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    command_.args(["/C", command]);
+    #[cfg(not(windows))]
+    let mut command_ = tokio::process::Command::new("sh");
+    #[cfg(not(windows))]
+    command_.args(["-c", command]);
+
+    let mut child = command_
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        // A timed out attempt doesn't leave the process behind
+        .kill_on_drop(true)
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().next() {
+            Some(line) => bail!("Exited with {}: {line}", output.status),
+            None => bail!("Exited with {}", output.status),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::audio_recorder::RecordingInfo;
+use crate::notifications::{AppNotifications, Notification};
+use crate::privacy;
+use crate::quota;
+use crate::settings::SettingsState;
+use crate::transcribe_icon::TranscribeIcon;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tauri_plugin_dialog::DialogExt;
+
+pub const RETRY_ID: &str = "retry_last_recording";
+pub const SAVE_ID: &str = "save_last_recording";
+
+/// The encoded bytes of the last recording, with `retention.keep_last_recording`.
+/// Only ever in memory, so it's gone on quit.
+#[derive(Default)]
+pub struct LastRecording(Mutex<Option<(Vec<u8>, RecordingInfo)>>);
+
+/// Whether a recording of `len` bytes may be kept under the current settings
+fn allowed(app_handle: &AppHandle, len: usize) -> bool {
+    let retention = app_handle.state::<SettingsState>().get().retention;
+    retention.keep_last_recording
+        && !privacy::enabled(app_handle)
+        && len as u64 <= retention.keep_last_recording_mb * 1024 * 1024
+}
+
+/// Keeps a copy of a recording before it's sent, replacing the one before. One that's
+/// over `retention.keep_last_recording_mb` isn't kept, and the old one goes anyway so
+/// a retry never picks up an older recording.
+pub fn keep(app_handle: &AppHandle, bytes: &[u8], info: &RecordingInfo) {
+    let mut last = app_handle.state::<LastRecording>().0.lock().unwrap();
+    if !allowed(app_handle, bytes.len()) {
+        if app_handle
+            .state::<SettingsState>()
+            .get()
+            .retention
+            .keep_last_recording
+        {
+            log::info!("Not keeping a recording of {} bytes", bytes.len());
+        }
+        *last = None;
+        return;
+    }
+    *last = Some((bytes.to_vec(), info.clone()));
+}
+
+/// Drops the kept recording once the settings no longer allow it, so turning the
+/// option off or privacy mode on takes effect right away
+pub fn apply(app_handle: &AppHandle) {
+    let mut last = app_handle.state::<LastRecording>().0.lock().unwrap();
+    if last
+        .as_ref()
+        .is_some_and(|(bytes, _)| !allowed(app_handle, bytes.len()))
+    {
+        log::info!("Dropped the kept recording");
+        *last = None;
+    }
+}
+
+pub fn clear(app_handle: &AppHandle) {
+    app_handle.state::<LastRecording>().0.lock().unwrap().take();
+}
+
+fn get(app_handle: &AppHandle) -> Option<(Vec<u8>, RecordingInfo)> {
+    let last = app_handle.state::<LastRecording>().0.lock().unwrap().clone();
+    if last.is_none() {
+        let enabled = app_handle
+            .state::<SettingsState>()
+            .get()
+            .retention
+            .keep_last_recording;
+        AppNotifications::new(app_handle)
+            .notify(Notification::NoLastRecording { enabled });
+    }
+    last
+}
+
+/// The tray's "Retry last recording": sends the kept recording again and delivers
+/// the transcript as if it had just been recorded
+pub fn retry(app_handle: &AppHandle) {
+    // The icon and the recorder belong to the recording in progress
+    if app_handle.state::<TranscribeIcon>().is_recording() {
+        log::info!("Not retrying the last recording while recording");
+        return;
+    }
+    if quota::paused(app_handle) {
+        AppNotifications::new(app_handle)
+            .notify(Notification::QuotaExceeded { paused: true });
+        return;
+    }
+    let Some((bytes, info)) = get(app_handle) else {
+        return;
+    };
+    log::info!("Retrying the last recording, {} bytes", bytes.len());
+    spawn(crate::transcribe_recording(app_handle.clone(), bytes, info, None));
+}
+
+/// The tray's "Save last recording…": writes the kept recording as a WAV
+pub fn save(app_handle: &AppHandle) {
+    let Some((bytes, info)) = get(app_handle) else {
+        return;
+    };
+    let stem = info
+        .started_at
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d_%H-%M-%S");
+    app_handle
+        .dialog()
+        .file()
+        .add_filter("WAV", &["wav"])
+        .set_file_name(format!("whistle-{stem}.wav"))
+        .save_file(move |path| {
+            let Some(path) = path.and_then(|p| p.into_path().ok()) else {
+                return;
+            };
+            match std::fs::write(&path, bytes) {
+                Ok(()) => log::info!("Saved the last recording to {}", path.display()),
+                Err(e) => log::error!("Failed to write {}: {e}", path.display()),
+            }
+        });
+}
@@ -0,0 +1,77 @@
+use crate::paths;
+use crate::settings::{BehaviorSettings, OverLength};
+use anyhow::Result;
+use std::path::PathBuf;
+use tauri::AppHandle;
+
+/// Appended where a transcription was cut short
+const TRUNCATION_MARKER: &str = " […]";
+
+/// What's left to deliver once `behavior.max_output_chars` was applied
+#[derive(Debug)]
+pub enum Limited {
+    Unchanged(String),
+    Truncated {
+        text: String,
+        /// Where the full text was written with `over_length = "split"`
+        full_text: Option<PathBuf>,
+    },
+    /// Too long and `over_length = "refuse"`
+    Refused {
+        chars: usize,
+    },
+}
+
+/// Cuts `text` down to `behavior.max_output_chars` according to
+/// `behavior.over_length`. Splitting writes the full text to `paths::overflow_dir`,
+/// except in privacy mode, and falls back to truncating if that fails.
+pub fn apply(
+    app_handle: &AppHandle,
+    text: String,
+    behavior: &BehaviorSettings,
+) -> Limited {
+    let max = behavior.max_output_chars;
+    let chars = text.chars().count();
+    if max == 0 || chars <= max {
+        return Limited::Unchanged(text);
+    }
+
+    log::info!("Transcription is {chars} chars, over the {max} char limit");
+    let full_text = match behavior.over_length {
+        OverLength::Refuse => return Limited::Refused { chars },
+        OverLength::Split if !behavior.privacy_mode => {
+            match write_overflow(app_handle, &text) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    log::error!("Failed to save the full transcription: {e:#}");
+                    None
+                }
+            }
+        }
+        OverLength::Truncate | OverLength::Split => None,
+    };
+    Limited::Truncated {
+        text: truncate(&text, max),
+        full_text,
+    }
+}
+
+/// First `max` chars including the marker, cut at the last word break when there is one
+fn truncate(text: &str, max: usize) -> String {
+    let keep = max.saturating_sub(TRUNCATION_MARKER.chars().count());
+    let head: String = text.chars().take(keep).collect();
+    let head = match head.rfind(char::is_whitespace) {
+        Some(end) if end > 0 => &head[..end],
+        _ => &head,
+    };
+    format!("{}{TRUNCATION_MARKER}", head.trim_end())
+}
+
+fn write_overflow(app_handle: &AppHandle, text: &str) -> Result<PathBuf> {
+    let dir = paths::overflow_dir(app_handle)?;
+    std::fs::create_dir_all(&dir)?;
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let path = dir.join(format!("transcription-{timestamp}.txt"));
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
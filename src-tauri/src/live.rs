@@ -0,0 +1,308 @@
+use crate::audio_recorder;
+use crate::filler::{self, FillerOutcome};
+use crate::history::{EntryKind, NewEntry};
+use crate::local_task_handler::Task;
+use crate::session;
+use crate::settings::{Settings, SettingsState};
+use crate::snippets;
+use crate::stats;
+use crate::transcribe_client::TranscribeClient;
+use crate::transcribe_icon::{Capture, TranscribeIcon};
+use crate::voice_commands;
+use anyhow::anyhow;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::sync::{mpsc, oneshot};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Live dictation: while the push-to-talk key is held the recording so far is
+/// transcribed every `live.partial_interval_ms` and typed into the focused app,
+/// correcting what was typed before. On release the final transcript replaces it.
+#[derive(Default)]
+pub struct LiveDictation {
+    /// Ends the running dictation, `true` commits the final transcript and `false`
+    /// erases everything that was typed
+    stop: Mutex<Option<oneshot::Sender<bool>>>,
+    /// Set by a cancel, so the erased text is kept as a partial transcript
+    keep_partial: AtomicBool,
+}
+
+impl LiveDictation {
+    pub fn is_active(&self) -> bool {
+        self.stop.lock().unwrap().is_some()
+    }
+}
+
+/// Keystrokes that turn what was typed into the target text
+#[derive(Debug, PartialEq, Eq)]
+struct Edit {
+    /// Backspaces, one per grapheme cluster
+    erase: usize,
+    insert: String,
+}
+
+/// Keeps the longest common prefix and retypes the rest. Counted in grapheme clusters,
+/// since that's what a backspace removes, so an accent or emoji is never half-erased.
+fn diff(typed: &str, target: &str) -> Edit {
+    let mut common = 0;
+    for (a, b) in typed.graphemes(true).zip(target.graphemes(true)) {
+        if a != b {
+            break;
+        }
+        common += a.len();
+    }
+    Edit {
+        erase: typed[common..].graphemes(true).count(),
+        insert: target[common..].to_string(),
+    }
+}
+
+pub fn start(app_handle: &AppHandle) {
+    let (tx_stop, rx_stop) = oneshot::channel();
+    // Set before recording starts, so an early release still finds it
+    *app_handle.state::<LiveDictation>().stop.lock().unwrap() = Some(tx_stop);
+
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let (tx_started, rx_started) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        let started = match tx_task.send(Task::StartSession(tx_started)).await {
+            Ok(()) => rx_started
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("The recorder went away"))),
+            Err(_) => Err(anyhow!("Failed to send 'StartSession' task to channel")),
+        };
+        if let Err(e) = started {
+            log::error!("Failed to start live dictation: {e:#}");
+            app_handle.state::<LiveDictation>().stop.lock().unwrap().take();
+            return;
+        }
+
+        app_handle.state::<TranscribeIcon>().set_capture(Some(Capture::Live));
+        log::info!("Live dictation started");
+        crate::prewarm_connection(&app_handle);
+        run(&app_handle, rx_stop).await;
+        app_handle.state::<TranscribeIcon>().set_capture(None);
+        log::info!("Live dictation finished");
+    });
+}
+
+/// `commit` types the final transcript in place of the partial one, otherwise all
+/// typed text is erased
+pub fn stop(app_handle: &AppHandle, commit: bool) {
+    if let Some(tx_stop) = app_handle.state::<LiveDictation>().stop.lock().unwrap().take()
+    {
+        _ = tx_stop.send(commit);
+    }
+}
+
+async fn run(app_handle: &AppHandle, mut rx_stop: oneshot::Receiver<bool>) {
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let mut typed = String::new();
+
+    let commit = loop {
+        let interval = app_handle.state::<SettingsState>().get().live.partial_interval_ms;
+        // Only waiting and transcribing are cut short, typing always runs to the end
+        // so `typed` stays in step with the focused app
+        let partial = tokio::select! {
+            commit = &mut rx_stop => break commit.unwrap_or(false),
+            partial = partial_transcript(app_handle, Duration::from_millis(interval)) => partial,
+        };
+        if let Some(partial) = partial {
+            retype(app_handle, &mut typed, &partial).await;
+        }
+    };
+
+    let (tx_stopped, rx_stopped) = oneshot::channel();
+    if tx_task.send(Task::StopSession(tx_stopped)).await.is_err() {
+        log::error!("Failed to send 'StopSession' task to channel");
+        return;
+    }
+    let bytes = rx_stopped.await.ok().flatten();
+
+    let keep_partial = app_handle
+        .state::<LiveDictation>()
+        .keep_partial
+        .swap(false, Ordering::Relaxed);
+    let Some(bytes) = bytes.filter(|_| commit) else {
+        let partial = typed.clone();
+        retype(app_handle, &mut typed, "").await;
+        if keep_partial {
+            session::keep_partial(app_handle, partial);
+        }
+        return;
+    };
+    let audio_duration = audio_recorder::wav_duration(&bytes);
+    let transcribe_client = app_handle.state::<TranscribeClient>();
+    let transcription = match transcribe_client.fetch_transcription(bytes).await {
+        Ok(transcription) => transcription,
+        Err(e) => {
+            // The partial text stays, it's the closest thing to a transcript we have
+            log::error!("Failed to transcribe live dictation: {e:#}");
+            crate::notify_api_error(app_handle, &e);
+            return;
+        }
+    };
+
+    let settings = app_handle.state::<SettingsState>().get();
+    let text = clean(transcription.text, &settings);
+    retype(app_handle, &mut typed, &text).await;
+    if typed != text {
+        log::error!("Live dictation could not type the final transcript");
+    }
+    if text.trim().is_empty() {
+        return;
+    }
+    stats::record(app_handle, &text);
+    crate::save_to_history(
+        app_handle,
+        NewEntry {
+            audio_duration_ms: audio_duration.map(|d| d.as_millis() as u64),
+            provider: Some(transcribe_client.provider()),
+            language: transcription.language,
+            ..NewEntry::new(EntryKind::Transcription, text)
+        },
+    );
+}
+
+/// `hotkeys.discard_recording`: erases what was typed like an uncommitted stop, but
+/// keeps the last partial transcript on the clipboard and in history
+pub fn cancel(app_handle: &AppHandle) {
+    let live = app_handle.state::<LiveDictation>();
+    if let Some(tx_stop) = live.stop.lock().unwrap().take() {
+        live.keep_partial.store(true, Ordering::Relaxed);
+        _ = tx_stop.send(false);
+    }
+}
+
+/// Transcribes the recording so far after waiting `interval`. Failures only skip
+/// this update, the final transcript reports them.
+async fn partial_transcript(
+    app_handle: &AppHandle,
+    interval: Duration,
+) -> Option<String> {
+    tokio::time::sleep(interval).await;
+    let (tx_peek, rx_peek) = oneshot::channel();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    tx_task.send(Task::PeekRecording(tx_peek)).await.ok()?;
+    let bytes = rx_peek.await.ok()??;
+    match app_handle
+        .state::<TranscribeClient>()
+        .fetch_transcription(bytes)
+        .await
+    {
+        Ok(transcription) => {
+            Some(clean(transcription.text, &app_handle.state::<SettingsState>().get()))
+        }
+        Err(e) => {
+            log::warn!("Skipping a live dictation update: {e:#}");
+            None
+        }
+    }
+}
+
+/// Filler removal, voice commands and snippets run on every partial too, so text doesn't jump when
+/// the final transcript arrives
+fn clean(text: String, settings: &Settings) -> String {
+    match filler::apply(text, &settings.filler) {
+        FillerOutcome::Text(text) => snippets::expand(
+            voice_commands::apply(text, &settings.voice_commands),
+            &settings.snippets,
+        ),
+        FillerOutcome::NoSpeech => String::new(),
+    }
+}
+
+/// Brings the focused app from `typed` to `target`. `typed` is only updated once the
+/// keystrokes went through.
+async fn retype(app_handle: &AppHandle, typed: &mut String, target: &str) {
+    let edit = diff(typed, target);
+    if edit.erase == 0 && edit.insert.is_empty() {
+        return;
+    }
+    let (tx, rx) = oneshot::channel();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let task = Task::Retype {
+        erase: edit.erase,
+        text: edit.insert,
+        tx,
+    };
+    if tx_task.send(task).await.is_err() {
+        log::error!("Failed to send 'Retype' task to channel");
+        return;
+    }
+    match rx.await {
+        Ok(Ok(())) => *typed = target.to_string(),
+        Ok(Err(e)) => log::error!("Failed to type live dictation: {e:#}"),
+        Err(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(erase: usize, insert: &str) -> Edit {
+        Edit {
+            erase,
+            insert: insert.into(),
+        }
+    }
+
+    /// What the focused field holds after the keystrokes
+    fn apply(typed: &str, edit: &Edit) -> String {
+        let graphemes: Vec<&str> = typed.graphemes(true).collect();
+        let kept = graphemes.len() - edit.erase;
+        graphemes[..kept].concat() + &edit.insert
+    }
+
+    fn assert_diff(typed: &str, target: &str, expected: Edit) {
+        let actual = diff(typed, target);
+        assert_eq!(apply(typed, &actual), target);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn appends_without_erasing() {
+        assert_diff("", "hello", edit(0, "hello"));
+        assert_diff("hello", "hello world", edit(0, " world"));
+        assert_diff("hello", "hello", edit(0, ""));
+    }
+
+    #[test]
+    fn revises_mid_word() {
+        assert_diff("I red the", "I read the", edit(5, "ad the"));
+        assert_diff("the cat", "the car sat", edit(1, "r sat"));
+        assert_diff("hello there", "hello", edit(6, ""));
+    }
+
+    #[test]
+    fn revises_punctuation() {
+        assert_diff("Hello world", "Hello, world.", edit(6, ", world."));
+        assert_diff("Wait.", "Wait?", edit(1, "?"));
+        assert_diff("it's", "it’s", edit(2, "’s"));
+    }
+
+    #[test]
+    fn erases_accents_as_one_grapheme() {
+        // "e" followed by a combining acute accent
+        assert_diff("cafe\u{301}", "cafe", edit(1, "e"));
+        assert_diff("café", "cafe", edit(1, "e"));
+        assert_diff("naïve", "naive", edit(3, "ive"));
+    }
+
+    #[test]
+    fn erases_emoji_as_one_grapheme() {
+        // A family emoji joined by zero width joiners, and a flag
+        assert_diff("hi 👨‍👩‍👧", "hi 👋", edit(1, "👋"));
+        assert_diff("go 🇫🇷!", "go 🇩🇪!", edit(2, "🇩🇪!"));
+    }
+
+    #[test]
+    fn keeps_a_shared_multibyte_prefix() {
+        assert_diff("日本語です", "日本語でした", edit(1, "した"));
+        assert_diff("Grüße", "Grüß Gott", edit(1, " Gott"));
+    }
+}
@@ -1,21 +1,121 @@
 use crate::{
-    audio_recorder::AudioRecorder,
+    audio_recorder::{self, AudioRecorder, RecordingInfo},
     enigo_instance::EnigoInstance,
     notifications::{AppNotifications, Notification},
+    settings::SettingsState,
+    wake_word::Frame,
 };
-use anyhow::Result;
-use std::{cell::RefCell, rc::Rc};
-use tauri::AppHandle;
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::{
     sync::{mpsc, oneshot},
-    task::LocalSet,
+    task::{JoinHandle, LocalSet},
 };
 
 /// Tasks that will only be run on a `LocalSet`
 pub enum Task {
-    ToggleRecording(oneshot::Sender<Vec<u8>>),
+    ToggleRecording(oneshot::Sender<RecordingOutcome>),
     PasteFromClipboard,
-    UndoText(oneshot::Sender<()>),
+    /// Presses undo this many times
+    Undo(usize),
+    /// Presses Return, starting a new line in the focused app
+    NewLine,
+    TypeText(String),
+    /// Presses backspace `erase` times, then types `text`
+    Retype {
+        erase: usize,
+        text: String,
+        tx: oneshot::Sender<Result<()>>,
+    },
+    /// Opens or closes the warm input stream after `[audio]` settings changed
+    ApplyAudioSettings,
+    /// Closes the idle input stream and resumes paused media before system sleep
+    SystemSleep(oneshot::Sender<()>),
+    /// Rebuilds the input stream after system sleep, answering with the device name
+    SystemWake(oneshot::Sender<Result<String>>),
+    /// Throws the recorder away and builds a new one from the `[audio]` settings, for
+    /// a stream that stopped delivering. Answers with the device name, refuses while
+    /// recording.
+    ReinitAudio(oneshot::Sender<Result<String>>),
+    /// Stops the current recording, if any, without handing back the audio
+    DiscardRecording(oneshot::Sender<()>),
+    /// The panic key: like `DiscardRecording` for whatever is recording, including a
+    /// dictation session or live dictation
+    AbortAll(oneshot::Sender<()>),
+    /// Starts recording for a dictation session, which is split into segments
+    StartSession(oneshot::Sender<Result<()>>),
+    /// Discards any recording, closes the input stream and resumes paused media before
+    /// the app quits
+    Shutdown(oneshot::Sender<()>),
+    /// Whether the recorder is capturing, for checking the icon against it
+    IsRecording(oneshot::Sender<bool>),
+    /// Loudness of the last ~100ms of the recording
+    InputLevel(oneshot::Sender<f32>),
+    /// The whole recording so far, without stopping it
+    PeekRecording(oneshot::Sender<Option<Vec<u8>>>),
+    /// Hands back the session audio since the last segment, keeping its last
+    /// `overlap` for the next one
+    TakeSegment {
+        overlap: Duration,
+        tx: oneshot::Sender<Option<Vec<u8>>>,
+    },
+    /// Drops session audio older than this, e.g. the silence before any speech
+    TrimSegment(Duration),
+    /// Stops the session, handing back the audio since the last segment
+    StopSession(oneshot::Sender<Option<Vec<u8>>>),
+    /// Starts handing frames to the wake phrase listener, or stops with `None`
+    WakeTap(Option<std::sync::mpsc::SyncSender<Frame>>),
+    MicTest {
+        duration: Duration,
+        playback: bool,
+        tx: oneshot::Sender<Result<MicTestReport>>,
+    },
+}
+
+/// A task that needs the recorder idle arrived while it was recording
+#[derive(Debug)]
+pub struct StillRecording;
+
+impl std::fmt::Display for StillRecording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Stop the current recording first")
+    }
+}
+
+impl std::error::Error for StillRecording {}
+
+/// Summary of a microphone test capture
+#[derive(Debug, Clone, Serialize)]
+pub struct MicTestReport {
+    pub peak: f32,
+    pub rms: f32,
+    pub duration_ms: u64,
+}
+
+/// Event carrying the live input level (0.0 - 1.0) during a mic test
+pub const MIC_LEVEL_EVENT: &str = "mic-level";
+const MIC_LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What happened as a result of a `Task::ToggleRecording`
+#[derive(Debug)]
+pub enum RecordingOutcome {
+    /// Resolves once the first audio buffer arrived
+    RecordingStarted(oneshot::Receiver<()>),
+    RecordingStopped(Vec<u8>, RecordingInfo),
+}
+
+/// Stops a running recording. One that captured no audio still stops, with empty
+/// bytes, so the caller can tell it apart from a failure.
+fn stop_recording(recorder: &mut AudioRecorder) -> RecordingOutcome {
+    let info = recorder.recording_info();
+    let bytes = recorder.stop_recording_and_get_bytes().unwrap_or_default();
+    RecordingOutcome::RecordingStopped(bytes, info)
 }
 
 /// - Instantiates its own tokio runtime
@@ -37,48 +137,190 @@ pub fn run_local_task_handler(mut rx: mpsc::Receiver<Task>, app_handle: AppHandl
         }
         let enigo = Rc::new(RefCell::new(enigo.unwrap()));
         let audio_recorder = Rc::new(RefCell::new(AudioRecorder::new()));
+        audio_recorder
+            .borrow_mut()
+            .apply_settings(&app_handle.state::<SettingsState>().get().audio);
         let media_manager = Rc::new(RefCell::new(MediaManager::new()));
         while let Some(task) = rx.recv().await {
             let enigo = Rc::clone(&enigo);
             let audio_recorder = Rc::clone(&audio_recorder);
             let media_manager = Rc::clone(&media_manager);
+            let settings = app_handle.state::<SettingsState>().get();
+            let app_handle = app_handle.clone();
             tokio::task::spawn_local(async move {
                 match task {
                     Task::ToggleRecording(tx_recording) => {
                         log::info!("ToggleRecording task received through channel");
 
                         let mut recorder = audio_recorder.borrow_mut();
-                        let mut media_manager = media_manager.borrow_mut();
 
                         if !recorder.is_recording {
-                            media_manager.pause_spotify();
-                            if let Err(e) = recorder.start_recording() {
-                                log::error!("Failed to start recording: {}", e);
-                                recorder.reset();
-                                return;
+                            let live = match recorder.start_recording(&settings.audio) {
+                                Ok(live) => live,
+                                Err(e) => {
+                                    log::error!("Failed to start recording: {}", e);
+                                    recorder.reset();
+                                    return;
+                                }
+                            };
+                            if settings.behavior.pause_media {
+                                schedule_media_pause(
+                                    &media_manager,
+                                    Duration::from_millis(
+                                        settings.behavior.pause_media_delay_ms,
+                                    ),
+                                );
                             }
-                            _ = tx_recording.send(vec![]);
+                            _ = tx_recording
+                                .send(RecordingOutcome::RecordingStarted(live));
                             return;
                         }
 
-                        let Some(recording_bytes) =
-                            recorder.stop_recording_and_get_bytes()
-                        else {
-                            log::error!("Failed to stop recording");
-                            return;
-                        };
-                        media_manager.play_spotify();
+                        let outcome = stop_recording(&mut recorder);
+                        media_manager.borrow_mut().resume();
 
-                        if tx_recording.send(recording_bytes).is_err() {
+                        if tx_recording.send(outcome).is_err() {
                             log::error!("Failed to send recording to channel");
                         }
                     }
                     Task::PasteFromClipboard => {
-                        enigo.borrow_mut().paste_from_clipboard().unwrap();
+                        if let Err(e) = enigo.borrow_mut().paste_from_clipboard() {
+                            log::error!("Failed to paste: {}", e);
+                        }
                     }
-                    Task::UndoText(tx_undo) => {
-                        enigo.borrow_mut().undo_text().unwrap();
-                        tx_undo.send(()).unwrap();
+                    Task::Undo(count) => {
+                        if let Err(e) = enigo.borrow_mut().undo(count) {
+                            log::error!("Failed to undo: {}", e);
+                        }
+                    }
+                    Task::NewLine => {
+                        if let Err(e) = enigo.borrow_mut().new_line() {
+                            log::error!("Failed to start a new line: {}", e);
+                        }
+                    }
+                    Task::TypeText(text) => {
+                        if let Err(e) = enigo.borrow_mut().type_text(&text) {
+                            log::error!("Failed to type text: {}", e);
+                        }
+                    }
+                    Task::Retype { erase, text, tx } => {
+                        let mut enigo = enigo.borrow_mut();
+                        _ = tx.send(
+                            enigo.erase(erase).and_then(|()| enigo.type_text(&text)),
+                        );
+                    }
+                    Task::ApplyAudioSettings => {
+                        audio_recorder.borrow_mut().apply_settings(&settings.audio);
+                    }
+                    Task::SystemSleep(tx) => {
+                        audio_recorder.borrow_mut().before_sleep();
+                        media_manager.borrow_mut().resume();
+                        _ = tx.send(());
+                    }
+                    Task::SystemWake(tx) => {
+                        _ = tx.send(
+                            audio_recorder.borrow_mut().after_wake(&settings.audio),
+                        );
+                    }
+                    Task::ReinitAudio(tx) => {
+                        if audio_recorder.borrow().is_recording {
+                            _ = tx.send(Err(StillRecording.into()));
+                            return;
+                        }
+                        log::info!("Rebuilding the audio recorder");
+                        // Dropping the old recorder closes its stream and device handle
+                        let mut recorder = audio_recorder.borrow_mut();
+                        let wake_tap = recorder.wake_tap();
+                        *recorder = AudioRecorder::new();
+                        recorder.set_wake_tap(wake_tap, &settings.audio);
+                        _ = tx.send(audio_recorder::device_name(&settings.audio));
+                    }
+                    Task::DiscardRecording(tx_discard) => {
+                        let mut recorder = audio_recorder.borrow_mut();
+                        if recorder.is_recording {
+                            recorder.discard();
+                            media_manager.borrow_mut().resume();
+                            log::info!("Discarded recording");
+                        }
+                        _ = tx_discard.send(());
+                    }
+                    Task::Shutdown(tx) => {
+                        audio_recorder.borrow_mut().shut_down();
+                        media_manager.borrow_mut().resume();
+                        _ = tx.send(());
+                    }
+                    Task::AbortAll(tx) => {
+                        let mut recorder = audio_recorder.borrow_mut();
+                        if recorder.is_recording {
+                            recorder.discard();
+                            media_manager.borrow_mut().resume();
+                            log::info!("Discarded recording on abort");
+                        }
+                        _ = tx.send(());
+                    }
+                    Task::StartSession(tx) => {
+                        log::info!("StartSession task received through channel");
+                        let mut recorder = audio_recorder.borrow_mut();
+                        if recorder.is_recording {
+                            _ = tx.send(Err(anyhow::anyhow!(
+                                "Stop the current recording before starting a session"
+                            )));
+                            return;
+                        }
+                        if let Err(e) = recorder.start_recording(&settings.audio) {
+                            recorder.reset();
+                            _ = tx.send(Err(e));
+                            return;
+                        }
+                        if settings.behavior.pause_media {
+                            media_manager.borrow_mut().pause_spotify();
+                        }
+                        _ = tx.send(Ok(()));
+                    }
+                    Task::IsRecording(tx) => {
+                        _ = tx.send(audio_recorder.borrow().is_recording);
+                    }
+                    Task::InputLevel(tx) => {
+                        _ = tx.send(audio_recorder.borrow().level());
+                    }
+                    Task::PeekRecording(tx) => {
+                        _ = tx.send(audio_recorder.borrow().snapshot());
+                    }
+                    Task::TakeSegment { overlap, tx } => {
+                        _ = tx.send(audio_recorder.borrow_mut().take_segment(overlap));
+                    }
+                    Task::TrimSegment(keep) => {
+                        audio_recorder.borrow_mut().trim(keep);
+                    }
+                    Task::StopSession(tx) => {
+                        let bytes =
+                            audio_recorder.borrow_mut().stop_recording_and_get_bytes();
+                        media_manager.borrow_mut().resume();
+                        _ = tx.send(bytes);
+                    }
+                    Task::WakeTap(tap) => {
+                        audio_recorder.borrow_mut().set_wake_tap(tap, &settings.audio);
+                    }
+                    Task::MicTest {
+                        duration,
+                        playback,
+                        tx,
+                    } => {
+                        log::info!("MicTest task received through channel");
+                        if audio_recorder.borrow().is_recording {
+                            _ = tx.send(Err(anyhow::anyhow!(
+                                "Stop the current recording before testing the mic"
+                            )));
+                            return;
+                        }
+                        let report = run_mic_test(
+                            &app_handle,
+                            &settings.audio,
+                            duration,
+                            playback,
+                        )
+                        .await;
+                        _ = tx.send(report);
                     }
                 }
             });
@@ -90,13 +332,92 @@ pub fn run_local_task_handler(mut rx: mpsc::Receiver<Task>, app_handle: AppHandl
     log::info!("Local task handler completed");
 }
 
+/// Captures from the configured input without transcribing, streaming the level to
+/// the frontend and optionally playing the capture back
+async fn run_mic_test(
+    app_handle: &AppHandle,
+    audio: &crate::settings::AudioSettings,
+    duration: Duration,
+    playback: bool,
+) -> Result<MicTestReport> {
+    let mut recorder = AudioRecorder::new();
+    recorder.start_recording(audio)?;
+
+    let started = Instant::now();
+    let mut peak: f32 = 0.0;
+    while started.elapsed() < duration {
+        tokio::time::sleep(MIC_LEVEL_INTERVAL).await;
+        let level = recorder.level();
+        peak = peak.max(level);
+        _ = app_handle.emit(MIC_LEVEL_EVENT, level);
+    }
+    _ = app_handle.emit(MIC_LEVEL_EVENT, 0.0);
+
+    let Some((samples, sample_rate, channels)) =
+        recorder.stop_recording_and_get_samples()
+    else {
+        bail!("The microphone did not produce any audio");
+    };
+    if samples.is_empty() {
+        bail!("The microphone did not produce any audio");
+    }
+
+    let sum: f64 = samples.iter().map(|&s| (s as f64 / 32768.0).powi(2)).sum();
+    let rms = (sum / samples.len() as f64).sqrt() as f32;
+    let captured = Duration::from_secs_f64(
+        samples.len() as f64 / (sample_rate as f64 * channels as f64),
+    );
+
+    if playback {
+        let _stream = audio_recorder::play_samples(samples, sample_rate, channels)?;
+        tokio::time::sleep(captured).await;
+    }
+
+    log::info!("Mic test finished: peak {peak:.2}, rms {rms:.2}");
+
+    Ok(MicTestReport {
+        peak,
+        rms,
+        duration_ms: captured.as_millis() as u64,
+    })
+}
+
+/// Pauses media once the recording has lasted `delay`, unless it stops first
+fn schedule_media_pause(media_manager: &Rc<RefCell<MediaManager>>, delay: Duration) {
+    if delay.is_zero() {
+        media_manager.borrow_mut().pause_spotify();
+        return;
+    }
+    let media_manager_ = Rc::clone(media_manager);
+    let pending = tokio::task::spawn_local(async move {
+        tokio::time::sleep(delay).await;
+        let mut media_manager = media_manager_.borrow_mut();
+        media_manager.pending_pause = None;
+        media_manager.pause_spotify();
+    });
+    media_manager.borrow_mut().pending_pause = Some(pending);
+}
+
 struct MediaManager {
     was_playing: bool,
+    /// Deferred pause for the current recording, see `schedule_media_pause`
+    pending_pause: Option<JoinHandle<()>>,
 }
 
 impl MediaManager {
     fn new() -> Self {
-        Self { was_playing: false }
+        Self {
+            was_playing: false,
+            pending_pause: None,
+        }
+    }
+
+    /// Cancels a pause that hasn't fired yet, or resumes media it paused
+    pub fn resume(&mut self) {
+        if let Some(pending) = self.pending_pause.take() {
+            pending.abort();
+        }
+        self.play_spotify();
     }
 
     pub fn pause_spotify(&mut self) {
@@ -146,3 +467,20 @@ impl MediaManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stopping_without_audio_is_not_an_error() {
+        let mut recorder = AudioRecorder::new();
+        recorder.is_recording = true;
+
+        match stop_recording(&mut recorder) {
+            RecordingOutcome::RecordingStopped(bytes, _) => assert!(bytes.is_empty()),
+            outcome => panic!("expected a stopped recording, got {outcome:?}"),
+        }
+        assert!(!recorder.is_recording);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::settings::{self, LogLevel, SettingsState};
+use crate::transcribe_app_logger::{LOG_FILE, RotatingLog};
+use anyhow::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::{
+    AppHandle, Manager, Wry,
+    async_runtime::spawn,
+    menu::{CheckMenuItem, MenuItem, Submenu},
+};
+
+pub const LOG_LEVEL_ID_PREFIX: &str = "log_level:";
+pub const DIAGNOSTICS_ID: &str = "collect_diagnostics";
+const DIAGNOSTICS_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// The tray's "Logging" submenu: one check item per level and "Collect diagnostics"
+pub struct LogMenu {
+    levels: Vec<(LogLevel, CheckMenuItem<Wry>)>,
+    diagnostics: MenuItem<Wry>,
+    /// Bumped on every diagnostics run, 0 while none is active. Only the latest run
+    /// reverts the level.
+    diagnostics_run: AtomicU64,
+}
+
+impl LogMenu {
+    pub fn new(
+        app_handle: &AppHandle,
+        submenu: &Submenu<Wry>,
+        current: LogLevel,
+    ) -> Result<Self> {
+        let mut levels = vec![];
+        for level in LogLevel::ALL {
+            let item = CheckMenuItem::with_id(
+                app_handle,
+                format!("{LOG_LEVEL_ID_PREFIX}{level:?}"),
+                format!("{level:?}"),
+                true,
+                level == current,
+                None::<&str>,
+            )?;
+            submenu.append(&item)?;
+            levels.push((level, item));
+        }
+        let diagnostics = MenuItem::with_id(
+            app_handle,
+            DIAGNOSTICS_ID,
+            "Collect diagnostics",
+            true,
+            None::<&str>,
+        )?;
+        submenu.append(&diagnostics)?;
+        Ok(Self {
+            levels,
+            diagnostics,
+            diagnostics_run: AtomicU64::new(0),
+        })
+    }
+
+    /// Check items toggle themselves when clicked, so this also puts them back
+    pub fn set_checked(&self, current: LogLevel) {
+        for (level, item) in &self.levels {
+            if let Err(e) = item.set_checked(*level == current) {
+                log::error!("Unable to update log level menu: {e}");
+            }
+        }
+    }
+
+    fn collecting(&self) -> bool {
+        self.diagnostics_run.load(Ordering::Relaxed) != 0
+    }
+}
+
+/// Makes `logging.level` effective, raised to Debug while collecting diagnostics
+pub fn apply_level(app_handle: &AppHandle) {
+    let level: log::LevelFilter =
+        app_handle.state::<SettingsState>().get().logging.level.into();
+    let level = match app_handle.state::<LogMenu>().collecting() {
+        true => level.max(log::LevelFilter::Debug),
+        false => level,
+    };
+    log::set_max_level(level);
+}
+
+/// Handles a click on one of the level items
+pub fn select(app_handle: &AppHandle, id: &str) {
+    let Some(level) = LogLevel::ALL.into_iter().find(|level| format!("{level:?}") == id)
+    else {
+        return;
+    };
+    if let Err(e) =
+        settings::update(app_handle, |settings| settings.logging.level = level)
+    {
+        log::error!("Failed to save log level: {e:#}");
+    }
+    let current = app_handle.state::<SettingsState>().get().logging.level;
+    app_handle.state::<LogMenu>().set_checked(current);
+    apply_level(app_handle);
+    log::info!("Log level set to {current:?}");
+}
+
+/// Logs at Debug for a while without saving it, then goes back to the configured
+/// level and reveals the log file
+pub fn collect_diagnostics(app_handle: &AppHandle) {
+    let log_menu = app_handle.state::<LogMenu>();
+    let run = log_menu.diagnostics_run.fetch_add(1, Ordering::Relaxed) + 1;
+    _ = log_menu.diagnostics.set_text("Collecting diagnostics…");
+    apply_level(app_handle);
+    log::info!("Collecting diagnostics for {DIAGNOSTICS_DURATION:?}");
+
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        tokio::time::sleep(DIAGNOSTICS_DURATION).await;
+        let log_menu = app_handle.state::<LogMenu>();
+        if log_menu
+            .diagnostics_run
+            .compare_exchange(run, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            // A later run took over
+            return;
+        }
+        _ = log_menu.diagnostics.set_text("Collect diagnostics");
+        apply_level(&app_handle);
+        log::info!("Finished collecting diagnostics");
+        reveal_log_file(&app_handle);
+    });
+}
+
+pub fn reveal_log_file(app_handle: &AppHandle) {
+    let Some(dir) = app_handle.state::<RotatingLog>().dir() else {
+        return;
+    };
+    let path = dir.join(LOG_FILE);
+    if let Err(e) = tauri_plugin_opener::reveal_item_in_dir(&path) {
+        log::error!("Failed to reveal {}: {}", path.display(), e);
+    }
+}
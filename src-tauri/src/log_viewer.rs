@@ -0,0 +1,165 @@
+use crate::transcribe_app_logger::{LOG_FILE, RotatingLog};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{
+    AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder, async_runtime::spawn,
+};
+
+pub const LOG_WINDOW: &str = "logs";
+/// Carries a `LogChunk` of lines appended since the last one
+pub const LOG_LINES_EVENT: &str = "log-lines";
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+const TAIL_BLOCK: u64 = 64 * 1024;
+/// Caps a single read so a burst of logging can't stall the viewer
+const MAX_CHUNK: u64 = 1024 * 1024;
+pub const REPORT_LINES: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogChunk {
+    pub lines: Vec<String>,
+    /// Where the next read continues from
+    pub offset: u64,
+}
+
+/// Where the viewer is in the log file while it follows it
+#[derive(Default)]
+pub struct LogFollower {
+    /// Unset until the viewer loaded its first lines with `tail`
+    offset: Mutex<Option<u64>>,
+    running: AtomicBool,
+}
+
+fn log_path(app_handle: &AppHandle) -> Result<PathBuf> {
+    let dir = app_handle
+        .state::<RotatingLog>()
+        .dir()
+        .context("The log file isn't open yet")?;
+    Ok(dir.join(LOG_FILE))
+}
+
+/// Opens the log viewer, creating its window on first use
+pub fn open(app_handle: &AppHandle) -> Result<()> {
+    let window = match app_handle.get_webview_window(LOG_WINDOW) {
+        Some(window) => window,
+        None => WebviewWindowBuilder::new(
+            app_handle,
+            LOG_WINDOW,
+            WebviewUrl::App("index.html?view=logs".into()),
+        )
+        .title("Logs")
+        .inner_size(900.0, 600.0)
+        .build()?,
+    };
+    window.show()?;
+    window.set_focus()?;
+    follow(app_handle.clone());
+    Ok(())
+}
+
+/// The last `max_lines` lines for the viewer. Following continues from the end.
+pub fn tail(app_handle: &AppHandle, max_lines: usize) -> Result<LogChunk> {
+    let chunk = read_tail(app_handle, max_lines)?;
+    *app_handle.state::<LogFollower>().offset.lock().unwrap() = Some(chunk.offset);
+    Ok(chunk)
+}
+
+/// Reads backwards from the end so large files aren't loaded whole
+fn read_tail(app_handle: &AppHandle, max_lines: usize) -> Result<LogChunk> {
+    let mut file = File::open(log_path(app_handle)?)?;
+    let end = file.metadata()?.len();
+    let mut start = end;
+    let mut buf = Vec::new();
+    while start > 0 && buf.iter().filter(|&&b| b == b'\n').count() <= max_lines {
+        let block = TAIL_BLOCK.min(start);
+        start -= block;
+        let mut chunk = vec![0; block as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend(buf);
+        buf = chunk;
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    if start > 0 && !lines.is_empty() {
+        // Most likely cut off by the block boundary
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(max_lines);
+    lines.drain(..skip);
+
+    Ok(LogChunk { lines, offset: end })
+}
+
+/// Complete lines written since `offset`. Starts over if the file was rotated.
+fn read_from(app_handle: &AppHandle, offset: u64) -> Result<LogChunk> {
+    let mut file = File::open(log_path(app_handle)?)?;
+    let len = file.metadata()?.len();
+    let offset = if len < offset { 0 } else { offset };
+    let mut buf = vec![0; (len - offset).min(MAX_CHUNK) as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    // Leave a partially written line for the next read
+    let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    buf.truncate(complete);
+    Ok(LogChunk {
+        lines: String::from_utf8_lossy(&buf).lines().map(String::from).collect(),
+        offset: offset + complete as u64,
+    })
+}
+
+/// Emits new lines while the viewer window is visible
+pub fn follow(app_handle: AppHandle) {
+    if app_handle
+        .state::<LogFollower>()
+        .running
+        .swap(true, Ordering::Relaxed)
+    {
+        return;
+    }
+    spawn(async move {
+        loop {
+            tokio::time::sleep(FOLLOW_INTERVAL).await;
+            let visible = app_handle
+                .get_webview_window(LOG_WINDOW)
+                .is_some_and(|window| window.is_visible().unwrap_or(false));
+            if !visible {
+                break;
+            }
+            let follower = app_handle.state::<LogFollower>();
+            let Some(offset) = *follower.offset.lock().unwrap() else {
+                continue;
+            };
+            // Can't log failures here without feeding the loop
+            let Ok(chunk) = read_from(&app_handle, offset) else {
+                continue;
+            };
+            *follower.offset.lock().unwrap() = Some(chunk.offset);
+            if !chunk.lines.is_empty() {
+                _ = app_handle.emit_to(LOG_WINDOW, LOG_LINES_EVENT, chunk);
+            }
+        }
+        app_handle
+            .state::<LogFollower>()
+            .running
+            .store(false, Ordering::Relaxed);
+    });
+}
+
+/// Last lines of the log with the app version and OS up front, for bug reports
+pub fn report(app_handle: &AppHandle) -> Result<String> {
+    let chunk = read_tail(app_handle, REPORT_LINES)?;
+    Ok(format!(
+        "App version: {}\nOS: {} {}\n\n{}\n",
+        app_handle.package_info().version,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        chunk.lines.join("\n")
+    ))
+}
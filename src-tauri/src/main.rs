@@ -1,311 +1,829 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod abort;
+mod activity;
+mod api_key;
+mod audio_device;
+mod audio_file;
 mod audio_recorder;
+mod automation;
+mod autostart;
+mod cli;
+mod clip_ring;
+mod collect;
+mod commands;
 mod constants;
+mod deep_link;
+mod disk_budget;
+mod dsp;
 mod enigo_instance;
+mod filler;
+mod frontmost_app;
+mod history;
+mod history_cipher;
+mod history_export;
+mod hook;
+mod last_recording;
+mod length_limit;
+mod live;
 mod local_task_handler;
+mod log_menu;
+mod log_viewer;
+mod mock;
 mod notifications;
+mod ogg_opus;
+mod paths;
+mod permissions;
+mod pinned_menu;
+mod polish_diff;
+mod polish_limit;
+mod power;
+mod privacy;
+mod prompts;
+mod push_to_talk;
+mod quality;
+mod quick_note;
+mod quota;
+mod recent_menu;
+mod repeat;
+mod retention;
+mod rich_text;
+mod saved_recordings;
+mod selection;
+mod self_test;
+mod session;
+mod session_window;
+mod settings;
+mod shutdown;
+mod single_instance;
+mod sinks;
+mod snippets;
+mod sounds;
+mod stats;
+mod tag_menu;
+mod target_picker;
+mod text_format;
 mod transcribe_app_logger;
 mod transcribe_client;
 mod transcribe_icon;
+mod transcript_header;
+mod tray;
+mod updater;
+mod upload;
+mod voice_commands;
+mod wake_word;
+mod watch_folder;
 
-use anyhow::{Context, Result, bail};
+use abort::AbortEpoch;
+use activity::Activity;
+use anyhow::{Context, Result, anyhow, bail};
+use audio_file::PendingFiles;
+use audio_recorder::RecordingInfo;
+use automation::AutomationServer;
+use clip_ring::ClipRing;
 use colored::*;
-use local_task_handler::{Task, run_local_task_handler};
+use filler::FillerOutcome;
+use history::{EntryKind, History, NewEntry};
+use history_cipher::Locked;
+use hook::HookPayload;
+use last_recording::LastRecording;
+use length_limit::Limited;
+use live::LiveDictation;
+use local_task_handler::{RecordingOutcome, Task, run_local_task_handler};
+use log_menu::LogMenu;
+use log_viewer::{LOG_WINDOW, LogFollower};
 use notifications::{AppNotifications, Notification};
-use serde::{Deserialize, Serialize};
-use std::fs::read_to_string;
+use pinned_menu::PinnedMenu;
+use privacy::PrivacyMenuItem;
+use prompts::PromptLibrary;
+use push_to_talk::PushToTalk;
+use quality::Plan;
+use recent_menu::{RECENT_LIMIT, RecentMenu};
+use repeat::LastAction;
+use saved_recordings::Sidecar;
+use selection::Content;
+use session::DictationSession;
+use session_window::SessionTranscript;
+use settings::{
+    HotkeySettings, OutputMode, PolishPasteBack, PromptSettings, ResolvedOutput,
+    Settings, SettingsState, SinkKind,
+};
+use sinks::DeliveryCount;
+use sounds::{Cue, SoundCues};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tag_menu::TagMenu;
+use target_picker::TargetPicker;
 use tauri::{
-    AppHandle, Manager,
+    AppHandle, Emitter, Manager,
     async_runtime::spawn,
-    menu::{MenuBuilder, MenuItem},
     path::{BaseDirectory, PathResolver},
-    tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
 };
 use tauri_plugin_clipboard_manager::ClipboardExt;
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tauri_plugin_global_shortcut::{
+    Code, GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState,
+};
+use tauri_plugin_log::{Target, TargetKind};
 use tauri_plugin_notification::NotificationExt;
 use tokio::sync::{mpsc, oneshot};
-use transcribe_client::TranscribeClient;
-use transcribe_icon::{Icon, TranscribeIcon};
+use transcribe_app_logger::{Logger, QUIET_TARGETS, RotatingLog};
+use transcribe_client::{ApiFailure, TranscribeClient, TranscribeOutcome};
+use transcribe_icon::{RecorderState, TranscribeIcon, Work};
+use upload::UploadPlan;
+use wake_word::WakeWord;
+use watch_folder::WatchFolder;
+
+/// Tells the frontend to prompt for a new API key
+const API_KEY_REQUIRED_EVENT: &str = "api-key-required";
+/// Emitted once the first audio buffer of a recording arrived
+const RECORDING_LIVE_EVENT: &str = "recording-live";
+/// How long the microphone gets to deliver audio before we stop waiting for it
+const RECORDING_LIVE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How much of a live recording has to be digital silence before we suspect a mute switch
+const MUTED_CHECK_DURATION: Duration = Duration::from_secs(1);
+const MUTED_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Tells the user where the export went and offers to reveal it in the file manager
+fn show_history_exported(app_handle: &AppHandle, path: PathBuf, count: usize) {
+    app_handle
+        .dialog()
+        .message(format!("Exported {count} entries to {}", path.display()))
+        .title("History exported")
+        .buttons(MessageDialogButtons::OkCancelCustom("Reveal".into(), "OK".into()))
+        .show(move |reveal| {
+            if reveal {
+                if let Err(e) = tauri_plugin_opener::reveal_item_in_dir(&path) {
+                    log::error!("Failed to reveal {}: {}", path.display(), e);
+                }
+            }
+        });
+}
+
+/// Shows what an import from the tray would change and applies it once confirmed.
+/// The tray can't ask for a passphrase, so an API key in the export is left out.
+fn confirm_settings_import(app_handle: AppHandle, path: PathBuf) {
+    let changed = match commands::preview_settings_import_from(&app_handle, &path) {
+        Ok(changed) if changed.is_empty() => {
+            AppNotifications::new(&app_handle)
+                .notify(Notification::SettingsImported(changed));
+            return;
+        }
+        Ok(changed) => changed,
+        Err(e) => {
+            log::error!("Failed to read settings import: {e:#}");
+            AppNotifications::new(&app_handle)
+                .notify(Notification::SettingsImportFailed(format!("{e:#}")));
+            return;
+        }
+    };
 
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+    app_handle
+        .dialog()
+        .message(format!(
+            "This changes {} setting(s):\n\n{}",
+            changed.len(),
+            changed.join("\n")
+        ))
+        .title("Import settings?")
+        .buttons(MessageDialogButtons::OkCancelCustom("Import".into(), "Cancel".into()))
+        .show(move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            let notification =
+                match commands::import_settings_from(&app_handle, &path, None) {
+                    Ok(changed) => Notification::SettingsImported(changed),
+                    Err(e) => {
+                        log::error!("Failed to import settings: {e:#}");
+                        Notification::SettingsImportFailed(format!("{e:#}"))
+                    }
+                };
+            AppNotifications::new(&app_handle).notify(notification);
+        });
 }
 
-#[tauri::command]
-fn assign_shortcut(app_handle: AppHandle, name: &str, shortcut: &str) -> String {
-    if name != "toggle-recording" && name != "cleanse-clipboard" {
-        return "Invalid shortcut name".into();
+#[derive(Debug, Clone)]
+struct ShortcutsConfig {
+    toggle_recording: Shortcut,
+    cleanse_clipboard: Shortcut,
+    push_to_talk: Option<Shortcut>,
+    cycle_clipboard: Option<Shortcut>,
+    /// Stops a recording like `toggle_recording`, typing the transcription
+    type_boost: Option<Shortcut>,
+    /// Stops the recording without transcribing it
+    discard_recording: Option<Shortcut>,
+    /// Stops everything, see `abort::abort_all`
+    panic: Option<Shortcut>,
+    /// Runs the last polish or clipboard prompt again, see `repeat::repeat`
+    repeat_last: Option<Shortcut>,
+    /// Starts and stops a recording like `toggle_recording`, appending the
+    /// transcription to the quick note file
+    quick_note: Option<Shortcut>,
+    /// Starts collecting transcripts, or delivers the collected ones
+    collect: Option<Shortcut>,
+    /// Hotkeys of the prompt library with the name of the prompt they run
+    prompts: Vec<(Shortcut, String)>,
+}
+
+impl ShortcutsConfig {
+    /// `prompts` must already be checked for clashes, see `prompts::usable`
+    fn from_settings(
+        hotkeys: &HotkeySettings,
+        prompts: &[PromptSettings],
+    ) -> Result<Self> {
+        Ok(Self {
+            toggle_recording: Shortcut::from_str(&hotkeys.toggle_recording)?,
+            cleanse_clipboard: Shortcut::from_str(&hotkeys.cleanse_clipboard)?,
+            push_to_talk: optional_shortcut(&hotkeys.push_to_talk)?,
+            cycle_clipboard: optional_shortcut(&hotkeys.cycle_clipboard)?,
+            type_boost: optional_shortcut(&hotkeys.type_boost().unwrap_or_default())?,
+            discard_recording: optional_shortcut(&hotkeys.discard_recording)?,
+            panic: optional_shortcut(&hotkeys.panic)?,
+            repeat_last: optional_shortcut(&hotkeys.repeat_last)?,
+            quick_note: optional_shortcut(&hotkeys.quick_note)?,
+            collect: optional_shortcut(&hotkeys.collect)?,
+            prompts: prompts
+                .iter()
+                .filter_map(|p| {
+                    Some((optional_shortcut(&p.hotkey).ok()??, p.name.clone()))
+                })
+                .collect(),
+        })
     }
 
-    let Ok(shortcut) = Shortcut::from_str(shortcut) else {
-        return "Invalid shortcut".into();
-    };
+    fn all(&self) -> Vec<Shortcut> {
+        let mut all = vec![self.toggle_recording, self.cleanse_clipboard];
+        all.extend(self.push_to_talk);
+        all.extend(self.cycle_clipboard);
+        all.extend(self.type_boost);
+        all.extend(self.discard_recording);
+        all.extend(self.panic);
+        all.extend(self.repeat_last);
+        all.extend(self.quick_note);
+        all.extend(self.collect);
+        all.extend(self.prompts.iter().map(|(shortcut, _)| *shortcut));
+        all
+    }
+
+    fn prompt_for(&self, shortcut: &Shortcut) -> Option<&str> {
+        self.prompts
+            .iter()
+            .find(|(s, _)| s == shortcut)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+/// Empty hotkeys are disabled
+fn optional_shortcut(hotkey: &str) -> Result<Option<Shortcut>> {
+    match hotkey {
+        "" => Ok(None),
+        hotkey => Ok(Some(Shortcut::from_str(hotkey)?)),
+    }
+}
 
-    if let Ok(old_shortcuts) = parse_shortcuts_config() {
-        if name == "toggle-recording" {
-            _ = app_handle
-                .global_shortcut()
-                .unregister(old_shortcuts.toggle_recording);
-        } else if name == "cleanse-clipboard" {
-            _ = app_handle
-                .global_shortcut()
-                .unregister(old_shortcuts.cleanse_clipboard);
+/// Options the backend would ignore are pointed out instead of doing nothing quietly
+fn warn_unsupported_options(app_handle: &AppHandle, settings: &Settings) {
+    let client = app_handle.state::<TranscribeClient>();
+    let unsupported = client.capabilities().unsupported_options(settings);
+    if unsupported.is_empty() {
+        return;
+    }
+    log::warn!("Not supported by {}: {}", client.provider(), unsupported.join(", "));
+    AppNotifications::new(app_handle).notify(Notification::UnsupportedOptions(
+        unsupported.into_iter().map(String::from).collect(),
+    ));
+}
+
+/// Re-registers global shortcuts and updates clients after the settings file changed
+fn on_settings_changed(app_handle: &AppHandle, old: &Settings, new: &Settings) {
+    if old.hotkeys != new.hotkeys || old.prompts != new.prompts {
+        let prompts = prompts::reload(app_handle, new);
+        let Ok(new_shortcuts) = ShortcutsConfig::from_settings(&new.hotkeys, &prompts)
+        else {
+            log::error!("Invalid hotkeys in reloaded settings");
+            return;
+        };
+        let shortcuts_config = app_handle.state::<Mutex<ShortcutsConfig>>();
+        let mut shortcuts_config = shortcuts_config.lock().unwrap();
+        let global_shortcut = app_handle.global_shortcut();
+        _ = global_shortcut.unregister_multiple(shortcuts_config.all());
+        if permissions::register_or_report(app_handle, &new_shortcuts.all()) {
+            log::info!("Re-registered global shortcuts");
         }
-    } else {
-        return "Failed to parse shortcuts config".into();
+        *shortcuts_config = new_shortcuts;
     }
 
-    // register the new shortcut
-    _ = app_handle.global_shortcut().register(shortcut);
+    if old.api != new.api {
+        app_handle.state::<TranscribeClient>().apply_settings(&new.api);
+        if !new.api.pause_on_quota_error {
+            quota::resume(app_handle);
+        }
+    }
 
-    // update the config
-    let shortcuts_config = app_handle.state::<Mutex<ShortcutsConfig>>();
-    let mut shortcuts_config = shortcuts_config.lock().unwrap();
-    if name == "toggle-recording" {
-        shortcuts_config.toggle_recording = shortcut;
-    } else if name == "cleanse-clipboard" {
-        shortcuts_config.cleanse_clipboard = shortcut;
+    if old.api != new.api
+        || old.prompts != new.prompts
+        || old.behavior.polish_in_detected_language
+            != new.behavior.polish_in_detected_language
+        || old.behavior.polish_language != new.behavior.polish_language
+    {
+        warn_unsupported_options(app_handle, new);
     }
 
-    // write the new config to disk
-    let config_dir = dirs::home_dir().unwrap().join(".config/whistle/shortcuts.json");
-    let file_contents = serde_json::to_string(&shortcuts_config.clone()).unwrap();
-    std::fs::write(config_dir, file_contents).unwrap();
+    if old.behavior.quick_tags != new.behavior.quick_tags {
+        app_handle
+            .state::<TagMenu>()
+            .refresh(app_handle, &new.behavior.quick_tags);
+    }
 
-    "".into()
-}
+    if old.behavior.launch_at_login != new.behavior.launch_at_login {
+        autostart::apply(app_handle, new.behavior.launch_at_login);
+    }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
-struct ShortcutsConfig {
-    toggle_recording: Shortcut,
-    cleanse_clipboard: Shortcut,
-}
+    // The token is read on every request, only these need a new listener
+    if old.automation.enabled != new.automation.enabled
+        || old.automation.port != new.automation.port
+    {
+        automation::apply(app_handle);
+    }
+
+    if old.sounds != new.sounds {
+        sounds::load(app_handle);
+    }
+
+    if old.watch_folder.folder != new.watch_folder.folder {
+        watch_folder::apply(app_handle);
+    }
+
+    if old.behavior.privacy_mode != new.behavior.privacy_mode {
+        app_handle
+            .state::<PrivacyMenuItem>()
+            .set_checked(new.behavior.privacy_mode);
+    }
+
+    if old.retention != new.retention
+        || old.behavior.privacy_mode != new.behavior.privacy_mode
+    {
+        last_recording::apply(app_handle);
+    }
 
-impl Default for ShortcutsConfig {
-    fn default() -> Self {
-        Self {
-            toggle_recording: Shortcut::from_str("CmdOrCtrl+Option+R").unwrap(),
-            cleanse_clipboard: Shortcut::from_str("CmdOrCtrl+Option+C").unwrap(),
+    if old.wake != new.wake || old.behavior.privacy_mode != new.behavior.privacy_mode {
+        wake_word::apply(app_handle);
+        if new.wake.enabled && !old.wake.enabled && !wake_word::taught(app_handle) {
+            AppNotifications::new(app_handle).notify(Notification::WakeUntaught);
         }
     }
-}
 
-fn parse_shortcuts_config() -> Result<ShortcutsConfig> {
-    let config_dir = dirs::home_dir()
-        .context("Could not find home directory")?
-        .join(".config/whistle/shortcuts.json");
-    let file_contents = read_to_string(config_dir)?;
-    let config: ShortcutsConfig = serde_json::from_str(&file_contents)?;
-    Ok(config)
+    if old.logging != new.logging {
+        log_menu::apply_level(app_handle);
+        app_handle.state::<LogMenu>().set_checked(new.logging.level);
+        let rotating_log = app_handle.state::<RotatingLog>();
+        if let Some(dir) = rotating_log.dir() {
+            if let Err(e) = rotating_log.configure(dir, &new.logging) {
+                log::error!("Failed to apply logging settings: {e:#}");
+            }
+        }
+    }
+
+    if old.audio != new.audio {
+        let app_handle = app_handle.clone();
+        spawn(async move {
+            let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+            if tx_task.send(Task::ApplyAudioSettings).await.is_err() {
+                log::error!("Failed to send 'ApplyAudioSettings' task to channel");
+            }
+        });
+    }
 }
 
 fn main() {
+    let command = match cli::parse(std::env::args()) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e:#}\n\n{}", cli::USAGE);
+            std::process::exit(cli::EXIT_USAGE);
+        }
+    };
+    // Needs no app of its own, a running one answers over its status port
+    if command == Some(cli::Command::Status) {
+        std::process::exit(cli::print_status());
+    }
+
+    let files = cli::files(std::env::args());
+
+    let rotating_log = RotatingLog::default();
     tauri::Builder::default()
+        // Registered first so a second launch exits before setting anything up
+        .plugin(tauri_plugin_single_instance::init(
+            single_instance::on_second_instance,
+        ))
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
+        // Only the session window remembers where it was put
         .plugin(
-            tauri_plugin_log::Builder::new()
-                .level(log::LevelFilter::Debug)
-                .level_for("enigo", log::LevelFilter::Error)
+            tauri_plugin_window_state::Builder::new()
+                .with_denylist(&["main", LOG_WINDOW])
                 .build(),
         )
-        .plugin(tauri_plugin_notification::init())
-        .setup(|app| {
-            #[cfg(desktop)]
-            {
-                let shortcuts_config = {
-                    if let Ok(shortcuts_config) = parse_shortcuts_config() {
-                        shortcuts_config
-                    } else {
-                        let whistle_dir =
-                            dirs::home_dir().unwrap().join(".config/whistle");
-
-                        // create dirs if they don't exist
-                        std::fs::create_dir_all(&whistle_dir).unwrap();
-
-                        let shortcuts_config = ShortcutsConfig::default();
-
-                        let file_contents =
-                            serde_json::to_string(&shortcuts_config).unwrap();
-                        std::fs::write(whistle_dir.join("shortcuts.json"), file_contents)
-                            .unwrap();
-
-                        shortcuts_config
-                    }
-                };
+        .manage(PendingFiles::new(files))
+        .setup(move |app| Ok(setup(app, rotating_log, command)?))
+        .on_tray_icon_event(tray::on_icon_event)
+        .on_window_event(on_window_event)
+        .on_menu_event(tray::on_menu_event)
+        .invoke_handler(tauri::generate_handler![
+            commands::greet,
+            commands::assign_shortcut,
+            commands::get_shortcuts,
+            commands::get_recent_transcripts,
+            commands::get_polish_diff,
+            commands::copy_polish_diff,
+            commands::get_stats,
+            commands::get_transcription_timing,
+            commands::get_pinned_history,
+            commands::pin_history_entry,
+            commands::unpin_history_entry,
+            commands::reset_stats,
+            commands::get_settings,
+            commands::read_log_tail,
+            commands::copy_log_report,
+            commands::get_session_transcript,
+            commands::clear_session_transcript,
+            commands::list_targets,
+            commands::choose_target,
+            commands::set_settings,
+            commands::get_disk_usage,
+            commands::get_history_encryption,
+            commands::unlock_history,
+            commands::set_history_encryption,
+            commands::set_api_key,
+            commands::clear_api_key,
+            commands::test_microphone,
+            commands::test_hook,
+            commands::reset_audio,
+            commands::get_capabilities,
+            commands::search_history,
+            commands::set_history_tags,
+            commands::get_history_tags,
+            commands::get_history_range,
+            commands::export_history,
+            commands::get_app_overrides,
+            commands::set_app_override,
+            commands::remove_app_override,
+            commands::get_frontmost_app,
+            commands::export_settings,
+            commands::preview_settings_import,
+            commands::import_settings,
+            commands::set_polish_style,
+            commands::set_polish_language,
+            commands::toggle_recording_cmd,
+            commands::cancel_cmd,
+            commands::polish_clipboard_cmd,
+            commands::get_pipeline_state
+        ])
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(on_run_event);
+}
 
-                app.manage(Mutex::new(shortcuts_config));
-
-                app.handle().plugin(
-                    tauri_plugin_global_shortcut::Builder::default()
-                        .with_handler(move |app, shortcut, event| {
-                            let shortcuts_config = app.state::<Mutex<ShortcutsConfig>>();
-                            let shortcuts_config = shortcuts_config.lock().unwrap();
-
-                            if event.state() == ShortcutState::Pressed {
-                                log::info!("Shortcut triggered: {:?}", shortcut);
-                            }
-
-                            if shortcut == &shortcuts_config.toggle_recording
-                                && event.state() == ShortcutState::Pressed
-                            {
-                                log::info!(
-                                    "F19 shortcut triggered - Start/Stop Recording"
-                                );
-                                toggle_recording(app.clone(), false);
-                            }
-                            // Check if the shortcut matches F20
-                            else if shortcut == &shortcuts_config.cleanse_clipboard
-                                && event.state() == ShortcutState::Pressed
-                            {
-                                log::info!("F20 shortcut triggered - Polish Clipboard");
-                                cleanse_clipboard(app.clone(), false);
-                            }
-                        })
-                        .build(),
-                )?;
-                app.global_shortcut().register_multiple([
-                    shortcuts_config.toggle_recording,
-                    shortcuts_config.cleanse_clipboard,
-                ])?;
-                log::info!("Registered global shortcuts");
-            }
+/// Sets up logging, settings, state, hotkeys and the tray before the first window opens
+fn setup(
+    app: &mut tauri::App,
+    rotating_log: RotatingLog,
+    command: Option<cli::Command>,
+) -> Result<()> {
+    install_logger(app.handle(), rotating_log.clone())?;
+    let moved = paths::migrate();
+    if !moved.is_empty() {
+        log::info!("Moved {} files left by an earlier version", moved.len());
+    }
+    // The self-test's folder check reports this too
+    if let Err(e) = paths::ensure(app.handle()) {
+        log::error!("Failed to create the app's folders: {e:#}");
+    }
+    let (settings, settings_error) = match settings::load_or_create() {
+        Ok(settings) => (settings, None),
+        Err(e) => {
+            log::error!("{e:#}. Falling back to default settings");
+            (Settings::default(), Some(format!("{e:#}")))
+        }
+    };
+    log::set_max_level(settings.logging.level.into());
+    rotating_log.configure(paths::log_dir(app.handle())?, &settings.logging)?;
+    app.manage(rotating_log);
+    app.manage(LogFollower::default());
+    app.manage(SettingsState::new(settings.clone()));
+    app.manage(Activity::new());
+    #[cfg(target_os = "linux")]
+    app.manage(selection::primary::PrimarySelection::default());
+    app.manage(PushToTalk::default());
+    app.manage(LiveDictation::default());
+    app.manage(SessionTranscript::default());
+    app.manage(TargetPicker::default());
+    app.manage(DeliveryCount::default());
+    app.manage(AbortEpoch::default());
+    app.manage(LastAction::default());
+    app.manage(LastRecording::default());
+    let (usable_prompts, skipped_prompts) = prompts::usable(&settings);
+    app.manage(PromptLibrary::new(usable_prompts.clone()));
+    activity::watch_idle(app.handle().clone());
 
-            // TODO: Add activation policy for macos for app run background
-            // #[cfg(target_os = "macos")]
-            // app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+    #[cfg(desktop)]
+    {
+        let shortcuts_config =
+            ShortcutsConfig::from_settings(&settings.hotkeys, &usable_prompts)?;
 
-            // Channel for sending tasks to the local task handler
-            let (localtask_tx, localtask_rx) = mpsc::channel::<Task>(1);
+        app.manage(Mutex::new(shortcuts_config));
 
-            // Spawn a thread for the `LocalSet` to run on since
-            // `Enigo` and `AudioRecorder` are not `Send` nor `Sync`
-            let app_handle = app.handle().clone();
-            std::thread::spawn(move || {
-                run_local_task_handler(localtask_rx, app_handle);
-            });
+        app.handle().plugin(
+            tauri_plugin_global_shortcut::Builder::default()
+                .with_handler(on_shortcut)
+                .build(),
+        )?;
+        permissions::register_hotkeys_when_permitted(
+            app.handle().clone(),
+            |app_handle| {
+                let shortcuts = app_handle.state::<Mutex<ShortcutsConfig>>();
+                shortcuts.lock().unwrap().all()
+            },
+        );
+    }
 
-            app.notification()
-                .builder()
-                .title("Tauri")
-                .body("Tauri is awesome")
-                .show()
-                .unwrap();
-
-            let menu = MenuBuilder::new(app)
-                .item(&MenuItem::with_id(
-                    app,
-                    "toggle_recording",
-                    "Toggle Recording",
-                    true,
-                    None::<&str>,
-                )?)
-                .item(&MenuItem::with_id(
-                    app,
-                    "cleanse",
-                    "Polish clipboard",
-                    true,
-                    None::<&str>,
-                )?)
-                .separator()
-                .item(&MenuItem::with_id(
-                    app,
-                    "open_window",
-                    "Open Window",
-                    true,
-                    None::<&str>,
-                )?)
-                .separator()
-                .item(&MenuItem::with_id(app, "quit", "Quit app", true, None::<&str>)?)
-                .build()?;
-
-            let tray_icon = TrayIconBuilder::new()
-                .icon(app.default_window_icon().unwrap().clone())
-                .show_menu_on_left_click(false)
-                .menu(&menu)
-                .build(app)?;
-
-            let transcribe_client = TranscribeClient::new();
-
-            app.manage(localtask_tx)
-                .then(|| app.manage(transcribe_client))
-                .and_then(|_| app.manage(TranscribeIcon::new(tray_icon)).into())
-                .and_then(|_| app.manage(Arc::new(Mutex::new(false))).into())
-                .context("Failed to manage app state")?;
-
-            log::info!("Successfully managed app state");
-
-            Ok(())
-        })
-        .on_tray_icon_event(|app_handle, event| match event {
-            TrayIconEvent::Click {
-                button: MouseButton::Right,
-                button_state: MouseButtonState::Down,
-                ..
-            } => {
-                log::info!("Tray icon right clicked");
-                if let Err(e) = app_handle.show_menu() {
-                    log::error!("Failed to show menu: {}", e);
-                }
-            }
-            TrayIconEvent::Click {
-                button: MouseButton::Left,
-                button_state: MouseButtonState::Down,
-                ..
-            } => {
-                toggle_recording(app_handle.clone(), false);
-            }
-            _ => {}
-        })
-        .on_window_event(|window, event| {
-            log::info!("Window event received: {:?}", event);
-            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                window.hide().unwrap();
-                api.prevent_close();
-                log::info!("Window close requested");
-            }
-        })
-        .on_menu_event(|app_handle, event| {
-            log::info!("Menu event received: {:?}", event.id);
-            match event.id.as_ref() {
-                "quit" => {
-                    log::info!("{} application on user's request", "Quitting".red());
-                    app_handle.exit(0);
-                }
-                "toggle_recording" => {
-                    toggle_recording(app_handle.clone(), false);
-                }
-                "cleanse" => {
-                    cleanse_clipboard(app_handle.clone(), false);
-                }
-                "open_window" => {
-                    if let Some(window) = app_handle.get_webview_window("main") {
-                        if let Err(e) = window.show().and_then(|_| window.set_focus()) {
-                            log::error!("Failed to show and focus window: {}", e);
-                        }
-                    } else {
-                        log::error!("Failed to get webview window");
-                    }
-                }
-                id => {
-                    log::warn!("Unknown menu event: {}", id);
-                }
-            }
+    // TODO: Add activation policy for macos for app run background
+    // #[cfg(target_os = "macos")]
+    // app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+
+    // Channel for sending tasks to the local task handler
+    let (localtask_tx, localtask_rx) = mpsc::channel::<Task>(1);
+
+    // Spawn a thread for the `LocalSet` to run on since
+    // `Enigo` and `AudioRecorder` are not `Send` nor `Sync`
+    let app_handle = app.handle().clone();
+    std::thread::spawn(move || {
+        run_local_task_handler(localtask_rx, app_handle);
+    });
+
+    app.notification()
+        .builder()
+        .title("Tauri")
+        .body("Tauri is awesome")
+        .show()
+        .unwrap();
+
+    let history = History::open(&paths::data_dir(app.handle())?)?;
+    app.manage(history);
+    let tray_icon = tray::build(app, &settings, &usable_prompts)?;
+
+    let transcribe_client =
+        TranscribeClient::new(&settings.api, api_key::load().as_deref());
+
+    if !skipped_prompts.is_empty() {
+        AppNotifications::new(app.handle())
+            .notify(Notification::PromptsSkipped(skipped_prompts));
+    }
+    if let Some(reason) = settings_error {
+        AppNotifications::new(app.handle()).notify(Notification::SettingsInvalid(reason));
+    }
+    refresh_history_menus(app.handle().clone());
+    retention::schedule(app.handle().clone());
+    disk_budget::enforce(app.handle().clone());
+    updater::schedule(app.handle().clone());
+    power::watch_sleep(app.handle().clone());
+    autostart::sync(app.handle());
+    app.manage(WatchFolder::default());
+    watch_folder::apply(app.handle());
+    app.manage(SoundCues::default());
+    sounds::load(app.handle());
+    app.manage(AutomationServer::default());
+    automation::apply(app.handle());
+
+    app.manage(localtask_tx)
+        .then(|| app.manage(transcribe_client))
+        .and_then(|_| app.manage(TranscribeIcon::new(tray_icon)).into())
+        .and_then(|_| app.manage(Arc::new(Mutex::new(false))).into())
+        .context("Failed to manage app state")?;
+
+    stats::refresh_tooltip(app.handle().clone());
+    transcribe_icon::check_consistency(app.handle().clone());
+    warn_unsupported_options(app.handle(), &settings);
+    self_test::run_on_first_launch(app.handle().clone());
+    app.manage(WakeWord::default());
+    wake_word::apply(app.handle());
+    settings::watch(app.handle().clone(), on_settings_changed);
+
+    log::info!("Successfully managed app state");
+
+    let app_handle = app.handle().clone();
+    app.deep_link().on_open_url(move |event| {
+        deep_link::open_urls(&app_handle, event.urls());
+    });
+    // Installed builds register through the bundle, this covers dev runs
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(e) = app.deep_link().register_all() {
+        log::error!("Failed to register the {} scheme: {e}", deep_link::SCHEME);
+    }
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => deep_link::open_urls(app.handle(), urls),
+        Ok(None) => {}
+        Err(e) => log::error!("Failed to read the launch URL: {e}"),
+    }
+
+    if let Err(e) = cli::serve_status(app.handle().clone()) {
+        log::error!("Failed to serve --status: {e:#}");
+    }
+    if let Some(command) = command {
+        cli::run(app.handle(), command);
+    }
+    audio_file::open_pending(app.handle());
+
+    Ok(())
+}
+
+fn on_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    let shortcuts_config = app.state::<Mutex<ShortcutsConfig>>();
+    let shortcuts_config = shortcuts_config.lock().unwrap();
+
+    if event.state() == ShortcutState::Pressed {
+        log::info!("Shortcut triggered: {:?}", shortcut);
+    }
+
+    if shortcut == &shortcuts_config.toggle_recording
+        && event.state() == ShortcutState::Pressed
+    {
+        log::info!("F19 shortcut triggered - Start/Stop Recording");
+        toggle_recording(app.clone(), None);
+    } else if shortcuts_config.type_boost.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        toggle_recording(app.clone(), Some(OutputMode::Type));
+    } else if shortcuts_config.discard_recording.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        cancel_recording(app.clone());
+    } else if shortcuts_config.panic.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        abort::abort_all(app);
+    } else if shortcuts_config.repeat_last.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        repeat::repeat(app);
+    } else if shortcuts_config.quick_note.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        toggle_recording(app.clone(), Some(OutputMode::QuickNote));
+    } else if shortcuts_config.collect.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        collect::toggle(app);
+    }
+    // Check if the shortcut matches F20
+    else if shortcut == &shortcuts_config.cleanse_clipboard
+        && event.state() == ShortcutState::Pressed
+    {
+        log::info!("F20 shortcut triggered - Polish Clipboard");
+        cleanse_clipboard(app.clone(), None, PolishOverrides::default());
+    } else if shortcuts_config.push_to_talk.as_ref() == Some(shortcut) {
+        match event.state() {
+            ShortcutState::Pressed => push_to_talk::press(app),
+            ShortcutState::Released => push_to_talk::release(app),
+        }
+    } else if shortcuts_config.cycle_clipboard.as_ref() == Some(shortcut)
+        && event.state() == ShortcutState::Pressed
+    {
+        clip_ring::cycle(app);
+    } else if let Some(name) = shortcuts_config.prompt_for(shortcut) {
+        if event.state() == ShortcutState::Pressed {
+            prompts::run(app, name);
+        }
+    }
+}
+
+fn on_window_event(window: &tauri::Window, event: &tauri::WindowEvent) {
+    log::info!("Window event received: {:?}", event);
+    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+        window.hide().unwrap();
+        api.prevent_close();
+        log::info!("Window close requested");
+        // Closing the picker cancels it
+        if window.label() == target_picker::TARGET_WINDOW {
+            target_picker::choose(window.app_handle(), None);
+        }
+    }
+    // Audio files dropped on the main window are transcribed
+    if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event
+    {
+        if window.label() == "main" {
+            audio_file::transcribe(window.app_handle(), paths.clone());
+        }
+    }
+}
+
+/// Audio files dropped on the Dock icon, or opened with the app from Finder
+fn on_run_event(app_handle: &AppHandle, event: tauri::RunEvent) {
+    #[cfg(target_os = "macos")]
+    if let tauri::RunEvent::Opened { urls } = event {
+        let paths = urls.into_iter().filter_map(|url| url.to_file_path().ok()).collect();
+        audio_file::open(app_handle, paths);
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = (app_handle, event);
+}
+
+/// Everything passes the logger, `logging.level` caps it through `log::set_max_level`
+/// so it can change without a restart
+fn install_logger(app_handle: &AppHandle, file: RotatingLog) -> Result<()> {
+    let mut builder = tauri_plugin_log::Builder::new()
+        .clear_targets()
+        .target(Target::new(TargetKind::Stdout))
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "{}",
+                transcribe_app_logger::console_line(message, record)
+            ))
         })
-        .invoke_handler(tauri::generate_handler![greet, assign_shortcut])
-        .plugin(tauri_plugin_clipboard_manager::init())
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .level(log::LevelFilter::Trace);
+    for (target, level) in QUIET_TARGETS {
+        builder = builder.level_for(target, level);
+    }
+    let (plugin, console, _) = builder.split(app_handle)?;
+    app_handle.plugin(plugin)?;
+    log::set_boxed_logger(Box::new(Logger { console, file }))?;
+    // Until the settings are loaded
+    log::set_max_level(log::LevelFilter::Trace);
+    Ok(())
 }
 
-pub fn toggle_recording(app_handle: AppHandle, paste_from_clipboard: bool) {
+/// Asks which app a transcript goes to with `behavior.pick_target`, or copies it only
+async fn pick_target(
+    app_handle: &AppHandle,
+    output_mode: Option<OutputMode>,
+) -> Option<OutputMode> {
+    let settings = app_handle.state::<SettingsState>().get();
+    if !settings.behavior.pick_target {
+        return output_mode;
+    }
+    let mode = output_mode.unwrap_or_else(|| {
+        let app_id = frontmost_app::frontmost_app();
+        settings.resolve_output(app_id.as_deref()).mode
+    });
+    if !matches!(mode, OutputMode::Paste | OutputMode::Type) {
+        return output_mode;
+    }
+    match target_picker::pick_and_focus(app_handle).await {
+        Ok(true) => output_mode,
+        Ok(false) => Some(OutputMode::Clipboard),
+        Err(e) => {
+            log::warn!("Delivering without the target picker: {e:#}");
+            output_mode
+        }
+    }
+}
+
+/// `output_mode` forces where the transcription goes instead of resolving it from settings.
+///
+/// The clipboard is only written by the sinks at the very end, once a transcript came
+/// back non-empty. Errors, timeouts, empty recordings and empty or filler-only
+/// transcripts all return before that and leave the clipboard as it was.
+pub fn toggle_recording(app_handle: AppHandle, output_mode: Option<OutputMode>) {
+    app_handle.state::<Activity>().touch();
+    // The recorder belongs to the session until it's stopped
+    if app_handle.state::<DictationSession>().is_active() {
+        session::toggle(&app_handle);
+        return;
+    }
+    if app_handle.state::<LiveDictation>().is_active() {
+        live::stop(&app_handle, true);
+        return;
+    }
+    // Don't start a recording that can't be transcribed, stopping one still works
+    if quota::paused(&app_handle) && !app_handle.state::<TranscribeIcon>().is_recording()
+    {
+        AppNotifications::new(&app_handle)
+            .notify(Notification::QuotaExceeded { paused: true });
+        return;
+    }
+    // Long recordings are cut up and pasted as they go, whatever the backend
+    if output_mode.is_none()
+        && !app_handle.state::<TranscribeIcon>().is_recording()
+        && app_handle.state::<SettingsState>().get().session.chunk_recordings
+    {
+        session::toggle(&app_handle);
+        return;
+    }
     spawn(async move {
         let tx_task = app_handle.state::<mpsc::Sender<Task>>();
-        let (tx_recording, rx_recording) = oneshot::channel::<Vec<u8>>();
+        let (tx_recording, rx_recording) = oneshot::channel::<RecordingOutcome>();
 
         if let Err(e) = tx_task.send(Task::ToggleRecording(tx_recording)).await {
             log::error!("Failed to send 'ToggleRecording' task to channel: {}", e);
@@ -314,76 +832,482 @@ pub fn toggle_recording(app_handle: AppHandle, paste_from_clipboard: bool) {
 
         let transcribe_icon = app_handle.state::<TranscribeIcon>();
 
-        let recording_bytes = match rx_recording.await {
-            Ok(bytes) => {
-                if bytes.is_empty() {
-                    log::info!("Starting recording");
-                    transcribe_icon.change_icon(Icon::Recording);
-                    return;
-                }
-                bytes
+        let (recording_bytes, recording) = match rx_recording.await {
+            Ok(RecordingOutcome::RecordingStarted(live)) => {
+                log::info!("Starting recording");
+                sounds::play(&app_handle, Cue::Start);
+                audio_device::notice_bluetooth(&app_handle);
+                prewarm_connection(&app_handle);
+                transcribe_icon.sync_to(RecorderState::Starting);
+                wait_until_live(&app_handle, live).await;
+                return;
+            }
+            Ok(RecordingOutcome::RecordingStopped(bytes, _)) if bytes.is_empty() => {
+                log::warn!("Recording stopped without any audio captured");
+                sounds::play(&app_handle, Cue::Stop);
+                app_handle.state::<PromptLibrary>().take_pending();
+                transcribe_icon.sync_to(RecorderState::Idle);
+                return;
+            }
+            Ok(RecordingOutcome::RecordingStopped(bytes, info)) => {
+                sounds::play(&app_handle, Cue::Stop);
+                transcribe_icon.sync_to(RecorderState::Idle);
+                disk_budget::enforce(app_handle.clone());
+                (bytes, info)
             }
             Err(e) => {
                 log::error!(
                     "Failed to receive 'ToggleRecording' task from channel: {}",
                     e
                 );
-                transcribe_icon.change_icon(Icon::Default);
+                app_handle.state::<PromptLibrary>().take_pending();
+                transcribe_icon.sync_to(RecorderState::Idle);
                 return;
             }
         };
 
-        transcribe_icon.change_icon(Icon::Transcribing);
+        last_recording::keep(&app_handle, &recording_bytes, &recording);
+        transcribe_recording(app_handle, recording_bytes, recording, output_mode).await;
+    });
+}
 
-        let transcribe_client = app_handle.state::<TranscribeClient>();
-        let result = transcribe_client.fetch_transcription(recording_bytes).await;
+/// Transcribes a finished recording and delivers it. Also how "Retry last recording"
+/// sends the kept one again.
+pub async fn transcribe_recording(
+    app_handle: AppHandle,
+    recording_bytes: Vec<u8>,
+    mut recording: RecordingInfo,
+    output_mode: Option<OutputMode>,
+) {
+    let busy = transcribe_icon::busy(&app_handle, Work::Transcribing);
+    let ticket = sinks::ticket(&app_handle);
+    let epoch = abort::epoch(&app_handle);
 
-        transcribe_icon.change_icon(Icon::Default);
+    // Taken now so a failed transcription doesn't leave it for the next recording
+    let prompt = app_handle.state::<PromptLibrary>().take_pending();
+    let audio_duration = audio_recorder::wav_duration(&recording_bytes);
+    recording.duration = audio_duration.or(recording.duration);
+    let transcribe_client = app_handle.state::<TranscribeClient>();
+    let bytes = recording_bytes.len();
+    let warm = transcribe_client.connection_warm();
+    let settings = app_handle.state::<SettingsState>().get();
+    let plan = quality::choose(&settings, audio_duration);
+    let model = plan.and_then(|plan| plan.model(&settings));
+    let upload_plan = quality::upload(&settings, plan);
+    if let Some(plan) = plan {
+        log::info!(
+            "Quality plan {plan:?} for a {:.1}s recording, model {}, {upload_plan:?}",
+            audio_duration.unwrap_or_default().as_secs_f64(),
+            model.as_deref().unwrap_or("left to the backend")
+        );
+    }
+    let started = Instant::now();
+    let saved_copy =
+        saved_recordings::enabled(&app_handle).then(|| recording_bytes.clone());
+    let result = if upload_plan == UploadPlan::default() {
+        transcribe_client::request_transcription(
+            &*transcribe_client,
+            recording_bytes,
+            model.as_deref(),
+            settings.api.retry_on_empty,
+        )
+        .await
+    } else {
+        upload::transcribe_wav(
+            &*transcribe_client,
+            recording_bytes,
+            model.as_deref(),
+            upload_plan,
+        )
+        .await
+    };
+    log::info!(
+        stage = "transcribe",
+        duration_ms = started.elapsed().as_millis() as u64,
+        bytes = bytes,
+        warm = warm,
+        ok = result.is_ok();
+        "Transcription request finished"
+    );
+    drop(busy);
+    app_handle.state::<Activity>().touch();
+    if abort::aborted(&app_handle, epoch) {
+        log::info!("Dropping the transcription, aborted while it ran");
+        return;
+    }
 
-        let Ok(text) = result else {
-            log::error!("Failed to fetch transcription from API");
-            AppNotifications::new(&app_handle).notify(Notification::ApiError);
+    let transcription = match TranscribeOutcome::from(result) {
+        TranscribeOutcome::NoSpeech => {
+            log::info!("Transcription came back empty, skipping");
+            AppNotifications::new(&app_handle).notify(Notification::NoSpeech);
             return;
-        };
+        }
+        TranscribeOutcome::Deliver(transcription) => transcription,
+        TranscribeOutcome::Failed(e) => {
+            log::error!("Failed to fetch transcription from API: {e:#}");
+            notify_api_error(&app_handle, &e);
+            return;
+        }
+    };
 
-        log::info!("Transcription text: {}", text.yellow());
+    recording.language = transcription.language.clone();
+    recording.model = transcription.model.clone();
+    if let Some(bytes) = saved_copy {
+        let sidecar = Sidecar::new(
+            &app_handle,
+            transcription.text.clone(),
+            transcribe_client.provider(),
+            &recording,
+            transcribe_client.last_timing(),
+        );
+        saved_recordings::save(&app_handle, bytes, sidecar);
+    }
+    let text = transcription.text.clone();
+    log::info!(
+        "Transcription text: {}",
+        transcribe_app_logger::loggable(&app_handle, &text).yellow()
+    );
+    if let Some(language) = &transcription.language {
+        log::info!(
+            "Detected language {language:?} ({:?})",
+            transcription.language_probability
+        );
+    }
 
-        if let Err(e) = app_handle.clipboard().write_text(text) {
-            log::error!("Failed to write text to clipboard: {}", e);
+    let settings = app_handle.state::<SettingsState>().get();
+    let text = match filler::apply(text, &settings.filler) {
+        FillerOutcome::Text(text) => text,
+        FillerOutcome::NoSpeech => {
+            log::info!("Transcription contained only filler words, skipping");
+            AppNotifications::new(&app_handle).notify(Notification::NoSpeech);
             return;
         }
+    };
+    let text = wake_word::strip_stop_phrase(&app_handle, text);
+    if text.trim().is_empty() {
+        log::info!("Transcription was only the stop phrase, skipping");
+        return;
+    }
+
+    stats::record(&app_handle, &text);
+    save_to_history(
+        &app_handle,
+        NewEntry {
+            provider: Some(transcribe_client.provider()),
+            ..NewEntry::new(EntryKind::Transcription, text.clone())
+                .with_recording(&recording)
+        },
+    );
+
+    let mut kind = EntryKind::Transcription;
+    // Only a prompt's result may be formatted, not a failed prompt's transcript
+    let rich_text = prompt
+        .as_ref()
+        .is_some_and(|prompt| prompt.rich_text(&settings.behavior));
+    let text = match prompt {
+        Some(prompt) => {
+            let started = Instant::now();
+            let result = prompts::transform(
+                &app_handle,
+                &prompt,
+                text.clone(),
+                transcription.language.as_deref(),
+            )
+            .await;
+            log::info!(
+                stage = "prompt",
+                duration_ms = started.elapsed().as_millis() as u64,
+                ok = result.is_ok();
+                "Prompt {:?} finished", prompt.name
+            );
+            match result {
+                Ok(transformed) if transformed.trim().is_empty() => {
+                    log::warn!("Prompt {:?} returned nothing", prompt.name);
+                    text
+                }
+                Ok(transformed) => {
+                    kind = EntryKind::Polish;
+                    save_to_history(
+                        &app_handle,
+                        NewEntry {
+                            provider: Some(transcribe_client.provider()),
+                            source_text: Some(text),
+                            ..NewEntry::new(EntryKind::Polish, transformed.clone())
+                        },
+                    );
+                    transformed
+                }
+                Err(e) => {
+                    // The plain transcript is still worth delivering
+                    log::error!("Prompt {:?} failed: {e:#}", prompt.name);
+                    notify_api_error(&app_handle, &e);
+                    text
+                }
+            }
+        }
+        None => text,
+    };
 
-        if !paste_from_clipboard {
-            AppNotifications::new(&app_handle).notify(Notification::TranscribeSuccess);
+    let text = voice_commands::apply(text, &settings.voice_commands);
+    let text = snippets::expand(text, &settings.snippets);
+    // Delivered with the rest once collecting is finished
+    if collect::add(&app_handle, &text) {
+        sounds::play(&app_handle, Cue::Success);
+        return;
+    }
+    let text = match length_limit::apply(&app_handle, text, &settings.behavior) {
+        Limited::Unchanged(text) => text,
+        Limited::Truncated { text, full_text } => {
+            AppNotifications::new(&app_handle).notify(Notification::OutputTruncated {
+                max: settings.behavior.max_output_chars,
+                full_text,
+            });
+            text
+        }
+        Limited::Refused { chars } => {
+            AppNotifications::new(&app_handle).notify(Notification::OutputRefused {
+                chars,
+                max: settings.behavior.max_output_chars,
+            });
             return;
         }
+    };
+    let payload = HookPayload {
+        duration_ms: audio_duration.map(|d| d.as_millis() as u64),
+        language: transcription.language.clone(),
+        ..HookPayload::new(kind, text.clone())
+    };
+    if sinks::superseded(&app_handle, ticket) {
+        keep_superseded(&app_handle, false);
+        return;
+    }
+    let output_mode = pick_target(&app_handle, output_mode).await;
+    if abort::aborted(&app_handle, epoch) {
+        log::info!("Not delivering the transcription, aborted");
+        return;
+    }
+    let started = Instant::now();
+    let rich_text = rich_text && kind == EntryKind::Polish;
+    let Some(report) = sinks::deliver_unless_superseded(
+        &app_handle,
+        ticket,
+        text,
+        output_mode,
+        Some(&recording),
+        rich_text,
+    )
+    .await
+    else {
+        keep_superseded(&app_handle, false);
+        return;
+    };
+    log::info!(
+        stage = "deliver",
+        duration_ms = started.elapsed().as_millis() as u64,
+        failures = report.failures.len();
+        "Delivery finished"
+    );
+    hook::fire(&app_handle, payload);
+    if !report.failures.is_empty() {
+        sounds::play(&app_handle, Cue::Error);
+        AppNotifications::new(&app_handle)
+            .notify(Notification::DeliveryFailed(report.failures));
+        return;
+    }
+    sounds::play(&app_handle, Cue::Success);
+    if report.mode == Some(OutputMode::Clipboard) {
+        AppNotifications::new(&app_handle).notify(Notification::TranscribeSuccess {
+            language: transcription.language.clone(),
+            language_uncertain: transcription.language_uncertain(),
+            plan: plan.map(Plan::name),
+        });
+    }
+    log::info!("exiting toggle recording function");
+}
 
-        if let Err(e) = tx_task.send(Task::PasteFromClipboard).await {
-            log::error!("Failed to send 'PasteFromClipboard' task to channel: {}", e);
-        } else {
-            log::info!("Successfully pasted text from clipboard");
+/// Stops the current recording, if any, without transcribing it. A dictation session
+/// or live dictation that already transcribed some of it keeps that as a partial
+/// transcript.
+pub fn cancel_recording(app_handle: AppHandle) {
+    if app_handle.state::<DictationSession>().is_active() {
+        session::cancel(&app_handle);
+        return;
+    }
+    if app_handle.state::<LiveDictation>().is_active() {
+        live::cancel(&app_handle);
+        return;
+    }
+    // A prompt waiting for this recording's transcript would run on the next one
+    app_handle.state::<PromptLibrary>().take_pending();
+    spawn(async move {
+        let (tx_discard, rx_discard) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::DiscardRecording(tx_discard)).await.is_err() {
+            log::error!("Failed to send 'DiscardRecording' task to channel");
+            return;
         }
-        log::info!("exiting toggle recording function");
+        _ = rx_discard.await;
+        app_handle.state::<TranscribeIcon>().sync_to(RecorderState::Idle);
     });
 }
 
-pub fn cleanse_clipboard(app_handle: AppHandle, paste_from_clipboard: bool) {
+/// Gets a connection to the API ready while the user speaks, with `api.prewarm`
+fn prewarm_connection(app_handle: &AppHandle) {
+    if !app_handle.state::<SettingsState>().get().api.prewarm {
+        return;
+    }
+    let app_handle = app_handle.clone();
     spawn(async move {
-        let Ok(clipboard_text) = app_handle.clipboard().read_text() else {
-            log::error!("Failed to read from clipboard");
-            return;
-        };
+        app_handle.state::<TranscribeClient>().prewarm().await;
+    });
+}
 
-        let notifs = app_handle.notification();
+/// Tears down the recorder and its stream and builds them again from the current
+/// `[audio]` settings, which clears a stuck stream or a stale device handle. Fails
+/// while recording.
+async fn reinit_audio(app_handle: &AppHandle) -> Result<String> {
+    let (tx, rx) = oneshot::channel();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    tx_task
+        .send(Task::ReinitAudio(tx))
+        .await
+        .map_err(|_| anyhow!("Failed to send 'ReinitAudio' task to channel"))?;
+    let device = rx.await??;
+    log::info!("Audio reset, recording from {device:?}");
+    Ok(device)
+}
+
+/// Shows the recording icon once audio is really coming in. If the microphone stays
+/// silent past the timeout the icon switches anyway and the user is told.
+async fn wait_until_live(app_handle: &AppHandle, live: oneshot::Receiver<()>) {
+    let transcribe_icon = app_handle.state::<TranscribeIcon>();
+    let result = tokio::time::timeout(RECORDING_LIVE_TIMEOUT, live).await;
+    // Stopped or discarded before any audio came in
+    if !transcribe_icon.sync_to(RecorderState::Live) {
+        return;
+    }
+    match result {
+        Ok(Ok(())) => {
+            log::info!("Recording is live");
+            if let Err(e) = app_handle.emit(RECORDING_LIVE_EVENT, ()) {
+                log::error!("Failed to emit {RECORDING_LIVE_EVENT}: {}", e);
+            }
+            if app_handle.state::<SettingsState>().get().audio.warn_if_muted {
+                warn_if_muted(app_handle).await;
+            }
+        }
+        Ok(Err(_)) => log::debug!("Recording stopped before any audio arrived"),
+        Err(_) => {
+            log::warn!("No audio after {RECORDING_LIVE_TIMEOUT:?}, recording anyway");
+            AppNotifications::new(app_handle).notify(Notification::MicSlow);
+        }
+    }
+}
 
-        if clipboard_text.is_empty() {
-            _ = notifs
-                .builder()
-                .title("Empty clipboard")
-                .body("We couldn't find any text in your clipboard to polish")
-                .show();
+/// Samples the input level over the first second of a recording. A muted device
+/// still delivers buffers, just all zeros, so every reading comes back exactly 0.0.
+async fn warn_if_muted(app_handle: &AppHandle) {
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let started = Instant::now();
+    while started.elapsed() < MUTED_CHECK_DURATION {
+        tokio::time::sleep(MUTED_CHECK_INTERVAL).await;
+        // Stopped already, too short to tell
+        if !app_handle.state::<TranscribeIcon>().is_recording() {
+            return;
+        }
+        let (tx_level, rx_level) = oneshot::channel();
+        if tx_task.send(Task::InputLevel(tx_level)).await.is_err() {
             return;
         }
+        match rx_level.await {
+            Ok(level) if level == 0.0 => {}
+            _ => return,
+        }
+    }
+    log::warn!("Input stayed at digital silence for {MUTED_CHECK_DURATION:?}");
+    AppNotifications::new(app_handle).notify(Notification::MicPossiblyMuted);
+}
+
+/// Replaces the resolved polish style or the backend's instructions for one polish
+#[derive(Debug, Clone, Default)]
+pub struct PolishOverrides {
+    pub style: Option<String>,
+    pub prompt: Option<String>,
+}
+
+/// Readies the focused app for polished text that is about to be pasted or typed,
+/// following `polish_paste_back` for that app
+async fn prepare_paste_back(
+    app_handle: &AppHandle,
+    settings: &Settings,
+    output: &ResolvedOutput,
+    mode: OutputMode,
+) {
+    let outputs = settings
+        .sinks
+        .iter()
+        .any(|sink| sink.enabled && sink.kind == SinkKind::Output);
+    if !outputs || !matches!(mode, OutputMode::Paste | OutputMode::Type) {
+        return;
+    }
+    // Delivery leaves the text on the clipboard there, and Enter could submit a form
+    if sinks::into_password_field(&settings.behavior, mode) {
+        return;
+    }
+    let task = match output.polish_paste_back {
+        PolishPasteBack::Paste => return,
+        PolishPasteBack::Undo if output.polish_undo_count == 0 => return,
+        PolishPasteBack::Undo => Task::Undo(output.polish_undo_count),
+        PolishPasteBack::Below => Task::NewLine,
+    };
+    log::info!(
+        "Polish paste-back: {:?} (undo count {})",
+        output.polish_paste_back,
+        output.polish_undo_count
+    );
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    if tx_task.send(task).await.is_err() {
+        log::error!("Failed to send the paste-back task to channel");
+    }
+}
+
+/// Polishes the clipboard text and delivers it like a transcription, so the output
+/// mode and sinks apply to both. `output_mode` overrides the resolved mode.
+pub fn cleanse_clipboard(
+    app_handle: AppHandle,
+    output_mode: Option<OutputMode>,
+    overrides: PolishOverrides,
+) {
+    app_handle.state::<Activity>().touch();
+    spawn(async move {
+        let notifications = AppNotifications::new(&app_handle);
+        let clipboard_text = match selection::read_content(&app_handle).await {
+            Ok(Content::Text(text)) => text,
+            Ok(Content::Empty) => {
+                notifications.notify(Notification::ClipboardEmpty);
+                return;
+            }
+            Ok(Content::Image) => {
+                log::info!("Clipboard holds an image, nothing to polish");
+                notifications.notify(Notification::ClipboardImage);
+                return;
+            }
+            Ok(Content::Files(paths)) => {
+                log::info!("Clipboard holds {} file(s), nothing to polish", paths.len());
+                audio_file::offer(&app_handle, paths);
+                return;
+            }
+            Err(e) if e.is::<selection::Unsettled>() => {
+                log::warn!("Not polishing, {e}");
+                notifications.notify(Notification::ClipboardUnsettled);
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to read from clipboard: {e:#}");
+                notifications.notify(Notification::ClipboardUnavailable);
+                return;
+            }
+        };
 
         let is_cleansing_m = app_handle.state::<Arc<Mutex<bool>>>();
         let mut is_cleansing = is_cleansing_m.lock().unwrap();
@@ -394,51 +1318,341 @@ pub fn cleanse_clipboard(app_handle: AppHandle, paste_from_clipboard: bool) {
         *is_cleansing = true;
         drop(is_cleansing);
 
-        app_handle.state::<TranscribeIcon>().change_icon(Icon::Cleansing);
+        let busy = transcribe_icon::busy(&app_handle, Work::Polishing);
 
-        log::info!("Starting polish of: {}", clipboard_text.yellow());
+        log::info!(
+            "Starting polish of: {}",
+            transcribe_app_logger::loggable(&app_handle, &clipboard_text).yellow()
+        );
+        let ticket = sinks::ticket(&app_handle);
+        let epoch = abort::epoch(&app_handle);
 
         let app_handle_ = app_handle.clone();
         spawn(async move {
+            let _busy = busy;
             let client = app_handle_.state::<TranscribeClient>();
 
             AppNotifications::new(&app_handle_).notify(Notification::StartPolishing);
 
-            let Ok(cleansed_text) = client.clean_transcription(clipboard_text).await
-            else {
-                log::error!("Failed to clean transcription");
-                AppNotifications::new(&app_handle_).notify(Notification::ApiError);
-                app_handle_.state::<TranscribeIcon>().change_icon(Icon::Default);
-                *app_handle_.state::<Arc<Mutex<bool>>>().lock().unwrap() = false;
+            let settings = app_handle_.state::<SettingsState>().get();
+            let app_id = frontmost_app::frontmost_app();
+            let output = settings.resolve_output(app_id.as_deref());
+            let language = if let Some(language) = &settings.behavior.polish_language {
+                Some(language.clone())
+            } else if settings.behavior.polish_in_detected_language {
+                transcribed_language(&app_handle_, &clipboard_text).await
+            } else {
+                None
+            };
+            let format = text_format::for_polish(&settings.behavior, &clipboard_text);
+            let warm = client.connection_warm();
+            let polish = client.clean_transcription(
+                clipboard_text.clone(),
+                overrides.style.as_deref().unwrap_or(&output.polish_style),
+                overrides.prompt.as_deref(),
+                language.as_deref(),
+                format,
+            );
+            let started = Instant::now();
+            let result = polish.await;
+            log::info!(
+                stage = "polish",
+                duration_ms = started.elapsed().as_millis() as u64,
+                bytes = clipboard_text.len(),
+                warm = warm,
+                ok = result.is_ok();
+                "Polish request finished"
+            );
+            if abort::aborted(&app_handle_, epoch) {
+                log::info!("Dropping the polished text, aborted while it ran");
                 return;
+            }
+            let cleansed_text = match result {
+                Ok(text) => text,
+                Err(e) => {
+                    log::error!("Failed to clean transcription: {e:#}");
+                    notify_api_error(&app_handle_, &e);
+                    *app_handle_.state::<Arc<Mutex<bool>>>().lock().unwrap() = false;
+                    return;
+                }
             };
 
-            log::info!("Polished text: {}", cleansed_text.to_string().yellow());
+            log::info!(
+                "Polished text: {}",
+                transcribe_app_logger::loggable(&app_handle_, &cleansed_text).yellow()
+            );
 
-            app_handle_.clipboard().write_text(cleansed_text).unwrap();
+            save_to_history(
+                &app_handle_,
+                NewEntry {
+                    provider: Some(client.provider()),
+                    source_text: Some(clipboard_text),
+                    ..NewEntry::new(EntryKind::Polish, cleansed_text.clone())
+                },
+            );
 
-            if !paste_from_clipboard {
-                AppNotifications::new(&app_handle_).notify(Notification::PolishSuccess);
+            if sinks::superseded(&app_handle_, ticket) {
+                keep_superseded(&app_handle_, true);
+                *app_handle_.state::<Arc<Mutex<bool>>>().lock().unwrap() = false;
+                return;
+            }
+            let payload = HookPayload::new(EntryKind::Polish, cleansed_text.clone());
+            prepare_paste_back(
+                &app_handle_,
+                &settings,
+                &output,
+                output_mode.unwrap_or(output.mode),
+            )
+            .await;
+            let rich_text = settings.behavior.rich_text;
+            let Some(report) = sinks::deliver_unless_superseded(
+                &app_handle_,
+                ticket,
+                cleansed_text,
+                output_mode,
+                None,
+                rich_text,
+            )
+            .await
+            else {
+                keep_superseded(&app_handle_, true);
                 *app_handle_.state::<Arc<Mutex<bool>>>().lock().unwrap() = false;
-                app_handle_.state::<TranscribeIcon>().change_icon(Icon::Default);
                 return;
+            };
+            hook::fire(&app_handle_, payload);
+            if !report.failures.is_empty() {
+                AppNotifications::new(&app_handle_)
+                    .notify(Notification::DeliveryFailed(report.failures));
+            } else {
+                if report.mode == Some(OutputMode::Clipboard) {
+                    AppNotifications::new(&app_handle_)
+                        .notify(Notification::PolishSuccess);
+                }
+                repeat::record(
+                    &app_handle_,
+                    repeat::Action::Polish {
+                        output_mode,
+                        overrides,
+                    },
+                );
+            }
+
+            let is_cleansing = app_handle_.state::<Arc<Mutex<bool>>>();
+            *is_cleansing.lock().unwrap() = false;
+
+            log::info!("Cleansing complete. Set 'IsCleansing' to false");
+        });
+    });
+}
+
+/// Keeps text that finished after something else was delivered in history only, so it
+/// neither replaces the clipboard nor pastes a second time
+fn keep_superseded(app_handle: &AppHandle, polish: bool) {
+    log::warn!(
+        "Something else was delivered while {}, keeping this in history",
+        if polish { "polishing" } else { "transcribing" }
+    );
+    AppNotifications::new(app_handle).notify(Notification::DeliverySuperseded { polish });
+}
+
+/// Writes an entry to the history database in the background and refreshes the tray
+/// Skipped in privacy mode, apart from the in-memory clipboard ring
+fn save_to_history(app_handle: &AppHandle, entry: NewEntry) {
+    app_handle.state::<ClipRing>().push(app_handle, entry.text.clone());
+    if privacy::enabled(app_handle) {
+        return;
+    }
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        if let Err(e) = history.insert(entry).await {
+            if !e.is::<Locked>() {
+                log::error!("Failed to save history entry: {e:#}");
+            } else if history.pending_count() == 1 {
+                AppNotifications::new(&app_handle).notify(Notification::HistoryLocked);
             }
+            return;
+        }
+        refresh_history_menus(app_handle);
+    });
+}
 
-            let tx_task = app_handle_.state::<mpsc::Sender<Task>>();
-            let (tx_undo, rx_undo) = oneshot::channel::<()>();
+/// Rebuilds the "Recent" and "Pinned" tray submenus
+fn refresh_history_menus(app_handle: AppHandle) {
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        match history.recent(RECENT_LIMIT).await {
+            Ok(entries) => {
+                app_handle.state::<RecentMenu>().refresh(&app_handle, &entries)
+            }
+            Err(e) if e.is::<Locked>() => log::debug!("Recent history is locked"),
+            Err(e) => log::error!("Failed to load recent history: {e:#}"),
+        }
+        match history.pinned().await {
+            Ok(entries) => {
+                app_handle.state::<PinnedMenu>().refresh(&app_handle, &entries)
+            }
+            Err(e) if e.is::<Locked>() => log::debug!("Pinned history is locked"),
+            Err(e) => log::error!("Failed to load pinned history: {e:#}"),
+        }
+    });
+}
 
-            tx_task.send(Task::UndoText(tx_undo)).await.unwrap();
+/// Quick tagging from the tray, desktop notifications can't carry actions
+fn tag_latest_entry(app_handle: AppHandle, tag: String) {
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        let result = match history.recent(1).await {
+            Ok(entries) => match entries.first() {
+                Some(entry) => history.add_tag(entry.id, &tag).await,
+                None => {
+                    log::info!("No history entry to tag");
+                    return;
+                }
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => log::info!("Tagged the latest history entry with {tag:?}"),
+            Err(e) => log::error!("Failed to tag history entry: {e:#}"),
+        }
+    });
+}
 
-            _ = rx_undo.await; // Wait for the undo future to complete
+/// The detected language when `text` is the latest transcription, the usual case
+/// when polishing right after recording
+async fn transcribed_language(app_handle: &AppHandle, text: &str) -> Option<String> {
+    let history = app_handle.state::<History>().inner().clone();
+    let entry = history.recent(1).await.ok()?.into_iter().next()?;
+    (entry.kind == EntryKind::Transcription && entry.text.trim() == text.trim())
+        .then_some(entry.language)
+        .flatten()
+}
+
+/// Pastes the newest history entry again
+pub fn paste_latest_entry(app_handle: AppHandle) {
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        let entry = match history.recent(1).await {
+            Ok(entries) => match entries.into_iter().next() {
+                Some(entry) => entry,
+                None => {
+                    log::info!("No history entry to paste");
+                    return;
+                }
+            },
+            Err(e) => {
+                log::error!("Failed to load the latest history entry: {e:#}");
+                return;
+            }
+        };
+        if let Err(e) = app_handle.clipboard().write_text(entry.text) {
+            log::error!("Failed to write text to clipboard: {}", e);
+            return;
+        }
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::PasteFromClipboard).await.is_err() {
+            log::error!("Failed to send 'PasteFromClipboard' task to channel");
+        }
+    });
+}
 
-            tx_task.send(Task::PasteFromClipboard).await.unwrap();
+/// Copies a pinned entry, pasting it too when the pin asks for that
+fn use_pinned_entry(app_handle: AppHandle, entry_id: i64) {
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        let entry = match history.get(entry_id).await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                log::warn!("Pinned entry {entry_id} no longer exists");
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to load pinned entry: {e:#}");
+                return;
+            }
+        };
+        if let Err(e) = app_handle.clipboard().write_text(entry.text) {
+            log::error!("Failed to write text to clipboard: {}", e);
+            return;
+        }
+        if entry.pin_auto_paste {
+            let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+            if tx_task.send(Task::PasteFromClipboard).await.is_err() {
+                log::error!("Failed to send 'PasteFromClipboard' task to channel");
+            }
+        }
+    });
+}
 
-            app_handle_.state::<TranscribeIcon>().change_icon(Icon::Default);
+async fn set_entry_pinned(
+    app_handle: &AppHandle,
+    entry_id: i64,
+    pin: Option<(Option<String>, bool)>,
+) -> Result<()> {
+    let history = app_handle.state::<History>().inner().clone();
+    let found = match pin {
+        Some((title, auto_paste)) => history.pin(entry_id, title, auto_paste).await?,
+        None => history.unpin(entry_id).await?,
+    };
+    if !found {
+        bail!("History entry {entry_id} not found");
+    }
+    refresh_history_menus(app_handle.clone());
+    Ok(())
+}
 
-            let is_cleansing = app_handle_.state::<Arc<Mutex<bool>>>();
-            *is_cleansing.lock().unwrap() = false;
+/// Parses the entry id out of a menu item id like `recent:42`
+fn menu_entry_id(id: &str, prefix: &str) -> Option<i64> {
+    id.strip_prefix(prefix)?.parse().ok()
+}
 
-            log::info!("Cleansing complete. Set 'IsCleansing' to false");
-        });
+fn copy_history_entry(app_handle: AppHandle, entry_id: i64) {
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        match history.get(entry_id).await {
+            Ok(Some(entry)) => {
+                if let Err(e) = app_handle.clipboard().write_text(entry.text) {
+                    log::error!("Failed to write text to clipboard: {}", e);
+                }
+            }
+            Ok(None) => log::warn!("History entry {entry_id} no longer exists"),
+            Err(e) => log::error!("Failed to load history entry: {e:#}"),
+        }
     });
 }
+
+fn open_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        if let Err(e) = window.show().and_then(|_| window.set_focus()) {
+            log::error!("Failed to show and focus window: {}", e);
+        }
+    } else {
+        log::error!("Failed to get webview window");
+    }
+}
+
+/// A rejected API key sends the user to the settings window to enter a new one
+fn notify_api_error(app_handle: &AppHandle, error: &anyhow::Error) {
+    sounds::play(app_handle, Cue::Error);
+    let notification = match ApiFailure::from(error) {
+        ApiFailure::QuotaExceeded => {
+            quota::exceeded(app_handle);
+            return;
+        }
+        ApiFailure::TooLong { tokens, max } => {
+            Notification::PolishTooLong { tokens, max }
+        }
+        ApiFailure::BadResponse(diagnostic) => Notification::BadApiResponse(diagnostic),
+        ApiFailure::Other => Notification::ApiError,
+        ApiFailure::Unauthorized => {
+            AppNotifications::new(app_handle).notify(Notification::ApiKeyInvalid);
+            open_main_window(app_handle);
+            if let Err(e) = app_handle.emit(API_KEY_REQUIRED_EVENT, ()) {
+                log::error!("Failed to emit {API_KEY_REQUIRED_EVENT}: {}", e);
+            }
+            return;
+        }
+    };
+    AppNotifications::new(app_handle).notify(notification);
+}
@@ -0,0 +1,210 @@
+use crate::settings::UploadFormat;
+use crate::transcribe_client::{
+    Decode, QuotaExceeded, Transcription, TranscriptionBackend, Unauthorized,
+};
+use anyhow::{Result, bail};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+/// Set to an outcome like "ok" or "401" to answer every request without the network
+const MOCK_VAR: &str = "WHISTLE_MOCK";
+const DELAY_VAR: &str = "WHISTLE_MOCK_DELAY_MS";
+const DEFAULT_DELAY: Duration = Duration::from_millis(800);
+const TRANSCRIPT: &str = "This is a mock transcription, nothing was sent anywhere.";
+
+/// How the mock answers, one per error path the app handles differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockOutcome {
+    Succeed,
+    /// Succeeds without any text, as for a silent recording
+    Empty,
+    Timeout,
+    Unauthorized,
+    RateLimited,
+    QuotaExceeded,
+    BadResponse,
+}
+
+impl MockOutcome {
+    pub const ALL: [MockOutcome; 7] = [
+        MockOutcome::Succeed,
+        MockOutcome::Empty,
+        MockOutcome::Timeout,
+        MockOutcome::Unauthorized,
+        MockOutcome::RateLimited,
+        MockOutcome::QuotaExceeded,
+        MockOutcome::BadResponse,
+    ];
+
+    /// The value of `WHISTLE_MOCK` and the id suffix of its tray item
+    pub fn key(self) -> &'static str {
+        match self {
+            MockOutcome::Succeed => "ok",
+            MockOutcome::Empty => "empty",
+            MockOutcome::Timeout => "timeout",
+            MockOutcome::Unauthorized => "401",
+            MockOutcome::RateLimited => "429",
+            MockOutcome::QuotaExceeded => "quota",
+            MockOutcome::BadResponse => "bad_response",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|outcome| outcome.key() == key)
+    }
+}
+
+/// Stands in for the backend while working on the app, so no API key or credit is
+/// needed. Answers after `delay`, like a real request would.
+#[derive(Debug, Clone, Copy)]
+pub struct MockBackend {
+    pub outcome: MockOutcome,
+    pub delay: Duration,
+}
+
+impl MockBackend {
+    pub fn new(outcome: MockOutcome) -> Self {
+        let delay = std::env::var(DELAY_VAR)
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DELAY);
+        Self { outcome, delay }
+    }
+
+    /// From `WHISTLE_MOCK`, `None` when unset. An unknown value mocks a success.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(MOCK_VAR).ok().filter(|value| !value.is_empty())?;
+        let outcome = MockOutcome::from_key(&value).unwrap_or_else(|| {
+            log::warn!("Unknown {MOCK_VAR} value {value:?}, mocking success");
+            MockOutcome::Succeed
+        });
+        Some(Self::new(outcome))
+    }
+
+    /// Hands the text back unchanged, or nothing for `Empty`
+    pub async fn polish(&self, text: String) -> Result<String> {
+        self.respond().await?;
+        match self.outcome {
+            MockOutcome::Empty => Ok(String::new()),
+            _ => Ok(text),
+        }
+    }
+
+    /// Waits out the delay, then fails the way the real client would for the outcome
+    pub async fn respond(&self) -> Result<()> {
+        tokio::time::sleep(self.delay).await;
+        match self.outcome {
+            MockOutcome::Succeed | MockOutcome::Empty => Ok(()),
+            MockOutcome::Timeout => bail!("The request timed out (mock)"),
+            MockOutcome::Unauthorized => Err(Unauthorized.into()),
+            MockOutcome::RateLimited => bail!(
+                "The backend is rate limiting requests ({})",
+                StatusCode::TOO_MANY_REQUESTS
+            ),
+            MockOutcome::QuotaExceeded => Err(QuotaExceeded.into()),
+            MockOutcome::BadResponse => Err(Decode {
+                status: StatusCode::OK,
+                content_type: Some("text/html".into()),
+            }
+            .into()),
+        }
+    }
+}
+
+impl TranscriptionBackend for MockBackend {
+    fn upload_format(&self) -> Option<UploadFormat> {
+        None
+    }
+
+    async fn transcribe(
+        &self,
+        _recording: Vec<u8>,
+        _model: Option<&str>,
+    ) -> Result<Transcription> {
+        self.respond().await?;
+        let text = match self.outcome {
+            MockOutcome::Empty => "",
+            _ => TRANSCRIPT,
+        };
+        Ok(Transcription {
+            text: text.into(),
+            language: Some("en".into()),
+            language_probability: Some(0.99),
+            model: Some("mock".into()),
+        })
+    }
+}
+
+#[cfg(debug_assertions)]
+pub use menu::{MOCK_ID_PREFIX, MockMenu, select};
+
+/// The "Mock backend" tray submenu, only in debug builds
+#[cfg(debug_assertions)]
+mod menu {
+    use super::{MockBackend, MockOutcome};
+    use crate::transcribe_client::TranscribeClient;
+    use anyhow::Result;
+    use tauri::{
+        AppHandle, Manager, Wry,
+        menu::{CheckMenuItem, Submenu},
+    };
+
+    pub const MOCK_ID_PREFIX: &str = "mock:";
+    const OFF_KEY: &str = "off";
+
+    /// One check item for the real backend and one per mocked outcome
+    pub struct MockMenu {
+        items: Vec<(Option<MockOutcome>, CheckMenuItem<Wry>)>,
+    }
+
+    impl MockMenu {
+        pub fn new(
+            app_handle: &AppHandle,
+            submenu: &Submenu<Wry>,
+            current: Option<MockOutcome>,
+        ) -> Result<Self> {
+            let mut items = vec![];
+            let choices =
+                std::iter::once(None).chain(MockOutcome::ALL.into_iter().map(Some));
+            for outcome in choices {
+                let (key, label) = match outcome {
+                    Some(outcome) => (outcome.key(), format!("{outcome:?}")),
+                    None => (OFF_KEY, "Off".to_string()),
+                };
+                let item = CheckMenuItem::with_id(
+                    app_handle,
+                    format!("{MOCK_ID_PREFIX}{key}"),
+                    label,
+                    true,
+                    outcome == current,
+                    None::<&str>,
+                )?;
+                submenu.append(&item)?;
+                items.push((outcome, item));
+            }
+            Ok(Self { items })
+        }
+
+        fn set_checked(&self, current: Option<MockOutcome>) {
+            for (outcome, item) in &self.items {
+                if let Err(e) = item.set_checked(*outcome == current) {
+                    log::error!("Unable to update mock menu: {e}");
+                }
+            }
+        }
+    }
+
+    /// Switches the backend for the item with id suffix `key`
+    pub fn select(app_handle: &AppHandle, key: &str) {
+        let outcome = MockOutcome::from_key(key);
+        if outcome.is_none() && key != OFF_KEY {
+            log::warn!("Unknown mock menu item {key:?}");
+            return;
+        }
+        app_handle
+            .state::<TranscribeClient>()
+            .set_mock(outcome.map(MockBackend::new));
+        app_handle.state::<MockMenu>().set_checked(outcome);
+    }
+}
@@ -1,12 +1,147 @@
-use tauri::AppHandle;
+use crate::settings::SettingsState;
+use tauri::{AppHandle, Manager};
 use tauri_plugin_notification::NotificationExt;
 
 pub enum Notification {
     StartPolishing,
     PolishSuccess,
-    TranscribeSuccess, // when not pasting from clipboard
+    // when not pasting from clipboard
+    TranscribeSuccess {
+        language: Option<String>,
+        language_uncertain: bool,
+        /// The `quality.auto` plan, e.g. "fast"
+        plan: Option<&'static str>,
+    },
     ApiError,
+    BadApiResponse(String), // short diagnostic, e.g. status and content type
+    ApiKeyInvalid,
+    QuotaExceeded {
+        paused: bool,
+    },
+    NoSpeech,
+    MicSlow,
+    MicPossiblyMuted,
+    BluetoothMic {
+        device: String,
+        avoided: bool,
+    },
+    NoInputDevice,
+    AudioReset {
+        device: String,
+    },
+    AudioResetRefused,
+    /// The panic key stopped everything
+    Aborted,
+    /// A dictation was cancelled after some of it was transcribed, which was copied
+    PartialTranscript {
+        words: usize,
+    },
+    NothingToRepeat,
+    WakeTeach {
+        phrase: String,
+    },
+    WakeLearned {
+        phrase: String,
+        examples: usize,
+    },
+    WakeTeachTimedOut,
+    /// `wake.enabled` was turned on before a start phrase was taught
+    WakeUntaught,
+    RecordingInterrupted {
+        transcribed: bool, // the audio from before sleep is being transcribed
+    },
+    OutputTruncated {
+        max: usize,
+        full_text: Option<std::path::PathBuf>,
+    },
+    OutputRefused {
+        chars: usize,
+        max: usize,
+    },
+    PolishTooLong {
+        tokens: usize,
+        max: usize,
+    },
+    DeliveryFailed(Vec<String>), // one message per failed sink
+    /// Finished after another transcription or polish was delivered, so it was only
+    /// saved to history
+    DeliverySuperseded {
+        polish: bool,
+    },
+    PasswordField,
+    PromptsSkipped(Vec<String>), // one message per broken prompt
+    UnsupportedOptions(Vec<String>), // setting names the backend ignores
+    ClipRingItem {
+        preview: String,
+        position: usize,
+        len: usize,
+    },
     AccessibilityError,
+    AlreadyRunning,
+    AutostartFailed(String),
+    AutomationUnavailable(String),
+    HookFailed(String),
+    DeepLinkFailed(String), // why the link was ignored, never its parameters
+    HotkeysUnavailable,
+    HotkeyConflict(String), // which shortcut couldn't be registered and why
+    ClipboardEmpty,
+    ClipboardImage,
+    ClipboardFiles {
+        count: usize,
+    },
+    ClipboardUnavailable,
+    ClipboardUnsettled,
+    HotkeysEnabled,
+    SettingsExported,
+    SettingsImported(Vec<String>), // dotted paths of the values that changed
+    SettingsImportFailed(String),
+    SettingsInvalid(String), // why settings.toml failed to load
+    HistoryExportFailed,
+    NoSavedRecording,
+    NoLastRecording {
+        enabled: bool, // `retention.keep_last_recording`
+    },
+    ReportFailed,
+    FileTranscribed {
+        name: String,
+    },
+    FileFailed {
+        name: String,
+        reason: String,
+    },
+    WatchedFileTranscribed {
+        name: String,
+        preview: String,
+    },
+    QuickNoteSaved {
+        file: String,
+    },
+    QuickNoteFailed(String),
+    HistoryLocked,
+    UpdateAvailable {
+        version: String,
+        notes: String, // start of the release notes
+    },
+    UpdateFailed,
+    DiskBudgetExceeded {
+        used_mb: u64,
+        budget_mb: u64,
+    },
+    EverythingDeleted,
+    CollectDelivered {
+        segments: usize,
+    },
+    CollectDiscarded {
+        segments: usize,
+    },
+    CollectRestored {
+        segments: usize,
+    },
+    SelfTest {
+        passed: usize,
+        total: usize,
+        failed: Vec<String>, // names of the checks that failed
+    },
 }
 
 pub struct AppNotifications<'a> {
@@ -19,6 +154,11 @@ impl<'a> AppNotifications<'a> {
     }
 
     pub fn notify(&self, notification: Notification) {
+        if !self.is_enabled(&notification) {
+            log::debug!("Notification suppressed by settings");
+            return;
+        }
+
         let notifs = self.app_handle.notification().builder();
         if let Err(e) = match notification {
             Notification::PolishSuccess => notifs
@@ -29,20 +169,419 @@ impl<'a> AppNotifications<'a> {
                 .title("Loading...")
                 .body("We're starting to polish your text. Please wait")
                 .show(),
-            Notification::TranscribeSuccess => notifs
-                .title("Done")
-                .body("Your transcription is ready in your clipboard")
+            Notification::TranscribeSuccess {
+                language,
+                language_uncertain,
+                plan,
+            } => notifs
+                .title(match (plan, language) {
+                    (Some(plan), Some(language)) => {
+                        format!("Transcribed ({plan}, {}) & copied", title_case(&language))
+                    }
+                    (Some(plan), None) => format!("Transcribed ({plan}) & copied"),
+                    (None, Some(language)) => {
+                        format!("Transcribed ({}) & copied", title_case(&language))
+                    }
+                    (None, None) => "Done".to_string(),
+                })
+                .body(if language_uncertain {
+                    "The language was hard to make out, check that it came back right"
+                } else {
+                    "Your transcription is ready in your clipboard"
+                })
                 .show(),
             Notification::ApiError => notifs
                 .title("Error")
                 .body("Failed to connect to the API. Please try again later")
                 .show(),
+            Notification::BadApiResponse(diagnostic) => notifs
+                .title("Unexpected API response")
+                .body(format!("{diagnostic}. Check api.base_url in settings"))
+                .show(),
+            Notification::PolishTooLong { tokens, max } => notifs
+                .title("Too long to polish")
+                .body(format!(
+                    "The text is about {tokens} tokens and the limit is {max}. Set \
+                     api.over_polish_limit = \"chunk\" to polish it in parts"
+                ))
+                .show(),
+            Notification::DeliveryFailed(failures) => notifs
+                .title("Delivery problem")
+                .body(format!("Some outputs failed: {}", failures.join("; ")))
+                .show(),
+            Notification::ClipRingItem {
+                preview,
+                position,
+                len,
+            } => notifs.title(format!("Copied {position}/{len}")).body(preview).show(),
+            Notification::PromptsSkipped(skipped) => notifs
+                .title("Some prompts were skipped")
+                .body(skipped.join(", "))
+                .show(),
+            Notification::UnsupportedOptions(options) => notifs
+                .title("Not supported by this backend")
+                .body(format!(
+                    "{} won't have any effect with the current api.upload_format",
+                    options.join(", ")
+                ))
+                .show(),
+            Notification::MicSlow => notifs
+                .title("Microphone is slow to start")
+                .body("Recording, but no audio has arrived yet. Your first words may be cut off")
+                .show(),
+            Notification::MicPossiblyMuted => notifs
+                .title("Is your microphone muted?")
+                .body("Nothing but silence is coming in. Check its mute switch, you're still recording")
+                .show(),
+            Notification::BluetoothMic { device, avoided: false } => notifs
+                .title(format!("Recording from {device}"))
+                .body("Using its microphone drops the headset to a lower quality mode, for your music and the transcript. `audio.avoid_bluetooth_mic` records from the built-in microphone instead")
+                .show(),
+            Notification::BluetoothMic { device, avoided: true } => notifs
+                .title("Recording from the built-in microphone")
+                .body(format!("So {device} keeps its sound quality. Turn off `audio.avoid_bluetooth_mic` to use the headset's microphone"))
+                .show(),
+            Notification::NoInputDevice => notifs
+                .title("No microphone found")
+                .body("Connect a microphone or check `audio.input_device` before recording")
+                .show(),
+            Notification::AudioReset { device } => notifs
+                .title("Audio input reset")
+                .body(format!("Recording from {device}"))
+                .show(),
+            Notification::AudioResetRefused => notifs
+                .title("Can't reset audio while recording")
+                .body("Stop or discard the recording first, then try again")
+                .show(),
+            Notification::Aborted => notifs
+                .title("Stopped everything")
+                .body("Any recording was discarded and nothing in progress will be pasted")
+                .show(),
+            Notification::PartialTranscript { words } => notifs
+                .title(format!("Cancelled — partial transcript ({words} words) copied"))
+                .body("What was transcribed before you cancelled is on the clipboard and in history, marked partial")
+                .show(),
+            Notification::WakeTeach { phrase } => notifs
+                .title(format!("Say \"{phrase}\""))
+                .body("Say it once the way you'd say it to start or stop hands-free")
+                .show(),
+            Notification::WakeLearned { phrase, examples } => notifs
+                .title(format!("Learned \"{phrase}\""))
+                .body(format!(
+                    "{examples} of {} examples kept. Teaching it again in another tone makes it easier to recognize",
+                    crate::wake_word::MAX_EXAMPLES
+                ))
+                .show(),
+            Notification::WakeTeachTimedOut => notifs
+                .title("Nothing heard")
+                .body("No phrase was taught, choose it from the tray to try again")
+                .show(),
+            Notification::WakeUntaught => notifs
+                .title("Teach the wake phrases first")
+                .body("Choose \"Teach start phrase…\" and \"Teach stop phrase…\" under \"Wake phrase\" in the tray")
+                .show(),
+            Notification::NothingToRepeat => notifs
+                .title("Nothing to repeat yet")
+                .body("Polish the clipboard or run a clipboard prompt first")
+                .show(),
+            Notification::RecordingInterrupted { transcribed } => notifs
+                .title("Recording stopped by sleep")
+                .body(if transcribed {
+                    "What you said before the computer went to sleep is being transcribed"
+                } else {
+                    "The computer went to sleep, so the recording was discarded"
+                })
+                .show(),
+            Notification::NoSpeech => notifs
+                .title("No speech detected")
+                .body("We didn't catch any words, your clipboard was left as it was")
+                .show(),
+            Notification::ApiKeyInvalid => notifs
+                .title("Re-enter API key")
+                .body("Your API key was rejected. Enter a new one in settings")
+                .show(),
             Notification::AccessibilityError => notifs
                 .title("Error")
                 .body("Please grant accessibility permissions to the app and restart it")
                 .show(),
+            Notification::HotkeysUnavailable => notifs
+                .title("Shortcuts unavailable")
+                .body(
+                    "Open System Settings > Privacy & Security > Accessibility and allow \
+                     Whistle. Until then, use the tray menu",
+                )
+                .show(),
+            Notification::ClipboardEmpty => notifs
+                .title("Empty clipboard")
+                .body("We couldn't find any text in your clipboard to polish")
+                .show(),
+            Notification::ClipboardImage => notifs
+                .title("Clipboard contains an image")
+                .body("There's no text to polish, copy some text and try again")
+                .show(),
+            Notification::ClipboardFiles { count } => notifs
+                .title(match count {
+                    1 => "Clipboard contains a file".to_string(),
+                    _ => format!("Clipboard contains {count} files"),
+                })
+                .body(
+                    "There's no text to polish. To transcribe, copy a single audio \
+                     file and try again",
+                )
+                .show(),
+            Notification::ClipboardUnavailable => notifs
+                .title("Couldn't read the clipboard")
+                .body("Another app may be using it, try again in a moment")
+                .show(),
+            Notification::ClipboardUnsettled => notifs
+                .title("The clipboard kept changing")
+                .body("Nothing was polished, in case it was the wrong text. Try again once the copy is done")
+                .show(),
+            Notification::HotkeyConflict(reason) => notifs
+                .title("Shortcut unavailable")
+                .body(format!(
+                    "{reason}. Another app may be using it, pick a different one in the \
+                     settings. Until then, use the tray menu"
+                ))
+                .show(),
+            Notification::HotkeysEnabled => notifs
+                .title("Shortcuts enabled")
+                .body("Thanks! Your global shortcuts now work")
+                .show(),
+            Notification::SettingsExported => notifs
+                .title("Settings exported")
+                .body("Your API key was not included")
+                .show(),
+            Notification::SettingsImported(changed) if changed.is_empty() => notifs
+                .title("Settings imported")
+                .body("Nothing changed, the file matches your current settings")
+                .show(),
+            Notification::SettingsImported(changed) => notifs
+                .title("Settings imported")
+                .body(format!("Changed {}: {}", changed.len(), changed.join(", ")))
+                .show(),
+            Notification::SettingsImportFailed(reason) => {
+                notifs.title("Import failed").body(reason).show()
+            }
+            Notification::SettingsInvalid(reason) => notifs
+                .title("Settings couldn't be loaded")
+                .body(format!(
+                    "{reason}. Using defaults, and nothing is saved until settings.toml is fixed"
+                ))
+                .show(),
+            Notification::EverythingDeleted => notifs
+                .title("Deleted")
+                .body("All recordings, history and logs have been removed")
+                .show(),
+            Notification::CollectDelivered { segments } => notifs
+                .title("Collected text delivered")
+                .body(format!("{segments} transcripts were joined into one"))
+                .show(),
+            Notification::CollectDiscarded { segments } => notifs
+                .title("Collected text discarded")
+                .body(format!("{segments} transcripts weren't delivered, they're still in history"))
+                .show(),
+            Notification::CollectRestored { segments } => notifs
+                .title("Still collecting")
+                .body(format!(
+                    "{segments} transcripts collected before Whistle quit were restored. \
+                     Finish collecting from the tray to deliver them"
+                ))
+                .show(),
+            Notification::SelfTest {
+                passed,
+                total,
+                failed,
+            } => notifs
+                .title(format!("Self-test: {passed}/{total} passed"))
+                .body(if failed.is_empty() {
+                    "Everything Whistle needs is working".to_string()
+                } else {
+                    format!(
+                        "Missing: {}. Run the self-test from the tray for fixes",
+                        failed.join(", ")
+                    )
+                })
+                .show(),
+            Notification::HistoryLocked => notifs
+                .title("History is locked")
+                .body("Enter your passphrase in settings to save this and later transcripts")
+                .show(),
+            Notification::DiskBudgetExceeded { used_mb, budget_mb } => notifs
+                .title("Running out of space")
+                .body(format!(
+                    "Recordings take {used_mb} MB, over your {budget_mb} MB budget, \
+                     and some of them couldn't be deleted"
+                ))
+                .show(),
+            Notification::OutputTruncated { max, full_text } => notifs
+                .title("Transcription shortened")
+                .body(match full_text {
+                    Some(path) => format!(
+                        "Only the first {max} characters were delivered. The full text is in {}",
+                        path.display()
+                    ),
+                    None => format!("Only the first {max} characters were delivered"),
+                })
+                .show(),
+            Notification::PasswordField => notifs
+                .title("Not pasted into a password field")
+                .body("The text is on the clipboard instead. `behavior.password_fields` changes this")
+                .show(),
+            Notification::DeliverySuperseded { polish } => notifs
+                .title(if polish {
+                    "Polished text kept in history"
+                } else {
+                    "Transcription kept in history"
+                })
+                .body(
+                    "Something else was copied while it was in progress, so it wasn't \
+                     copied or pasted. Choose it under Recent in the tray to copy it",
+                )
+                .show(),
+            Notification::OutputRefused { chars, max } => notifs
+                .title("Transcription too long")
+                .body(format!(
+                    "It has {chars} characters, over your {max} limit. \
+                     You can still copy it from history"
+                ))
+                .show(),
+            Notification::AlreadyRunning => notifs
+                .title("Already running")
+                .body("The app was opened again, so the running one is used instead")
+                .show(),
+            Notification::QuotaExceeded { paused } => notifs
+                .title("Out of API credit")
+                .body(if paused {
+                    "Your account has run out of credit. Top it up, then choose \
+                     \"Resume after quota error\" in the tray"
+                } else {
+                    "Your account has run out of credit. Top it up to keep transcribing"
+                })
+                .show(),
+            Notification::AutostartFailed(reason) => notifs
+                .title("Couldn't change launch at login")
+                .body(reason)
+                .show(),
+            Notification::AutomationUnavailable(reason) => notifs
+                .title("Automation couldn't start")
+                .body(format!("{reason}. Try another automation.port in settings"))
+                .show(),
+            Notification::HookFailed(reason) => notifs
+                .title("Hook failed")
+                .body(reason)
+                .show(),
+            Notification::DeepLinkFailed(reason) => notifs
+                .title("Link not understood")
+                .body(reason)
+                .show(),
+            Notification::FileTranscribed { name } => notifs
+                .title("File transcribed")
+                .body(format!("{name} is done"))
+                .show(),
+            Notification::FileFailed { name, reason } => notifs
+                .title(format!("Couldn't transcribe {name}"))
+                .body(reason)
+                .show(),
+            Notification::WatchedFileTranscribed { name, preview } => notifs
+                .title(format!("Transcribed {name}"))
+                .body(preview)
+                .show(),
+            Notification::QuickNoteSaved { file } => notifs
+                .title("Note saved")
+                .body(format!("Added to {file}"))
+                .show(),
+            Notification::QuickNoteFailed(reason) => notifs
+                .title("Couldn't save the note")
+                .body(format!("{reason}. It was copied to the clipboard instead"))
+                .show(),
+            Notification::HistoryExportFailed => notifs
+                .title("Export failed")
+                .body("We couldn't write your history to that file")
+                .show(),
+            Notification::NoSavedRecording => notifs
+                .title("No saved recording to report")
+                .body("Turn on `retention.save_recordings`, then record the result you want to report")
+                .show(),
+            Notification::NoLastRecording { enabled } => notifs
+                .title("No recording kept")
+                .body(if enabled {
+                    "Nothing was recorded since Whistle started, or the last recording was over `retention.keep_last_recording_mb`"
+                } else {
+                    "Turn on `retention.keep_last_recording` to keep the last recording for a retry"
+                })
+                .show(),
+            Notification::ReportFailed => notifs
+                .title("Report failed")
+                .body("We couldn't write the recording and its transcript to that file")
+                .show(),
+            Notification::UpdateAvailable { version, notes } => notifs
+                .title(format!("Version {version} is available"))
+                .body(if notes.is_empty() {
+                    "Choose \"Download & install\" in the tray to update".to_string()
+                } else {
+                    notes
+                })
+                .show(),
+            Notification::UpdateFailed => notifs
+                .title("Update failed")
+                .body("The update couldn't be downloaded or verified. The log has details")
+                .show(),
         } {
             log::error!("Failed to trigger notification: {}", e);
         }
     }
+
+    fn is_enabled(&self, notification: &Notification) -> bool {
+        let Some(state) = self.app_handle.try_state::<SettingsState>() else {
+            return true;
+        };
+        let settings = state.get();
+        match notification {
+            Notification::AccessibilityError
+            | Notification::HotkeysUnavailable
+            | Notification::HotkeyConflict(_)
+            | Notification::ApiKeyInvalid
+            | Notification::QuotaExceeded { .. }
+            | Notification::MicPossiblyMuted
+            | Notification::RecordingInterrupted { .. }
+            | Notification::FileFailed { .. }
+            | Notification::QuickNoteFailed(_)
+            | Notification::AutostartFailed(_)
+            | Notification::AutomationUnavailable(_)
+            | Notification::SettingsImportFailed(_)
+            | Notification::SettingsInvalid(_)
+            | Notification::HistoryLocked
+            | Notification::OutputRefused { .. }
+            | Notification::DeliverySuperseded { .. }
+            | Notification::PasswordField
+            | Notification::PolishTooLong { .. }
+            | Notification::SelfTest { .. }
+            | Notification::CollectRestored { .. }
+            | Notification::Aborted
+            | Notification::PartialTranscript { .. }
+            | Notification::WakeTeach { .. }
+            | Notification::WakeLearned { .. }
+            | Notification::WakeTeachTimedOut
+            | Notification::WakeUntaught
+            | Notification::PromptsSkipped(_) => true,
+            _ if !settings.notifications.enabled => false,
+            Notification::PolishSuccess | Notification::TranscribeSuccess { .. } => {
+                settings.notifications.on_success
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Backends report languages like "spanish" or "es"
+fn title_case(language: &str) -> String {
+    if language.len() <= 3 {
+        return language.to_uppercase();
+    }
+    let mut chars = language.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels, Encoder};
+
+/// Wideband, which covers speech and keeps the encoder cheap
+const SAMPLE_RATE: u32 = 16_000;
+/// 20 ms frames
+const FRAME_SAMPLES: usize = SAMPLE_RATE as usize / 50;
+/// About a tenth of the same audio as 16 kHz WAV, and still clear speech
+const BITRATE: i32 = 24_000;
+/// Granule positions count 48 kHz samples whatever the encoder ran at
+const GRANULE_RATE: u64 = 48_000;
+/// A page a second keeps a damaged upload from losing more than that
+const FRAMES_PER_PAGE: usize = 50;
+/// Opus never produces a larger packet
+const MAX_PACKET_BYTES: usize = 4000;
+/// The only logical stream in the file
+const SERIAL: u32 = 1;
+
+/// Compresses mono 16-bit samples to Ogg Opus, resampled to 16 kHz
+pub fn encode(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>> {
+    let samples = resample(samples, sample_rate, SAMPLE_RATE);
+    let mut encoder = Encoder::new(SAMPLE_RATE, Channels::Mono, Application::Voip)?;
+    encoder.set_bitrate(Bitrate::Bits(BITRATE))?;
+    let scale = GRANULE_RATE / SAMPLE_RATE as u64;
+    // Decoders drop this many samples the encoder adds at the start
+    let pre_skip = encoder.get_lookahead()? as u64 * scale;
+
+    let mut bytes = Vec::new();
+    let mut writer = PacketWriter::new(&mut bytes);
+    writer.write_packet(head(pre_skip as u16), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+    writer.write_packet(tags(), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    let frames = samples.len().div_ceil(FRAME_SAMPLES).max(1);
+    let mut frame = vec![0; FRAME_SAMPLES];
+    for n in 0..frames {
+        let start = (n * FRAME_SAMPLES).min(samples.len());
+        let end = (start + FRAME_SAMPLES).min(samples.len());
+        // The last frame is padded with silence, which the final granule trims off
+        frame.fill(0);
+        frame[..end - start].copy_from_slice(&samples[start..end]);
+        let packet = encoder.encode_vec(&frame, MAX_PACKET_BYTES)?;
+        let granule = pre_skip + end as u64 * scale;
+        let end_info = if n + 1 == frames {
+            PacketWriteEndInfo::EndStream
+        } else if (n + 1) % FRAMES_PER_PAGE == 0 {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        writer.write_packet(packet, SERIAL, end_info, granule)?;
+    }
+    drop(writer);
+    Ok(bytes)
+}
+
+/// The identification header: one channel and channel mapping family 0
+fn head(pre_skip: u16) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend(pre_skip.to_le_bytes());
+    head.extend(SAMPLE_RATE.to_le_bytes());
+    head.extend(0i16.to_le_bytes()); // output gain
+    head.push(0);
+    head
+}
+
+/// The comment header, with only the vendor string
+fn tags() -> Vec<u8> {
+    let vendor = b"whisper-app";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend((vendor.len() as u32).to_le_bytes());
+    tags.extend(vendor);
+    tags.extend(0u32.to_le_bytes());
+    tags
+}
+
+/// Linear interpolation, plenty for speech headed to a speech model
+fn resample(samples: &[i16], from: u32, to: u32) -> Vec<i16> {
+    if from == to || from == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let step = from as f64 / to as f64;
+    let len = (samples.len() as f64 / step) as usize;
+    (0..len)
+        .map(|n| {
+            let at = n as f64 * step;
+            let i = at as usize;
+            let a = samples[i] as f64;
+            let b = samples.get(i + 1).map_or(a, |&b| b as f64);
+            (a + (b - a) * (at - i as f64)).round() as i16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ogg::PacketReader;
+    use std::io::Cursor;
+
+    fn tone(seconds: f64, sample_rate: u32) -> Vec<i16> {
+        (0..(seconds * sample_rate as f64) as usize)
+            .map(|n| {
+                let t = n as f64 / sample_rate as f64;
+                ((t * 440.0 * std::f64::consts::TAU).sin() * 8000.0) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn writes_a_decodable_ogg_opus_stream() {
+        let bytes = encode(&tone(1.0, 48_000), 48_000).unwrap();
+        assert!(bytes.starts_with(b"OggS"));
+
+        let mut reader = PacketReader::new(Cursor::new(bytes));
+        let head = reader.read_packet_expected().unwrap();
+        assert!(head.data.starts_with(b"OpusHead"));
+        assert_eq!(head.data[9], 1);
+        let pre_skip = u16::from_le_bytes([head.data[10], head.data[11]]) as u64;
+        let tags = reader.read_packet_expected().unwrap();
+        assert!(tags.data.starts_with(b"OpusTags"));
+
+        let mut decoder = opus::Decoder::new(SAMPLE_RATE, Channels::Mono).unwrap();
+        let mut output = vec![0; FRAME_SAMPLES];
+        let mut packets = 0;
+        let mut last = None;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            let decoded = decoder.decode(&packet.data, &mut output, false).unwrap();
+            assert_eq!(decoded, FRAME_SAMPLES);
+            packets += 1;
+            last = Some(packet);
+        }
+        assert_eq!(packets, 50);
+        let last = last.unwrap();
+        assert!(last.last_in_stream());
+        assert_eq!(last.absgp_page(), pre_skip + GRANULE_RATE);
+    }
+
+    #[test]
+    fn is_much_smaller_than_wav() {
+        let samples = tone(10.0, 16_000);
+        let wav = crate::audio_recorder::encode_wav(&samples, 16_000, 1).unwrap();
+        let opus = encode(&samples, 16_000).unwrap();
+        assert!(opus.len() * 5 < wav.len(), "{} vs {}", opus.len(), wav.len());
+    }
+
+    #[test]
+    fn pads_a_partial_last_frame() {
+        // 1.5 frames
+        let bytes = encode(&tone(0.03, 16_000), 16_000).unwrap();
+        let mut reader = PacketReader::new(Cursor::new(bytes));
+        let head = reader.read_packet_expected().unwrap();
+        let pre_skip = u16::from_le_bytes([head.data[10], head.data[11]]) as u64;
+        reader.read_packet_expected().unwrap();
+        let first = reader.read_packet_expected().unwrap();
+        let second = reader.read_packet_expected().unwrap();
+        assert!(!first.last_in_stream());
+        assert!(second.last_in_stream());
+        assert_eq!(second.absgp_page(), pre_skip + 480 * 3);
+        assert!(reader.read_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn empty_audio_is_one_silent_frame() {
+        let bytes = encode(&[], 16_000).unwrap();
+        let mut reader = PacketReader::new(Cursor::new(bytes));
+        reader.read_packet_expected().unwrap();
+        reader.read_packet_expected().unwrap();
+        assert!(reader.read_packet_expected().unwrap().last_in_stream());
+    }
+
+    #[test]
+    fn resamples_to_the_new_rate() {
+        assert_eq!(resample(&[0, 300, 600, 900, 1200, 1500], 48_000, 16_000), [0, 900]);
+        assert_eq!(resample(&[0, 100], 8_000, 16_000), [0, 50, 100, 100]);
+        assert_eq!(resample(&[1, 2, 3], 16_000, 16_000), [1, 2, 3]);
+    }
+}
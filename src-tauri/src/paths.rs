@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Must match `identifier` in tauri.conf.json. The config directory is needed before
+/// the app is built and from `--status`, so it can't come from an `AppHandle`.
+const IDENTIFIER: &str = "com.whisper-app.app";
+
+/// The settings file and the status port file. Where Tauri's `app_config_dir` is.
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .context("Could not find the config directory")?
+        .join(IDENTIFIER))
+}
+
+/// History, markers and anything else the app keeps between runs
+pub fn data_dir(app_handle: &AppHandle) -> Result<PathBuf> {
+    Ok(app_handle.path().app_data_dir()?)
+}
+
+pub fn log_dir(app_handle: &AppHandle) -> Result<PathBuf> {
+    Ok(app_handle.path().app_log_dir()?)
+}
+
+/// For files that can be rebuilt or thrown away
+pub fn cache_dir(app_handle: &AppHandle) -> Result<PathBuf> {
+    Ok(app_handle.path().app_cache_dir()?)
+}
+
+/// Where recordings that outlive a single transcription are kept
+pub fn recordings_dir(app_handle: &AppHandle) -> Result<PathBuf> {
+    Ok(data_dir(app_handle)?.join("recordings"))
+}
+
+/// Where the full text of split transcriptions is saved
+pub fn overflow_dir(app_handle: &AppHandle) -> Result<PathBuf> {
+    Ok(data_dir(app_handle)?.join("overflow"))
+}
+
+/// A file directly in the data directory
+pub fn data_file(app_handle: &AppHandle, name: &str) -> Result<PathBuf> {
+    Ok(data_dir(app_handle)?.join(name))
+}
+
+/// The directories the app writes to on its own. Created on every launch, so a first
+/// run or a deleted folder doesn't fail later on a write.
+pub fn ensure(app_handle: &AppHandle) -> Result<Vec<PathBuf>> {
+    let dirs = vec![
+        config_dir()?,
+        data_dir(app_handle)?,
+        log_dir(app_handle)?,
+        cache_dir(app_handle)?,
+    ];
+    for dir in &dirs {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(dirs)
+}
+
+/// Settings lived in `~/.config/whistle` before they moved to the config directory
+fn legacy_config_dir() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".config/whistle"))
+}
+
+/// Moves files from where earlier versions kept them. Runs before the settings are
+/// loaded, and does nothing once the legacy folders are gone. A file that already
+/// exists in the new place is left where it was. Returns what moved.
+pub fn migrate() -> Vec<PathBuf> {
+    let (Some(legacy), Ok(config)) = (legacy_config_dir(), config_dir()) else {
+        return vec![];
+    };
+    match migrate_dir(&legacy, &config) {
+        Ok(moved) => moved,
+        Err(e) => {
+            log::error!("Failed to move files from {}: {e:#}", legacy.display());
+            vec![]
+        }
+    }
+}
+
+fn migrate_dir(from: &Path, to: &Path) -> Result<Vec<PathBuf>> {
+    if !from.is_dir() || from == to {
+        return Ok(vec![]);
+    }
+    std::fs::create_dir_all(to)?;
+    let mut moved = vec![];
+    for entry in std::fs::read_dir(from)? {
+        let source = entry?.path();
+        let Some(name) = source.file_name() else {
+            continue;
+        };
+        let target = to.join(name);
+        if target.exists() {
+            log::warn!(
+                "Not moving {}, {} already exists",
+                source.display(),
+                target.display()
+            );
+            continue;
+        }
+        move_path(&source, &target)
+            .with_context(|| format!("Failed to move {}", source.display()))?;
+        log::info!("Moved {} to {}", source.display(), target.display());
+        moved.push(target);
+    }
+    // Only goes once it's empty, so anything left behind stays findable
+    if std::fs::remove_dir(from).is_ok() {
+        log::info!("Removed the old folder {}", from.display());
+    }
+    Ok(moved)
+}
+
+/// A rename, or a copy where that can't cross file systems
+fn move_path(source: &Path, target: &Path) -> Result<()> {
+    if std::fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    if source.is_dir() {
+        std::fs::create_dir_all(target)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?.path();
+            move_path(&entry, &target.join(entry.file_name().unwrap_or_default()))?;
+        }
+        std::fs::remove_dir(source)?;
+    } else {
+        std::fs::copy(source, target)?;
+        std::fs::remove_file(source)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A legacy folder as an earlier version left it
+    fn legacy_tree(root: &Path) -> PathBuf {
+        let legacy = root.join("home/.config/whistle");
+        fs::create_dir_all(legacy.join("prompts/archive")).unwrap();
+        fs::write(legacy.join("settings.toml"), "[api]\n").unwrap();
+        fs::write(legacy.join("status.port"), "4815").unwrap();
+        fs::write(legacy.join("prompts/email.md"), "Write an email").unwrap();
+        fs::write(legacy.join("prompts/archive/old.md"), "Old").unwrap();
+        legacy
+    }
+
+    fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn moves_the_legacy_tree_and_removes_the_old_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = legacy_tree(root.path());
+        let config = root.path().join("config/com.whisper-app.app");
+
+        let moved = migrate_dir(&legacy, &config).unwrap();
+        assert_eq!(
+            sorted(moved),
+            [
+                config.join("prompts"),
+                config.join("settings.toml"),
+                config.join("status.port")
+            ]
+        );
+        assert_eq!(fs::read_to_string(config.join("settings.toml")).unwrap(), "[api]\n");
+        assert_eq!(
+            fs::read_to_string(config.join("prompts/archive/old.md")).unwrap(),
+            "Old"
+        );
+        assert!(!legacy.exists());
+        assert!(root.path().join("home/.config").is_dir());
+    }
+
+    #[test]
+    fn leaves_files_that_already_exist_in_the_new_place() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = legacy_tree(root.path());
+        let config = root.path().join("config");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(config.join("settings.toml"), "[audio]\n").unwrap();
+
+        let moved = migrate_dir(&legacy, &config).unwrap();
+        assert_eq!(sorted(moved), [config.join("prompts"), config.join("status.port")]);
+        assert_eq!(
+            fs::read_to_string(config.join("settings.toml")).unwrap(),
+            "[audio]\n"
+        );
+        // Still there to be found, along with its folder
+        assert_eq!(fs::read_to_string(legacy.join("settings.toml")).unwrap(), "[api]\n");
+        assert!(!legacy.join("status.port").exists());
+    }
+
+    #[test]
+    fn runs_once() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = legacy_tree(root.path());
+        let config = root.path().join("config");
+        assert_eq!(migrate_dir(&legacy, &config).unwrap().len(), 3);
+        assert!(migrate_dir(&legacy, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn does_nothing_without_a_legacy_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let config = root.path().join("config");
+        assert!(migrate_dir(&root.path().join("missing"), &config).unwrap().is_empty());
+        assert!(!config.exists(), "not created when there is nothing to move");
+
+        let legacy = legacy_tree(root.path());
+        assert!(migrate_dir(&legacy, &legacy).unwrap().is_empty());
+        assert!(legacy.join("settings.toml").exists());
+    }
+
+    #[test]
+    fn copies_a_folder_when_it_cant_be_renamed() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = legacy_tree(root.path());
+        // Renaming onto a folder that isn't empty fails, as it does across file systems
+        let target = root.path().join("prompts");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("kept.md"), "Kept").unwrap();
+
+        move_path(&legacy.join("prompts"), &target).unwrap();
+        assert!(!legacy.join("prompts").exists());
+        assert_eq!(
+            fs::read_to_string(target.join("email.md")).unwrap(),
+            "Write an email"
+        );
+        assert_eq!(fs::read_to_string(target.join("archive/old.md")).unwrap(), "Old");
+        assert_eq!(fs::read_to_string(target.join("kept.md")).unwrap(), "Kept");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::notifications::{AppNotifications, Notification};
+use anyhow::{Result, bail};
+use std::time::Duration;
+use tauri::{AppHandle, async_runtime::spawn};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether macOS lets the app observe and send keyboard input (accessibility / input
+/// monitoring). Always true on other platforms.
+pub fn input_access_granted() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        macos_accessibility_client::accessibility::application_is_trusted()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        true
+    }
+}
+
+/// Opens the System Settings pane where the permission can be granted
+pub fn open_privacy_settings() {
+    open_system_settings("com.apple.preference.security?Privacy_Accessibility");
+}
+
+pub fn open_microphone_settings() {
+    open_system_settings("com.apple.preference.security?Privacy_Microphone");
+}
+
+pub fn open_notification_settings() {
+    open_system_settings("com.apple.preference.notifications");
+}
+
+fn open_system_settings(pane: &str) {
+    #[cfg(target_os = "macos")]
+    if let Err(e) = std::process::Command::new("open")
+        .arg(format!("x-apple.systempreferences:{pane}"))
+        .spawn()
+    {
+        log::error!("Failed to open {pane}: {}", e);
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = pane;
+}
+
+/// Registers `shortcuts` all or nothing. When one can't be registered, the ones
+/// registered before it are unregistered again, so a later attempt doesn't find them
+/// taken by the app itself.
+pub fn register_all(app_handle: &AppHandle, shortcuts: &[Shortcut]) -> Result<()> {
+    let global_shortcut = app_handle.global_shortcut();
+    let mut registered = vec![];
+    for &shortcut in shortcuts {
+        if let Err(e) = global_shortcut.register(shortcut) {
+            _ = global_shortcut.unregister_multiple(registered);
+            bail!("{} can't be registered: {e}", shortcut.into_string());
+        }
+        registered.push(shortcut);
+    }
+    Ok(())
+}
+
+/// Registers the shortcuts, telling the user which one is taken when that fails.
+/// Returns whether they were registered.
+pub fn register_or_report(app_handle: &AppHandle, shortcuts: &[Shortcut]) -> bool {
+    match register_all(app_handle, shortcuts) {
+        Ok(()) => {
+            log::info!("Registered global shortcuts");
+            true
+        }
+        Err(e) => {
+            log::error!("Global shortcuts unavailable: {e:#}");
+            AppNotifications::new(app_handle)
+                .notify(Notification::HotkeyConflict(format!("{e:#}")));
+            false
+        }
+    }
+}
+
+/// Registers the global hotkeys once input access is granted. Until then the user is
+/// told how to grant it, the tray menu keeps working, and the permission is checked
+/// again in the background. Anything else that stops a shortcut from registering,
+/// like another app holding it, is reported once and not retried.
+pub fn register_hotkeys_when_permitted(
+    app_handle: AppHandle,
+    shortcuts: impl Fn(&AppHandle) -> Vec<Shortcut> + Send + 'static,
+) {
+    if input_access_granted() {
+        register_or_report(&app_handle, &shortcuts(&app_handle));
+        return;
+    }
+
+    log::warn!("Input access has not been granted, falling back to tray only");
+    AppNotifications::new(&app_handle).notify(Notification::HotkeysUnavailable);
+    open_privacy_settings();
+
+    spawn(async move {
+        loop {
+            tokio::time::sleep(RETRY_INTERVAL).await;
+            if !input_access_granted() {
+                continue;
+            }
+            log::info!("Input access was granted");
+            if register_or_report(&app_handle, &shortcuts(&app_handle)) {
+                AppNotifications::new(&app_handle).notify(Notification::HotkeysEnabled);
+            }
+            return;
+        }
+    });
+}
@@ -0,0 +1,56 @@
+use crate::history::HistoryEntry;
+use crate::recent_menu;
+use anyhow::Result;
+use tauri::{
+    AppHandle, Wry,
+    menu::{MenuItem, Submenu},
+};
+
+pub const PINNED_ID_PREFIX: &str = "pinned:";
+
+/// The tray's "Pinned" submenu, listing pinned history entries by title
+pub struct PinnedMenu(Submenu<Wry>);
+
+impl PinnedMenu {
+    pub fn new(submenu: Submenu<Wry>) -> Self {
+        Self(submenu)
+    }
+
+    pub fn refresh(&self, app_handle: &AppHandle, entries: &[HistoryEntry]) {
+        if let Err(e) = self.refresh_(app_handle, entries) {
+            log::error!("Unable to refresh pinned menu: {e}");
+        }
+    }
+
+    fn refresh_(&self, app_handle: &AppHandle, entries: &[HistoryEntry]) -> Result<()> {
+        for item in self.0.items()? {
+            self.0.remove(&item)?;
+        }
+
+        if entries.is_empty() {
+            self.0.append(&MenuItem::new(
+                app_handle,
+                "Nothing pinned yet",
+                false,
+                None::<&str>,
+            )?)?;
+            return Ok(());
+        }
+
+        for entry in entries {
+            let title = match &entry.pin_title {
+                Some(title) => recent_menu::label(title),
+                None => recent_menu::label(&entry.text),
+            };
+            self.0.append(&MenuItem::with_id(
+                app_handle,
+                format!("{PINNED_ID_PREFIX}{}", entry.id),
+                title,
+                true,
+                None::<&str>,
+            )?)?;
+        }
+
+        Ok(())
+    }
+}
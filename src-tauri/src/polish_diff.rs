@@ -0,0 +1,91 @@
+use serde::Serialize;
+
+/// Beyond this many words on either side the diff degrades to "everything changed"
+/// to keep the LCS table small
+const MAX_WORDS: usize = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One run of words in a word-level diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffOp {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+/// Word-level diff from `original` to `polished`. Whitespace differences are ignored.
+pub fn diff_words(original: &str, polished: &str) -> Vec<DiffOp> {
+    let old: Vec<&str> = original.split_whitespace().collect();
+    let new: Vec<&str> = polished.split_whitespace().collect();
+
+    if old.len() > MAX_WORDS || new.len() > MAX_WORDS {
+        let mut ops = vec![];
+        push(&mut ops, DiffKind::Delete, &old);
+        push(&mut ops, DiffKind::Insert, &new);
+        return ops;
+    }
+
+    // lcs[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push(&mut ops, DiffKind::Equal, &old[i..=i]);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(&mut ops, DiffKind::Delete, &old[i..=i]);
+            i += 1;
+        } else {
+            push(&mut ops, DiffKind::Insert, &new[j..=j]);
+            j += 1;
+        }
+    }
+    ops
+}
+
+/// Appends words to the last op when it is the same kind, so runs stay together
+fn push(ops: &mut Vec<DiffOp>, kind: DiffKind, words: &[&str]) {
+    if words.is_empty() {
+        return;
+    }
+    match ops.last_mut() {
+        Some(last) if last.kind == kind => {
+            last.text.push(' ');
+            last.text.push_str(&words.join(" "));
+        }
+        _ => ops.push(DiffOp {
+            kind,
+            text: words.join(" "),
+        }),
+    }
+}
+
+/// Renders the diff as plain text in `wdiff` style: `[-removed-]{+added+}`
+pub fn annotate(ops: &[DiffOp]) -> String {
+    ops.iter()
+        .map(|op| match op.kind {
+            DiffKind::Equal => op.text.clone(),
+            DiffKind::Delete => format!("[-{}-]", op.text),
+            DiffKind::Insert => format!("{{+{}+}}", op.text),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
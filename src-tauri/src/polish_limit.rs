@@ -0,0 +1,174 @@
+/// A token is about 4 chars of English text, counting 3 overestimates for nearly
+/// everything, so text that passes the check also fits the model
+const CHARS_PER_TOKEN: usize = 3;
+
+/// The text to polish is over `api.max_polish_tokens` and `api.over_polish_limit` is
+/// "refuse"
+#[derive(Debug)]
+pub struct TooLong {
+    pub tokens: usize,
+    pub max: usize,
+}
+
+impl std::fmt::Display for TooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The text is about {} tokens, over the {} token limit",
+            self.tokens, self.max
+        )
+    }
+}
+
+impl std::error::Error for TooLong {}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Splits `text` into consecutive parts of at most `max_tokens` each. A part ends at
+/// the last paragraph break that fits, else the last sentence end, else the last
+/// space, so words are only cut when a single one is longer than a part. Each part
+/// keeps the whitespace that followed it, so joining them gives back `text`.
+pub fn chunks(text: &str, max_tokens: usize) -> Vec<&str> {
+    let max_chars = (max_tokens * CHARS_PER_TOKEN).max(1);
+    let mut parts = vec![];
+    let mut rest = text;
+    while rest.chars().count() > max_chars {
+        // Byte offset just past the last char that fits
+        let limit = rest.char_indices().nth(max_chars).map_or(rest.len(), |(i, _)| i);
+        let window = &rest[..limit];
+        let end = last_break(window, "\n\n")
+            .or_else(|| last_sentence_end(window))
+            .or_else(|| last_break(window, " "))
+            .or_else(|| last_break(window, "\n"))
+            .unwrap_or(limit);
+        // The whitespace after the cut belongs to this part
+        let end = end + whitespace_len(&rest[end..]);
+        parts.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// End of the text before the last `separator` in `window`, `None` if there is no text
+/// before it
+fn last_break(window: &str, separator: &str) -> Option<usize> {
+    let at = window.rfind(separator)?;
+    let end = window[..at].trim_end().len();
+    (end > 0).then_some(end)
+}
+
+fn last_sentence_end(window: &str) -> Option<usize> {
+    [". ", "! ", "? ", ".\n", "!\n", "?\n"]
+        .into_iter()
+        .filter_map(|end| window.rfind(end).map(|at| at + 1))
+        .max()
+}
+
+fn whitespace_len(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+/// Splits a part into its text and the whitespace after it
+pub fn split_trailing_whitespace(part: &str) -> (&str, &str) {
+    let text = part.trim_end();
+    (text, &part[text.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every part fits and joining them gives back the text
+    fn assert_chunks(text: &str, max_tokens: usize, expected: &[&str]) {
+        let parts = chunks(text, max_tokens);
+        assert_eq!(parts, expected);
+        assert_eq!(parts.concat(), text);
+        for part in parts {
+            assert!(part.trim_end().chars().count() <= max_tokens * CHARS_PER_TOKEN);
+        }
+    }
+
+    #[test]
+    fn short_text_is_one_part() {
+        assert_chunks("Hello there.", 10, &["Hello there."]);
+        assert_chunks("", 10, &[]);
+    }
+
+    #[test]
+    fn ends_parts_at_paragraph_breaks_first() {
+        assert_chunks(
+            "First para here.\n\nSecond para is longer than that.",
+            10,
+            &[
+                "First para here.\n\n",
+                "Second para is longer than ",
+                "that.",
+            ],
+        );
+    }
+
+    #[test]
+    fn ends_parts_at_sentences_before_spaces() {
+        assert_chunks(
+            "One two. Three four five.",
+            5,
+            &["One two. ", "Three four ", "five."],
+        );
+        assert_chunks("Why? Because.", 2, &["Why? ", "Becaus", "e."]);
+    }
+
+    #[test]
+    fn never_cuts_a_word_that_fits() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        for max_tokens in 3..30 {
+            let parts = chunks(&text, max_tokens);
+            assert_eq!(parts.concat(), text);
+            for pair in parts.windows(2) {
+                let before = pair[0].chars().last().unwrap();
+                let after = pair[1].chars().next().unwrap();
+                assert!(
+                    before.is_whitespace() || !after.is_alphanumeric(),
+                    "{max_tokens} tokens cut between {:?} and {:?}",
+                    pair[0],
+                    pair[1]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cuts_a_word_longer_than_a_part() {
+        assert_chunks("abcdefghij", 1, &["abc", "def", "ghi", "j"]);
+        assert_chunks("ab abcdefgh", 1, &["ab ", "abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn cuts_on_char_boundaries() {
+        assert_chunks("ééé ééé", 1, &["ééé ", "ééé"]);
+        assert_chunks("日本語のテキスト", 1, &["日本語", "のテキ", "スト"]);
+    }
+
+    #[test]
+    fn keeps_line_breaks_with_the_part_before() {
+        assert_chunks("one\ntwo\nthree", 2, &["one\n", "two\n", "three"]);
+    }
+
+    #[test]
+    fn estimates_conservatively() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 2);
+        // Four chars per token would give 250
+        assert_eq!(estimate_tokens(&"a".repeat(1000)), 334);
+    }
+
+    #[test]
+    fn splits_off_trailing_whitespace() {
+        assert_eq!(split_trailing_whitespace("text \n\n"), ("text", " \n\n"));
+        assert_eq!(split_trailing_whitespace("text"), ("text", ""));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::live::{self, LiveDictation};
+use crate::local_task_handler::Task;
+use crate::notifications::{AppNotifications, Notification};
+use crate::session::{self, DictationSession};
+use crate::settings::{SettingsState, SleepAction};
+use crate::transcribe_icon::TranscribeIcon;
+#[cfg(not(target_os = "macos"))]
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(not(target_os = "macos"))]
+const HEARTBEAT: Duration = Duration::from_secs(5);
+/// Wall clock time past the heartbeat that counts as having been asleep
+#[cfg(not(target_os = "macos"))]
+const SLEEP_GAP: Duration = Duration::from_secs(15);
+
+/// Handles the system going to sleep and waking up, and on macOS the screen locking.
+/// macOS says so before it sleeps. Elsewhere only waking is noticed: timers don't run
+/// while asleep but the wall clock does, so a heartbeat that comes back late means the
+/// machine was suspended.
+pub fn watch_sleep(app_handle: AppHandle) {
+    #[cfg(target_os = "macos")]
+    {
+        let sleep_handle = app_handle.clone();
+        let lock_handle = app_handle.clone();
+        mac::observe(
+            move || {
+                let app_handle = sleep_handle.clone();
+                spawn(async move { on_sleep(&app_handle).await });
+            },
+            move || {
+                let app_handle = app_handle.clone();
+                spawn(async move { on_wake(&app_handle).await });
+            },
+            move || on_lock(&lock_handle),
+        );
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    spawn(async move {
+        loop {
+            let before = SystemTime::now();
+            tokio::time::sleep(HEARTBEAT).await;
+            let elapsed = SystemTime::now().duration_since(before).unwrap_or_default();
+            if elapsed >= HEARTBEAT + SLEEP_GAP {
+                log::info!("Woke up after about {}s asleep", elapsed.as_secs());
+                on_wake(&app_handle).await;
+            }
+        }
+    });
+}
+
+/// Ends whatever is being recorded before the input stream dies with the sleep, then
+/// lets go of the input device and any paused media
+async fn on_sleep(app_handle: &AppHandle) {
+    log::info!("Going to sleep");
+    interrupt_recording(app_handle);
+
+    let (tx, rx) = oneshot::channel();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    if tx_task.send(Task::SystemSleep(tx)).await.is_err() {
+        log::error!("Failed to send 'SystemSleep' task to channel");
+        return;
+    }
+    _ = rx.await;
+    // History, settings and stats are written as they change, nothing is left to save
+}
+
+/// Nobody is at the keyboard to stop a recording once the screen locks, so it ends the
+/// same way it would for sleep. The input device stays open.
+#[cfg(target_os = "macos")]
+fn on_lock(app_handle: &AppHandle) {
+    log::info!("Screen locked");
+    interrupt_recording(app_handle);
+}
+
+/// Anything still recording ends with the audio captured before sleep, and the stream
+/// is rebuilt
+async fn on_wake(app_handle: &AppHandle) {
+    log::info!("Woke up from sleep");
+    // Already done before sleep where the system announces it
+    interrupt_recording(app_handle);
+
+    let (tx, rx) = oneshot::channel();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    if tx_task.send(Task::SystemWake(tx)).await.is_err() {
+        log::error!("Failed to send 'SystemWake' task to channel");
+        return;
+    }
+    match rx.await {
+        Ok(Ok(device)) => log::info!("Recording from {device:?} after wake"),
+        Ok(Err(e)) => {
+            log::error!("No input device after wake: {e:#}");
+            AppNotifications::new(app_handle).notify(Notification::NoInputDevice);
+        }
+        Err(_) => {}
+    }
+}
+
+/// Stops the recording, session or live dictation, transcribing or discarding it per
+/// `behavior.on_sleep`
+fn interrupt_recording(app_handle: &AppHandle) {
+    let on_sleep = app_handle.state::<SettingsState>().get().behavior.on_sleep;
+    let notifications = AppNotifications::new(app_handle);
+
+    if app_handle.state::<DictationSession>().is_active() {
+        // Stopping transcribes what was said since the last pause
+        session::toggle(app_handle);
+        notifications.notify(Notification::RecordingInterrupted { transcribed: true });
+    } else if app_handle.state::<LiveDictation>().is_active() {
+        live::stop(app_handle, on_sleep == SleepAction::Transcribe);
+        notifications.notify(Notification::RecordingInterrupted {
+            transcribed: on_sleep == SleepAction::Transcribe,
+        });
+    } else if app_handle.state::<TranscribeIcon>().is_recording() {
+        match on_sleep {
+            SleepAction::Transcribe => crate::toggle_recording(app_handle.clone(), None),
+            SleepAction::Discard => crate::cancel_recording(app_handle.clone()),
+        }
+        notifications.notify(Notification::RecordingInterrupted {
+            transcribed: on_sleep == SleepAction::Transcribe,
+        });
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod mac {
+    use block2::RcBlock;
+    use objc2_app_kit::{
+        NSWorkspace, NSWorkspaceDidWakeNotification, NSWorkspaceWillSleepNotification,
+    };
+    use objc2_foundation::{
+        NSDistributedNotificationCenter, NSNotification, NSNotificationCenter,
+        NSNotificationName, NSOperationQueue, NSString,
+    };
+    use std::ptr::NonNull;
+
+    /// Posted by loginwindow to other apps when the screen locks
+    const SCREEN_LOCKED: &str = "com.apple.screenIsLocked";
+
+    /// Registers for NSWorkspace's sleep and wake notifications and the distributed
+    /// screen lock one, delivered on the main queue. The observers live as long as the
+    /// app.
+    pub fn observe(
+        on_sleep: impl Fn() + 'static,
+        on_wake: impl Fn() + 'static,
+        on_lock: impl Fn() + 'static,
+    ) {
+        let workspace = NSWorkspace::sharedWorkspace().notificationCenter();
+        let distributed = NSDistributedNotificationCenter::defaultCenter();
+        let queue = NSOperationQueue::mainQueue();
+        let add = |center: &NSNotificationCenter,
+                   name: &NSNotificationName,
+                   handler: Box<dyn Fn()>| {
+            let block = RcBlock::new(move |_: NonNull<NSNotification>| handler());
+            // SAFETY: the name is a valid notification name and the block is 'static
+            let observer = unsafe {
+                center.addObserverForName_object_queue_usingBlock(
+                    Some(name),
+                    None,
+                    Some(&queue),
+                    &block,
+                )
+            };
+            std::mem::forget(observer);
+        };
+        // SAFETY: the notification names are immutable statics exported by AppKit
+        unsafe {
+            add(&workspace, NSWorkspaceWillSleepNotification, Box::new(on_sleep));
+            add(&workspace, NSWorkspaceDidWakeNotification, Box::new(on_wake));
+        }
+        add(&distributed, &NSString::from_str(SCREEN_LOCKED), Box::new(on_lock));
+    }
+}
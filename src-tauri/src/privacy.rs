@@ -0,0 +1,165 @@
+use crate::history::History;
+use crate::paths;
+use crate::settings::{self, SettingsState};
+use crate::transcribe_app_logger;
+use anyhow::Result;
+use std::path::Path;
+use tauri::{AppHandle, Manager, Wry, async_runtime::spawn, menu::CheckMenuItem};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+pub const PRIVACY_MENU_ID: &str = "privacy_mode";
+
+/// Log lines that carry transcript or clipboard text. Keep in sync with the
+/// `log::info!` calls that go through `transcribe_app_logger::loggable`.
+const TRANSCRIPT_LOG_MARKERS: &[&str] = &[
+    "Transcription text: ",
+    "Starting polish of: ",
+    "Polished text: ",
+];
+
+/// The tray's "Privacy mode" check item
+pub struct PrivacyMenuItem(CheckMenuItem<Wry>);
+
+impl PrivacyMenuItem {
+    pub fn new(item: CheckMenuItem<Wry>) -> Self {
+        Self(item)
+    }
+
+    pub fn set_checked(&self, checked: bool) {
+        if let Err(e) = self.0.set_checked(checked) {
+            log::error!("Unable to update privacy mode menu item: {e}");
+        }
+    }
+}
+
+pub fn enabled(app_handle: &AppHandle) -> bool {
+    app_handle.state::<SettingsState>().get().behavior.privacy_mode
+}
+
+/// Saves the setting. Turning privacy mode on offers to purge what was already kept.
+pub fn set_enabled(app_handle: &AppHandle, enabled: bool) -> Result<()> {
+    settings::update(app_handle, |settings| settings.behavior.privacy_mode = enabled)?;
+    log::info!("Privacy mode {}", if enabled { "enabled" } else { "disabled" });
+    if enabled {
+        offer_purge(app_handle.clone());
+    }
+    Ok(())
+}
+
+fn offer_purge(app_handle: AppHandle) {
+    app_handle
+        .dialog()
+        .message(
+            "New transcripts won't be logged, saved to history or shown in \
+             notifications. Also delete the existing history and remove \
+             transcripts from the log files?",
+        )
+        .title("Privacy mode")
+        .buttons(MessageDialogButtons::OkCancelCustom("Delete".into(), "Keep".into()))
+        .show(move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            spawn(async move {
+                if let Err(e) = purge(&app_handle).await {
+                    log::error!("Failed to purge transcripts: {e:#}");
+                }
+                crate::refresh_history_menus(app_handle.clone());
+            });
+        });
+}
+
+async fn purge(app_handle: &AppHandle) -> Result<()> {
+    let history = app_handle.state::<History>().inner().clone();
+    let entries = history.clear().await?;
+    let lines = scrub_logs(&paths::log_dir(app_handle)?)?;
+    log::warn!("Purged {entries} history entries and {lines} transcript log lines");
+    Ok(())
+}
+
+/// Rewrites every log file without the records that carry transcripts. Compressed
+/// rotated logs are deleted outright.
+fn scrub_logs(dir: &Path) -> Result<usize> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .to_str()
+            .is_some_and(|path| path.ends_with(transcribe_app_logger::ARCHIVE_SUFFIX))
+        {
+            std::fs::remove_file(&path)?;
+            continue;
+        }
+        if path.extension().is_none_or(|ext| ext != "log") {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let kept = without_transcripts(&contents);
+        let dropped = contents.lines().count() - kept.len();
+        if dropped > 0 {
+            std::fs::write(&path, kept.join("\n") + "\n")?;
+            removed += dropped;
+        }
+    }
+    Ok(removed)
+}
+
+/// The lines of a log file minus the records that carry transcripts. Logs written
+/// before newlines were escaped can continue a record over several lines, those go
+/// with the line that starts it.
+fn without_transcripts(contents: &str) -> Vec<&str> {
+    let mut dropping = false;
+    contents
+        .lines()
+        .filter(|line| {
+            if starts_record(line) {
+                dropping = TRANSCRIPT_LOG_MARKERS.iter().any(|m| line.contains(m));
+            }
+            !dropping
+        })
+        .collect()
+}
+
+/// Text records start with `[2024-01-31]`, JSON records with `{"`
+fn starts_record(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    match bytes.first() {
+        Some(b'[') => bytes.get(1..5).is_some_and(|y| y.iter().all(u8::is_ascii_digit)),
+        Some(b'{') => bytes.get(1) == Some(&b'"'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_every_line_of_a_transcript_record() {
+        let log = "[2024-01-31][10:00:00][whistle][INFO] Recording is live\n\
+                   [2024-01-31][10:00:05][whistle][INFO] Transcription text: first line\n\
+                   second line\n\
+                   [not a date] third line\n\
+                   [2024-01-31][10:00:06][whistle][INFO] Copied to clipboard";
+        assert_eq!(
+            without_transcripts(log),
+            [
+                "[2024-01-31][10:00:00][whistle][INFO] Recording is live",
+                "[2024-01-31][10:00:06][whistle][INFO] Copied to clipboard",
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_json_transcript_records() {
+        let log = "{\"level\":\"INFO\",\"message\":\"Polished text: hi\"}\n\
+                   {\"level\":\"INFO\",\"message\":\"Done\"}";
+        assert_eq!(
+            without_transcripts(log),
+            ["{\"level\":\"INFO\",\"message\":\"Done\"}"]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::history::{EntryKind, NewEntry};
+use crate::notifications::{AppNotifications, Notification};
+use crate::recent_menu;
+use crate::repeat;
+use crate::rich_text;
+use crate::settings::{PromptAction, PromptSettings, Settings, SettingsState};
+use crate::text_format;
+use crate::transcribe_client::TranscribeClient;
+use crate::transcribe_icon::{self, Work};
+use anyhow::Result;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use tauri::{
+    AppHandle, Manager, Wry,
+    async_runtime::spawn,
+    menu::{MenuItem, Submenu},
+};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_global_shortcut::Shortcut;
+
+pub const PROMPT_ID_PREFIX: &str = "prompt:";
+
+/// The usable entries of `[[prompts]]`, rebuilt whenever settings are loaded
+pub struct PromptLibrary {
+    prompts: RwLock<Vec<PromptSettings>>,
+    /// Transcript prompt to run on the current recording once it stops
+    pending: Mutex<Option<PromptSettings>>,
+}
+
+impl PromptLibrary {
+    pub fn new(prompts: Vec<PromptSettings>) -> Self {
+        Self {
+            prompts: RwLock::new(prompts),
+            pending: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<PromptSettings> {
+        self.prompts.read().unwrap().iter().find(|p| p.name == name).cloned()
+    }
+
+    pub fn take_pending(&self) -> Option<PromptSettings> {
+        self.pending.lock().unwrap().take()
+    }
+}
+
+/// The tray's "Prompts" submenu
+pub struct PromptsMenu(Submenu<Wry>);
+
+impl PromptsMenu {
+    pub fn new(submenu: Submenu<Wry>) -> Self {
+        Self(submenu)
+    }
+
+    pub fn refresh(&self, app_handle: &AppHandle, prompts: &[PromptSettings]) {
+        if let Err(e) = self.refresh_(app_handle, prompts) {
+            log::error!("Unable to refresh prompts menu: {e}");
+        }
+    }
+
+    fn refresh_(&self, app_handle: &AppHandle, prompts: &[PromptSettings]) -> Result<()> {
+        for item in self.0.items()? {
+            self.0.remove(&item)?;
+        }
+
+        let prompts: Vec<_> = prompts.iter().filter(|p| p.in_tray).collect();
+        if prompts.is_empty() {
+            self.0.append(&MenuItem::new(
+                app_handle,
+                "No prompts configured",
+                false,
+                None::<&str>,
+            )?)?;
+            return Ok(());
+        }
+
+        for prompt in prompts {
+            self.0.append(&MenuItem::with_id(
+                app_handle,
+                format!("{PROMPT_ID_PREFIX}{}", prompt.name),
+                recent_menu::label(&prompt.name),
+                true,
+                None::<&str>,
+            )?)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `[[prompts]]` into usable entries and messages naming the broken ones.
+/// Hotkeys may not clash with the built-in ones or each other.
+pub fn usable(settings: &Settings) -> (Vec<PromptSettings>, Vec<String>) {
+    let builtin: Vec<_> = settings
+        .hotkeys
+        .named()
+        .into_iter()
+        .filter_map(|(name, hotkey)| Some((name, Shortcut::from_str(&hotkey).ok()?)))
+        .collect();
+    let mut usable: Vec<PromptSettings> = vec![];
+    let mut hotkeys: Vec<(Shortcut, &str)> = vec![];
+    let mut skipped = vec![];
+
+    for prompt in &settings.prompts {
+        let hotkey = match prompt.hotkey.as_str() {
+            "" => Ok(None),
+            hotkey => Shortcut::from_str(hotkey).map(Some),
+        };
+        let problem = if prompt.name.trim().is_empty() {
+            Some("it has no name".to_string())
+        } else if prompt.prompt.trim().is_empty() {
+            Some("its prompt is empty".into())
+        } else if usable.iter().any(|p| p.name == prompt.name) {
+            Some("another prompt has the same name".into())
+        } else {
+            match hotkey {
+                Err(_) => Some(format!("{:?} is not a valid hotkey", prompt.hotkey)),
+                Ok(Some(hotkey)) => builtin
+                    .iter()
+                    .find(|(_, h)| *h == hotkey)
+                    .map(|(name, _)| format!("its hotkey is taken by `hotkeys.{name}`"))
+                    .or_else(|| {
+                        hotkeys.iter().find(|(h, _)| *h == hotkey).map(|(_, other)| {
+                            format!("its hotkey is taken by prompt {other:?}")
+                        })
+                    }),
+                Ok(None) => None,
+            }
+        };
+
+        match problem {
+            Some(problem) => {
+                log::warn!("Skipping prompt {:?}: {problem}", prompt.name);
+                skipped.push(format!("{:?} ({problem})", prompt.name));
+            }
+            None => {
+                if let Ok(Some(hotkey)) = hotkey {
+                    hotkeys.push((hotkey, &prompt.name));
+                }
+                usable.push(prompt.clone());
+            }
+        }
+    }
+
+    (usable, skipped)
+}
+
+/// Rebuilds the library and menu from `settings`, notifying about skipped entries.
+/// Returns the usable prompts so their hotkeys can be registered.
+pub fn reload(app_handle: &AppHandle, settings: &Settings) -> Vec<PromptSettings> {
+    let (prompts, skipped) = usable(settings);
+    *app_handle.state::<PromptLibrary>().prompts.write().unwrap() = prompts.clone();
+    if let Some(menu) = app_handle.try_state::<PromptsMenu>() {
+        menu.refresh(app_handle, &prompts);
+    }
+    if !skipped.is_empty() {
+        AppNotifications::new(app_handle).notify(Notification::PromptsSkipped(skipped));
+    }
+    prompts
+}
+
+/// Runs a prompt by name from its hotkey or tray item
+pub fn run(app_handle: &AppHandle, name: &str) {
+    let library = app_handle.state::<PromptLibrary>();
+    let Some(prompt) = library.get(name) else {
+        log::warn!("Prompt {name:?} no longer exists");
+        return;
+    };
+    log::info!("Running prompt {name:?}");
+
+    match prompt.action {
+        PromptAction::Clipboard => transform_clipboard(app_handle.clone(), prompt),
+        PromptAction::Transcript => {
+            // Starts a recording, or stops the current one, and applies the prompt
+            // to its transcript
+            *library.pending.lock().unwrap() = Some(prompt);
+            crate::toggle_recording(app_handle.clone(), None);
+        }
+    }
+}
+
+/// Rewrites `text` with the prompt, used on a transcript before it is delivered
+/// `language` is the one `text` was transcribed in, it's asked for in the result with
+/// `behavior.polish_in_detected_language`, unless `behavior.polish_language` is set
+pub async fn transform(
+    app_handle: &AppHandle,
+    prompt: &PromptSettings,
+    text: String,
+    language: Option<&str>,
+) -> Result<String> {
+    let behavior = app_handle.state::<SettingsState>().get().behavior.clone();
+    let language = match &behavior.polish_language {
+        Some(language) => Some(language.as_str()),
+        None => language.filter(|_| behavior.polish_in_detected_language),
+    };
+    let format = text_format::for_polish(&behavior, &text);
+    let client = app_handle.state::<TranscribeClient>();
+    client
+        .clean_transcription(
+            text,
+            &behavior.polish_style,
+            Some(&prompt.prompt),
+            language,
+            format,
+        )
+        .await
+}
+
+fn transform_clipboard(app_handle: AppHandle, prompt: PromptSettings) {
+    spawn(async move {
+        let text = match app_handle.clipboard().read_text() {
+            Ok(text) if !text.trim().is_empty() => text,
+            _ => {
+                log::warn!("Nothing in the clipboard for prompt {:?}", prompt.name);
+                return;
+            }
+        };
+
+        let busy = transcribe_icon::busy(&app_handle, Work::Polishing);
+        AppNotifications::new(&app_handle).notify(Notification::StartPolishing);
+
+        let result = transform(&app_handle, &prompt, text.clone(), None).await;
+        drop(busy);
+
+        let transformed = match result {
+            Ok(transformed) => transformed,
+            Err(e) => {
+                log::error!("Prompt {:?} failed: {e:#}", prompt.name);
+                crate::notify_api_error(&app_handle, &e);
+                return;
+            }
+        };
+
+        crate::save_to_history(
+            &app_handle,
+            NewEntry {
+                provider: Some(app_handle.state::<TranscribeClient>().provider()),
+                source_text: Some(text),
+                ..NewEntry::new(EntryKind::Polish, transformed.clone())
+            },
+        );
+
+        let behavior = app_handle.state::<SettingsState>().get().behavior.clone();
+        let result = match prompt.rich_text(&behavior) {
+            true => rich_text::write(&app_handle, transformed, false),
+            false => app_handle.clipboard().write_text(transformed).map_err(Into::into),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to write text to clipboard: {e:#}");
+            return;
+        }
+        AppNotifications::new(&app_handle).notify(Notification::PolishSuccess);
+        repeat::record(&app_handle, repeat::Action::Prompt(prompt.name.clone()));
+    });
+}
@@ -0,0 +1,71 @@
+use crate::live::{self, LiveDictation};
+use crate::settings::{OutputMode, SettingsState};
+use crate::sinks;
+use crate::transcribe_icon::TranscribeIcon;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// When the push-to-talk hotkey went down, while it is held
+#[derive(Default)]
+pub struct PushToTalk {
+    pressed_at: Mutex<Option<Instant>>,
+}
+
+/// Starts recording until the hotkey is released. Ignored while a toggled recording
+/// runs, which only the toggle hotkey stops, so the release doesn't stop or discard it.
+pub fn press(app_handle: &AppHandle) {
+    let state = app_handle.state::<PushToTalk>();
+    let mut pressed_at = state.pressed_at.lock().unwrap();
+    // Ignore key repeat while held
+    if pressed_at.is_some() {
+        return;
+    }
+    if app_handle.state::<TranscribeIcon>().is_recording() {
+        log::info!("Ignoring push-to-talk, a toggled recording is running");
+        return;
+    }
+    *pressed_at = Some(Instant::now());
+    let settings = app_handle.state::<SettingsState>().get();
+    // Live dictation types as it goes, a normal recording can still be held back
+    if settings.live.enabled
+        && !sinks::into_password_field(&settings.behavior, OutputMode::Type)
+    {
+        live::start(app_handle);
+    } else {
+        crate::toggle_recording(app_handle.clone(), None);
+    }
+}
+
+/// Stops and transcribes the recording, or silently throws it away when the
+/// hotkey was released before `behavior.push_to_talk_min_hold_ms`. Live dictation
+/// erases what it typed in that case.
+pub fn release(app_handle: &AppHandle) {
+    let Some(pressed_at) =
+        app_handle.state::<PushToTalk>().pressed_at.lock().unwrap().take()
+    else {
+        return;
+    };
+
+    let min_hold = app_handle
+        .state::<SettingsState>()
+        .get()
+        .behavior
+        .push_to_talk_min_hold_ms;
+    let held = pressed_at.elapsed();
+    let long_enough = held >= Duration::from_millis(min_hold);
+    if app_handle.state::<LiveDictation>().is_active() {
+        live::stop(app_handle, long_enough);
+        return;
+    }
+    if long_enough {
+        crate::toggle_recording(app_handle.clone(), None);
+        return;
+    }
+
+    log::info!(
+        "Push-to-talk released after {}ms, discarding recording",
+        held.as_millis()
+    );
+    crate::cancel_recording(app_handle.clone());
+}
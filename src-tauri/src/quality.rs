@@ -0,0 +1,126 @@
+use crate::settings::{Compression, Settings};
+use crate::upload::UploadPlan;
+use std::time::Duration;
+
+/// How a recording is transcribed with `quality.auto`, picked from its length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plan {
+    Fast,
+    Standard,
+    Accurate,
+}
+
+impl Plan {
+    /// Shown in the notification, e.g. "Transcribed (fast) & copied"
+    pub fn name(self) -> &'static str {
+        match self {
+            Plan::Fast => "fast",
+            Plan::Standard => "standard",
+            Plan::Accurate => "accurate",
+        }
+    }
+
+    /// The model to ask for, `None` leaves it to the backend
+    pub fn model(self, settings: &Settings) -> Option<String> {
+        let model = match self {
+            Plan::Fast => &settings.quality.fast_model,
+            Plan::Standard => return None,
+            Plan::Accurate => &settings.quality.accurate_model,
+        };
+        (!model.is_empty()).then(|| model.clone())
+    }
+}
+
+/// `None` unless `quality.auto` is on and neither `api.model` nor `api.compression` is
+/// set, which always win
+pub fn choose(settings: &Settings, duration: Option<Duration>) -> Option<Plan> {
+    if !settings.quality.auto
+        || !settings.api.model.trim().is_empty()
+        || settings.api.compression != Compression::Auto
+    {
+        return None;
+    }
+    let secs = duration?.as_secs_f64();
+    Some(if secs < settings.quality.short_secs as f64 {
+        Plan::Fast
+    } else if secs > settings.quality.long_secs as f64 {
+        Plan::Accurate
+    } else {
+        Plan::Standard
+    })
+}
+
+/// How to upload the recording. Only the accurate plan is sent in parts, and it's
+/// compressed too unless `api.compression` says otherwise.
+pub fn upload(settings: &Settings, plan: Option<Plan>) -> UploadPlan {
+    let accurate = plan == Some(Plan::Accurate);
+    UploadPlan {
+        compress: match settings.api.compression {
+            Compression::Auto => accurate,
+            Compression::Off => false,
+            Compression::Opus => true,
+        },
+        chunk: accurate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto() -> Settings {
+        let mut settings = Settings::default();
+        settings.quality.auto = true;
+        settings
+    }
+
+    fn secs(secs: u64) -> Option<Duration> {
+        Some(Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn picks_the_plan_by_length() {
+        let settings = auto();
+        assert_eq!(choose(&settings, secs(5)), Some(Plan::Fast));
+        assert_eq!(choose(&settings, secs(60)), Some(Plan::Standard));
+        assert_eq!(choose(&settings, secs(600)), Some(Plan::Accurate));
+        assert_eq!(choose(&settings, None), None);
+        assert_eq!(choose(&Settings::default(), secs(600)), None);
+    }
+
+    #[test]
+    fn explicit_model_or_compression_turns_auto_off() {
+        let mut settings = auto();
+        settings.api.model = "whisper-1".into();
+        assert_eq!(choose(&settings, secs(600)), None);
+
+        for compression in [Compression::Off, Compression::Opus] {
+            let mut settings = auto();
+            settings.api.compression = compression;
+            assert_eq!(choose(&settings, secs(600)), None);
+        }
+    }
+
+    #[test]
+    fn only_the_accurate_plan_compresses_and_chunks() {
+        let settings = auto();
+        for plan in [None, Some(Plan::Fast), Some(Plan::Standard)] {
+            assert_eq!(upload(&settings, plan), UploadPlan::default());
+        }
+        let accurate = UploadPlan {
+            compress: true,
+            chunk: true,
+        };
+        assert_eq!(upload(&settings, Some(Plan::Accurate)), accurate);
+    }
+
+    #[test]
+    fn explicit_compression_applies_to_every_recording() {
+        let mut settings = Settings::default();
+        settings.api.compression = Compression::Opus;
+        assert!(upload(&settings, None).compress);
+        assert!(!upload(&settings, None).chunk);
+        settings.api.compression = Compression::Off;
+        assert_eq!(upload(&settings, None), UploadPlan::default());
+    }
+}
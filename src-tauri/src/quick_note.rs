@@ -0,0 +1,62 @@
+use crate::settings::QuickNoteSettings;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Appends `text` under a timestamp heading to today's note, creating the file and its
+/// folder if needed. Returns the file written to.
+pub fn append(settings: &QuickNoteSettings, text: &str) -> Result<PathBuf> {
+    let now = Local::now();
+    let path = note_path(&settings.path, now)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut heading = String::new();
+    write!(heading, "{}", now.format(&settings.heading))
+        .map_err(|_| anyhow!("`quick_note.heading` isn't a valid time format"))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    // A file that doesn't end in a newline would run into the heading
+    let mut entry = String::new();
+    if file.metadata()?.len() > 0 {
+        let mut last = [0; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            entry.push('\n');
+        }
+        entry.push('\n');
+    }
+    entry.push_str(&format!("{heading}\n\n{}\n", text.trim()));
+
+    // One write on an O_APPEND file, so notes appended at the same time never interleave
+    file.write_all(entry.as_bytes())
+        .with_context(|| format!("Failed to write to {}", path.display()))?;
+    Ok(path)
+}
+
+/// Fills in the date tokens and a leading `~`
+fn note_path(template: &str, now: DateTime<Local>) -> Result<PathBuf> {
+    let path = template
+        .replace("{YYYY-MM-DD}", &now.format("%Y-%m-%d").to_string())
+        .replace("{YYYY}", &now.format("%Y").to_string())
+        .replace("{MM}", &now.format("%m").to_string())
+        .replace("{DD}", &now.format("%d").to_string());
+    match path.strip_prefix("~/") {
+        Some(rest) => {
+            Ok(dirs::home_dir().context("Could not find home directory")?.join(rest))
+        }
+        None => Ok(PathBuf::from(path)),
+    }
+}
@@ -0,0 +1,51 @@
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::SettingsState;
+use crate::transcribe_client::TranscribeClient;
+use crate::wake_word;
+use tauri::{AppHandle, Manager, Wry, menu::MenuItem};
+
+pub const QUOTA_MENU_ID: &str = "resume_after_quota";
+
+/// The tray's "Resume after quota error" item, only enabled while paused
+pub struct QuotaMenuItem(MenuItem<Wry>);
+
+impl QuotaMenuItem {
+    pub fn new(item: MenuItem<Wry>) -> Self {
+        Self(item)
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        if let Err(e) = self.0.set_enabled(enabled) {
+            log::error!("Unable to update quota menu item: {e}");
+        }
+    }
+}
+
+/// Tells the user their credit ran out and, with `api.pause_on_quota_error`, stops
+/// further requests so they don't keep recording into the same error
+pub fn exceeded(app_handle: &AppHandle) {
+    let pause = app_handle.state::<SettingsState>().get().api.pause_on_quota_error;
+    if pause {
+        log::warn!("API quota exceeded, pausing requests until resumed");
+        app_handle.state::<TranscribeClient>().set_quota_paused(true);
+        app_handle.state::<QuotaMenuItem>().set_enabled(true);
+        wake_word::apply(app_handle);
+    }
+    AppNotifications::new(app_handle)
+        .notify(Notification::QuotaExceeded { paused: pause });
+}
+
+pub fn paused(app_handle: &AppHandle) -> bool {
+    app_handle.state::<TranscribeClient>().quota_paused()
+}
+
+/// Lets requests through again, after topping up or switching the API key
+pub fn resume(app_handle: &AppHandle) {
+    if !paused(app_handle) {
+        return;
+    }
+    log::info!("Resuming API requests after a quota error");
+    app_handle.state::<TranscribeClient>().set_quota_paused(false);
+    app_handle.state::<QuotaMenuItem>().set_enabled(false);
+    wake_word::apply(app_handle);
+}
@@ -0,0 +1,86 @@
+use crate::history::HistoryEntry;
+use anyhow::Result;
+use tauri::{
+    AppHandle, Wry,
+    menu::{MenuItem, Submenu, SubmenuBuilder},
+};
+
+pub const RECENT_LIMIT: u32 = 5;
+pub const RECENT_ID_PREFIX: &str = "recent:";
+pub const PIN_ID_PREFIX: &str = "pin:";
+pub const UNPIN_ID_PREFIX: &str = "unpin:";
+const LABEL_MAX_CHARS: usize = 40;
+
+/// The tray's "Recent" submenu, listing the latest history entries, each with
+/// copy and pin actions
+pub struct RecentMenu(Submenu<Wry>);
+
+impl RecentMenu {
+    pub fn new(submenu: Submenu<Wry>) -> Self {
+        Self(submenu)
+    }
+
+    pub fn refresh(&self, app_handle: &AppHandle, entries: &[HistoryEntry]) {
+        if let Err(e) = self.refresh_(app_handle, entries) {
+            log::error!("Unable to refresh recent menu: {e}");
+        }
+    }
+
+    fn refresh_(&self, app_handle: &AppHandle, entries: &[HistoryEntry]) -> Result<()> {
+        for item in self.0.items()? {
+            self.0.remove(&item)?;
+        }
+
+        if entries.is_empty() {
+            self.0.append(&MenuItem::new(
+                app_handle,
+                "No history yet",
+                false,
+                None::<&str>,
+            )?)?;
+            return Ok(());
+        }
+
+        for entry in entries {
+            let (pin_prefix, pin_label) = match entry.pinned {
+                true => (UNPIN_ID_PREFIX, "Unpin"),
+                false => (PIN_ID_PREFIX, "Pin"),
+            };
+            // A fragment of a cancelled dictation shouldn't pass for the whole of it
+            let text = match entry.partial {
+                true => format!("(partial) {}", label(&entry.text)),
+                false => label(&entry.text),
+            };
+            self.0.append(
+                &SubmenuBuilder::new(app_handle, text)
+                    .item(&MenuItem::with_id(
+                        app_handle,
+                        format!("{RECENT_ID_PREFIX}{}", entry.id),
+                        "Copy",
+                        true,
+                        None::<&str>,
+                    )?)
+                    .item(&MenuItem::with_id(
+                        app_handle,
+                        format!("{pin_prefix}{}", entry.id),
+                        pin_label,
+                        true,
+                        None::<&str>,
+                    )?)
+                    .build()?,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Single-line preview of `text` short enough for a menu item
+pub fn label(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= LABEL_MAX_CHARS {
+        return text;
+    }
+    let truncated: String = text.chars().take(LABEL_MAX_CHARS).collect();
+    format!("{truncated}…")
+}
@@ -0,0 +1,48 @@
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::OutputMode;
+use crate::{PolishOverrides, prompts};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+pub const REPEAT_ID: &str = "repeat_last";
+
+/// Something the user started that finished and can be run again as it was.
+/// Recordings aren't kept, repeating a toggle would only flip it back.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Polishing the clipboard, with the style or prompt it was asked for
+    Polish {
+        output_mode: Option<OutputMode>,
+        overrides: PolishOverrides,
+    },
+    /// A clipboard prompt from the library, by name
+    Prompt(String),
+}
+
+/// The last finished action, for the tray's "Repeat last action" and
+/// `hotkeys.repeat_last`
+#[derive(Default)]
+pub struct LastAction(Mutex<Option<Action>>);
+
+pub fn record(app_handle: &AppHandle, action: Action) {
+    log::debug!("Last action is now {action:?}");
+    *app_handle.state::<LastAction>().0.lock().unwrap() = Some(action);
+}
+
+/// Runs the last action again through its usual entry point, so a polish that is
+/// still running turns the repeat away the same way it would a new one
+pub fn repeat(app_handle: &AppHandle) {
+    let Some(action) = app_handle.state::<LastAction>().0.lock().unwrap().clone() else {
+        log::info!("Nothing to repeat yet");
+        AppNotifications::new(app_handle).notify(Notification::NothingToRepeat);
+        return;
+    };
+    log::info!("Repeating {action:?}");
+    match action {
+        Action::Polish {
+            output_mode,
+            overrides,
+        } => crate::cleanse_clipboard(app_handle.clone(), output_mode, overrides),
+        Action::Prompt(name) => prompts::run(app_handle, &name),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::history::History;
+use crate::last_recording;
+use crate::paths;
+use crate::settings::SettingsState;
+use anyhow::Result;
+use chrono::Utc;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    pub recordings: usize,
+    pub history: usize,
+    pub logs: usize,
+}
+
+/// Applies the retention policy now, at startup and then once a day
+pub fn schedule(app_handle: AppHandle) {
+    spawn(async move {
+        loop {
+            match cleanup(&app_handle).await {
+                Ok(report) => log::info!(
+                    "Retention cleanup removed {} recordings, {} history entries, {} logs",
+                    report.recordings,
+                    report.history,
+                    report.logs
+                ),
+                Err(e) => log::error!("Retention cleanup failed: {e:#}"),
+            }
+            tokio::time::sleep(CLEANUP_INTERVAL).await;
+        }
+    });
+}
+
+pub async fn cleanup(app_handle: &AppHandle) -> Result<CleanupReport> {
+    let retention = app_handle.state::<SettingsState>().get().retention.clone();
+    let mut report = CleanupReport::default();
+
+    if retention.recordings_days > 0 {
+        let recordings = recordings_dir(app_handle)?;
+        let max_age = DAY * retention.recordings_days;
+        report.recordings = delete_files_older_than(&recordings, Some("wav"), max_age)?;
+        // Their transcripts, see `saved_recordings`
+        delete_files_older_than(&recordings, Some("json"), max_age)?;
+    }
+    if retention.history_days > 0 {
+        let cutoff = Utc::now() - chrono::Duration::days(retention.history_days.into());
+        let history = app_handle.state::<History>().inner().clone();
+        report.history = history.prune_before(cutoff).await?;
+    }
+    if retention.logs_days > 0 {
+        let log_dir = paths::log_dir(app_handle)?;
+        report.logs =
+            delete_files_older_than(&log_dir, Some("log"), DAY * retention.logs_days)?
+                + delete_files_older_than(
+                    &log_dir,
+                    Some("gz"),
+                    DAY * retention.logs_days,
+                )?;
+    }
+
+    Ok(report)
+}
+
+/// Removes all recordings, history (pinned entries included) and old logs
+pub async fn delete_everything(app_handle: &AppHandle) -> Result<CleanupReport> {
+    last_recording::clear(app_handle);
+    let history = app_handle.state::<History>().inner().clone();
+    delete_files_older_than(&recordings_dir(app_handle)?, Some("json"), Duration::ZERO)?;
+    let report = CleanupReport {
+        recordings: delete_files_older_than(
+            &recordings_dir(app_handle)?,
+            Some("wav"),
+            Duration::ZERO,
+        )?,
+        history: history.clear().await?,
+        logs: delete_files_older_than(
+            &paths::log_dir(app_handle)?,
+            Some("log"),
+            Duration::ZERO,
+        )?,
+    };
+    log::warn!(
+        "Deleted everything: {} recordings, {} history entries, {} logs",
+        report.recordings,
+        report.history,
+        report.logs
+    );
+    Ok(report)
+}
+
+fn delete_files_older_than(
+    dir: &Path,
+    extension: Option<&str>,
+    max_age: Duration,
+) -> Result<usize> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let now = SystemTime::now();
+    let mut deleted = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if extension
+            .is_some_and(|ext| path.extension().and_then(|e| e.to_str()) != Some(ext))
+        {
+            continue;
+        }
+        let age = std::fs::metadata(&path)?
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age < max_age {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => deleted += 1,
+            Err(e) => log::warn!("Failed to delete {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(deleted)
+}
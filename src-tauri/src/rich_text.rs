@@ -0,0 +1,43 @@
+use crate::selection;
+use anyhow::Result;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use tauri::AppHandle;
+
+const OPTIONS: Options = Options::ENABLE_TABLES.union(Options::ENABLE_STRIKETHROUGH);
+
+/// Copies `text` as HTML, with the Markdown itself as the plain text version, when
+/// it has any formatting. Rich text editors paste the HTML and terminals the Markdown.
+pub fn write(app_handle: &AppHandle, text: String, paste: bool) -> Result<()> {
+    match to_html(&text) {
+        Some(html) => {
+            log::info!("Copying formatted text as HTML too");
+            selection::write_html(app_handle, html, text, paste)
+        }
+        None => selection::write_text(app_handle, text, paste),
+    }
+}
+
+/// Renders Markdown to HTML. `None` for plain prose, which gains nothing from it.
+pub fn to_html(markdown: &str) -> Option<String> {
+    let events: Vec<Event> = Parser::new_ext(markdown, OPTIONS)
+        .map(|event| match event {
+            // HTML from the backend ends up as text, never as markup
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        })
+        .collect();
+    if !events.iter().any(is_formatting) {
+        return None;
+    }
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+    Some(html)
+}
+
+fn is_formatting(event: &Event) -> bool {
+    match event {
+        Event::Start(Tag::Paragraph) => false,
+        Event::Start(_) | Event::Code(_) | Event::Rule => true,
+        _ => false,
+    }
+}
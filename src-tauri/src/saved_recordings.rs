@@ -0,0 +1,178 @@
+use crate::audio_recorder::RecordingInfo;
+use crate::notifications::{AppNotifications, Notification};
+use crate::paths::recordings_dir;
+use crate::privacy;
+use crate::settings::SettingsState;
+use crate::transcribe_client::TranscriptionTiming;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, async_runtime::spawn_blocking};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use zip::write::SimpleFileOptions;
+
+pub const REPORT_RESULT_ID: &str = "report_result";
+
+/// What a saved recording was transcribed to, in a JSON file named like its WAV
+#[derive(Debug, Serialize)]
+pub struct Sidecar {
+    pub text: String,
+    pub provider: String,
+    pub model: Option<String>,
+    pub language: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+    pub duration_ms: Option<u64>,
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub timing: Option<TranscriptionTiming>,
+    pub app_version: String,
+}
+
+impl Sidecar {
+    pub fn new(
+        app_handle: &AppHandle,
+        text: String,
+        provider: String,
+        recording: &RecordingInfo,
+        timing: Option<TranscriptionTiming>,
+    ) -> Self {
+        Self {
+            text,
+            provider,
+            model: recording.model.clone(),
+            language: recording.language.clone(),
+            recorded_at: recording.started_at,
+            duration_ms: recording.duration.map(|d| d.as_millis() as u64),
+            device: recording.device.clone(),
+            sample_rate: recording.sample_rate,
+            timing,
+            app_version: app_handle.package_info().version.to_string(),
+        }
+    }
+}
+
+/// With `retention.save_recordings`, outside privacy mode
+pub fn enabled(app_handle: &AppHandle) -> bool {
+    app_handle.state::<SettingsState>().get().retention.save_recordings
+        && !privacy::enabled(app_handle)
+}
+
+/// Writes the recording and its sidecar to the recordings folder in the background,
+/// where `retention.recordings_days` and the disk budget clean them up
+pub fn save(app_handle: &AppHandle, bytes: Vec<u8>, sidecar: Sidecar) {
+    let Ok(dir) = recordings_dir(app_handle) else {
+        return;
+    };
+    spawn_blocking(move || {
+        let stem = sidecar
+            .recorded_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d_%H-%M-%S")
+            .to_string();
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(std::fs::write(dir.join(format!("{stem}.wav")), bytes)?))
+            .and_then(|_| {
+                let json = serde_json::to_vec_pretty(&sidecar)?;
+                Ok(std::fs::write(dir.join(format!("{stem}.json")), json)?)
+            });
+        match result {
+            Ok(()) => log::info!("Saved recording {stem}"),
+            Err(e) => log::error!("Failed to save recording {stem}: {e:#}"),
+        }
+    });
+}
+
+/// The tray's "Report last result…": zips the newest saved recording with its
+/// sidecar, ready to attach to an issue
+pub fn report_latest(app_handle: &AppHandle) {
+    let latest = recordings_dir(app_handle).and_then(|dir| latest_pair(&dir));
+    let (wav, json) = match latest {
+        Ok(Some(pair)) => pair,
+        Ok(None) => {
+            AppNotifications::new(app_handle).notify(Notification::NoSavedRecording);
+            return;
+        }
+        Err(e) => {
+            log::error!("Failed to look for saved recordings: {e:#}");
+            AppNotifications::new(app_handle).notify(Notification::NoSavedRecording);
+            return;
+        }
+    };
+    let stem = wav.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let app_handle = app_handle.clone();
+    app_handle
+        .dialog()
+        .file()
+        .add_filter("Zip", &["zip"])
+        .set_file_name(format!("whistle-report-{stem}.zip"))
+        .save_file(move |path| {
+            let Some(path) = path.and_then(|p| p.into_path().ok()) else {
+                return;
+            };
+            if let Err(e) = write_zip(&path, &[&wav, &json]) {
+                log::error!("Failed to write {}: {e:#}", path.display());
+                AppNotifications::new(&app_handle).notify(Notification::ReportFailed);
+                return;
+            }
+            log::info!("Wrote result report {}", path.display());
+            show_report_written(&app_handle, path);
+        });
+}
+
+/// The newest WAV that still has its sidecar
+fn latest_pair(dir: &Path) -> Result<Option<(PathBuf, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut pairs = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let wav = entry?.path();
+        let json = wav.with_extension("json");
+        if wav.extension().is_some_and(|ext| ext == "wav") && json.is_file() {
+            let modified = std::fs::metadata(&wav)?.modified()?;
+            pairs.push((modified, wav, json));
+        }
+    }
+    Ok(pairs
+        .into_iter()
+        .max_by_key(|(modified, ..)| *modified)
+        .map(|(_, wav, json)| (wav, json)))
+}
+
+fn write_zip(path: &Path, files: &[&Path]) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(File::create(path)?);
+    let options =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for file in files {
+        let name = file
+            .file_name()
+            .context("Saved recording has no file name")?
+            .to_string_lossy();
+        zip.start_file(name, options)?;
+        zip.write_all(&std::fs::read(file)?)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn show_report_written(app_handle: &AppHandle, path: PathBuf) {
+    app_handle
+        .dialog()
+        .message(format!(
+            "Saved the recording and its transcript to {}. Attach it to your issue",
+            path.display()
+        ))
+        .title("Result report saved")
+        .buttons(MessageDialogButtons::OkCancelCustom("Reveal".into(), "OK".into()))
+        .show(move |reveal| {
+            if reveal {
+                if let Err(e) = tauri_plugin_opener::reveal_item_in_dir(&path) {
+                    log::error!("Failed to reveal {}: {}", path.display(), e);
+                }
+            }
+        });
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use arboard::Clipboard;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// The clipboard can be briefly held open by another app, mostly on Windows
+const RETRY_DELAY: Duration = Duration::from_millis(150);
+/// A copy made just before polishing can take a moment to reach the clipboard, and
+/// a read in between still returns what was there before
+const SETTLE_DELAY: Duration = Duration::from_millis(80);
+/// Further reads before a clipboard that keeps changing is given up on
+const SETTLE_READS: usize = 4;
+
+/// What there is to polish, or why there isn't anything
+#[derive(Debug, PartialEq)]
+pub enum Content {
+    Text(String),
+    Files(Vec<PathBuf>),
+    Image,
+    Empty,
+}
+
+/// Where polish reads its input and deliveries write their output. That's the
+/// clipboard, or on Linux with `behavior.linux_primary_selection` the primary
+/// selection (the middle-click buffer).
+pub fn read_text(app_handle: &AppHandle) -> Result<String> {
+    #[cfg(target_os = "linux")]
+    if use_primary(app_handle) {
+        return primary::read_text(app_handle);
+    }
+    Ok(app_handle.clipboard().read_text()?)
+}
+
+/// The clipboard read again until two reads agree
+#[derive(Debug)]
+pub struct Unsettled;
+
+impl std::fmt::Display for Unsettled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The clipboard kept changing while it was read")
+    }
+}
+
+impl std::error::Error for Unsettled {}
+
+/// Like `read_text`, but tells copied files and images apart from an empty
+/// clipboard. It's read until two reads in a row agree, so text that was just copied
+/// isn't mistaken for what it replaced. Fails with `Unsettled` if they never do.
+pub async fn read_content(app_handle: &AppHandle) -> Result<Content> {
+    let mut content = read_once(app_handle).await?;
+    for _ in 0..SETTLE_READS {
+        tokio::time::sleep(SETTLE_DELAY).await;
+        let again = read_once(app_handle).await?;
+        if again == content {
+            return Ok(content);
+        }
+        log::debug!("Clipboard changed while it was read, reading again");
+        content = again;
+    }
+    Err(Unsettled.into())
+}
+
+/// A clipboard that can't be read is tried once more before giving up
+async fn read_once(app_handle: &AppHandle) -> Result<Content> {
+    match content(app_handle) {
+        Ok(content) => Ok(content),
+        Err(e) => {
+            log::warn!("Failed to read the clipboard, retrying: {e:#}");
+            tokio::time::sleep(RETRY_DELAY).await;
+            content(app_handle)
+        }
+    }
+}
+
+fn content(app_handle: &AppHandle) -> Result<Content> {
+    // The primary selection only ever holds text
+    #[cfg(target_os = "linux")]
+    if use_primary(app_handle) {
+        let text = primary::read_text(app_handle)?;
+        return Ok(match text.is_empty() {
+            true => Content::Empty,
+            false => Content::Text(text),
+        });
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = app_handle;
+
+    let mut clipboard = Clipboard::new()?;
+    // Copied files come with their names as text too, so they're checked first
+    if let Ok(files) = clipboard.get().file_list() {
+        if !files.is_empty() {
+            return Ok(Content::Files(files));
+        }
+    }
+    match clipboard.get_text() {
+        Ok(text) if !text.is_empty() => return Ok(Content::Text(text)),
+        Ok(_) | Err(arboard::Error::ContentNotAvailable) => {}
+        Err(e) => return Err(e.into()),
+    }
+    if clipboard.get_image().is_ok() {
+        return Ok(Content::Image);
+    }
+    Ok(Content::Empty)
+}
+
+/// `paste` also fills the clipboard in primary selection mode, since the paste
+/// keystroke only reads from the clipboard
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub fn write_text(app_handle: &AppHandle, text: String, paste: bool) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if use_primary(app_handle) {
+        primary::write_text(app_handle, text.clone())?;
+        if !paste {
+            return Ok(());
+        }
+    }
+    Ok(app_handle.clipboard().write_text(text)?)
+}
+
+/// Like `write_text`, with `html` for apps that take rich text. The primary
+/// selection only gets `text`.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub fn write_html(
+    app_handle: &AppHandle,
+    html: String,
+    text: String,
+    paste: bool,
+) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if use_primary(app_handle) {
+        primary::write_text(app_handle, text.clone())?;
+        if !paste {
+            return Ok(());
+        }
+    }
+    Ok(app_handle.clipboard().write_html(html, Some(text))?)
+}
+
+#[cfg(target_os = "linux")]
+fn use_primary(app_handle: &AppHandle) -> bool {
+    use tauri::Manager;
+    app_handle
+        .state::<crate::settings::SettingsState>()
+        .get()
+        .behavior
+        .linux_primary_selection
+}
+
+#[cfg(target_os = "linux")]
+pub mod primary {
+    use anyhow::{Context, Result};
+    use arboard::{Clipboard, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+    use std::sync::Mutex;
+    use tauri::{AppHandle, Manager};
+
+    /// Kept for the app's lifetime, X11 only serves the selection while its owner
+    /// is alive
+    #[derive(Default)]
+    pub struct PrimarySelection(Mutex<Option<Clipboard>>);
+
+    fn with_clipboard<T>(
+        app_handle: &AppHandle,
+        f: impl FnOnce(&mut Clipboard) -> Result<T>,
+    ) -> Result<T> {
+        let state = app_handle.state::<PrimarySelection>();
+        let mut clipboard = state.0.lock().unwrap();
+        if clipboard.is_none() {
+            *clipboard =
+                Some(Clipboard::new().context("No primary selection available")?);
+        }
+        f(clipboard.as_mut().expect("set above"))
+    }
+
+    pub fn read_text(app_handle: &AppHandle) -> Result<String> {
+        with_clipboard(app_handle, |clipboard| {
+            Ok(clipboard.get().clipboard(LinuxClipboardKind::Primary).text()?)
+        })
+    }
+
+    pub fn write_text(app_handle: &AppHandle, text: String) -> Result<()> {
+        with_clipboard(app_handle, |clipboard| {
+            Ok(clipboard.set().clipboard(LinuxClipboardKind::Primary).text(text)?)
+        })
+    }
+}
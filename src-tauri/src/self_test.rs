@@ -0,0 +1,252 @@
+use crate::local_task_handler::Task;
+use crate::notifications::{AppNotifications, Notification};
+use crate::transcribe_client::TranscribeClient;
+use crate::{ShortcutsConfig, api_key, paths, permissions};
+use anyhow::{Context, Result, anyhow, bail};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tauri_plugin_global_shortcut::GlobalShortcutExt;
+use tauri_plugin_notification::{NotificationExt, PermissionState};
+use tokio::sync::{mpsc, oneshot};
+
+pub const SELF_TEST_ID: &str = "self_test";
+/// Written to the data directory once the first launch has been checked
+const MARKER: &str = "self-test-done";
+/// Gives the hotkeys a moment to register before the first launch check
+const FIRST_LAUNCH_DELAY: Duration = Duration::from_secs(5);
+const MIC_TEST_DURATION: Duration = Duration::from_secs(1);
+
+/// Where the user can fix a failing check
+#[derive(Debug, Clone, Copy)]
+enum Fix {
+    MicrophoneSettings,
+    AccessibilitySettings,
+    NotificationSettings,
+    SettingsWindow,
+    DataDirectory,
+}
+
+impl Fix {
+    fn label(self) -> &'static str {
+        match self {
+            Fix::MicrophoneSettings => "Open microphone settings",
+            Fix::AccessibilitySettings => "Open accessibility settings",
+            Fix::NotificationSettings => "Open notification settings",
+            Fix::SettingsWindow => "Open settings",
+            Fix::DataDirectory => "Show data folder",
+        }
+    }
+
+    fn open(self, app_handle: &AppHandle) {
+        match self {
+            Fix::MicrophoneSettings => permissions::open_microphone_settings(),
+            Fix::AccessibilitySettings => permissions::open_privacy_settings(),
+            Fix::NotificationSettings => permissions::open_notification_settings(),
+            Fix::SettingsWindow => crate::open_main_window(app_handle),
+            Fix::DataDirectory => {
+                let Ok(dir) = paths::data_dir(app_handle) else {
+                    return;
+                };
+                if let Err(e) = tauri_plugin_opener::reveal_item_in_dir(&dir) {
+                    log::error!("Failed to reveal {}: {}", dir.display(), e);
+                }
+            }
+        }
+    }
+}
+
+struct Check {
+    name: &'static str,
+    fix: Fix,
+    result: Result<()>,
+}
+
+/// The tray's "Run self-test…": checks everything the app needs and shows the results
+pub fn run(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let checks = run_checks(&app_handle).await;
+        notify(&app_handle, &checks);
+        show_results(&app_handle, &checks);
+    });
+}
+
+/// Runs the checks once after the first launch, only telling the user when something
+/// is missing
+pub fn run_on_first_launch(app_handle: AppHandle) {
+    let Ok(marker) = paths::data_file(&app_handle, MARKER) else {
+        return;
+    };
+    if marker.exists() {
+        return;
+    }
+    spawn(async move {
+        tokio::time::sleep(FIRST_LAUNCH_DELAY).await;
+        let checks = run_checks(&app_handle).await;
+        if checks.iter().any(|check| check.result.is_err()) {
+            notify(&app_handle, &checks);
+        }
+        if let Err(e) = std::fs::write(&marker, "") {
+            log::warn!("Failed to write {}: {e}", marker.display());
+        }
+    });
+}
+
+/// In the order a new install usually runs into them, each result is logged
+async fn run_checks(app_handle: &AppHandle) -> Vec<Check> {
+    log::info!("Running self-test");
+    let checks = vec![
+        Check {
+            name: "Microphone",
+            fix: Fix::MicrophoneSettings,
+            result: check_microphone(app_handle).await,
+        },
+        Check {
+            name: "Accessibility",
+            fix: Fix::AccessibilitySettings,
+            result: check_accessibility(),
+        },
+        Check {
+            name: "Notifications",
+            fix: Fix::NotificationSettings,
+            result: check_notifications(app_handle),
+        },
+        Check {
+            name: "API key",
+            fix: Fix::SettingsWindow,
+            result: check_api_key(app_handle).await,
+        },
+        Check {
+            name: "Shortcuts",
+            fix: Fix::SettingsWindow,
+            result: check_hotkeys(app_handle),
+        },
+        Check {
+            name: "Data folders",
+            fix: Fix::DataDirectory,
+            result: check_data_dir(app_handle),
+        },
+    ];
+    for check in &checks {
+        match &check.result {
+            Ok(()) => log::info!("Self-test: {} ok", check.name),
+            Err(e) => log::warn!("Self-test: {} failed: {e:#}", check.name),
+        }
+    }
+    checks
+}
+
+/// A one second capture that is measured and dropped, never transcribed. macOS hands
+/// silence to apps without microphone access, so all-zero frames fail too.
+async fn check_microphone(app_handle: &AppHandle) -> Result<()> {
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let (tx, rx) = oneshot::channel();
+    tx_task
+        .send(Task::MicTest {
+            duration: MIC_TEST_DURATION,
+            playback: false,
+            tx,
+        })
+        .await?;
+    let report = rx.await??;
+    if report.peak == 0.0 && report.rms == 0.0 {
+        bail!("The input device only delivered silence, microphone access may be denied");
+    }
+    Ok(())
+}
+
+fn check_accessibility() -> Result<()> {
+    if !permissions::input_access_granted() {
+        bail!("Accessibility access has not been granted");
+    }
+    Ok(())
+}
+
+fn check_notifications(app_handle: &AppHandle) -> Result<()> {
+    match app_handle.notification().permission_state()? {
+        PermissionState::Granted => Ok(()),
+        state => bail!("Notification permission is {state:?}"),
+    }
+}
+
+async fn check_api_key(app_handle: &AppHandle) -> Result<()> {
+    let api_key = api_key::load().context("No API key has been entered")?;
+    app_handle
+        .state::<TranscribeClient>()
+        .validate_api_key(&api_key)
+        .await
+}
+
+fn check_hotkeys(app_handle: &AppHandle) -> Result<()> {
+    let Some(shortcuts) = app_handle.try_state::<Mutex<ShortcutsConfig>>() else {
+        bail!("Global shortcuts aren't available on this platform");
+    };
+    let shortcuts = shortcuts.lock().unwrap().all();
+    let global_shortcut = app_handle.global_shortcut();
+    let missing = shortcuts
+        .iter()
+        .filter(|shortcut| !global_shortcut.is_registered(**shortcut))
+        .count();
+    if missing > 0 {
+        bail!("{missing} of {} shortcuts aren't registered", shortcuts.len());
+    }
+    Ok(())
+}
+
+/// Every folder from `paths::ensure` has to take a file
+fn check_data_dir(app_handle: &AppHandle) -> Result<()> {
+    for dir in paths::ensure(app_handle)? {
+        let probe = dir.join(".write-test");
+        std::fs::write(&probe, b"")
+            .map_err(|e| anyhow!("Can't write to {}: {e}", dir.display()))?;
+        _ = std::fs::remove_file(&probe);
+    }
+    Ok(())
+}
+
+/// "5/6 passed", naming what failed
+fn notify(app_handle: &AppHandle, checks: &[Check]) {
+    let failed = checks
+        .iter()
+        .filter(|check| check.result.is_err())
+        .map(|check| check.name.to_string())
+        .collect();
+    AppNotifications::new(app_handle).notify(Notification::SelfTest {
+        passed: checks.iter().filter(|check| check.result.is_ok()).count(),
+        total: checks.len(),
+        failed,
+    });
+}
+
+/// Lists every check, offering to open the fix for the first one that failed
+fn show_results(app_handle: &AppHandle, checks: &[Check]) {
+    let lines: Vec<String> = checks
+        .iter()
+        .map(|check| match &check.result {
+            Ok(()) => format!("✓ {}", check.name),
+            Err(e) => format!("✗ {}: {e:#}", check.name),
+        })
+        .collect();
+    let dialog = app_handle.dialog().message(lines.join("\n")).title("Self-test");
+    let Some(fix) = checks
+        .iter()
+        .find(|check| check.result.is_err())
+        .map(|check| check.fix)
+    else {
+        dialog.show(|_| {});
+        return;
+    };
+    let app_handle = app_handle.clone();
+    dialog
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            fix.label().into(),
+            "Close".into(),
+        ))
+        .show(move |open| {
+            if open {
+                fix.open(&app_handle);
+            }
+        });
+}
@@ -0,0 +1,397 @@
+use crate::activity::Activity;
+use crate::audio_recorder;
+use crate::filler::{self, FillerOutcome};
+use crate::history::{EntryKind, NewEntry};
+use crate::hook::{self, HookPayload};
+use crate::local_task_handler::Task;
+use crate::notifications::{AppNotifications, Notification};
+use crate::selection;
+use crate::session_window;
+use crate::settings::SettingsState;
+use crate::sinks;
+use crate::snippets;
+use crate::stats;
+use crate::transcribe_client::TranscribeClient;
+use crate::transcribe_icon::{Capture, TranscribeIcon};
+use crate::voice_commands;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, Wry, async_runtime::spawn, menu::MenuItem};
+use tokio::sync::{mpsc, oneshot};
+
+pub const SESSION_MENU_ID: &str = "dictation_session";
+const START_TEXT: &str = "Start dictation session";
+const STOP_TEXT: &str = "Stop dictation session";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Audio kept from before speech starts, so the first word isn't clipped
+const LEAD_IN: Duration = Duration::from_millis(300);
+/// How far back the start of a segment is checked for words repeated by the overlap
+const MAX_REPEATED_WORDS: usize = 8;
+
+/// Audio waiting to be transcribed
+struct Segment {
+    bytes: Vec<u8>,
+    /// Starts with the end of the previous segment, which was cut mid-speech
+    overlaps: bool,
+}
+
+/// Hands-free dictation: records until stopped, and every stretch of speech followed
+/// by a pause of `session.silence_ms` is transcribed and delivered in order. Speech
+/// that runs past `session.max_segment_secs` is cut without waiting for a pause.
+pub struct DictationSession {
+    item: MenuItem<Wry>,
+    /// Feeds the transcription worker while a session runs
+    segments: Mutex<Option<mpsc::UnboundedSender<Segment>>>,
+    /// Bumped on every start, so the listener of a stopped session winds down
+    run: AtomicU64,
+    /// Whether the audio since the last cut starts with an overlap
+    overlapping: AtomicBool,
+    /// The run that was cancelled, whose queued segments are dropped
+    cancelled: AtomicU64,
+    /// What this run has delivered so far, kept as a partial transcript on cancel
+    completed: Mutex<Vec<String>>,
+}
+
+impl DictationSession {
+    pub fn new(item: MenuItem<Wry>) -> Self {
+        Self {
+            item,
+            segments: Mutex::new(None),
+            run: AtomicU64::new(0),
+            overlapping: AtomicBool::new(false),
+            cancelled: AtomicU64::new(0),
+            completed: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.segments.lock().unwrap().is_some()
+    }
+
+    fn set_text(&self, text: &str) {
+        if let Err(e) = self.item.set_text(text) {
+            log::error!("Unable to update dictation session menu item: {e}");
+        }
+    }
+}
+
+pub fn toggle(app_handle: &AppHandle) {
+    if app_handle.state::<DictationSession>().is_active() {
+        stop(app_handle);
+    } else {
+        start(app_handle);
+    }
+}
+
+fn start(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let (tx_started, rx_started) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::StartSession(tx_started)).await.is_err() {
+            log::error!("Failed to send 'StartSession' task to channel");
+            return;
+        }
+        match rx_started.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                log::error!("Failed to start dictation session: {e:#}");
+                return;
+            }
+            Err(_) => return,
+        }
+
+        let session = app_handle.state::<DictationSession>();
+        let (segments_tx, segments_rx) = mpsc::unbounded_channel();
+        *session.segments.lock().unwrap() = Some(segments_tx);
+        let run = session.run.fetch_add(1, Ordering::Relaxed) + 1;
+        session.completed.lock().unwrap().clear();
+        session.set_text(STOP_TEXT);
+        app_handle
+            .state::<TranscribeIcon>()
+            .set_capture(Some(Capture::Session));
+        log::info!("Dictation session started");
+        crate::prewarm_connection(&app_handle);
+        if app_handle.state::<SettingsState>().get().session.show_window {
+            if let Err(e) = session_window::open(&app_handle) {
+                log::error!("Failed to open the session window: {e:#}");
+            }
+        }
+
+        spawn(transcribe_segments(app_handle.clone(), segments_rx, run));
+        listen(&app_handle, run).await;
+    });
+}
+
+fn stop(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let session = app_handle.state::<DictationSession>();
+        let Some(segments_tx) = session.segments.lock().unwrap().take() else {
+            return;
+        };
+        session.set_text(START_TEXT);
+        app_handle.state::<TranscribeIcon>().set_capture(None);
+
+        let (tx_stopped, rx_stopped) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::StopSession(tx_stopped)).await.is_err() {
+            log::error!("Failed to send 'StopSession' task to channel");
+            return;
+        }
+        // Whatever was said since the last cut goes after the queued segments. It may
+        // start with the overlap of a cut made mid-speech.
+        if let Ok(Some(bytes)) = rx_stopped.await {
+            let overlaps = session.overlapping.load(Ordering::Relaxed);
+            _ = segments_tx.send(Segment { bytes, overlaps });
+        }
+        log::info!("Dictation session stopped");
+    });
+}
+
+/// Stops without transcribing what was said since the last cut, and drops segments
+/// still waiting. If some of the session was already delivered, it's copied as one
+/// partial transcript, otherwise this is a plain abort.
+pub fn cancel(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let session = app_handle.state::<DictationSession>();
+        if session.segments.lock().unwrap().take().is_none() {
+            return;
+        }
+        let run = session.run.load(Ordering::Relaxed);
+        session.cancelled.store(run, Ordering::Relaxed);
+        session.set_text(START_TEXT);
+        app_handle.state::<TranscribeIcon>().set_capture(None);
+
+        let (tx_stopped, rx_stopped) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::StopSession(tx_stopped)).await.is_err() {
+            log::error!("Failed to send 'StopSession' task to channel");
+            return;
+        }
+        _ = rx_stopped.await;
+
+        let completed = std::mem::take(&mut *session.completed.lock().unwrap());
+        if completed.is_empty() {
+            log::info!("Dictation session cancelled before anything was transcribed");
+            return;
+        }
+        let separator = app_handle.state::<SettingsState>().get().session.separator;
+        log::info!(
+            "Dictation session cancelled after {} segments were transcribed",
+            completed.len()
+        );
+        keep_partial(&app_handle, completed.join(&separator));
+    });
+}
+
+/// Copies what a cancelled dictation transcribed before it was cancelled, and saves
+/// it to history marked as partial
+pub fn keep_partial(app_handle: &AppHandle, text: String) {
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return;
+    }
+    let words = text.split_whitespace().count();
+    if let Err(e) = selection::write_text(app_handle, text.clone(), false) {
+        log::error!("Failed to copy the partial transcript: {e:#}");
+        return;
+    }
+    crate::save_to_history(
+        app_handle,
+        NewEntry {
+            partial: true,
+            ..NewEntry::new(EntryKind::Transcription, text)
+        },
+    );
+    AppNotifications::new(app_handle).notify(Notification::PartialTranscript { words });
+}
+
+/// Watches the input level and cuts a segment once speech is followed by silence, or
+/// has gone on for `session.max_segment_secs`
+async fn listen(app_handle: &AppHandle, run: u64) {
+    let session = app_handle.state::<DictationSession>();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let mut heard_speech = false;
+    let mut quiet_since: Option<Instant> = None;
+    let mut segment_since: Option<Instant> = None;
+    session.overlapping.store(false, Ordering::Relaxed);
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if session.run.load(Ordering::Relaxed) != run || !session.is_active() {
+            return;
+        }
+        let settings = app_handle.state::<SettingsState>().get();
+
+        let (tx_level, rx_level) = oneshot::channel();
+        if tx_task.send(Task::InputLevel(tx_level)).await.is_err() {
+            return;
+        }
+        let Ok(level) = rx_level.await else {
+            return;
+        };
+
+        let max_segment = Duration::from_secs(settings.session.max_segment_secs);
+        let too_long = !max_segment.is_zero()
+            && segment_since.is_some_and(|since| since.elapsed() >= max_segment);
+        if too_long {
+            let overlap = Duration::from_millis(settings.session.overlap_ms);
+            if !cut(app_handle, overlap).await {
+                return;
+            }
+            segment_since = Some(Instant::now());
+            continue;
+        }
+
+        if level >= settings.session.silence_threshold {
+            heard_speech = true;
+            quiet_since = None;
+            segment_since.get_or_insert_with(Instant::now);
+            continue;
+        }
+        if !heard_speech {
+            _ = tx_task.send(Task::TrimSegment(LEAD_IN)).await;
+            continue;
+        }
+        let silence = Duration::from_millis(settings.session.silence_ms);
+        if quiet_since.get_or_insert_with(Instant::now).elapsed() < silence {
+            continue;
+        }
+
+        heard_speech = false;
+        quiet_since = None;
+        segment_since = None;
+        if !cut(app_handle, Duration::ZERO).await {
+            return;
+        }
+    }
+}
+
+/// Queues the audio since the last cut, keeping `overlap` of it for the next segment.
+/// False once the recorder is gone.
+async fn cut(app_handle: &AppHandle, overlap: Duration) -> bool {
+    let session = app_handle.state::<DictationSession>();
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+    let (tx, rx) = oneshot::channel();
+    if tx_task.send(Task::TakeSegment { overlap, tx }).await.is_err() {
+        return false;
+    }
+    // Set before queueing, as it says whether the segment after this one overlaps
+    let overlaps = session.overlapping.swap(!overlap.is_zero(), Ordering::Relaxed);
+    if let Ok(Some(bytes)) = rx.await {
+        log::debug!("Dictation segment of {} bytes", bytes.len());
+        if let Some(segments) = &*session.segments.lock().unwrap() {
+            _ = segments.send(Segment { bytes, overlaps });
+        }
+    }
+    true
+}
+
+/// Transcribes segments one at a time so they're delivered in the order spoken.
+/// Ends once the session is stopped and the last segment is through, or right away
+/// when it's cancelled.
+async fn transcribe_segments(
+    app_handle: AppHandle,
+    mut segments: mpsc::UnboundedReceiver<Segment>,
+    run: u64,
+) {
+    let session = app_handle.state::<DictationSession>();
+    // What the last segment said, to drop what the overlap repeats of it
+    let mut previous = String::new();
+    while let Some(Segment { bytes, overlaps }) = segments.recv().await {
+        let audio_duration = audio_recorder::wav_duration(&bytes);
+        let transcribe_client = app_handle.state::<TranscribeClient>();
+        let transcription = match transcribe_client.fetch_transcription(bytes).await {
+            Ok(transcription) => transcription,
+            Err(e) => {
+                log::error!("Failed to transcribe dictation segment: {e:#}");
+                crate::notify_api_error(&app_handle, &e);
+                continue;
+            }
+        };
+        if session.cancelled.load(Ordering::Relaxed) == run {
+            log::info!("Dropping dictation segments of a cancelled session");
+            return;
+        }
+        app_handle.state::<Activity>().touch();
+
+        let settings = app_handle.state::<SettingsState>().get();
+        // Pauses are expected here, so no "no speech" notification
+        let FillerOutcome::Text(text) =
+            filler::apply(transcription.text, &settings.filler)
+        else {
+            continue;
+        };
+        let text = match overlaps {
+            true => drop_repeated(&previous, &text).to_string(),
+            false => text,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        previous.clone_from(&text);
+
+        stats::record(&app_handle, &text);
+        crate::save_to_history(
+            &app_handle,
+            NewEntry {
+                audio_duration_ms: audio_duration.map(|d| d.as_millis() as u64),
+                provider: Some(transcribe_client.provider()),
+                language: transcription.language.clone(),
+                ..NewEntry::new(EntryKind::Transcription, text.clone())
+            },
+        );
+
+        let text = voice_commands::apply(text, &settings.voice_commands);
+        let text = snippets::expand(text, &settings.snippets);
+        session.completed.lock().unwrap().push(text.clone());
+        let text = format!("{text}{}", settings.session.separator);
+        session_window::append(&app_handle, &text);
+        let payload = HookPayload {
+            duration_ms: audio_duration.map(|d| d.as_millis() as u64),
+            language: transcription.language.clone(),
+            ..HookPayload::new(EntryKind::Transcription, text.clone())
+        };
+        let report = sinks::deliver(&app_handle, text, None, None, false).await;
+        hook::fire(&app_handle, payload);
+        if !report.failures.is_empty() {
+            AppNotifications::new(&app_handle)
+                .notify(Notification::DeliveryFailed(report.failures));
+        }
+    }
+}
+
+/// `text` without the words at its start that repeat the end of `previous`, heard twice
+/// because of the overlap. Words are compared ignoring case and punctuation, and the
+/// longest repeat wins.
+fn drop_repeated<'a>(previous: &str, text: &'a str) -> &'a str {
+    let tail: Vec<String> = previous.split_whitespace().map(normalize).collect();
+    let tail = &tail[tail.len().saturating_sub(MAX_REPEATED_WORDS)..];
+    let head: Vec<String> = text
+        .split_whitespace()
+        .take(MAX_REPEATED_WORDS)
+        .map(normalize)
+        .collect();
+    let repeated = (1..=tail.len().min(head.len()))
+        .rev()
+        .find(|&n| tail[tail.len() - n..] == head[..n])
+        .unwrap_or(0);
+    if repeated > 0 {
+        log::debug!("Dropped {repeated} words repeated by the segment overlap");
+    }
+    let mut rest = text.trim_start();
+    for _ in 0..repeated {
+        rest = rest.trim_start_matches(|c: char| !c.is_whitespace()).trim_start();
+    }
+    rest
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
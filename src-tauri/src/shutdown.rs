@@ -0,0 +1,39 @@
+use crate::history::History;
+use crate::local_task_handler::Task;
+use colored::Colorize;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::sync::{mpsc, oneshot};
+
+/// How long each step may take before quitting anyway
+const STEP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Quits once the recorder has let go of the microphone and history writes are done.
+/// A recording still running is discarded, paused media is resumed.
+pub fn quit(app_handle: &AppHandle, reason: &str) {
+    log::info!("{} application {reason}", "Quitting".red());
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let (tx, rx) = oneshot::channel();
+        let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+        if tx_task.send(Task::Shutdown(tx)).await.is_err() {
+            log::error!("Failed to send 'Shutdown' task to channel");
+        } else if tokio::time::timeout(STEP_TIMEOUT, rx).await.is_err() {
+            log::warn!("The recorder didn't shut down in time");
+        }
+
+        if let Some(history) = app_handle.try_state::<History>() {
+            let pending = history.pending_count();
+            if pending > 0 {
+                log::warn!("{pending} history entries are lost, history is still locked");
+            }
+            match tokio::time::timeout(STEP_TIMEOUT, history.flush()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::error!("Failed to flush history: {e:#}"),
+                Err(_) => log::warn!("History writes didn't finish in time"),
+            }
+        }
+
+        app_handle.exit(0);
+    });
+}
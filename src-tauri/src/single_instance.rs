@@ -0,0 +1,35 @@
+use crate::audio_file;
+use crate::cli;
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::SettingsState;
+use crate::transcribe_icon::TranscribeIcon;
+use tauri::{AppHandle, Manager};
+
+/// Runs in the first instance whenever the app is launched again, e.g. by the login
+/// item and by hand, or by a script passing a command. The second instance exits
+/// right after handing over its arguments.
+pub fn on_second_instance(app_handle: &AppHandle, args: Vec<String>, _cwd: String) {
+    log::info!("Redirected a second launch with {args:?}");
+    if app_handle.try_state::<TranscribeIcon>().is_none() {
+        log::warn!("Still starting up, ignoring the second launch");
+        return;
+    }
+    let files = cli::files(args.clone());
+    if !files.is_empty() {
+        audio_file::open(app_handle, files);
+        return;
+    }
+    match cli::parse(args) {
+        Ok(Some(command)) => cli::run(app_handle, command),
+        Ok(None) => {
+            let settings = app_handle.state::<SettingsState>().get();
+            if settings.behavior.open_window_on_relaunch {
+                crate::open_main_window(app_handle);
+            } else {
+                AppNotifications::new(app_handle).notify(Notification::AlreadyRunning)
+            }
+        }
+        // The second instance already told its caller
+        Err(e) => log::warn!("Ignoring a second launch: {e:#}"),
+    }
+}
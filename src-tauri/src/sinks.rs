@@ -0,0 +1,402 @@
+use crate::audio_recorder::RecordingInfo;
+use crate::frontmost_app;
+use crate::local_task_handler::Task;
+use crate::notifications::{AppNotifications, Notification};
+use crate::quick_note;
+use crate::rich_text;
+use crate::selection;
+use crate::settings::{
+    BehaviorSettings, OutputMode, PasswordFields, ResolvedOutput, SettingsState,
+    SinkKind, SinkSettings, TranscriptHeaderSettings,
+};
+use crate::transcript_header;
+use anyhow::{Result, anyhow};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc;
+
+/// Outcome of running every enabled sink
+#[derive(Debug, Default)]
+pub struct DeliveryReport {
+    /// Set when the `output` sink ran successfully
+    pub mode: Option<OutputMode>,
+    /// One message per sink that failed
+    pub failures: Vec<String>,
+}
+
+/// Counts deliveries to the clipboard, so a transcription and a polish running at the
+/// same time can tell when the other one delivered first
+#[derive(Default)]
+pub struct DeliveryCount {
+    delivered: AtomicU64,
+    /// Held while delivering, so nothing is delivered between the check and the write
+    turn: tokio::sync::Mutex<()>,
+}
+
+/// Taken when a transcription or polish starts
+#[derive(Debug, Clone, Copy)]
+pub struct Ticket(u64);
+
+impl DeliveryCount {
+    pub fn ticket(&self) -> Ticket {
+        Ticket(self.delivered.load(Ordering::SeqCst))
+    }
+
+    /// Whether something else was delivered since `ticket` was taken. The text that
+    /// finished later then stays in history instead of replacing the clipboard and
+    /// being pasted a second time.
+    pub fn superseded(&self, ticket: Ticket) -> bool {
+        self.delivered.load(Ordering::SeqCst) != ticket.0
+    }
+
+    /// Runs `delivery` one at a time with the others, unless something was delivered
+    /// since `ticket`. Without a ticket it always runs. It counts once it reached the
+    /// output sink.
+    pub async fn deliver(
+        &self,
+        ticket: Option<Ticket>,
+        delivery: impl Future<Output = DeliveryReport>,
+    ) -> Option<DeliveryReport> {
+        let _turn = self.turn.lock().await;
+        if ticket.is_some_and(|ticket| self.superseded(ticket)) {
+            return None;
+        }
+        let report = delivery.await;
+        if report.mode.is_some() {
+            self.delivered.fetch_add(1, Ordering::SeqCst);
+        }
+        Some(report)
+    }
+}
+
+pub fn ticket(app_handle: &AppHandle) -> Ticket {
+    app_handle.state::<DeliveryCount>().ticket()
+}
+
+pub fn superseded(app_handle: &AppHandle, ticket: Ticket) -> bool {
+    app_handle.state::<DeliveryCount>().superseded(ticket)
+}
+
+/// Like `deliver`, but `None` without delivering anything when something else was
+/// delivered since `ticket`
+pub async fn deliver_unless_superseded(
+    app_handle: &AppHandle,
+    ticket: Ticket,
+    text: String,
+    forced_mode: Option<OutputMode>,
+    recording: Option<&RecordingInfo>,
+    rich_text: bool,
+) -> Option<DeliveryReport> {
+    let delivery = run_sinks(app_handle, text, forced_mode, recording, rich_text);
+    app_handle
+        .state::<DeliveryCount>()
+        .deliver(Some(ticket), delivery)
+        .await
+}
+
+/// Hands the text to every enabled sink in order. A failing sink is logged and
+/// skipped so the remaining ones still run. `forced_mode` overrides the output mode
+/// that would otherwise be resolved for the frontmost app. `recording` describes the
+/// recording `text` was transcribed from, for `[transcript_header]`. `rich_text`
+/// copies formatted text as HTML too, for polished text only.
+pub async fn deliver(
+    app_handle: &AppHandle,
+    text: String,
+    forced_mode: Option<OutputMode>,
+    recording: Option<&RecordingInfo>,
+    rich_text: bool,
+) -> DeliveryReport {
+    let delivery = run_sinks(app_handle, text, forced_mode, recording, rich_text);
+    let report = app_handle.state::<DeliveryCount>().deliver(None, delivery).await;
+    report.expect("only a ticket can be superseded")
+}
+
+async fn run_sinks(
+    app_handle: &AppHandle,
+    text: String,
+    forced_mode: Option<OutputMode>,
+    recording: Option<&RecordingInfo>,
+    rich_text: bool,
+) -> DeliveryReport {
+    let settings = app_handle.state::<SettingsState>().get();
+    let app_id = frontmost_app::frontmost_app();
+    let output = settings.resolve_output(app_id.as_deref());
+    let mut mode = forced_mode.unwrap_or(output.mode);
+    if into_password_field(&settings.behavior, mode) {
+        log::warn!(
+            "The focused field is a password field, leaving the text on the clipboard"
+        );
+        AppNotifications::new(app_handle).notify(Notification::PasswordField);
+        mode = OutputMode::Clipboard;
+    }
+
+    log::info!(
+        "Delivering to {} with {:?} ({})",
+        app_id.as_deref().unwrap_or("unknown app"),
+        mode,
+        match &output.matched_app {
+            Some(app) => format!("override for {app}"),
+            None => "global settings".into(),
+        }
+    );
+
+    let mut report = DeliveryReport::default();
+
+    for sink in settings.sinks.iter().filter(|sink| sink.enabled) {
+        let text =
+            with_header(&settings.transcript_header, sink.kind, mode, recording, &text);
+        let result = match sink.kind {
+            SinkKind::Output => {
+                output_sink(app_handle, &text, mode, &output, rich_text).await
+            }
+            SinkKind::AppendFile => append_file_sink(sink, &text),
+            SinkKind::Webhook => webhook_sink(sink, &text).await,
+        };
+        match result {
+            Ok(()) if sink.kind == SinkKind::Output => report.mode = Some(mode),
+            Ok(()) => log::info!("Delivered to {:?} sink", sink.kind),
+            Err(e) => {
+                log::error!("{:?} sink failed: {e:#}", sink.kind);
+                report.failures.push(format!("{:?}: {e:#}", sink.kind));
+            }
+        }
+    }
+
+    report
+}
+
+/// Whether `mode` would paste or type into a password field that
+/// `behavior.password_fields` keeps text out of
+pub fn into_password_field(behavior: &BehaviorSettings, mode: OutputMode) -> bool {
+    behavior.password_fields == PasswordFields::Clipboard
+        && matches!(mode, OutputMode::Paste | OutputMode::Type)
+        && frontmost_app::secure_field_focused()
+}
+
+/// Prepends the header for the targets `[transcript_header]` turns it on for
+fn with_header(
+    header: &TranscriptHeaderSettings,
+    kind: SinkKind,
+    mode: OutputMode,
+    recording: Option<&RecordingInfo>,
+    text: &str,
+) -> String {
+    let wanted = match kind {
+        SinkKind::Output if mode == OutputMode::QuickNote => header.quick_note,
+        SinkKind::Output => header.output,
+        SinkKind::AppendFile => header.append_file,
+        SinkKind::Webhook => false,
+    };
+    match recording {
+        Some(info) if wanted => {
+            format!("{}\n{text}", transcript_header::render(&header.format, info))
+        }
+        _ => text.to_string(),
+    }
+}
+
+async fn output_sink(
+    app_handle: &AppHandle,
+    text: &str,
+    mode: OutputMode,
+    output: &ResolvedOutput,
+    rich_text: bool,
+) -> Result<()> {
+    if mode == OutputMode::QuickNote {
+        quick_note_output(app_handle, text)?;
+        return Ok(());
+    }
+    let text = format!("{text}{}", output.trailing_separator);
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+
+    match mode {
+        OutputMode::Clipboard if rich_text => rich_text::write(app_handle, text, false)?,
+        OutputMode::Clipboard => selection::write_text(app_handle, text, false)?,
+        OutputMode::Paste => {
+            match rich_text {
+                true => rich_text::write(app_handle, text, true)?,
+                false => selection::write_text(app_handle, text, true)?,
+            }
+            tx_task
+                .send(Task::PasteFromClipboard)
+                .await
+                .map_err(|_| anyhow!("Local task handler is gone"))?;
+        }
+        OutputMode::Type => tx_task
+            .send(Task::TypeText(text))
+            .await
+            .map_err(|_| anyhow!("Local task handler is gone"))?,
+        OutputMode::QuickNote => unreachable!("handled above"),
+    }
+
+    Ok(())
+}
+
+/// Appends to the quick note file, copying the text instead if that fails so it isn't
+/// lost
+fn quick_note_output(app_handle: &AppHandle, text: &str) -> Result<()> {
+    let settings = app_handle.state::<SettingsState>().get().quick_note.clone();
+    let notifications = AppNotifications::new(app_handle);
+    match quick_note::append(&settings, text) {
+        Ok(path) => {
+            log::info!("Appended to quick note {}", path.display());
+            notifications.notify(Notification::QuickNoteSaved {
+                file: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string()),
+            });
+        }
+        Err(e) => {
+            log::error!("Failed to append to quick note: {e:#}");
+            selection::write_text(app_handle, text.to_string(), false)?;
+            notifications.notify(Notification::QuickNoteFailed(format!("{e:#}")));
+        }
+    }
+    Ok(())
+}
+
+fn append_file_sink(sink: &SinkSettings, text: &str) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&sink.target)?;
+    writeln!(file, "{text}")?;
+    Ok(())
+}
+
+async fn webhook_sink(sink: &SinkSettings, text: &str) -> Result<()> {
+    reqwest::Client::new()
+        .post(&sink.target)
+        .json(&serde_json::json!({ "text": text }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// What a clipboard would hold and what was pasted from it
+    #[derive(Default)]
+    struct MockClipboard {
+        text: Mutex<Option<String>>,
+        pasted: Mutex<Vec<String>>,
+    }
+
+    impl MockClipboard {
+        /// What the output sink does, letting the other pipeline run in the middle
+        async fn output(&self, text: &str, mode: OutputMode) -> DeliveryReport {
+            *self.text.lock().unwrap() = Some(text.to_string());
+            tokio::task::yield_now().await;
+            if mode == OutputMode::Paste {
+                self.pasted.lock().unwrap().push(text.to_string());
+            }
+            DeliveryReport {
+                mode: Some(mode),
+                failures: vec![],
+            }
+        }
+
+        fn text(&self) -> Option<String> {
+            self.text.lock().unwrap().clone()
+        }
+
+        fn pasted(&self) -> Vec<String> {
+            self.pasted.lock().unwrap().clone()
+        }
+    }
+
+    async fn deliver(
+        count: &DeliveryCount,
+        clipboard: &MockClipboard,
+        ticket: Option<Ticket>,
+        text: &str,
+    ) -> bool {
+        let delivery = clipboard.output(text, OutputMode::Paste);
+        count.deliver(ticket, delivery).await.is_some()
+    }
+
+    #[tokio::test]
+    async fn a_transcription_during_a_polish_wins() {
+        let (count, clipboard) = (DeliveryCount::default(), MockClipboard::default());
+        let polish = count.ticket();
+        let transcription = count.ticket();
+        assert!(deliver(&count, &clipboard, Some(transcription), "spoken").await);
+        assert!(count.superseded(polish));
+        assert!(!deliver(&count, &clipboard, Some(polish), "polished").await);
+        assert_eq!(clipboard.text().as_deref(), Some("spoken"));
+        assert_eq!(clipboard.pasted(), ["spoken"]);
+    }
+
+    #[tokio::test]
+    async fn a_polish_during_a_transcription_wins() {
+        let (count, clipboard) = (DeliveryCount::default(), MockClipboard::default());
+        let transcription = count.ticket();
+        let polish = count.ticket();
+        assert!(deliver(&count, &clipboard, Some(polish), "polished").await);
+        assert!(!deliver(&count, &clipboard, Some(transcription), "spoken").await);
+        assert_eq!(clipboard.text().as_deref(), Some("polished"));
+        assert_eq!(clipboard.pasted(), ["polished"]);
+    }
+
+    #[tokio::test]
+    async fn one_after_the_other_both_deliver() {
+        let (count, clipboard) = (DeliveryCount::default(), MockClipboard::default());
+        let transcription = count.ticket();
+        assert!(deliver(&count, &clipboard, Some(transcription), "spoken").await);
+        let polish = count.ticket();
+        assert!(!count.superseded(polish));
+        assert!(deliver(&count, &clipboard, Some(polish), "polished").await);
+        assert_eq!(clipboard.text().as_deref(), Some("polished"));
+        assert_eq!(clipboard.pasted(), ["spoken", "polished"]);
+    }
+
+    #[tokio::test]
+    async fn finishing_together_only_one_delivers() {
+        let (count, clipboard) = (DeliveryCount::default(), MockClipboard::default());
+        let transcription = count.ticket();
+        let polish = count.ticket();
+        // Both passed the early check before either wrote the clipboard
+        assert!(!count.superseded(transcription) && !count.superseded(polish));
+        let (spoken, polished) = tokio::join!(
+            deliver(&count, &clipboard, Some(transcription), "spoken"),
+            deliver(&count, &clipboard, Some(polish), "polished"),
+        );
+        assert!(spoken != polished);
+        let winner = if spoken { "spoken" } else { "polished" };
+        assert_eq!(clipboard.text().as_deref(), Some(winner));
+        assert_eq!(clipboard.pasted(), [winner]);
+    }
+
+    #[tokio::test]
+    async fn a_failed_delivery_supersedes_nothing() {
+        let (count, clipboard) = (DeliveryCount::default(), MockClipboard::default());
+        let transcription = count.ticket();
+        let polish = count.ticket();
+        let failed = async {
+            DeliveryReport {
+                mode: None,
+                failures: vec!["Output: no clipboard".into()],
+            }
+        };
+        assert!(count.deliver(Some(polish), failed).await.is_some());
+        assert!(!count.superseded(transcription));
+        assert!(deliver(&count, &clipboard, Some(transcription), "spoken").await);
+        assert_eq!(clipboard.pasted(), ["spoken"]);
+    }
+
+    #[tokio::test]
+    async fn deliveries_without_a_ticket_always_run_and_count() {
+        let (count, clipboard) = (DeliveryCount::default(), MockClipboard::default());
+        let polish = count.ticket();
+        assert!(deliver(&count, &clipboard, None, "file transcript").await);
+        assert!(deliver(&count, &clipboard, None, "session").await);
+        assert!(!deliver(&count, &clipboard, Some(polish), "polished").await);
+        assert_eq!(clipboard.pasted(), ["file transcript", "session"]);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::settings::SnippetSettings;
+
+/// Replaces every spoken trigger phrase with its snippet. Triggers match whole words,
+/// ignoring case, and the longest trigger wins where several overlap.
+pub fn expand(text: String, snippets: &SnippetSettings) -> String {
+    if !snippets.enabled || snippets.phrases.is_empty() {
+        return text;
+    }
+
+    let mut triggers: Vec<(Vec<char>, &str)> = snippets
+        .phrases
+        .iter()
+        .filter(|(trigger, _)| !trigger.trim().is_empty())
+        .map(|(trigger, snippet)| (lowercase(trigger.trim()), snippet.as_str()))
+        .collect();
+    triggers.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+
+    let chars: Vec<char> = text.chars().collect();
+    let lower = lowercase(&text);
+    let mut expanded = String::with_capacity(text.len());
+    let mut count = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let at_word_start = i == 0 || !chars[i - 1].is_alphanumeric();
+        let matched = at_word_start
+            .then(|| {
+                triggers.iter().find(|(trigger, _)| {
+                    lower[i..].starts_with(trigger)
+                        && chars
+                            .get(i + trigger.len())
+                            .is_none_or(|c| !c.is_alphanumeric())
+                })
+            })
+            .flatten();
+        match matched {
+            Some((trigger, snippet)) => {
+                expanded.push_str(snippet);
+                i += trigger.len();
+                count += 1;
+            }
+            None => {
+                expanded.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    if count > 0 {
+        log::debug!("Expanded {count} snippets");
+    }
+    expanded
+}
+
+/// One char per char, so indices line up with the original text
+fn lowercase(text: &str) -> Vec<char> {
+    text.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect()
+}
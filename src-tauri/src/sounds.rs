@@ -0,0 +1,106 @@
+use crate::settings::{SettingsState, SoundSettings};
+use anyhow::{Context, Result};
+use rodio::{Decoder, OutputStream, Sink};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cue {
+    Start,
+    Stop,
+    Success,
+    Error,
+}
+
+impl Cue {
+    const ALL: [Cue; 4] = [Cue::Start, Cue::Stop, Cue::Success, Cue::Error];
+
+    fn bundled(self) -> &'static [u8] {
+        match self {
+            Cue::Start => include_bytes!("../sounds/start.wav"),
+            Cue::Stop => include_bytes!("../sounds/stop.wav"),
+            Cue::Success => include_bytes!("../sounds/success.wav"),
+            Cue::Error => include_bytes!("../sounds/error.wav"),
+        }
+    }
+
+    fn custom_path(self, settings: &SoundSettings) -> &str {
+        match self {
+            Cue::Start => &settings.start,
+            Cue::Stop => &settings.stop,
+            Cue::Success => &settings.success,
+            Cue::Error => &settings.error,
+        }
+    }
+}
+
+/// The audio for every cue, custom files that decoded at load time or the bundled ones
+#[derive(Default)]
+pub struct SoundCues {
+    sounds: Mutex<HashMap<Cue, Arc<[u8]>>>,
+}
+
+/// Reads the configured sound files, falling back to the bundled sound for any that is
+/// missing or can't be decoded. Runs at startup and whenever `[sounds]` changes.
+pub fn load(app_handle: &AppHandle) {
+    let settings = app_handle.state::<SettingsState>().get().sounds.clone();
+    let sounds = Cue::ALL
+        .into_iter()
+        .map(|cue| {
+            let path = cue.custom_path(&settings);
+            if path.is_empty() {
+                return (cue, Arc::from(cue.bundled()));
+            }
+            match read_playable(path) {
+                Ok(bytes) => {
+                    log::info!("Using {path:?} for the {cue:?} sound");
+                    (cue, bytes)
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Using the built-in {cue:?} sound, {path:?} failed: {e:#}"
+                    );
+                    (cue, Arc::from(cue.bundled()))
+                }
+            }
+        })
+        .collect();
+    *app_handle.state::<SoundCues>().sounds.lock().unwrap() = sounds;
+}
+
+fn read_playable(path: &str) -> Result<Arc<[u8]>> {
+    let bytes: Arc<[u8]> = std::fs::read(path)?.into();
+    Decoder::new(Cursor::new(bytes.clone())).context("Not a playable audio file")?;
+    Ok(bytes)
+}
+
+/// Plays `cue` on the default output device without waiting for it to finish
+pub fn play(app_handle: &AppHandle, cue: Cue) {
+    let settings = app_handle.state::<SettingsState>().get();
+    if !settings.sounds.enabled {
+        return;
+    }
+    let volume = settings.sounds.volume;
+    let Some(cues) = app_handle.try_state::<SoundCues>() else {
+        return;
+    };
+    let Some(bytes) = cues.sounds.lock().unwrap().get(&cue).cloned() else {
+        return;
+    };
+    // The output stream can't leave the thread that opened it
+    std::thread::spawn(move || {
+        let result = (|| -> Result<()> {
+            let (_stream, handle) = OutputStream::try_default()?;
+            let sink = Sink::try_new(&handle)?;
+            sink.set_volume(volume);
+            sink.append(Decoder::new(Cursor::new(bytes))?);
+            sink.sleep_until_end();
+            Ok(())
+        })();
+        if let Err(e) = result {
+            log::warn!("Failed to play the {cue:?} sound: {e:#}");
+        }
+    });
+}
@@ -0,0 +1,100 @@
+use crate::collect::Collector;
+use crate::history::History;
+use crate::transcribe_icon::TranscribeIcon;
+use crate::wake_word;
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stats {
+    pub words_today: u64,
+    pub total_words: u64,
+    /// Consecutive days with at least one transcription, ending today or yesterday
+    pub streak_days: u32,
+}
+
+pub async fn load(history: &History) -> Result<Stats> {
+    let days = history.word_days().await?;
+    Ok(compute(&days, Local::now().date_naive()))
+}
+
+/// `days` must be newest first
+fn compute(days: &[(NaiveDate, u64)], today: NaiveDate) -> Stats {
+    let words_today = days
+        .iter()
+        .find(|(day, _)| *day == today)
+        .map_or(0, |(_, words)| *words);
+
+    // Today doesn't break the streak until it's over
+    let mut expected = if words_today > 0 {
+        Some(today)
+    } else {
+        today.pred_opt()
+    };
+    let mut streak_days = 0;
+    for (day, _) in days.iter().filter(|(_, words)| *words > 0) {
+        if Some(*day) > expected {
+            continue;
+        }
+        if Some(*day) != expected {
+            break;
+        }
+        streak_days += 1;
+        expected = day.pred_opt();
+    }
+
+    Stats {
+        words_today,
+        total_words: days.iter().map(|(_, words)| words).sum(),
+        streak_days,
+    }
+}
+
+/// Counts the words of a successful transcription towards the local day it finished on
+pub fn record(app_handle: &AppHandle, text: &str) {
+    let words = text.split_whitespace().count() as u64;
+    if words == 0 {
+        return;
+    }
+    let today = Local::now().date_naive();
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        if let Err(e) = history.add_words(today, words).await {
+            log::error!("Failed to record word count: {e:#}");
+            return;
+        }
+        refresh_tooltip(app_handle);
+    });
+}
+
+/// Shows what's been collected instead while collecting
+pub fn refresh_tooltip(app_handle: AppHandle) {
+    spawn(async move {
+        let history = app_handle.state::<History>().inner().clone();
+        let stats = match load(&history).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                log::error!("Failed to load stats: {e:#}");
+                return;
+            }
+        };
+        let mut tooltip = format!("Whistle - {} words today", stats.words_today);
+        if stats.streak_days > 1 {
+            tooltip.push_str(&format!(", {}-day streak", stats.streak_days));
+        }
+        if let Some(phrase) = wake_word::listening_for(&app_handle) {
+            tooltip.push_str(&format!(" - listening for \"{phrase}\""));
+        }
+        // Checked last, so a tooltip computed before collecting started can't win
+        if let Some(status) = app_handle
+            .try_state::<Collector>()
+            .and_then(|collector| collector.status())
+        {
+            tooltip = status;
+        }
+        app_handle.state::<TranscribeIcon>().set_tooltip(&tooltip);
+    });
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use tauri::{
+    AppHandle, Wry,
+    menu::{MenuItem, Submenu},
+};
+
+pub const TAG_ID_PREFIX: &str = "tag:";
+
+/// The tray's "Tag last entry" submenu, one item per `behavior.quick_tags`
+pub struct TagMenu(Submenu<Wry>);
+
+impl TagMenu {
+    pub fn new(submenu: Submenu<Wry>) -> Self {
+        Self(submenu)
+    }
+
+    pub fn refresh(&self, app_handle: &AppHandle, tags: &[String]) {
+        if let Err(e) = self.refresh_(app_handle, tags) {
+            log::error!("Unable to refresh tag menu: {e}");
+        }
+    }
+
+    fn refresh_(&self, app_handle: &AppHandle, tags: &[String]) -> Result<()> {
+        for item in self.0.items()? {
+            self.0.remove(&item)?;
+        }
+
+        if tags.is_empty() {
+            self.0.append(&MenuItem::new(
+                app_handle,
+                "Add tags to behavior.quick_tags",
+                false,
+                None::<&str>,
+            )?)?;
+            return Ok(());
+        }
+
+        for tag in tags {
+            self.0.append(&MenuItem::with_id(
+                app_handle,
+                format!("{TAG_ID_PREFIX}{tag}"),
+                tag,
+                true,
+                None::<&str>,
+            )?)?;
+        }
+
+        Ok(())
+    }
+}
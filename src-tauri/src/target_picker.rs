@@ -0,0 +1,110 @@
+use crate::frontmost_app::{self, RunningApp};
+use anyhow::{Result, bail};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+use tokio::sync::oneshot;
+
+pub const TARGET_WINDOW: &str = "target";
+/// Carries the apps to pick from each time the picker opens
+const TARGET_APPS_EVENT: &str = "target-apps";
+/// A picker nobody answers counts as cancelled
+const PICK_TIMEOUT: Duration = Duration::from_secs(60);
+/// Lets the chosen app take focus before the paste lands in it
+const ACTIVATE_DELAY: Duration = Duration::from_millis(200);
+
+/// The open picker's choices and who is waiting for the answer
+#[derive(Default)]
+pub struct TargetPicker {
+    apps: Mutex<Vec<RunningApp>>,
+    pending: Mutex<Option<oneshot::Sender<Option<String>>>>,
+}
+
+impl TargetPicker {
+    pub fn apps(&self) -> Vec<RunningApp> {
+        self.apps.lock().unwrap().clone()
+    }
+}
+
+/// With `behavior.pick_target`, asks which app the transcript goes to and brings it
+/// to the front. `Ok(false)` means the user cancelled, so nothing should be pasted.
+/// An error means there was nothing to pick from and delivery goes ahead as usual.
+pub async fn pick_and_focus(app_handle: &AppHandle) -> Result<bool> {
+    let mut apps = frontmost_app::running_apps();
+    if apps.is_empty() {
+        bail!("No running apps to pick from");
+    }
+    // The app the user was in comes first, so Enter keeps the usual behavior
+    if let Some(current) = frontmost_app::frontmost_app() {
+        if let Some(n) = apps.iter().position(|app| app.id == current) {
+            let app = apps.remove(n);
+            apps.insert(0, app);
+        }
+    }
+    // Our own windows can't take the paste
+    let own_id = app_handle.config().identifier.clone();
+    apps.retain(|app| app.id != own_id);
+
+    let picker = app_handle.state::<TargetPicker>();
+    let (tx, rx) = oneshot::channel();
+    *picker.apps.lock().unwrap() = apps;
+    // A picker that is still open from an earlier recording is answered as cancelled
+    if let Some(previous) = picker.pending.lock().unwrap().replace(tx) {
+        _ = previous.send(None);
+    }
+    open(app_handle)?;
+
+    let chosen = match tokio::time::timeout(PICK_TIMEOUT, rx).await {
+        Ok(Ok(chosen)) => chosen,
+        _ => None,
+    };
+    close(app_handle);
+    let Some(id) = chosen else {
+        log::info!("Target picker cancelled");
+        return Ok(false);
+    };
+    log::info!("Delivering into picked app {id}");
+    frontmost_app::activate(&id)?;
+    tokio::time::sleep(ACTIVATE_DELAY).await;
+    Ok(true)
+}
+
+/// Answers the open picker. `None` cancels it.
+pub fn choose(app_handle: &AppHandle, id: Option<String>) {
+    if let Some(tx) = app_handle.state::<TargetPicker>().pending.lock().unwrap().take() {
+        _ = tx.send(id);
+    }
+}
+
+/// A small always-on-top list that takes focus, so it can be driven by the keyboard
+fn open(app_handle: &AppHandle) -> Result<()> {
+    let window = match app_handle.get_webview_window(TARGET_WINDOW) {
+        Some(window) => window,
+        None => WebviewWindowBuilder::new(
+            app_handle,
+            TARGET_WINDOW,
+            WebviewUrl::App("index.html?view=target".into()),
+        )
+        .title("Send to…")
+        .inner_size(320.0, 360.0)
+        .always_on_top(true)
+        .resizable(false)
+        .center()
+        .build()?,
+    };
+    let apps = app_handle.state::<TargetPicker>().apps();
+    if let Err(e) = app_handle.emit_to(TARGET_WINDOW, TARGET_APPS_EVENT, apps) {
+        log::error!("Failed to emit {TARGET_APPS_EVENT}: {e}");
+    }
+    window.show()?;
+    window.set_focus()?;
+    Ok(())
+}
+
+fn close(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window(TARGET_WINDOW) {
+        if let Err(e) = window.hide() {
+            log::error!("Failed to hide the target picker: {e}");
+        }
+    }
+}
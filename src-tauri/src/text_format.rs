@@ -0,0 +1,97 @@
+use crate::settings::BehaviorSettings;
+
+/// What kind of text is being polished, sent along so its formatting is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Plain,
+    Markdown,
+    Code,
+}
+
+impl TextFormat {
+    /// The `format` hint sent to the backend, `None` for plain text
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            TextFormat::Plain => None,
+            TextFormat::Markdown => Some("markdown"),
+            TextFormat::Code => Some("code"),
+        }
+    }
+
+    /// Added to a custom prompt, which replaces the backend's own instructions
+    pub fn instruction(self) -> Option<&'static str> {
+        match self {
+            TextFormat::Plain => None,
+            TextFormat::Markdown => Some(
+                "The text is Markdown. Keep its headings, lists, links, emphasis and \
+                 code blocks exactly as they are, and leave code inside fences untouched.",
+            ),
+            TextFormat::Code => Some(
+                "The text is source code. Only change comments and string contents, \
+                 keep the code, indentation and line breaks exactly as they are.",
+            ),
+        }
+    }
+}
+
+/// The format to tell the backend about, `Plain` unless `behavior.preserve_formatting`
+/// is on
+pub fn for_polish(behavior: &BehaviorSettings, text: &str) -> TextFormat {
+    if !behavior.preserve_formatting {
+        return TextFormat::Plain;
+    }
+    let format = detect(text);
+    if format != TextFormat::Plain {
+        log::info!("Polishing as {format:?}, formatting will be kept");
+    }
+    format
+}
+
+/// A rough guess from line shapes. Fences, headings and quotes are Markdown on sight,
+/// list items need two lines since a lone "- " is often just a dash. Code is text whose
+/// lines mostly end in a brace or semicolon or are indented.
+pub fn detect(text: &str) -> TextFormat {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    let mut list_items = 0;
+    let mut code_like = 0;
+    for line in &lines {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```")
+            || trimmed.starts_with("~~~")
+            || trimmed.starts_with("> ")
+            || is_heading(trimmed)
+        {
+            return TextFormat::Markdown;
+        }
+        if is_list_item(trimmed) {
+            list_items += 1;
+        }
+        let end = line.trim_end();
+        if end.ends_with(['{', '}', ';'])
+            || line.starts_with("    ")
+            || line.starts_with('\t')
+        {
+            code_like += 1;
+        }
+    }
+    if list_items >= 2 || text.contains("](") {
+        return TextFormat::Markdown;
+    }
+    if lines.len() >= 2 && code_like * 2 >= lines.len() {
+        return TextFormat::Code;
+    }
+    TextFormat::Plain
+}
+
+fn is_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+}
+
+fn is_list_item(line: &str) -> bool {
+    if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+        return true;
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") "))
+}
@@ -1,4 +1,48 @@
+use crate::settings::{LogFormat, LoggingSettings, SettingsState};
+use anyhow::Result;
+use colored::Colorize;
 use env_logger::{Builder, WriteStyle};
+use flate2::{Compression, write::GzEncoder};
+use log::kv::{Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+pub const LOG_FILE: &str = "whistle.log";
+/// Rotated files are named `whistle-<timestamp>.log.gz`, so they sort by age
+const ARCHIVE_PREFIX: &str = "whistle-";
+pub const ARCHIVE_SUFFIX: &str = ".log.gz";
+const MB: u64 = 1024 * 1024;
+
+/// Chatty dependencies, capped regardless of `logging.level`
+pub const QUIET_TARGETS: [(&str, LevelFilter); 3] = [
+    ("enigo", LevelFilter::Error),
+    ("reqwest", LevelFilter::Info),
+    ("hyper", LevelFilter::Info),
+];
+
+/// Only the size of `text`, e.g. `«87 words, 412 chars»`
+pub fn redact(text: &str) -> String {
+    format!(
+        "«{} words, {} chars»",
+        text.split_whitespace().count(),
+        text.chars().count()
+    )
+}
+
+/// Transcript or clipboard text as it should appear in logs. Redacted unless
+/// `logging.log_transcripts` is on, and always in privacy mode.
+pub fn loggable(app_handle: &AppHandle, text: &str) -> String {
+    let settings = app_handle.state::<SettingsState>().get();
+    if settings.logging.log_transcripts && !settings.behavior.privacy_mode {
+        text.to_string()
+    } else {
+        redact(text)
+    }
+}
 
 pub fn init(log_level: log::LevelFilter) {
     Builder::new()
@@ -21,3 +65,303 @@ pub fn init(log_level: log::LevelFilter) {
         .write_style(WriteStyle::Always)
         .init();
 }
+
+/// Hands every record to the plugin's console logger and to the log file. The file
+/// sees the original record, the plugin's own formatting would drop its key-value
+/// fields.
+pub struct Logger {
+    pub console: Box<dyn Log>,
+    pub file: RotatingLog,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        !QUIET_TARGETS.iter().any(|(target, level)| {
+            metadata.target().starts_with(target) && metadata.level() > *level
+        })
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.console.log(record);
+        self.file.write_record(record);
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        self.file.flush();
+    }
+}
+
+/// Console line, with the level colored in dev builds
+pub fn console_line(message: &std::fmt::Arguments, record: &Record) -> String {
+    let level = record.level().to_string();
+    let level = if cfg!(debug_assertions) {
+        match record.level() {
+            Level::Error => level.red(),
+            Level::Warn => level.yellow(),
+            Level::Info => level.green(),
+            Level::Debug => level.blue(),
+            Level::Trace => level.normal(),
+        }
+        .to_string()
+    } else {
+        level
+    };
+    format!(
+        "[{}][{}][{level}] {message}{}",
+        chrono::Local::now().format("%H:%M:%S"),
+        record.target(),
+        text_fields(record)
+    )
+}
+
+/// `[date][time][target][LEVEL] message key=value`, the format the log viewer parses
+fn text_line(record: &Record) -> String {
+    // Multi-line text stays on one line, so every line of the file is a whole record
+    // and scrubbing one removes all of it
+    let message = escape_newlines(&format!("{}{}", record.args(), text_fields(record)));
+    format!(
+        "{}[{}][{}] {message}\n",
+        chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+        record.target(),
+        record.level(),
+    )
+}
+
+fn escape_newlines(text: &str) -> String {
+    text.replace('\r', "\\r").replace('\n', "\\n")
+}
+
+/// One JSON object per line. Fields sit next to the fixed keys but never replace them.
+fn json_line(record: &Record) -> String {
+    let mut line = serde_json::Map::new();
+    line.insert("timestamp".into(), chrono::Local::now().to_rfc3339().into());
+    line.insert("level".into(), record.level().as_str().into());
+    line.insert("target".into(), record.target().into());
+    line.insert("message".into(), strip_ansi(&record.args().to_string()).into());
+    for (key, value) in fields(record) {
+        line.entry(key).or_insert(value);
+    }
+    format!("{}\n", serde_json::Value::Object(line))
+}
+
+fn text_fields(record: &Record) -> String {
+    fields(record)
+        .into_iter()
+        .map(|(key, value)| format!(" {key}={value}"))
+        .collect()
+}
+
+/// Key-value pairs passed like `log::info!(stage = "transcribe"; "...")`
+fn fields(record: &Record) -> Vec<(String, serde_json::Value)> {
+    let mut fields = Fields::default();
+    if let Err(e) = record.key_values().visit(&mut fields) {
+        eprintln!("Failed to read log fields: {e}");
+    }
+    fields.0
+}
+
+#[derive(Default)]
+struct Fields(Vec<(String, serde_json::Value)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(
+        &mut self,
+        key: Key<'kvs>,
+        value: Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            value.into()
+        } else if let Some(value) = value.to_u64() {
+            value.into()
+        } else if let Some(value) = value.to_i64() {
+            value.into()
+        } else if let Some(number) = value.to_f64().and_then(serde_json::Number::from_f64)
+        {
+            number.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+/// Drops terminal color codes, e.g. from `.yellow()` in messages
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip up to and including the final letter of the sequence
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// File log target that starts a new file past `logging.max_file_mb`, gzips the old
+/// one in the background and keeps the newest `logging.keep_files`. Lines logged
+/// before `configure` are held in memory so startup isn't lost.
+#[derive(Clone, Default)]
+pub struct RotatingLog(Arc<Mutex<RotatingState>>);
+
+#[derive(Default)]
+struct RotatingState {
+    dir: Option<PathBuf>,
+    file: Option<File>,
+    written: u64,
+    max_bytes: u64,
+    keep_files: usize,
+    format: LogFormat,
+    /// Rotation waits for the current line to be finished, so none is split
+    mid_line: bool,
+    early: Vec<u8>,
+}
+
+impl RotatingLog {
+    /// Opens the log in `dir` on first call, later calls only change the limits and
+    /// line format
+    pub fn configure(&self, dir: PathBuf, logging: &LoggingSettings) -> Result<()> {
+        let mut state = self.0.lock().unwrap();
+        state.max_bytes = logging.max_file_mb * MB;
+        state.keep_files = logging.keep_files;
+        state.format = logging.format;
+        if state.dir.as_ref() == Some(&dir) {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&dir)?;
+        let file = open_log(&dir)?;
+        state.written = file.metadata()?.len();
+        state.file = Some(file);
+        state.dir = Some(dir);
+        let early = std::mem::take(&mut state.early);
+        state.write_bytes(&early)?;
+        Ok(())
+    }
+
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.0.lock().unwrap().dir.clone()
+    }
+
+    /// Writes one line in the configured format
+    fn write_record(&self, record: &Record) {
+        let mut state = self.0.lock().unwrap();
+        let line = match state.format {
+            LogFormat::Text => text_line(record),
+            LogFormat::Json => json_line(record),
+        };
+        if state.file.is_none() {
+            state.early.extend_from_slice(line.as_bytes());
+        } else if let Err(e) = state.write_bytes(line.as_bytes()) {
+            // Logging from inside the logger would deadlock
+            eprintln!("Failed to write to the log file: {e}");
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &mut self.0.lock().unwrap().file {
+            _ = file.flush();
+        }
+    }
+}
+
+impl RotatingState {
+    fn write_bytes(&mut self, buf: &[u8]) -> io::Result<()> {
+        if !self.mid_line && self.max_bytes > 0 && self.written >= self.max_bytes {
+            // Logging from inside the logger would deadlock
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate the log file: {e:#}");
+            }
+        }
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+        file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        if let Some(&last) = buf.last() {
+            self.mid_line = last != b'\n';
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let Some(dir) = self.dir.clone() else {
+            return Ok(());
+        };
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+        let rotated = dir.join(format!("{ARCHIVE_PREFIX}{timestamp}.log"));
+        let renamed = std::fs::rename(dir.join(LOG_FILE), &rotated);
+        // Keep writing to the old file rather than dropping lines if it can't move
+        self.file = Some(open_log(&dir)?);
+        renamed?;
+        self.written = 0;
+
+        let keep_files = self.keep_files;
+        std::thread::spawn(move || {
+            if let Err(e) = compress(&rotated).and_then(|()| prune(&dir, keep_files)) {
+                eprintln!("Failed to compress {}: {e:#}", rotated.display());
+            }
+        });
+        Ok(())
+    }
+}
+
+fn open_log(dir: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))
+}
+
+fn compress(path: &Path) -> Result<()> {
+    let mut encoder = GzEncoder::new(
+        File::create(path.with_extension("log.gz"))?,
+        Compression::default(),
+    );
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Deletes all but the newest `keep` archives
+fn prune(dir: &Path, keep: usize) -> Result<()> {
+    let mut archives: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.file_name().and_then(|name| name.to_str()).is_some_and(|name| {
+                name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_SUFFIX)
+            })
+        })
+        .collect();
+    archives.sort();
+    let excess = archives.len().saturating_sub(keep);
+    for path in &archives[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_lines_never_span_lines() {
+        let line = text_line(
+            &Record::builder()
+                .args(format_args!("Transcription text: one\ntwo\r\nthree"))
+                .level(Level::Info)
+                .target("whistle")
+                .build(),
+        );
+        assert_eq!(line.lines().count(), 1);
+        assert!(line.ends_with("Transcription text: one\\ntwo\\r\\nthree\n"));
+    }
+}
@@ -1,49 +1,629 @@
-use crate::constants::API_BASE_URL;
-use anyhow::{Context, Result};
-use reqwest::Client;
-use serde::Deserialize;
+use crate::audio_recorder;
+use crate::mock::MockBackend;
+use crate::polish_limit::{self, TooLong};
+use crate::settings::{ApiSettings, OverPolishLimit, Settings, UploadFormat};
+use crate::text_format::TextFormat;
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderValue};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const HEALTH_PATH: &str = "/health";
+/// Idle connections are kept this long, longer than the usual pause between
+/// dictations, so the next request skips DNS and the TLS handshake
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Keeps routers from dropping pooled connections that sit idle
+const TCP_KEEPALIVE: Duration = Duration::from_secs(30);
+/// Error codes the backend and the providers behind it use for an exhausted balance
+const QUOTA_ERROR_CODES: &[&str] = &[
+    "insufficient_quota",
+    "quota_exceeded",
+    "billing_hard_limit_reached",
+    "insufficient_credits",
+    "payment_required",
+];
+
+/// An empty transcript is only retried for recordings at least this long and loud
+const RETRY_MIN_DURATION: Duration = Duration::from_secs(1);
+const RETRY_MIN_LEVEL: f32 = 0.01;
+/// Below this the detected language is flagged as a guess
+const LOW_LANGUAGE_CONFIDENCE: f32 = 0.5;
+/// OpenAI reports its processing time in this header, other servers may use
+/// `Server-Timing`
+const PROCESSING_MS_HEADER: &str = "openai-processing-ms";
 
 #[derive(Debug, Deserialize)]
 struct TranscriptionResponse {
     text: String,
     original_text: Option<String>,
+    language: Option<String>,
+    /// Whisper servers report how sure the detection was, OpenAI doesn't
+    language_probability: Option<f32>,
+    /// OpenAI-style servers name the model that answered
+    model: Option<String>,
 }
 
-pub struct TranscribeClient {
+/// Where the time of the last transcription request went. The upload and the
+/// server's work only come apart when the server reports its own time.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct TranscriptionTiming {
+    pub bytes: usize,
+    /// Building the request body from the WAV bytes
+    pub encode_ms: u64,
+    /// From sending until the response headers arrived
+    pub request_ms: u64,
+    pub upload_ms: Option<u64>,
+    pub server_ms: Option<u64>,
+    /// Reading and parsing the response body
+    pub download_ms: u64,
+}
+
+/// A transcript with the language the model detected, when the backend reports it
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    pub text: String,
+    pub language: Option<String>,
+    pub language_probability: Option<f32>,
+    pub model: Option<String>,
+}
+
+impl Transcription {
+    /// The backend reported a low confidence for the detected language
+    pub fn language_uncertain(&self) -> bool {
+        self.language_probability
+            .is_some_and(|probability| probability < LOW_LANGUAGE_CONFIDENCE)
+    }
+}
+
+/// How a finished transcription request ends. Only `Deliver` goes on to the sinks, so
+/// the clipboard is never written on an error, a timeout or an empty transcript.
+#[derive(Debug)]
+pub enum TranscribeOutcome {
+    Deliver(Transcription),
+    NoSpeech,
+    Failed(anyhow::Error),
+}
+
+impl From<Result<Transcription>> for TranscribeOutcome {
+    fn from(result: Result<Transcription>) -> Self {
+        match result {
+            Ok(transcription) if transcription.text.trim().is_empty() => Self::NoSpeech,
+            Ok(transcription) => Self::Deliver(transcription),
+            Err(e) => Self::Failed(e),
+        }
+    }
+}
+
+/// What a failed request means for the user, so every caller reacts to it the same way
+#[derive(Debug, PartialEq, Eq)]
+pub enum ApiFailure {
+    QuotaExceeded,
+    TooLong {
+        tokens: usize,
+        max: usize,
+    },
+    /// A short diagnostic, see `Decode`
+    BadResponse(String),
+    Unauthorized,
+    /// Timeouts, rate limits, server errors and the like, worth trying again later
+    Other,
+}
+
+impl From<&anyhow::Error> for ApiFailure {
+    fn from(error: &anyhow::Error) -> Self {
+        if error.is::<QuotaExceeded>() {
+            return Self::QuotaExceeded;
+        }
+        if let Some(&TooLong { tokens, max }) = error.downcast_ref::<TooLong>() {
+            return Self::TooLong { tokens, max };
+        }
+        if let Some(decode) = error.downcast_ref::<Decode>() {
+            return Self::BadResponse(decode.to_string());
+        }
+        if error.is::<Unauthorized>() {
+            return Self::Unauthorized;
+        }
+        Self::Other
+    }
+}
+
+/// Where recordings are sent to be transcribed. `TranscribeClient` talks to the
+/// configured server and `MockBackend` answers without the network.
+pub trait TranscriptionBackend {
+    /// How the recording is encoded into the request body, `None` when nothing is sent
+    fn upload_format(&self) -> Option<UploadFormat>;
+
+    /// `model` overrides `api.model` for this request
+    fn transcribe(
+        &self,
+        recording: Vec<u8>,
+        model: Option<&str>,
+    ) -> impl Future<Output = Result<Transcription>> + Send;
+}
+
+/// Sends a finished recording, and once more with `retry_on_empty` when it comes back
+/// empty though it's long and loud enough to hold speech. Genuine silence still ends
+/// up empty.
+pub async fn request_transcription(
+    backend: &impl TranscriptionBackend,
+    recording: Vec<u8>,
+    model: Option<&str>,
+    retry_on_empty: bool,
+) -> Result<Transcription> {
+    let retry_copy = retry_on_empty.then(|| recording.clone());
+    let result = backend.transcribe(recording, model).await;
+    let came_back_empty =
+        matches!(&result, Ok(transcription) if transcription.text.trim().is_empty());
+    match retry_copy.filter(|recording| came_back_empty && worth_retrying(recording)) {
+        Some(recording) => {
+            log::warn!("Empty transcript for a recording with sound, retrying once");
+            backend.transcribe(recording, model).await
+        }
+        None => result,
+    }
+}
+
+/// An empty transcript of a recording this long and loud is more likely a backend
+/// glitch than silence
+fn worth_retrying(recording: &[u8]) -> bool {
+    let duration = audio_recorder::wav_duration(recording).unwrap_or_default();
+    let level = audio_recorder::wav_level(recording).unwrap_or_default();
+    log::debug!("Empty transcript for {duration:?} of audio at level {level:.3}");
+    duration >= RETRY_MIN_DURATION && level >= RETRY_MIN_LEVEL
+}
+
+/// What the configured backend can do with a request, so options it would ignore can
+/// be shown as unavailable instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Sends back partial transcripts while audio is still coming in
+    pub streaming: bool,
+    /// Tells speakers apart
+    pub diarization: bool,
+    /// Polishes into a requested language
+    pub language_hint: bool,
+    /// Takes custom instructions for polish, as prompts need
+    pub prompt: bool,
+    /// Reports when each part of the transcript was said
+    pub timestamps: bool,
+}
+
+impl Capabilities {
+    /// Setting names of options that are on but do nothing with this backend
+    pub fn unsupported_options(&self, settings: &Settings) -> Vec<&'static str> {
+        let mut unsupported = vec![];
+        if !self.language_hint && settings.behavior.polish_in_detected_language {
+            unsupported.push("behavior.polish_in_detected_language");
+        }
+        if !self.language_hint && settings.behavior.polish_language.is_some() {
+            unsupported.push("behavior.polish_language");
+        }
+        if !self.prompt && !settings.prompts.is_empty() {
+            unsupported.push("prompts");
+        }
+        unsupported
+    }
+}
+
+/// The backend rejected the API key (HTTP 401/403)
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl std::fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The API key was rejected")
+    }
+}
+
+impl std::error::Error for Unauthorized {}
+
+/// The account ran out of credit (HTTP 402, or 429 with a quota error code)
+#[derive(Debug)]
+pub struct QuotaExceeded;
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The account has run out of API credit")
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// The backend answered with something other than the expected JSON, e.g. an HTML
+/// error page from a wrong `api.base_url`
+#[derive(Debug)]
+pub struct Decode {
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+}
+
+impl std::fmt::Display for Decode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unexpected response from the backend ({}, {})",
+            self.status,
+            self.content_type.as_deref().unwrap_or("no content type")
+        )
+    }
+}
+
+impl std::error::Error for Decode {}
+
+#[derive(Clone)]
+struct Inner {
     http_client: Client,
+    base_url: String,
+    upload_format: UploadFormat,
+    /// `api.model`, `None` leaves it to the backend
+    model: Option<String>,
+    max_polish_tokens: usize,
+    over_polish_limit: OverPolishLimit,
+    api_key: Option<HeaderValue>,
+    /// Caps concurrent transcribe and polish requests at `api.max_concurrency`
+    limiter: Arc<Semaphore>,
+}
+
+pub struct TranscribeClient {
+    inner: RwLock<Inner>,
+    /// Set after a quota error with `api.pause_on_quota_error`, requests fail right
+    /// away until the user resumes from the tray
+    quota_paused: AtomicBool,
+    /// Answers every request instead of the backend, see `mock`
+    mock: RwLock<Option<MockBackend>>,
+    /// When a response last came back, to tell if a pooled connection is likely open
+    last_response: Mutex<Option<Instant>>,
+    last_timing: Mutex<Option<TranscriptionTiming>>,
 }
 
 impl TranscribeClient {
-    pub fn new() -> Self {
+    pub fn new(api: &ApiSettings, api_key: Option<&str>) -> Self {
+        let (http_client, base_url) = Self::build(api);
+        let mock = MockBackend::from_env();
+        if let Some(mock) = &mock {
+            log::warn!("Mocking the backend with {:?}", mock.outcome);
+        }
         Self {
-            http_client: Client::new(),
+            inner: RwLock::new(Inner {
+                http_client,
+                base_url,
+                upload_format: api.upload_format,
+                model: model_setting(api),
+                max_polish_tokens: api.max_polish_tokens,
+                over_polish_limit: api.over_polish_limit,
+                api_key: api_key.and_then(auth_header),
+                limiter: Arc::new(Semaphore::new(api.max_concurrency)),
+            }),
+            quota_paused: AtomicBool::new(false),
+            mock: RwLock::new(mock),
+            last_response: Mutex::new(None),
+            last_timing: Mutex::new(None),
+        }
+    }
+
+    /// Swaps in a client built from new API settings. In-flight requests keep the old one.
+    pub fn apply_settings(&self, api: &ApiSettings) {
+        let (http_client, base_url) = Self::build(api);
+        let mut inner = self.inner.write().unwrap();
+        inner.http_client = http_client;
+        inner.base_url = base_url;
+        inner.upload_format = api.upload_format;
+        inner.model = model_setting(api);
+        inner.max_polish_tokens = api.max_polish_tokens;
+        inner.over_polish_limit = api.over_polish_limit;
+        // Requests holding or waiting on the old limiter finish under it
+        inner.limiter = Arc::new(Semaphore::new(api.max_concurrency));
+        // The new client starts without connections
+        *self.last_response.lock().unwrap() = None;
+        log::info!("Transcribe client now using {}", api.base_url);
+    }
+
+    /// Used for every request after this call, no restart needed
+    pub fn set_api_key(&self, api_key: Option<&str>) {
+        self.inner.write().unwrap().api_key = api_key.and_then(auth_header);
+    }
+
+    pub fn quota_paused(&self) -> bool {
+        self.quota_paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_quota_paused(&self, paused: bool) {
+        self.quota_paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// `None` goes back to the real backend
+    pub fn set_mock(&self, mock: Option<MockBackend>) {
+        match &mock {
+            Some(mock) => log::warn!("Mocking the backend with {:?}", mock.outcome),
+            None => log::info!("Using the real backend"),
+        }
+        *self.mock.write().unwrap() = mock;
+    }
+
+    fn mock(&self) -> Option<MockBackend> {
+        *self.mock.read().unwrap()
+    }
+
+    fn build(api: &ApiSettings) -> (Client, String) {
+        // HTTP/2 is negotiated during the TLS handshake where the server offers it
+        let http_client = Client::builder()
+            .timeout(Duration::from_secs(api.timeout_secs))
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .build()
+            .unwrap_or_default();
+        (http_client, api.base_url.trim_end_matches('/').to_string())
+    }
+
+    /// Derived from `api.upload_format`, which says what kind of server this is. The
+    /// app's own backend takes raw or base64 JSON audio and polishes with prompts and
+    /// languages. OpenAI-style servers take multipart uploads and only transcribe, but
+    /// report segment timestamps in their verbose format.
+    pub fn capabilities(&self) -> Capabilities {
+        let none = Capabilities {
+            streaming: false,
+            diarization: false,
+            language_hint: false,
+            prompt: false,
+            timestamps: false,
+        };
+        if self.mock().is_some() {
+            return Capabilities {
+                language_hint: true,
+                prompt: true,
+                ..none
+            };
+        }
+        match self.inner.read().unwrap().upload_format {
+            UploadFormat::Raw | UploadFormat::JsonBase64 => Capabilities {
+                language_hint: true,
+                prompt: true,
+                ..none
+            },
+            UploadFormat::Multipart => Capabilities {
+                timestamps: true,
+                ..none
+            },
         }
     }
 
-    pub async fn fetch_transcription(&self, recording: Vec<u8>) -> Result<String> {
+    /// Identifies the backend in history entries
+    pub fn provider(&self) -> String {
+        if self.mock().is_some() {
+            return "mock".into();
+        }
+        self.inner.read().unwrap().base_url.clone()
+    }
+
+    /// Waits for a free request slot. Hold the permit until the response is read.
+    async fn acquire(&self) -> OwnedSemaphorePermit {
+        let limiter = Arc::clone(&self.inner.read().unwrap().limiter);
+        limiter
+            .acquire_owned()
+            .await
+            .expect("request limiter is never closed")
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        let inner = self.inner.read().unwrap().clone();
+        with_auth(
+            inner.http_client.post(format!("{}{path}", inner.base_url)),
+            inner.api_key,
+        )
+    }
+
+    /// Sends `request`, noting when the response came back
+    async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let res = request.send().await?;
+        *self.last_response.lock().unwrap() = Some(Instant::now());
+        Ok(res)
+    }
+
+    /// The breakdown of the last successful transcription request, for the statistics
+    pub fn last_timing(&self) -> Option<TranscriptionTiming> {
+        *self.last_timing.lock().unwrap()
+    }
+
+    /// Whether a pooled connection is probably still open, so a request skips DNS and
+    /// the handshakes. Logged with each request to compare timings.
+    pub fn connection_warm(&self) -> bool {
+        self.last_response
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() < POOL_IDLE_TIMEOUT)
+    }
+
+    /// Resolves the API host and opens a connection for the next request to reuse, as
+    /// a recording starts. Failures are only logged at debug level, the real request
+    /// reports them.
+    pub async fn prewarm(&self) {
+        if self.mock().is_some() || self.connection_warm() {
+            return;
+        }
+        let inner = self.inner.read().unwrap().clone();
+        let started = Instant::now();
+        let request = inner.http_client.head(format!("{}{HEALTH_PATH}", inner.base_url));
+        match self.send(request).await {
+            Ok(res) => log::debug!(
+                stage = "prewarm",
+                connect_ms = started.elapsed().as_millis() as u64,
+                status = res.status().as_u16();
+                "Opened a connection to the API"
+            ),
+            Err(e) => log::debug!("Couldn't prewarm the API connection: {e}"),
+        }
+    }
+
+    /// Makes a live request with `api_key` without touching the stored key
+    pub async fn validate_api_key(&self, api_key: &str) -> Result<()> {
+        if let Some(mock) = self.mock() {
+            return mock.respond().await;
+        }
+        let inner = self.inner.read().unwrap().clone();
+        let header =
+            auth_header(api_key).context("The API key contains invalid characters")?;
         let res = self
-            .http_client
-            .post(format!("{API_BASE_URL}/transcribe"))
-            .header("Content-Type", "audio/wav")
-            .body(recording)
-            .send()
+            .send(with_auth(
+                inner.http_client.get(format!("{}{HEALTH_PATH}", inner.base_url)),
+                Some(header),
+            ))
             .await?;
+        check_status(res).await?;
+        Ok(())
+    }
 
-        let res: TranscriptionResponse = res.json().await?;
+    pub async fn fetch_transcription(&self, recording: Vec<u8>) -> Result<Transcription> {
+        self.fetch_transcription_with(recording, None).await
+    }
+
+    /// Asks for `model` instead of `api.model`, as `quality.auto` does
+    pub async fn fetch_transcription_with(
+        &self,
+        recording: Vec<u8>,
+        model: Option<&str>,
+    ) -> Result<Transcription> {
+        if self.quota_paused() {
+            return Err(QuotaExceeded.into());
+        }
+        if let Some(mock) = self.mock() {
+            return mock.transcribe(recording, model).await;
+        }
+        let _permit = self.acquire().await;
+        let (upload_format, default_model) = {
+            let inner = self.inner.read().unwrap();
+            (inner.upload_format, inner.model.clone())
+        };
+        let model = model.or(default_model.as_deref());
+        let bytes = recording.len();
+        let started = Instant::now();
+        let request =
+            with_recording(self.post("/transcribe"), upload_format, model, recording)?;
+        let encode_ms = started.elapsed().as_millis() as u64;
+        let started = Instant::now();
+        let res = self.send(request).await?;
+        let request_ms = started.elapsed().as_millis() as u64;
+        let server_ms = server_time(&res);
 
-        Ok(res.text)
+        let started = Instant::now();
+        let res = decode(check_status(res).await?).await?;
+        let timing = TranscriptionTiming {
+            bytes,
+            encode_ms,
+            request_ms,
+            upload_ms: server_ms.map(|server_ms| request_ms.saturating_sub(server_ms)),
+            server_ms,
+            download_ms: started.elapsed().as_millis() as u64,
+        };
+        log::debug!(
+            stage = "transcribe_breakdown",
+            bytes = timing.bytes,
+            encode_ms = timing.encode_ms,
+            request_ms = timing.request_ms,
+            upload_ms = timing.upload_ms,
+            server_ms = timing.server_ms,
+            download_ms = timing.download_ms;
+            "Transcription timing"
+        );
+        *self.last_timing.lock().unwrap() = Some(timing);
+
+        Ok(Transcription {
+            text: res.text,
+            language: res.language.filter(|language| !language.is_empty()),
+            language_probability: res.language_probability,
+            model: res.model.filter(|model| !model.is_empty()),
+        })
     }
 
-    pub async fn clean_transcription(&self, transcription: String) -> Result<String> {
-        let res = self
-            .http_client
-            .post(format!("{API_BASE_URL}/clean-transcription"))
+    /// `prompt` replaces the backend's default instructions for `style`. With
+    /// `language` the result is asked for in that language instead of English.
+    /// `format` asks for Markdown or code formatting to be kept. Text over
+    /// `api.max_polish_tokens` fails with `TooLong` or is polished in parts, as
+    /// `api.over_polish_limit` says.
+    pub async fn clean_transcription(
+        &self,
+        transcription: String,
+        style: &str,
+        prompt: Option<&str>,
+        language: Option<&str>,
+        format: TextFormat,
+    ) -> Result<String> {
+        let (max, over_limit) = {
+            let inner = self.inner.read().unwrap();
+            (inner.max_polish_tokens, inner.over_polish_limit)
+        };
+        let tokens = polish_limit::estimate_tokens(&transcription);
+        if max == 0 || tokens <= max {
+            return self.clean_once(transcription, style, prompt, language, format).await;
+        }
+        if over_limit == OverPolishLimit::Refuse {
+            return Err(TooLong { tokens, max }.into());
+        }
+
+        let parts = polish_limit::chunks(&transcription, max);
+        log::info!("Polishing about {tokens} tokens in {} parts", parts.len());
+        let mut polished = String::with_capacity(transcription.len());
+        for (i, part) in parts.iter().enumerate() {
+            let (text, whitespace) = polish_limit::split_trailing_whitespace(part);
+            if !text.trim().is_empty() {
+                let result = self
+                    .clean_once(text.to_string(), style, prompt, language, format)
+                    .await
+                    .with_context(|| format!("Part {} of {}", i + 1, parts.len()))?;
+                polished.push_str(result.trim_end());
+            }
+            polished.push_str(whitespace);
+        }
+        Ok(polished)
+    }
+
+    async fn clean_once(
+        &self,
+        transcription: String,
+        style: &str,
+        prompt: Option<&str>,
+        language: Option<&str>,
+        format: TextFormat,
+    ) -> Result<String> {
+        if self.quota_paused() {
+            return Err(QuotaExceeded.into());
+        }
+        if let Some(mock) = self.mock() {
+            return mock.polish(transcription).await;
+        }
+        let capabilities = self.capabilities();
+        let prompt = prompt.filter(|_| capabilities.prompt);
+        let language = language.filter(|_| capabilities.language_hint);
+        let _permit = self.acquire().await;
+        let mut body = serde_json::json!({ "text": transcription, "style": style });
+        if let Some(prompt) = prompt {
+            let mut prompt = prompt.to_string();
+            if let Some(instruction) = format.instruction() {
+                prompt = format!("{prompt}\n\n{instruction}");
+            }
+            if let Some(language) = language {
+                prompt = format!("{prompt}\n\nRespond in {language}.");
+            }
+            body["prompt"] = prompt.into();
+        }
+        if let Some(language) = language {
+            body["language"] = language.into();
+        }
+        if let Some(hint) = format.hint() {
+            body["format"] = hint.into();
+        }
+        let request = self
+            .post("/clean-transcription")
             .header("Content-Type", "application/json")
-            .body(serde_json::json!({ "text": transcription }).to_string())
-            .send()
-            .await?;
+            .body(body.to_string());
+        let res = self.send(request).await?;
 
-        let response: TranscriptionResponse = res.json().await?;
+        let response = decode(check_status(res).await?).await?;
 
         let _original_text =
             response.original_text.context("Failed to get original text")?;
@@ -51,3 +631,575 @@ impl TranscribeClient {
         Ok(response.text)
     }
 }
+
+/// Marked sensitive so the key never shows up in `Debug` output or logs
+fn auth_header(api_key: &str) -> Option<HeaderValue> {
+    let mut value = HeaderValue::from_str(&format!("Bearer {api_key}")).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
+
+fn with_auth(builder: RequestBuilder, api_key: Option<HeaderValue>) -> RequestBuilder {
+    match api_key {
+        Some(value) => builder.header(AUTHORIZATION, value),
+        None => builder,
+    }
+}
+
+fn model_setting(api: &ApiSettings) -> Option<String> {
+    let model = api.model.trim();
+    (!model.is_empty()).then(|| model.to_string())
+}
+
+/// Encodes the recording the way `api.upload_format` says the backend expects it,
+/// labelled as Ogg Opus when it was compressed and WAV otherwise
+fn with_recording(
+    builder: RequestBuilder,
+    upload_format: UploadFormat,
+    model: Option<&str>,
+    recording: Vec<u8>,
+) -> Result<RequestBuilder> {
+    let (mime, file_name, format) = if recording.starts_with(b"OggS") {
+        ("audio/ogg", "recording.ogg", "ogg")
+    } else {
+        ("audio/wav", "recording.wav", "wav")
+    };
+    Ok(match upload_format {
+        UploadFormat::Raw => {
+            let builder = match model {
+                Some(model) => builder.query(&[("model", model)]),
+                None => builder,
+            };
+            builder.header(CONTENT_TYPE, mime).body(recording)
+        }
+        UploadFormat::Multipart => {
+            let file = Part::bytes(recording).file_name(file_name).mime_str(mime)?;
+            // OpenAI-style servers only report the language in the verbose format
+            let mut form =
+                Form::new().part("file", file).text("response_format", "verbose_json");
+            if let Some(model) = model {
+                form = form.text("model", model.to_string());
+            }
+            builder.multipart(form)
+        }
+        UploadFormat::JsonBase64 => {
+            let mut body = serde_json::json!({
+                "audio": BASE64.encode(&recording),
+                "format": format,
+            });
+            if let Some(model) = model {
+                body["model"] = model.into();
+            }
+            builder
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string())
+        }
+    })
+}
+
+/// How long the server says it worked on the request, from `openai-processing-ms` or
+/// the `total` (or else longest) `dur` in `Server-Timing`
+fn server_time(res: &Response) -> Option<u64> {
+    let header = |name| res.headers().get(name).and_then(|value| value.to_str().ok());
+    if let Some(ms) = header(PROCESSING_MS_HEADER).and_then(|ms| ms.trim().parse().ok()) {
+        return Some(ms);
+    }
+    let metrics: Vec<(&str, f64)> = header("server-timing")?
+        .split(',')
+        .filter_map(|metric| {
+            let mut params = metric.split(';').map(str::trim);
+            let name = params.next()?;
+            let dur = params.find_map(|param| param.strip_prefix("dur="))?;
+            Some((name, dur.parse().ok()?))
+        })
+        .collect();
+    let ms = match metrics.iter().find(|(name, _)| *name == "total") {
+        Some((_, ms)) => *ms,
+        None => metrics.iter().map(|(_, ms)| *ms).reduce(f64::max)?,
+    };
+    Some(ms.round() as u64)
+}
+
+impl TranscriptionBackend for TranscribeClient {
+    fn upload_format(&self) -> Option<UploadFormat> {
+        match self.mock() {
+            Some(mock) => mock.upload_format(),
+            None => Some(self.inner.read().unwrap().upload_format),
+        }
+    }
+
+    async fn transcribe(
+        &self,
+        recording: Vec<u8>,
+        model: Option<&str>,
+    ) -> Result<Transcription> {
+        self.fetch_transcription_with(recording, model).await
+    }
+}
+
+/// Only JSON is accepted, anything else fails with `Decode` instead of its body
+/// ending up in the clipboard
+async fn decode(res: Response) -> Result<TranscriptionResponse> {
+    let status = res.status();
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let decode_error = || Decode {
+        status,
+        content_type: content_type.clone(),
+    };
+    if !content_type.as_deref().is_some_and(|ct| ct.contains("json")) {
+        return Err(decode_error().into());
+    }
+    let bytes = res.bytes().await?;
+    serde_json::from_slice(&bytes).map_err(|e| {
+        log::error!("Failed to parse backend response: {e}");
+        decode_error().into()
+    })
+}
+
+async fn check_status(res: Response) -> Result<Response> {
+    match res.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(Unauthorized.into()),
+        StatusCode::PAYMENT_REQUIRED => Err(QuotaExceeded.into()),
+        StatusCode::TOO_MANY_REQUESTS => {
+            let status = res.status();
+            let body = res.bytes().await.unwrap_or_default();
+            if is_quota_error(&body) {
+                return Err(QuotaExceeded.into());
+            }
+            bail!("The backend is rate limiting requests ({status})")
+        }
+        _ => Ok(res.error_for_status()?),
+    }
+}
+
+/// Looks for a quota code in the usual spots: `{"error": {"code": ..}}`,
+/// `{"error": {"type": ..}}`, `{"error": ..}` or `{"code": ..}`
+fn is_quota_error(body: &[u8]) -> bool {
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return false;
+    };
+    let error = &json["error"];
+    [&error["code"], &error["type"], error, &json["code"]]
+        .into_iter()
+        .filter_map(|value| value.as_str())
+        .any(|code| QUOTA_ERROR_CODES.contains(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockOutcome;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    const CLIPBOARD: &str = "what the user copied before";
+    const TRANSCRIPT_SHOWN: &str =
+        "This is a mock transcription, nothing was sent anywhere.";
+
+    fn mock(outcome: MockOutcome) -> MockBackend {
+        MockBackend {
+            outcome,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// The clipboard after the outcome, written only the way `transcribe_recording` does
+    fn clipboard_after(outcome: TranscribeOutcome) -> String {
+        match outcome {
+            TranscribeOutcome::Deliver(transcription) => transcription.text,
+            TranscribeOutcome::NoSpeech | TranscribeOutcome::Failed(_) => {
+                CLIPBOARD.into()
+            }
+        }
+    }
+
+    /// A backend on localhost that answers one request with `body`, handing back the
+    /// request it got
+    async fn serve_once(
+        content_type: &str,
+        body: &[u8],
+    ) -> (String, JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut stream).await;
+            stream.write_all(&response).await.unwrap();
+            request
+        });
+        (base_url, server)
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> Vec<u8> {
+        let mut request = Vec::new();
+        let mut buf = [0; 8192];
+        loop {
+            let read = stream.read(&mut buf).await.unwrap();
+            if read == 0 {
+                return request;
+            }
+            request.extend_from_slice(&buf[..read]);
+            let Some(head_end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+            let head = String::from_utf8_lossy(&request[..head_end]).to_lowercase();
+            let body_len = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|len| len.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if request.len() >= head_end + 4 + body_len {
+                return request;
+            }
+        }
+    }
+
+    fn client(base_url: String, upload_format: UploadFormat) -> TranscribeClient {
+        let client = TranscribeClient::new(
+            &ApiSettings {
+                base_url,
+                upload_format,
+                ..Default::default()
+            },
+            None,
+        );
+        client.set_mock(None);
+        client
+    }
+
+    async fn decode_error(content_type: &str, body: &[u8]) -> Decode {
+        let (base_url, _server) = serve_once(content_type, body).await;
+        let error = client(base_url, UploadFormat::Raw)
+            .fetch_transcription(b"RIFF".to_vec())
+            .await
+            .unwrap_err();
+        error.downcast::<Decode>().expect("a Decode error")
+    }
+
+    #[tokio::test]
+    async fn an_html_page_is_a_decode_error() {
+        let error = decode_error(
+            "text/html; charset=utf-8",
+            b"<html><body><h1>404 Not Found</h1></body></html>",
+        )
+        .await;
+        assert_eq!(error.status, StatusCode::OK);
+        assert_eq!(error.content_type.as_deref(), Some("text/html; charset=utf-8"));
+        assert!(!error.to_string().contains("<html>"));
+    }
+
+    #[tokio::test]
+    async fn binary_is_a_decode_error() {
+        let error =
+            decode_error("application/octet-stream", &[0xff, 0xfe, 0x00, 0x9f]).await;
+        assert_eq!(error.content_type.as_deref(), Some("application/octet-stream"));
+    }
+
+    #[tokio::test]
+    async fn html_labelled_as_json_is_a_decode_error() {
+        let error = decode_error("application/json", b"<html>Bad Gateway</html>").await;
+        assert_eq!(error.content_type.as_deref(), Some("application/json"));
+    }
+
+    #[tokio::test]
+    async fn json_is_decoded() {
+        let (base_url, _server) =
+            serve_once("application/json", br#"{"text": "hello", "language": "en"}"#)
+                .await;
+        let transcription = client(base_url, UploadFormat::Raw)
+            .fetch_transcription(b"RIFF".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(transcription.text, "hello");
+        assert_eq!(transcription.language.as_deref(), Some("en"));
+    }
+
+    /// The head and body of the request that uploaded `recording`
+    async fn upload(
+        upload_format: UploadFormat,
+        recording: &[u8],
+        model: Option<&str>,
+    ) -> (String, Vec<u8>) {
+        let (base_url, server) =
+            serve_once("application/json", br#"{"text": "hello"}"#).await;
+        let client = client(base_url, upload_format);
+        assert_eq!(client.upload_format(), Some(upload_format));
+        TranscriptionBackend::transcribe(&client, recording.to_vec(), model)
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+        let head_end = request.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&request[..head_end]).to_lowercase();
+        (head, request[head_end + 4..].to_vec())
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[tokio::test]
+    async fn uploads_raw_wav() {
+        let wav = audio_recorder::encode_wav(&[0, 1000, -1000], 16000, 1).unwrap();
+        let (head, body) = upload(UploadFormat::Raw, &wav, Some("large-v3")).await;
+        assert!(head.starts_with("post /transcribe?model=large-v3 "));
+        assert!(head.contains("content-type: audio/wav"));
+        assert_eq!(body, wav);
+    }
+
+    #[tokio::test]
+    async fn uploads_multipart() {
+        let wav = audio_recorder::encode_wav(&[0, 1000, -1000], 16000, 1).unwrap();
+        let (head, body) = upload(UploadFormat::Multipart, &wav, Some("whisper-1")).await;
+        assert!(head.contains("content-type: multipart/form-data; boundary="));
+        assert!(contains(&body, br#"name="file"; filename="recording.wav""#));
+        assert!(contains(&body, b"Content-Type: audio/wav"));
+        assert!(contains(&body, &wav));
+        assert!(contains(&body, b"verbose_json"));
+        assert!(contains(&body, b"whisper-1"));
+    }
+
+    #[tokio::test]
+    async fn uploads_json_base64() {
+        let wav = audio_recorder::encode_wav(&[0, 1000, -1000], 16000, 1).unwrap();
+        let (head, body) = upload(UploadFormat::JsonBase64, &wav, None).await;
+        assert!(head.contains("content-type: application/json"));
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["audio"], BASE64.encode(&wav));
+        assert_eq!(json["format"], "wav");
+        assert!(json.get("model").is_none());
+    }
+
+    #[tokio::test]
+    async fn labels_compressed_recordings_as_ogg() {
+        let ogg = crate::ogg_opus::encode(&[0, 1000, -1000], 16000).unwrap();
+        let (head, body) = upload(UploadFormat::Raw, &ogg, None).await;
+        assert!(head.contains("content-type: audio/ogg"));
+        assert_eq!(body, ogg);
+
+        let (_, body) = upload(UploadFormat::Multipart, &ogg, None).await;
+        assert!(contains(&body, br#"name="file"; filename="recording.ogg""#));
+        assert!(contains(&body, b"Content-Type: audio/ogg"));
+
+        let (_, body) = upload(UploadFormat::JsonBase64, &ogg, None).await;
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["format"], "ogg");
+    }
+
+    #[test]
+    fn the_mock_sends_nothing() {
+        assert_eq!(mock(MockOutcome::Succeed).upload_format(), None);
+    }
+
+    /// Answers with `answers` in turn, counting the requests
+    struct Scripted {
+        answers: Mutex<Vec<&'static str>>,
+        requests: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Scripted {
+        fn new(answers: &[&'static str]) -> Self {
+            Self {
+                answers: Mutex::new(answers.iter().rev().copied().collect()),
+                requests: Default::default(),
+            }
+        }
+
+        fn requests(&self) -> usize {
+            self.requests.load(Ordering::Relaxed)
+        }
+    }
+
+    impl TranscriptionBackend for Scripted {
+        fn upload_format(&self) -> Option<UploadFormat> {
+            None
+        }
+
+        async fn transcribe(
+            &self,
+            _recording: Vec<u8>,
+            _model: Option<&str>,
+        ) -> Result<Transcription> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let text = self.answers.lock().unwrap().pop().unwrap_or_default();
+            Ok(transcription(text))
+        }
+    }
+
+    fn wav(seconds: f32, amplitude: i16) -> Vec<u8> {
+        let samples: Vec<i16> = (0..(16000.0 * seconds) as usize)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect();
+        audio_recorder::encode_wav(&samples, 16000, 1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_an_empty_transcript_of_speech_once() {
+        let backend = Scripted::new(&["", "hello"]);
+        let result = request_transcription(&backend, wav(1.5, 8000), None, true).await;
+        assert_eq!(result.unwrap().text, "hello");
+        assert_eq!(backend.requests(), 2);
+
+        let backend = Scripted::new(&["", ""]);
+        let result = request_transcription(&backend, wav(1.5, 8000), None, true).await;
+        assert_eq!(result.unwrap().text, "");
+        assert_eq!(backend.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_silence_short_audio_or_when_disabled() {
+        for (recording, retry_on_empty) in [
+            (wav(1.5, 0), true),
+            (wav(0.2, 8000), true),
+            (wav(1.5, 8000), false),
+        ] {
+            let backend = Scripted::new(&["", "hello"]);
+            let result =
+                request_transcription(&backend, recording, None, retry_on_empty).await;
+            assert_eq!(result.unwrap().text, "");
+            assert_eq!(backend.requests(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_a_transcript_or_an_error() {
+        let backend = Scripted::new(&["hello", "again"]);
+        let result = request_transcription(&backend, wav(1.5, 8000), None, true).await;
+        assert_eq!(result.unwrap().text, "hello");
+        assert_eq!(backend.requests(), 1);
+
+        let result = request_transcription(
+            &mock(MockOutcome::Timeout),
+            wav(1.5, 8000),
+            None,
+            true,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    fn transcription(text: &str) -> Transcription {
+        Transcription {
+            text: text.into(),
+            language: None,
+            language_probability: None,
+            model: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn errors_leave_the_clipboard_alone() {
+        for outcome in MockOutcome::ALL {
+            if matches!(outcome, MockOutcome::Succeed | MockOutcome::Empty) {
+                continue;
+            }
+            let result = mock(outcome).transcribe(Vec::new(), None).await;
+            let outcome = TranscribeOutcome::from(result);
+            assert!(matches!(outcome, TranscribeOutcome::Failed(_)));
+            assert_eq!(clipboard_after(outcome), CLIPBOARD);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_timeout_leaves_the_clipboard_alone() {
+        let result = mock(MockOutcome::Timeout).transcribe(Vec::new(), None).await;
+        let outcome = TranscribeOutcome::from(result);
+        assert!(
+            matches!(&outcome, TranscribeOutcome::Failed(e) if e.to_string().contains("timed out"))
+        );
+        assert_eq!(clipboard_after(outcome), CLIPBOARD);
+    }
+
+    #[test]
+    fn an_empty_transcript_leaves_the_clipboard_alone() {
+        for text in ["", "   ", "\n\t"] {
+            let outcome = TranscribeOutcome::from(Ok(transcription(text)));
+            assert!(matches!(outcome, TranscribeOutcome::NoSpeech));
+            assert_eq!(clipboard_after(outcome), CLIPBOARD);
+        }
+    }
+
+    /// Every mocked outcome through the client the way `transcribe_recording` sends a
+    /// stopped recording, with the clipboard and the failure the user is shown
+    #[tokio::test]
+    async fn each_mocked_outcome_ends_the_pipeline() {
+        let bad_response = "Unexpected response from the backend (200 OK, text/html)";
+        let expected = [
+            (MockOutcome::Succeed, TRANSCRIPT_SHOWN, None),
+            (MockOutcome::Empty, CLIPBOARD, None),
+            (MockOutcome::Timeout, CLIPBOARD, Some(ApiFailure::Other)),
+            (MockOutcome::Unauthorized, CLIPBOARD, Some(ApiFailure::Unauthorized)),
+            (MockOutcome::RateLimited, CLIPBOARD, Some(ApiFailure::Other)),
+            (MockOutcome::QuotaExceeded, CLIPBOARD, Some(ApiFailure::QuotaExceeded)),
+            (
+                MockOutcome::BadResponse,
+                CLIPBOARD,
+                Some(ApiFailure::BadResponse(bad_response.into())),
+            ),
+        ];
+        assert_eq!(expected.len(), MockOutcome::ALL.len());
+        for (outcome, clipboard, failure) in expected {
+            let client = client("http://127.0.0.1:9".into(), UploadFormat::Raw);
+            client.set_mock(Some(mock(outcome)));
+            let result = request_transcription(&client, wav(1.5, 8000), None, true).await;
+            let outcome = TranscribeOutcome::from(result);
+            let shown = match &outcome {
+                TranscribeOutcome::Failed(e) => Some(ApiFailure::from(e)),
+                _ => None,
+            };
+            assert_eq!(shown, failure);
+            assert_eq!(clipboard_after(outcome), clipboard);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_empty_mock_is_no_speech() {
+        let result = mock(MockOutcome::Empty).transcribe(Vec::new(), None).await;
+        assert!(matches!(TranscribeOutcome::from(result), TranscribeOutcome::NoSpeech));
+    }
+
+    #[tokio::test]
+    async fn a_paused_quota_fails_before_the_mock_answers() {
+        let client = client("http://127.0.0.1:9".into(), UploadFormat::Raw);
+        client.set_mock(Some(mock(MockOutcome::Succeed)));
+        client.set_quota_paused(true);
+        let error = request_transcription(&client, wav(1.5, 8000), None, true)
+            .await
+            .unwrap_err();
+        assert_eq!(ApiFailure::from(&error), ApiFailure::QuotaExceeded);
+    }
+
+    #[test]
+    fn classifies_polish_limits() {
+        let error = anyhow::Error::from(TooLong {
+            tokens: 9000,
+            max: 8000,
+        });
+        assert_eq!(
+            ApiFailure::from(&error),
+            ApiFailure::TooLong {
+                tokens: 9000,
+                max: 8000
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn only_a_transcript_with_text_is_delivered() {
+        let result = mock(MockOutcome::Succeed).transcribe(Vec::new(), None).await;
+        let outcome = TranscribeOutcome::from(result);
+        assert!(matches!(outcome, TranscribeOutcome::Deliver(_)));
+        assert_ne!(clipboard_after(outcome), CLIPBOARD);
+    }
+}
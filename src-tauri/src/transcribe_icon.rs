@@ -1,45 +1,363 @@
+use crate::local_task_handler::Task;
 use anyhow::Result;
-use tauri::{image::Image, tray::TrayIcon};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, async_runtime::spawn, image::Image, tray::TrayIcon};
+use tokio::sync::{mpsc, oneshot};
 
-#[derive(Debug, Clone, Copy)]
+/// How often the icon is compared against the recorder
+const CONSISTENCY_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Icon {
     Default,
+    /// Recording was requested but no audio has arrived yet
+    Starting,
     Recording,
     Transcribing,
     Cleansing,
+    /// A dictation session is running, see `session`
+    Session,
+    /// Live dictation is typing as the user speaks, see `live`
+    Live,
+}
+
+/// Where a plain recording is, as reported by the recording pipeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecorderState {
+    #[default]
+    Idle,
+    /// Started, waiting for the first audio
+    Starting,
+    /// Audio is coming in
+    Live,
 }
 
-pub struct TranscribeIcon(TrayIcon);
+/// A mode that keeps the recorder running under its own icon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    Session,
+    Live,
+}
+
+/// Backend requests that show on the icon while they run, see `busy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Work {
+    Transcribing,
+    Polishing,
+}
+
+/// Everything the icon can show. The icon is only ever derived from this, so
+/// whichever part finishes first can't put it back to idle under another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineState {
+    pub recorder: RecorderState,
+    pub capture: Option<Capture>,
+    pub transcribing: usize,
+    pub polishing: usize,
+    /// Listening for the wake phrase, shown instead of the idle icon
+    pub armed: bool,
+}
+
+impl PipelineState {
+    /// An open microphone outranks requests in flight
+    pub fn icon(&self) -> Icon {
+        match (self.capture, self.recorder) {
+            (Some(Capture::Live), _) => Icon::Live,
+            (Some(Capture::Session), _) => Icon::Session,
+            (None, RecorderState::Starting) => Icon::Starting,
+            (None, RecorderState::Live) => Icon::Recording,
+            (None, RecorderState::Idle) if self.polishing > 0 => Icon::Cleansing,
+            (None, RecorderState::Idle) if self.transcribing > 0 => Icon::Transcribing,
+            (None, RecorderState::Idle) => Icon::Default,
+        }
+    }
+
+    /// Something is recording or waiting on the backend
+    pub fn is_busy(&self) -> bool {
+        self.recorder != RecorderState::Idle
+            || self.capture.is_some()
+            || self.transcribing > 0
+            || self.polishing > 0
+    }
+
+    /// Only moves along transitions the recorder can make, so a late `Live` can't
+    /// bring back a recording that a stop already ended. Returns whether it moved.
+    fn recorder_to(&mut self, state: RecorderState) -> bool {
+        let next = match (state, self.recorder) {
+            (RecorderState::Starting, _) => RecorderState::Starting,
+            (RecorderState::Live, RecorderState::Starting) => RecorderState::Live,
+            (RecorderState::Idle, RecorderState::Starting | RecorderState::Live) => {
+                RecorderState::Idle
+            }
+            _ => return false,
+        };
+        self.recorder = next;
+        true
+    }
+
+    fn count(&mut self, work: Work) -> &mut usize {
+        match work {
+            Work::Transcribing => &mut self.transcribing,
+            Work::Polishing => &mut self.polishing,
+        }
+    }
+
+    fn begin(&mut self, work: Work) {
+        *self.count(work) += 1;
+    }
+
+    fn end(&mut self, work: Work) {
+        let count = self.count(work);
+        *count = count.saturating_sub(1);
+    }
+}
+
+pub struct TranscribeIcon {
+    tray_icon: TrayIcon,
+    state: Mutex<PipelineState>,
+}
 
 impl TranscribeIcon {
     pub fn new(tray_icon: TrayIcon) -> Self {
-        Self(tray_icon)
+        Self {
+            tray_icon,
+            state: Mutex::new(PipelineState::default()),
+        }
+    }
+
+    /// Changes the state and redraws the icon if what it shows changed. Held locked
+    /// while the image is swapped, so the tray can't end up behind the state.
+    fn update<T>(&self, f: impl FnOnce(&mut PipelineState) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        let before = (state.icon(), state.armed);
+        let result = f(&mut state);
+        let after = (state.icon(), state.armed);
+        if after != before {
+            self.apply(after.0, after.1);
+        }
+        result
     }
 
-    pub fn change_icon(&self, icon: Icon) {
-        if let Err(e) = self.change_icon_(icon) {
+    fn apply(&self, icon: Icon, armed: bool) {
+        if let Err(e) = self.change_icon_(icon, armed) {
             log::error!("Unable to change icon: {e}");
         } else {
             log::trace!("Successfully changed icon to: {icon:?}");
         }
     }
 
-    fn change_icon_(&self, icon: Icon) -> Result<()> {
+    pub fn set_armed(&self, armed: bool) {
+        self.update(|state| state.armed = armed);
+    }
+
+    /// Moves the recorder part of the state, see `PipelineState::recorder_to`
+    pub fn sync_to(&self, state: RecorderState) -> bool {
+        self.update(|s| s.recorder_to(state))
+    }
+
+    pub fn set_capture(&self, capture: Option<Capture>) {
+        self.update(|state| state.capture = capture);
+    }
+
+    pub fn set_tooltip(&self, tooltip: &str) {
+        if let Err(e) = self.tray_icon.set_tooltip(Some(tooltip)) {
+            log::error!("Unable to set tooltip: {e}");
+        }
+    }
+
+    /// A plain recording is between start and stop
+    pub fn is_recording(&self) -> bool {
+        self.state().recorder != RecorderState::Idle
+    }
+
+    pub fn current(&self) -> Icon {
+        self.state().icon()
+    }
+
+    pub fn state(&self) -> PipelineState {
+        *self.state.lock().unwrap()
+    }
+
+    fn change_icon_(&self, icon: Icon, armed: bool) -> Result<()> {
         let img = match icon {
+            Icon::Default if armed => {
+                Image::from_bytes(include_bytes!("../icons/armed-icon.png"))?
+            }
             Icon::Default => Image::from_bytes(include_bytes!("../icons/StoreLogo.png"))?,
             Icon::Recording => {
                 Image::from_bytes(include_bytes!("../icons/recording-icon.png"))?
             }
-            Icon::Transcribing => {
+            Icon::Starting | Icon::Transcribing => {
                 Image::from_bytes(include_bytes!("../icons/transcribing-icon.png"))?
             }
             Icon::Cleansing => {
                 Image::from_bytes(include_bytes!("../icons/transcribing-icon.png"))?
             }
+            Icon::Session => {
+                Image::from_bytes(include_bytes!("../icons/session-icon.png"))?
+            }
+            Icon::Live => Image::from_bytes(include_bytes!("../icons/live-icon.png"))?,
         };
 
-        self.0.set_icon(Some(img))?;
+        self.tray_icon.set_icon(Some(img))?;
 
         Ok(())
     }
 }
+
+/// Counts `work` as running until the returned guard is dropped
+pub fn busy(app_handle: &AppHandle, work: Work) -> Busy {
+    app_handle.state::<TranscribeIcon>().update(|state| state.begin(work));
+    Busy {
+        app_handle: app_handle.clone(),
+        work,
+    }
+}
+
+pub struct Busy {
+    app_handle: AppHandle,
+    work: Work,
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        let work = self.work;
+        self.app_handle
+            .state::<TranscribeIcon>()
+            .update(|state| state.end(work));
+    }
+}
+
+/// Every few seconds compares the icon with what the recorder is really doing and
+/// corrects it. A mismatch has to show up twice in a row, since the icon trails the
+/// recorder by a moment on every start and stop.
+pub fn check_consistency(app_handle: AppHandle) {
+    spawn(async move {
+        let mut mismatched = false;
+        loop {
+            tokio::time::sleep(CONSISTENCY_INTERVAL).await;
+            let (tx, rx) = oneshot::channel();
+            let tx_task = app_handle.state::<mpsc::Sender<Task>>();
+            if tx_task.send(Task::IsRecording(tx)).await.is_err() {
+                log::error!("Failed to send 'IsRecording' task to channel");
+                continue;
+            }
+            let Ok(recording) = rx.await else {
+                continue;
+            };
+            let transcribe_icon = app_handle.state::<TranscribeIcon>();
+            let state = transcribe_icon.state();
+            // Sessions and live dictation keep the recorder running under their own icon
+            let shows_recording =
+                state.recorder != RecorderState::Idle || state.capture.is_some();
+            if recording == shows_recording {
+                mismatched = false;
+                continue;
+            }
+            if !mismatched {
+                mismatched = true;
+                continue;
+            }
+            mismatched = false;
+            log::warn!("State was {state:?} while recording was {recording}, correcting");
+            transcribe_icon.update(|state| {
+                if recording {
+                    state.recorder = RecorderState::Live;
+                } else {
+                    state.recorder = RecorderState::Idle;
+                    state.capture = None;
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_by_default() {
+        let state = PipelineState::default();
+        assert_eq!(state.icon(), Icon::Default);
+        assert!(!state.is_busy());
+    }
+
+    #[test]
+    fn recording_goes_through_starting() {
+        let mut state = PipelineState::default();
+        assert!(!state.recorder_to(RecorderState::Live));
+        assert!(state.recorder_to(RecorderState::Starting));
+        assert_eq!(state.icon(), Icon::Starting);
+        assert!(state.recorder_to(RecorderState::Live));
+        assert_eq!(state.icon(), Icon::Recording);
+        assert!(state.recorder_to(RecorderState::Idle));
+        assert_eq!(state.icon(), Icon::Default);
+    }
+
+    #[test]
+    fn late_live_after_stop_is_ignored() {
+        let mut state = PipelineState::default();
+        state.recorder_to(RecorderState::Starting);
+        state.recorder_to(RecorderState::Idle);
+        assert!(!state.recorder_to(RecorderState::Live));
+        assert_eq!(state.icon(), Icon::Default);
+    }
+
+    #[test]
+    fn finished_transcription_keeps_a_new_recording_shown() {
+        let mut state = PipelineState::default();
+        state.recorder_to(RecorderState::Starting);
+        state.recorder_to(RecorderState::Live);
+        state.recorder_to(RecorderState::Idle);
+        state.begin(Work::Transcribing);
+        assert_eq!(state.icon(), Icon::Transcribing);
+
+        state.recorder_to(RecorderState::Starting);
+        state.recorder_to(RecorderState::Live);
+        state.end(Work::Transcribing);
+        assert_eq!(state.icon(), Icon::Recording);
+        assert!(state.is_busy());
+    }
+
+    #[test]
+    fn overlapping_work_shows_until_the_last_one_ends() {
+        let mut state = PipelineState::default();
+        state.begin(Work::Transcribing);
+        state.begin(Work::Transcribing);
+        state.end(Work::Transcribing);
+        assert_eq!(state.icon(), Icon::Transcribing);
+        state.begin(Work::Polishing);
+        assert_eq!(state.icon(), Icon::Cleansing);
+        state.end(Work::Polishing);
+        state.end(Work::Transcribing);
+        assert_eq!(state.icon(), Icon::Default);
+        state.end(Work::Transcribing);
+        assert_eq!(state.transcribing, 0);
+    }
+
+    #[test]
+    fn capture_outranks_everything() {
+        let mut state = PipelineState {
+            capture: Some(Capture::Session),
+            ..Default::default()
+        };
+        state.begin(Work::Transcribing);
+        assert_eq!(state.icon(), Icon::Session);
+        state.capture = Some(Capture::Live);
+        assert_eq!(state.icon(), Icon::Live);
+        state.capture = None;
+        assert_eq!(state.icon(), Icon::Transcribing);
+    }
+
+    #[test]
+    fn armed_alone_is_not_busy() {
+        let state = PipelineState {
+            armed: true,
+            ..Default::default()
+        };
+        assert_eq!(state.icon(), Icon::Default);
+        assert!(!state.is_busy());
+    }
+}
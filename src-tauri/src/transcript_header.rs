@@ -0,0 +1,90 @@
+use crate::audio_recorder::RecordingInfo;
+use anyhow::{Result, bail};
+use chrono::Local;
+use std::time::Duration;
+
+const PLACEHOLDERS: &[&str] = &[
+    "date",
+    "time",
+    "duration",
+    "device",
+    "language",
+    "sample_rate",
+    "model",
+];
+
+enum Piece<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Checks that every `{...}` in `format` is a known placeholder, run when settings load
+pub fn validate(format: &str) -> Result<()> {
+    parse(format).map(|_| ())
+}
+
+/// Fills in `format` for the recording. Values the recording doesn't have are left
+/// empty.
+pub fn render(format: &str, info: &RecordingInfo) -> String {
+    // Validated when the settings were loaded
+    let Ok(pieces) = parse(format) else {
+        return String::new();
+    };
+    let started_at = info.started_at.with_timezone(&Local);
+    pieces
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Literal(text) => text.to_string(),
+            Piece::Placeholder("date") => started_at.format("%Y-%m-%d").to_string(),
+            Piece::Placeholder("time") => started_at.format("%H:%M").to_string(),
+            Piece::Placeholder("duration") => {
+                info.duration.map(format_duration).unwrap_or_default()
+            }
+            Piece::Placeholder("device") => info.device.clone().unwrap_or_default(),
+            Piece::Placeholder("language") => info.language.clone().unwrap_or_default(),
+            Piece::Placeholder("sample_rate") => {
+                info.sample_rate.map(|rate| format!("{rate} Hz")).unwrap_or_default()
+            }
+            Piece::Placeholder("model") => info.model.clone().unwrap_or_default(),
+            Piece::Placeholder(_) => String::new(),
+        })
+        .collect()
+}
+
+fn parse(format: &str) -> Result<Vec<Piece<'_>>> {
+    let mut pieces = vec![];
+    let mut rest = format;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            bail!("`transcript_header.format` has a `}}` without a `{{`");
+        }
+        let Some(len) = rest[open..].find('}') else {
+            bail!("`transcript_header.format` has a `{{` without a `}}`");
+        };
+        let name = &rest[open + 1..open + len];
+        if !PLACEHOLDERS.contains(&name) {
+            bail!(
+                "Unknown placeholder `{{{name}}}` in `transcript_header.format`, use {}",
+                PLACEHOLDERS
+                    .iter()
+                    .map(|name| format!("{{{name}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        pieces.push(Piece::Literal(&rest[..open]));
+        pieces.push(Piece::Placeholder(name));
+        rest = &rest[open + len + 1..];
+    }
+    pieces.push(Piece::Literal(rest));
+    Ok(pieces)
+}
+
+/// "1m12s", or "45s" under a minute
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64().round() as u64;
+    match secs / 60 {
+        0 => format!("{secs}s"),
+        minutes => format!("{minutes}m{:02}s", secs % 60),
+    }
+}
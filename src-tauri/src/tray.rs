@@ -0,0 +1,553 @@
+use crate::audio_file::{self, TRANSCRIBE_FILE_ID};
+use crate::clip_ring::{self, ClipRing, RING_CYCLE_ID, RING_ID_PREFIX};
+use crate::collect::{self, Collector};
+use crate::last_recording;
+use crate::local_task_handler::StillRecording;
+use crate::log_menu::{self, DIAGNOSTICS_ID, LOG_LEVEL_ID_PREFIX, LogMenu};
+use crate::log_viewer;
+#[cfg(debug_assertions)]
+use crate::mock;
+use crate::notifications::{AppNotifications, Notification};
+use crate::pinned_menu::{PINNED_ID_PREFIX, PinnedMenu};
+use crate::privacy::{self, PRIVACY_MENU_ID, PrivacyMenuItem};
+use crate::prompts::{self, PROMPT_ID_PREFIX, PromptsMenu};
+use crate::quota::{self, QUOTA_MENU_ID, QuotaMenuItem};
+use crate::recent_menu::{PIN_ID_PREFIX, RECENT_ID_PREFIX, RecentMenu, UNPIN_ID_PREFIX};
+use crate::repeat::{self, REPEAT_ID};
+use crate::retention;
+use crate::saved_recordings::{self, REPORT_RESULT_ID};
+use crate::self_test;
+use crate::session::{self, DictationSession, SESSION_MENU_ID};
+use crate::settings::{PromptSettings, Settings};
+use crate::tag_menu::{TAG_ID_PREFIX, TagMenu};
+use crate::updater::{self, INSTALL_UPDATE_ID, PendingUpdate};
+use crate::wake_word;
+use crate::{
+    PolishOverrides, cancel_recording, cleanse_clipboard, commands,
+    confirm_settings_import, copy_history_entry, menu_entry_id, open_main_window,
+    refresh_history_menus, reinit_audio, set_entry_pinned, show_history_exported,
+    shutdown, tag_latest_entry, toggle_recording, use_pinned_entry,
+};
+use anyhow::Result;
+use tauri::{
+    App, AppHandle, Manager,
+    async_runtime::spawn,
+    menu::{CheckMenuItem, MenuBuilder, MenuEvent, MenuItem, SubmenuBuilder},
+    tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent},
+};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+/// Builds the tray icon with its menu, and manages the menus and items that change
+/// while the app runs
+pub fn build(
+    app: &App,
+    settings: &Settings,
+    prompts: &[PromptSettings],
+) -> Result<TrayIcon> {
+    let recent_submenu = SubmenuBuilder::with_id(app, "recent", "Recent").build()?;
+    let pinned_submenu = SubmenuBuilder::with_id(app, "pinned", "Pinned").build()?;
+    let prompts_submenu = SubmenuBuilder::with_id(app, "prompts", "Prompts").build()?;
+    let tag_submenu = SubmenuBuilder::with_id(app, "tag", "Tag last entry").build()?;
+    let clip_ring_submenu =
+        SubmenuBuilder::with_id(app, "clip_ring", "Clipboard ring").build()?;
+    let logging_submenu = SubmenuBuilder::with_id(app, "logging", "Logging").build()?;
+    let wake_submenu = SubmenuBuilder::with_id(app, "wake", "Wake phrase")
+        .text(wake_word::TEACH_START_ID, "Teach start phrase…")
+        .text(wake_word::TEACH_STOP_ID, "Teach stop phrase…")
+        .text(wake_word::FORGET_ID, "Forget wake phrases")
+        .build()?;
+    app.manage(LogMenu::new(app.handle(), &logging_submenu, settings.logging.level)?);
+
+    #[cfg(debug_assertions)]
+    let mock_submenu = {
+        let submenu = SubmenuBuilder::with_id(app, "mock", "Mock backend").build()?;
+        app.manage(mock::MockMenu::new(
+            app.handle(),
+            &submenu,
+            mock::MockBackend::from_env().map(|mock| mock.outcome),
+        )?);
+        submenu
+    };
+
+    let session_item = MenuItem::with_id(
+        app,
+        SESSION_MENU_ID,
+        "Start dictation session",
+        true,
+        None::<&str>,
+    )?;
+
+    let collect_item = MenuItem::with_id(
+        app,
+        collect::COLLECT_MENU_ID,
+        "Start collecting",
+        true,
+        None::<&str>,
+    )?;
+    let discard_collected_item = MenuItem::with_id(
+        app,
+        collect::DISCARD_COLLECTED_ID,
+        "Discard collected text…",
+        false,
+        None::<&str>,
+    )?;
+
+    let quota_item = MenuItem::with_id(
+        app,
+        QUOTA_MENU_ID,
+        "Resume after quota error",
+        false,
+        None::<&str>,
+    )?;
+
+    let update_item = MenuItem::with_id(
+        app,
+        INSTALL_UPDATE_ID,
+        "Download & install update",
+        false,
+        None::<&str>,
+    )?;
+
+    let privacy_item = CheckMenuItem::with_id(
+        app,
+        PRIVACY_MENU_ID,
+        "Privacy mode",
+        true,
+        settings.behavior.privacy_mode,
+        None::<&str>,
+    )?;
+
+    let menu = MenuBuilder::new(app)
+        .item(&MenuItem::with_id(
+            app,
+            "toggle_recording",
+            "Toggle Recording",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            "discard_recording",
+            "Stop and discard recording",
+            true,
+            None::<&str>,
+        )?)
+        .item(&session_item)
+        .item(&collect_item)
+        .item(&discard_collected_item)
+        .item(&MenuItem::with_id(
+            app,
+            "cleanse",
+            "Polish clipboard",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            REPEAT_ID,
+            "Repeat last action",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            TRANSCRIBE_FILE_ID,
+            "Transcribe audio file…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&prompts_submenu)
+        .item(&recent_submenu)
+        .item(&pinned_submenu)
+        .item(&tag_submenu)
+        .item(&clip_ring_submenu)
+        .item(&wake_submenu)
+        .item(&MenuItem::with_id(
+            app,
+            "export_history",
+            "Export history…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            last_recording::RETRY_ID,
+            "Retry last recording",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            last_recording::SAVE_ID,
+            "Save last recording…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            REPORT_RESULT_ID,
+            "Report last result…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            "delete_everything",
+            "Delete everything now…",
+            true,
+            None::<&str>,
+        )?)
+        .separator()
+        .item(&quota_item)
+        .item(&privacy_item)
+        .item(&MenuItem::with_id(
+            app,
+            "export_settings",
+            "Export settings…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            "import_settings",
+            "Import settings…",
+            true,
+            None::<&str>,
+        )?)
+        .separator()
+        .item(&logging_submenu)
+        .item(&MenuItem::with_id(
+            app,
+            "view_logs",
+            "View logs…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            "open_log_folder",
+            "Open log folder",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            "reset_audio",
+            "Reset audio input",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            self_test::SELF_TEST_ID,
+            "Run self-test…",
+            true,
+            None::<&str>,
+        )?)
+        .item(&MenuItem::with_id(
+            app,
+            "open_window",
+            "Open Window",
+            true,
+            None::<&str>,
+        )?)
+        .separator()
+        .item(&update_item)
+        .item(&MenuItem::with_id(app, "quit", "Quit app", true, None::<&str>)?)
+        .build()?;
+    // Just above "Quit app"
+    #[cfg(debug_assertions)]
+    menu.insert(&mock_submenu, menu.items()?.len() - 1)?;
+
+    let tray_icon = TrayIconBuilder::new()
+        .icon(app.default_window_icon().unwrap().clone())
+        .show_menu_on_left_click(false)
+        .menu(&menu)
+        .build(app)?;
+
+    app.manage(RecentMenu::new(recent_submenu));
+    app.manage(PinnedMenu::new(pinned_submenu));
+    let tag_menu = TagMenu::new(tag_submenu);
+    tag_menu.refresh(app.handle(), &settings.behavior.quick_tags);
+    app.manage(tag_menu);
+    app.manage(PrivacyMenuItem::new(privacy_item));
+    app.manage(QuotaMenuItem::new(quota_item));
+    app.manage(DictationSession::new(session_item));
+    app.manage(Collector::new(app.handle(), collect_item, discard_collected_item));
+    app.manage(PendingUpdate::new(update_item));
+    let prompts_menu = PromptsMenu::new(prompts_submenu);
+    prompts_menu.refresh(app.handle(), prompts);
+    app.manage(prompts_menu);
+    app.manage(ClipRing::new(app.handle(), clip_ring_submenu));
+
+    Ok(tray_icon)
+}
+
+pub fn on_icon_event(app_handle: &AppHandle, event: TrayIconEvent) {
+    match event {
+        TrayIconEvent::Click {
+            button: MouseButton::Right,
+            button_state: MouseButtonState::Down,
+            ..
+        } => {
+            log::info!("Tray icon right clicked");
+            if let Err(e) = app_handle.show_menu() {
+                log::error!("Failed to show menu: {}", e);
+            }
+        }
+        TrayIconEvent::Click {
+            button: MouseButton::Left,
+            button_state: MouseButtonState::Down,
+            ..
+        } => {
+            toggle_recording(app_handle.clone(), None);
+        }
+        _ => {}
+    }
+}
+
+pub fn on_menu_event(app_handle: &AppHandle, event: MenuEvent) {
+    log::info!("Menu event received: {:?}", event.id);
+    match event.id.as_ref() {
+        "quit" => shutdown::quit(app_handle, "on user's request"),
+        "toggle_recording" => {
+            toggle_recording(app_handle.clone(), None);
+        }
+        "discard_recording" => {
+            cancel_recording(app_handle.clone());
+        }
+        "cleanse" => {
+            cleanse_clipboard(app_handle.clone(), None, PolishOverrides::default());
+        }
+        REPEAT_ID => {
+            repeat::repeat(app_handle);
+        }
+        wake_word::TEACH_START_ID => {
+            wake_word::teach(app_handle, wake_word::Phrase::Start);
+        }
+        wake_word::TEACH_STOP_ID => {
+            wake_word::teach(app_handle, wake_word::Phrase::Stop);
+        }
+        wake_word::FORGET_ID => {
+            wake_word::forget(app_handle);
+        }
+        last_recording::RETRY_ID => {
+            last_recording::retry(app_handle);
+        }
+        last_recording::SAVE_ID => {
+            last_recording::save(app_handle);
+        }
+        REPORT_RESULT_ID => {
+            saved_recordings::report_latest(app_handle);
+        }
+        SESSION_MENU_ID => {
+            session::toggle(app_handle);
+        }
+        collect::COLLECT_MENU_ID => {
+            collect::toggle(app_handle);
+        }
+        collect::DISCARD_COLLECTED_ID => {
+            collect::discard(app_handle);
+        }
+        TRANSCRIBE_FILE_ID => {
+            audio_file::pick(app_handle);
+        }
+        QUOTA_MENU_ID => {
+            quota::resume(app_handle);
+        }
+        INSTALL_UPDATE_ID => {
+            updater::install(app_handle);
+        }
+        "open_window" => {
+            open_main_window(app_handle);
+        }
+        "view_logs" => {
+            if let Err(e) = log_viewer::open(app_handle) {
+                log::error!("Failed to open log viewer: {e:#}");
+            }
+        }
+        "open_log_folder" => {
+            log_menu::reveal_log_file(app_handle);
+        }
+        "reset_audio" => {
+            let app_handle = app_handle.clone();
+            spawn(async move {
+                let notification = match reinit_audio(&app_handle).await {
+                    Ok(device) => Notification::AudioReset { device },
+                    Err(e) if e.is::<StillRecording>() => {
+                        log::warn!("Not resetting audio: {e:#}");
+                        Notification::AudioResetRefused
+                    }
+                    Err(e) => {
+                        log::error!("Failed to reset audio: {e:#}");
+                        Notification::NoInputDevice
+                    }
+                };
+                AppNotifications::new(&app_handle).notify(notification);
+            });
+        }
+        DIAGNOSTICS_ID => {
+            log_menu::collect_diagnostics(app_handle);
+        }
+        self_test::SELF_TEST_ID => {
+            self_test::run(app_handle);
+        }
+        id if id.starts_with(LOG_LEVEL_ID_PREFIX) => {
+            log_menu::select(app_handle, &id[LOG_LEVEL_ID_PREFIX.len()..]);
+        }
+        #[cfg(debug_assertions)]
+        id if id.starts_with(mock::MOCK_ID_PREFIX) => {
+            mock::select(app_handle, &id[mock::MOCK_ID_PREFIX.len()..]);
+        }
+        "export_history" => {
+            let app_handle = app_handle.clone();
+            app_handle
+                .dialog()
+                .file()
+                .add_filter("Markdown", &["md"])
+                .add_filter("JSON", &["json"])
+                .set_file_name("whistle-history.md")
+                .save_file(move |path| {
+                    let Some(path) = path.and_then(|p| p.into_path().ok()) else {
+                        return;
+                    };
+                    spawn(async move {
+                        match commands::export_history_to(
+                            &app_handle,
+                            path.clone(),
+                            None,
+                            None,
+                        )
+                        .await
+                        {
+                            Ok(count) => show_history_exported(&app_handle, path, count),
+                            Err(e) => {
+                                log::error!("Failed to export history: {e:#}");
+                                AppNotifications::new(&app_handle)
+                                    .notify(Notification::HistoryExportFailed);
+                            }
+                        }
+                    });
+                });
+        }
+        "delete_everything" => {
+            let app_handle = app_handle.clone();
+            app_handle
+                .dialog()
+                .message(
+                    "This permanently deletes all recordings, history (including \
+                     pinned entries) and logs.",
+                )
+                .title("Delete everything?")
+                .buttons(MessageDialogButtons::OkCancelCustom(
+                    "Delete".into(),
+                    "Cancel".into(),
+                ))
+                .show(move |confirmed| {
+                    if !confirmed {
+                        return;
+                    }
+                    spawn(async move {
+                        if let Err(e) = retention::delete_everything(&app_handle).await {
+                            log::error!("Failed to delete everything: {e:#}");
+                            return;
+                        }
+                        refresh_history_menus(app_handle.clone());
+                        app_handle.state::<ClipRing>().clear(&app_handle);
+                        AppNotifications::new(&app_handle)
+                            .notify(Notification::EverythingDeleted);
+                    });
+                });
+        }
+        "export_settings" => {
+            let app_handle = app_handle.clone();
+            app_handle
+                .dialog()
+                .file()
+                .add_filter("JSON", &["json"])
+                .set_file_name("whistle-settings.json")
+                .save_file(move |path| {
+                    let Some(path) = path.and_then(|p| p.into_path().ok()) else {
+                        return;
+                    };
+                    if let Err(e) = commands::export_settings_to(&app_handle, &path, None)
+                    {
+                        log::error!("Failed to export settings: {e:#}");
+                        return;
+                    }
+                    AppNotifications::new(&app_handle)
+                        .notify(Notification::SettingsExported);
+                });
+        }
+        "import_settings" => {
+            let app_handle = app_handle.clone();
+            app_handle.dialog().file().add_filter("JSON", &["json"]).pick_file(
+                move |path| {
+                    let Some(path) = path.and_then(|p| p.into_path().ok()) else {
+                        return;
+                    };
+                    confirm_settings_import(app_handle.clone(), path);
+                },
+            );
+        }
+        id if id.starts_with(PROMPT_ID_PREFIX) => {
+            prompts::run(app_handle, &id[PROMPT_ID_PREFIX.len()..]);
+        }
+        PRIVACY_MENU_ID => {
+            let enabled = !privacy::enabled(app_handle);
+            if let Err(e) = privacy::set_enabled(app_handle, enabled) {
+                log::error!("Failed to toggle privacy mode: {e:#}");
+            }
+            // Clicking already flipped the check mark, make sure it matches
+            app_handle
+                .state::<PrivacyMenuItem>()
+                .set_checked(privacy::enabled(app_handle));
+        }
+        id if id.starts_with(TAG_ID_PREFIX) => {
+            tag_latest_entry(app_handle.clone(), id[TAG_ID_PREFIX.len()..].into());
+        }
+        RING_CYCLE_ID => {
+            clip_ring::cycle(app_handle);
+        }
+        id if id.starts_with(RING_ID_PREFIX) => {
+            let Ok(index) = id[RING_ID_PREFIX.len()..].parse::<usize>() else {
+                log::warn!("Invalid clipboard ring menu id: {}", id);
+                return;
+            };
+            clip_ring::copy_index(app_handle, index);
+        }
+        id if id.starts_with(RECENT_ID_PREFIX) => {
+            let Some(entry_id) = menu_entry_id(id, RECENT_ID_PREFIX) else {
+                log::warn!("Invalid recent menu id: {}", id);
+                return;
+            };
+            copy_history_entry(app_handle.clone(), entry_id);
+        }
+        id if id.starts_with(PINNED_ID_PREFIX) => {
+            let Some(entry_id) = menu_entry_id(id, PINNED_ID_PREFIX) else {
+                log::warn!("Invalid pinned menu id: {}", id);
+                return;
+            };
+            use_pinned_entry(app_handle.clone(), entry_id);
+        }
+        id if id.starts_with(PIN_ID_PREFIX) || id.starts_with(UNPIN_ID_PREFIX) => {
+            let (pin, entry_id) = match menu_entry_id(id, PIN_ID_PREFIX) {
+                Some(entry_id) => (Some((None, false)), Some(entry_id)),
+                None => (None, menu_entry_id(id, UNPIN_ID_PREFIX)),
+            };
+            let Some(entry_id) = entry_id else {
+                log::warn!("Invalid pin menu id: {}", id);
+                return;
+            };
+            let app_handle = app_handle.clone();
+            spawn(async move {
+                if let Err(e) = set_entry_pinned(&app_handle, entry_id, pin).await {
+                    log::error!("Failed to update pin: {e:#}");
+                }
+            });
+        }
+        id => {
+            log::warn!("Unknown menu event: {}", id);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::{ReleaseChannel, SettingsState};
+use crate::transcribe_icon::TranscribeIcon;
+use anyhow::Result;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Url, Wry, async_runtime::spawn, menu::MenuItem};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+pub const INSTALL_UPDATE_ID: &str = "install_update";
+/// Stands for the release channel in the endpoints of `plugins.updater` in
+/// tauri.conf.json, each channel publishes its manifest on a rolling release
+const CHANNEL_PLACEHOLDER: &str = "{{channel}}";
+/// Checks are spread out, but the setting is re-read this often
+const SETTINGS_POLL: Duration = Duration::from_secs(60 * 60);
+const IDLE_POLL: Duration = Duration::from_secs(5);
+const NOTES_PREVIEW_CHARS: usize = 200;
+
+/// An update found by the last check, waiting for "Download & install"
+pub struct PendingUpdate {
+    update: Mutex<Option<Update>>,
+    item: MenuItem<Wry>,
+}
+
+impl PendingUpdate {
+    pub fn new(item: MenuItem<Wry>) -> Self {
+        Self {
+            update: Mutex::new(None),
+            item,
+        }
+    }
+
+    fn set(&self, update: Option<Update>) {
+        let text = match &update {
+            Some(update) => format!("Download & install {}", update.version),
+            None => "Download & install update".into(),
+        };
+        if let Err(e) = self
+            .item
+            .set_text(text)
+            .and_then(|()| self.item.set_enabled(update.is_some()))
+        {
+            log::error!("Unable to update the install menu item: {e}");
+        }
+        *self.update.lock().unwrap() = update;
+    }
+}
+
+/// The endpoints from `plugins.updater` in tauri.conf.json, or `None` when the build
+/// has no public key to verify updates with
+fn configured_endpoints(app_handle: &AppHandle) -> Option<Vec<String>> {
+    let config = app_handle.config().plugins.0.get("updater")?;
+    let pubkey = config.get("pubkey")?.as_str()?;
+    if pubkey.trim().is_empty() {
+        return None;
+    }
+    let endpoints = config.get("endpoints")?.as_array()?;
+    Some(
+        endpoints
+            .iter()
+            .filter_map(|e| e.as_str().map(str::to_string))
+            .collect(),
+    )
+}
+
+/// Checks for updates at startup and then every `updates.check_interval_hours`, as
+/// long as `updates.enabled` is on. Builds without an updater public key never check.
+pub fn schedule(app_handle: AppHandle) {
+    let Some(endpoints) = configured_endpoints(&app_handle) else {
+        log::info!("Updates are off, this build has no updater public key");
+        return;
+    };
+    spawn(async move {
+        let mut since_check: Option<Duration> = None;
+        loop {
+            let updates = app_handle.state::<SettingsState>().get().updates.clone();
+            let interval = Duration::from_secs(updates.check_interval_hours * 60 * 60);
+            if updates.enabled && since_check.is_none_or(|since| since >= interval) {
+                since_check = Some(Duration::ZERO);
+                if let Err(e) = check(&app_handle, &endpoints, updates.channel).await {
+                    log::error!("Failed to check for updates: {e:#}");
+                }
+            }
+            tokio::time::sleep(SETTINGS_POLL).await;
+            since_check = since_check.map(|since| since + SETTINGS_POLL);
+        }
+    });
+}
+
+async fn check(
+    app_handle: &AppHandle,
+    endpoints: &[String],
+    channel: ReleaseChannel,
+) -> Result<()> {
+    let name = match channel {
+        ReleaseChannel::Stable => "stable",
+        ReleaseChannel::Beta => "beta",
+    };
+    let endpoints = endpoints
+        .iter()
+        .map(|e| Url::parse(&e.replace(CHANNEL_PLACEHOLDER, name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    log::info!("Checking for {channel:?} updates");
+    let update = app_handle
+        .updater_builder()
+        .endpoints(endpoints)?
+        .build()?
+        .check()
+        .await?;
+    let Some(update) = update else {
+        log::info!("No update available");
+        return Ok(());
+    };
+
+    log::info!("Update {} is available", update.version);
+    let version = update.version.clone();
+    let notes: String = update
+        .body
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .take(NOTES_PREVIEW_CHARS)
+        .collect();
+    app_handle.state::<PendingUpdate>().set(Some(update));
+
+    wait_until_idle(app_handle).await;
+    AppNotifications::new(app_handle)
+        .notify(Notification::UpdateAvailable { version, notes });
+    Ok(())
+}
+
+/// Downloads the pending update, which the plugin verifies against the configured
+/// public key before installing, then restarts. Waits for any recording to finish.
+pub fn install(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        let Some(update) =
+            app_handle.state::<PendingUpdate>().update.lock().unwrap().take()
+        else {
+            return;
+        };
+        app_handle.state::<PendingUpdate>().set(None);
+        wait_until_idle(&app_handle).await;
+
+        log::info!("Installing update {}", update.version);
+        match update.download_and_install(|_, _| {}, || {}).await {
+            Ok(()) => app_handle.restart(),
+            Err(e) => {
+                log::error!("Failed to install update {}: {e:#}", update.version);
+                AppNotifications::new(&app_handle).notify(Notification::UpdateFailed);
+            }
+        }
+    });
+}
+
+/// Nothing is being recorded, transcribed or polished
+async fn wait_until_idle(app_handle: &AppHandle) {
+    loop {
+        if !app_handle.state::<TranscribeIcon>().state().is_busy() {
+            return;
+        }
+        tokio::time::sleep(IDLE_POLL).await;
+    }
+}
@@ -0,0 +1,197 @@
+use crate::audio_recorder;
+use crate::ogg_opus;
+use crate::transcribe_client::{Transcription, TranscriptionBackend};
+use anyhow::Result;
+use std::time::Duration;
+
+/// Uploads stay under the 25 MB most providers accept, longer audio is sent in parts
+pub const MAX_UPLOAD_BYTES: usize = 24 * 1024 * 1024;
+/// WAV header size, the rest of an upload is 16-bit mono samples
+const WAV_HEADER_BYTES: usize = 44;
+/// Length of each part when uploading in parts, short enough that one slow or failed
+/// request doesn't hold up the whole recording for long
+const PART_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// How audio is sent, `Default` is one WAV upload as long as it fits
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UploadPlan {
+    /// Compress to Ogg Opus first
+    pub compress: bool,
+    /// Send it in `PART_DURATION` parts and join the transcripts
+    pub chunk: bool,
+}
+
+/// Sends a WAV recording the way `plan` says
+pub async fn transcribe_wav(
+    backend: &impl TranscriptionBackend,
+    wav: Vec<u8>,
+    model: Option<&str>,
+    plan: UploadPlan,
+) -> Result<Transcription> {
+    if plan == UploadPlan::default() {
+        return backend.transcribe(wav, model).await;
+    }
+    let (samples, sample_rate) =
+        tokio::task::spawn_blocking(move || audio_recorder::wav_mono(&wav)).await??;
+    transcribe_samples(backend, &samples, sample_rate, model, plan).await
+}
+
+/// Sends mono samples, split into parts when `plan` asks for it or they wouldn't fit
+/// in one upload. The transcripts are joined, and the language and model are the first
+/// ones reported.
+pub async fn transcribe_samples(
+    backend: &impl TranscriptionBackend,
+    samples: &[i16],
+    sample_rate: u32,
+    model: Option<&str>,
+    plan: UploadPlan,
+) -> Result<Transcription> {
+    let parts = parts(samples, sample_rate, plan);
+    if parts.len() > 1 {
+        log::info!("Sending the audio in {} parts", parts.len());
+    }
+    let mut texts = Vec::new();
+    let mut joined: Option<Transcription> = None;
+    for part in parts {
+        let part = part.to_vec();
+        let bytes = tokio::task::spawn_blocking(move || {
+            if plan.compress {
+                ogg_opus::encode(&part, sample_rate)
+            } else {
+                audio_recorder::encode_wav(&part, sample_rate, 1)
+            }
+        })
+        .await??;
+        let transcription = backend.transcribe(bytes, model).await?;
+        texts.push(transcription.text.trim().to_string());
+        joined = Some(match joined {
+            Some(joined) => Transcription {
+                language: joined.language.or(transcription.language),
+                language_probability: joined
+                    .language_probability
+                    .or(transcription.language_probability),
+                model: joined.model.or(transcription.model),
+                ..joined
+            },
+            None => transcription,
+        });
+    }
+    let mut transcription = joined.expect("there is always at least one part");
+    texts.retain(|text| !text.is_empty());
+    transcription.text = texts.join(" ");
+    Ok(transcription)
+}
+
+/// Where the audio is cut, always at least one part so an empty file still gets an
+/// answer
+fn parts(samples: &[i16], sample_rate: u32, plan: UploadPlan) -> Vec<&[i16]> {
+    let mut part_len = (MAX_UPLOAD_BYTES - WAV_HEADER_BYTES) / 2;
+    if plan.chunk {
+        part_len = part_len.min(PART_DURATION.as_secs() as usize * sample_rate as usize);
+    }
+    if samples.is_empty() {
+        return vec![samples];
+    }
+    samples.chunks(part_len).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe_client::UploadFormat;
+    use std::sync::Mutex;
+
+    /// Answers each upload with its number, keeping what was sent
+    #[derive(Default)]
+    struct Recorder {
+        uploads: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl TranscriptionBackend for Recorder {
+        fn upload_format(&self) -> Option<UploadFormat> {
+            None
+        }
+
+        async fn transcribe(
+            &self,
+            recording: Vec<u8>,
+            _model: Option<&str>,
+        ) -> Result<Transcription> {
+            let mut uploads = self.uploads.lock().unwrap();
+            uploads.push(recording);
+            Ok(Transcription {
+                text: format!(" part {} ", uploads.len()),
+                language: (uploads.len() == 2).then(|| "de".to_string()),
+                language_probability: None,
+                model: Some(format!("model {}", uploads.len())),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn the_default_plan_sends_the_wav_as_it_is() {
+        let wav = audio_recorder::encode_wav(&[1, 2, 3, 4], 44100, 2).unwrap();
+        let backend = Recorder::default();
+        let transcription =
+            transcribe_wav(&backend, wav.clone(), None, UploadPlan::default())
+                .await
+                .unwrap();
+        assert_eq!(transcription.text, " part 1 ");
+        assert_eq!(*backend.uploads.lock().unwrap(), [wav]);
+    }
+
+    #[tokio::test]
+    async fn compresses_to_ogg_opus() {
+        let wav = audio_recorder::encode_wav(&vec![500; 16000], 16000, 1).unwrap();
+        let backend = Recorder::default();
+        let plan = UploadPlan {
+            compress: true,
+            chunk: false,
+        };
+        transcribe_wav(&backend, wav.clone(), None, plan).await.unwrap();
+        let uploads = backend.uploads.lock().unwrap();
+        assert_eq!(uploads.len(), 1);
+        assert!(uploads[0].starts_with(b"OggS"));
+        assert!(uploads[0].len() < wav.len() / 5);
+    }
+
+    #[tokio::test]
+    async fn sends_long_audio_in_parts_and_joins_the_transcripts() {
+        // Two full parts and a bit
+        let sample_rate = 1000;
+        let samples = vec![0; PART_DURATION.as_secs() as usize * 2000 + 10];
+        let backend = Recorder::default();
+        let plan = UploadPlan {
+            compress: false,
+            chunk: true,
+        };
+        let transcription =
+            transcribe_samples(&backend, &samples, sample_rate, Some("large"), plan)
+                .await
+                .unwrap();
+        assert_eq!(transcription.text, "part 1 part 2 part 3");
+        assert_eq!(transcription.language.as_deref(), Some("de"));
+        assert_eq!(transcription.model.as_deref(), Some("model 1"));
+
+        let uploads = backend.uploads.lock().unwrap();
+        let durations: Vec<_> = uploads
+            .iter()
+            .map(|wav| audio_recorder::wav_duration(wav).unwrap().as_millis())
+            .collect();
+        assert_eq!(durations, [300_000, 300_000, 10]);
+    }
+
+    #[test]
+    fn splits_only_when_asked_or_too_big() {
+        let samples = vec![0; 16000 * 60 * 10];
+        assert_eq!(parts(&samples, 16000, UploadPlan::default()).len(), 1);
+        let chunked = UploadPlan {
+            compress: true,
+            chunk: true,
+        };
+        assert_eq!(parts(&samples, 16000, chunked).len(), 2);
+        let too_big = vec![0; MAX_UPLOAD_BYTES / 2 + 1];
+        assert_eq!(parts(&too_big, 16000, UploadPlan::default()).len(), 2);
+        assert_eq!(parts(&[], 16000, chunked), [&[] as &[i16]]);
+    }
+}
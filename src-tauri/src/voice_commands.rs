@@ -0,0 +1,255 @@
+use crate::settings::VoiceCommandSettings;
+
+/// Symbols that end up after the word they follow, e.g. "hello comma" is "hello,"
+const CLOSING: &[char] = &[
+    '.', ',', ';', ':', '!', '?', ')', ']', '}', '…', '”', '’', '»',
+];
+/// Symbols the next word attaches to
+const OPENING: &[char] = &['(', '[', '{', '“', '‘', '«'];
+/// Punctuation a backend tends to put around a spoken command, like "Hello, comma,"
+const STRAY: &[char] = &[',', '.'];
+
+/// Turns spoken commands like "new line" or "comma" into what they stand for. Phrases
+/// match whole words ignoring case, the longest wins where several overlap, and the
+/// escape word before a phrase keeps it as words. The spaces around each symbol are
+/// fixed up so "hello comma world" reads "hello, world".
+pub fn apply(text: String, settings: &VoiceCommandSettings) -> String {
+    if !settings.enabled || settings.commands.is_empty() {
+        return text;
+    }
+
+    let mut commands: Vec<(Vec<Vec<char>>, &str)> = settings
+        .commands
+        .iter()
+        .map(|(phrase, symbol)| (words(phrase), symbol.as_str()))
+        .filter(|(phrase, _)| !phrase.is_empty())
+        .collect();
+    let length = |phrase: &[Vec<char>]| phrase.iter().map(Vec::len).sum::<usize>();
+    commands.sort_by_key(|(phrase, _)| std::cmp::Reverse(length(phrase)));
+    let escape = words(&settings.escape);
+
+    let chars: Vec<char> = text.chars().collect();
+    let lower = lowercase(&text);
+    let find_command = |at: usize| {
+        commands.iter().find_map(|(phrase, symbol)| {
+            Some((match_phrase(&lower, at, phrase)?, *symbol))
+        })
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut quote_open = false;
+    // Where the last spoken symbol ended, so the next one doesn't take it for a stray
+    let mut spoken_end = None;
+    let mut skip_spaces = false;
+    let mut count = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if i == 0 || !chars[i - 1].is_alphanumeric() {
+            // "literally new line" keeps the words, dropping "literally"
+            let escaped = match escape.is_empty() {
+                true => None,
+                false => match_phrase(&lower, i, &escape)
+                    .map(|end| skip_whitespace(&chars, end))
+                    .and_then(|start| Some((start, find_command(start)?.0))),
+            };
+            if let Some((start, end)) = escaped {
+                out.extend(&chars[start..end]);
+                skip_spaces = false;
+                i = end;
+                continue;
+            }
+            if let Some((end, symbol)) = find_command(i) {
+                skip_spaces = insert(&mut out, symbol, &mut quote_open, spoken_end);
+                spoken_end = Some(out.len());
+                count += 1;
+                i = end;
+                if chars.get(i).is_some_and(|c| STRAY.contains(c)) {
+                    i += 1;
+                }
+                continue;
+            }
+        }
+        if skip_spaces && is_space(chars[i]) {
+            i += 1;
+            continue;
+        }
+        skip_spaces = false;
+        out.push(chars[i]);
+        i += 1;
+    }
+
+    if count > 0 {
+        log::debug!("Applied {count} voice commands");
+    }
+    out
+}
+
+/// Appends `symbol` with the spacing its kind needs. Returns whether the spaces that
+/// follow should be dropped.
+fn insert(
+    out: &mut String,
+    symbol: &str,
+    quote_open: &mut bool,
+    spoken_end: Option<usize>,
+) -> bool {
+    let is_break = symbol.contains('\n') && symbol.chars().all(char::is_whitespace);
+    let is_quote = symbol == "\"" || symbol == "'";
+    let closes = match is_quote {
+        true => *quote_open,
+        false => is_break || symbol.starts_with(CLOSING),
+    };
+    let opens = match is_quote {
+        true => !*quote_open,
+        false => is_break || symbol.ends_with(OPENING),
+    };
+    if is_quote {
+        *quote_open = !*quote_open;
+    }
+
+    if closes {
+        out.truncate(out.trim_end_matches(is_space).len());
+        // The backend's guess at the punctuation gives way to the spoken one
+        if !is_quote && !is_break && out.ends_with(STRAY) && spoken_end != Some(out.len())
+        {
+            out.pop();
+        }
+    }
+    out.push_str(symbol);
+    opens
+}
+
+/// Where `phrase` ends if it starts at `at`. Its words may be separated by any run of
+/// whitespace, and it must not run into a longer word.
+fn match_phrase(lower: &[char], at: usize, phrase: &[Vec<char>]) -> Option<usize> {
+    let mut pos = at;
+    for (n, word) in phrase.iter().enumerate() {
+        if n > 0 {
+            let next = skip_whitespace(lower, pos);
+            if next == pos {
+                return None;
+            }
+            pos = next;
+        }
+        if !lower[pos..].starts_with(word) {
+            return None;
+        }
+        pos += word.len();
+    }
+    lower.get(pos).is_none_or(|c| !c.is_alphanumeric()).then_some(pos)
+}
+
+fn skip_whitespace(chars: &[char], mut pos: usize) -> usize {
+    while chars.get(pos).is_some_and(|c| c.is_whitespace()) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Whitespace other than line breaks, which may come from a command
+fn is_space(c: char) -> bool {
+    c.is_whitespace() && c != '\n'
+}
+
+fn words(phrase: &str) -> Vec<Vec<char>> {
+    phrase.split_whitespace().map(lowercase).collect()
+}
+
+/// One char per char, so indices line up with the original text
+fn lowercase(text: &str) -> Vec<char> {
+    text.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_default(text: &str) -> String {
+        let settings = VoiceCommandSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        apply(text.into(), &settings)
+    }
+
+    #[test]
+    fn replaces_commands_with_their_symbols() {
+        assert_eq!(apply_default("hello comma world"), "hello, world");
+        assert_eq!(apply_default("Is it question mark"), "Is it?");
+        assert_eq!(apply_default("one new line two"), "one\ntwo");
+        assert_eq!(apply_default("one new paragraph two"), "one\n\ntwo");
+    }
+
+    #[test]
+    fn matches_whole_words_ignoring_case() {
+        assert_eq!(apply_default("Hello Comma world"), "Hello, world");
+        assert_eq!(apply_default("hello NEW   LINE world"), "hello\nworld");
+        assert_eq!(apply_default("commas are periodic"), "commas are periodic");
+        assert_eq!(apply_default("a newline"), "a newline");
+    }
+
+    #[test]
+    fn applies_adjacent_commands() {
+        assert_eq!(apply_default("wait question mark exclamation mark"), "wait?!");
+        assert_eq!(apply_default("hello comma new line world"), "hello,\nworld");
+        assert_eq!(apply_default("so period period period"), "so...");
+        assert_eq!(
+            apply_default("open paren open quote hi close quote close paren"),
+            "(\"hi\")"
+        );
+    }
+
+    #[test]
+    fn fixes_spacing_around_punctuation() {
+        assert_eq!(
+            apply_default("an open paren aside close paren here"),
+            "an (aside) here"
+        );
+        assert_eq!(
+            apply_default("he said open quote hi close quote then left"),
+            "he said \"hi\" then left"
+        );
+        assert_eq!(apply_default("list colon a semicolon b"), "list: a; b");
+    }
+
+    #[test]
+    fn replaces_the_backends_punctuation_around_a_command() {
+        assert_eq!(apply_default("Hello, comma, world."), "Hello, world.");
+        assert_eq!(apply_default("Is it, question mark."), "Is it?");
+        assert_eq!(apply_default("Done. Period."), "Done.");
+    }
+
+    #[test]
+    fn handles_commands_at_the_start_and_end() {
+        assert_eq!(apply_default("comma"), ",");
+        assert_eq!(apply_default("period hello"), ". hello");
+        assert_eq!(apply_default("new line hello"), "\nhello");
+        assert_eq!(apply_default("hello new line"), "hello\n");
+        assert_eq!(apply_default("hello period"), "hello.");
+    }
+
+    #[test]
+    fn escape_keeps_the_words() {
+        assert_eq!(apply_default("say literally new line please"), "say new line please");
+        assert_eq!(apply_default("literally comma"), "comma");
+        assert_eq!(apply_default("literally speaking comma"), "literally speaking,");
+    }
+
+    #[test]
+    fn longest_phrase_wins() {
+        let settings = VoiceCommandSettings {
+            enabled: true,
+            escape: String::new(),
+            commands: [("new", "N"), ("new line", "\n")]
+                .into_iter()
+                .map(|(phrase, symbol)| (phrase.into(), symbol.into()))
+                .collect(),
+        };
+        assert_eq!(apply("a new line b new c".into(), &settings), "a\nb N c");
+    }
+
+    #[test]
+    fn disabled_leaves_the_text_alone() {
+        let settings = VoiceCommandSettings::default();
+        assert_eq!(apply("hello comma world".into(), &settings), "hello comma world");
+    }
+}
@@ -0,0 +1,499 @@
+use crate::local_task_handler::Task;
+use crate::notifications::{AppNotifications, Notification};
+use crate::paths;
+use crate::privacy;
+use crate::quota;
+use crate::settings::SettingsState;
+use crate::stats;
+use crate::transcribe_icon::{Icon, TranscribeIcon};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::sync::mpsc;
+
+pub const TEACH_START_ID: &str = "wake_teach_start";
+pub const TEACH_STOP_ID: &str = "wake_teach_stop";
+pub const FORGET_ID: &str = "wake_forget";
+
+/// The taught examples, in the data directory
+const PHRASES_FILE: &str = "wake-phrases.json";
+/// Audio is boiled down to one frame of features this long
+const FRAME: Duration = Duration::from_millis(20);
+/// Frames waiting between the audio callback and the listener, about two seconds.
+/// Past this they're dropped, nothing more is ever held.
+const QUEUE_FRAMES: usize = 100;
+/// Speech longer than this, about two seconds, isn't a phrase
+const MAX_UTTERANCE_FRAMES: usize = 100;
+const MIN_UTTERANCE_FRAMES: usize = 15;
+/// Quiet frames kept before speech, so a soft first sound isn't cut
+const LEAD_IN_FRAMES: usize = 3;
+/// The pause that ends an utterance
+const TRAILING_SILENCE_FRAMES: usize = 15;
+/// How far above the noise floor a frame has to be to count as speech
+const SPEECH_ABOVE_FLOOR_DB: f32 = 12.0;
+/// How quickly the noise floor follows louder surroundings, per frame
+const FLOOR_RISE: f32 = 0.002;
+pub const MAX_EXAMPLES: usize = 3;
+const TEACH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What's kept of a frame of audio. Enough to tell phrases apart by their rhythm and
+/// sounds, not enough to get the audio or the words back.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Frame {
+    /// Loudness in dB
+    energy: f32,
+    /// Zero crossings per sample, high for hissing sounds like "s" and "t"
+    zcr: f32,
+    /// Loudness of the change from sample to sample, relative to `energy`. Higher for
+    /// bright sounds.
+    tilt: f32,
+}
+
+/// Turns samples into frames inside the audio callback, a few additions per sample
+pub struct FrameBuilder {
+    len: usize,
+    count: usize,
+    power: f64,
+    diff_power: f64,
+    crossings: usize,
+    last: f32,
+}
+
+impl FrameBuilder {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            len: ((sample_rate as f64 * FRAME.as_secs_f64()) as usize).max(1),
+            count: 0,
+            power: 0.0,
+            diff_power: 0.0,
+            crossings: 0,
+            last: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, sample: f32) -> Option<Frame> {
+        let diff = sample - self.last;
+        self.power += (sample * sample) as f64;
+        self.diff_power += (diff * diff) as f64;
+        if (sample >= 0.0) != (self.last >= 0.0) {
+            self.crossings += 1;
+        }
+        self.last = sample;
+        self.count += 1;
+        if self.count < self.len {
+            return None;
+        }
+        let count = self.count as f64;
+        let energy = db(self.power / count);
+        let frame = Frame {
+            energy,
+            zcr: self.crossings as f32 / self.count as f32,
+            tilt: db(self.diff_power / count) - energy,
+        };
+        self.count = 0;
+        self.power = 0.0;
+        self.diff_power = 0.0;
+        self.crossings = 0;
+        Some(frame)
+    }
+}
+
+fn db(power: f64) -> f32 {
+    (10.0 * power.max(1e-10).log10()) as f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phrase {
+    Start,
+    Stop,
+}
+
+/// Examples of each phrase, as taught from the tray
+#[derive(Default, Serialize, Deserialize)]
+struct Examples {
+    start: Vec<Vec<Frame>>,
+    stop: Vec<Vec<Frame>>,
+}
+
+impl Examples {
+    fn of(&mut self, phrase: Phrase) -> &mut Vec<Vec<Frame>> {
+        match phrase {
+            Phrase::Start => &mut self.start,
+            Phrase::Stop => &mut self.stop,
+        }
+    }
+}
+
+fn load(app_handle: &AppHandle) -> Result<Examples> {
+    let path = paths::data_file(app_handle, PHRASES_FILE)?;
+    if !path.exists() {
+        return Ok(Examples::default());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save(app_handle: &AppHandle, examples: &Examples) -> Result<()> {
+    let path = paths::data_file(app_handle, PHRASES_FILE)?;
+    std::fs::write(path, serde_json::to_vec(examples)?)?;
+    Ok(())
+}
+
+/// Hands-free start and stop. While armed the input stream stays open and is boiled
+/// down to frames on a thread of its own, which only compares anything once a short
+/// utterance ends in a pause. Nothing leaves the computer.
+#[derive(Default)]
+pub struct WakeWord {
+    /// Set from the tray, the next utterance becomes an example of this phrase
+    teaching: Mutex<Option<(Phrase, Instant)>>,
+    /// Acting on the phrases, as opposed to only listening to be taught
+    listening: AtomicBool,
+    /// The stop phrase ended the recording, so it's in the audio
+    stopped_by_phrase: AtomicBool,
+}
+
+/// The start phrase while listening, for the tray tooltip
+pub fn listening_for(app_handle: &AppHandle) -> Option<String> {
+    let wake = app_handle.try_state::<WakeWord>()?;
+    wake.listening
+        .load(Ordering::Relaxed)
+        .then(|| app_handle.state::<SettingsState>().get().wake.start_phrase.clone())
+}
+
+/// Whether a start phrase was taught, without which `wake.enabled` does nothing
+pub fn taught(app_handle: &AppHandle) -> bool {
+    load(app_handle).is_ok_and(|examples| !examples.start.is_empty())
+}
+
+/// Starts or stops listening to match `wake.enabled`, privacy mode and a quota pause.
+/// Teaching listens regardless. Each call starts a fresh listener with the current
+/// examples, the old one ends when its frames stop coming.
+pub fn apply(app_handle: &AppHandle) {
+    let wake = app_handle.state::<WakeWord>();
+    let settings = app_handle.state::<SettingsState>().get();
+    let examples = match load(app_handle) {
+        Ok(examples) => examples,
+        Err(e) => {
+            log::error!("Failed to load the wake phrases: {e:#}");
+            Examples::default()
+        }
+    };
+    let teaching = wake.teaching.lock().unwrap().is_some();
+    let listen = settings.wake.enabled
+        && !examples.start.is_empty()
+        && !privacy::enabled(app_handle)
+        && !quota::paused(app_handle);
+    if settings.wake.enabled && examples.start.is_empty() {
+        log::warn!("`wake.enabled` is on, but no start phrase was taught yet");
+    }
+    let was_listening = wake.listening.swap(listen, Ordering::Relaxed);
+    if was_listening != listen {
+        log::info!(
+            "{} for the wake phrase",
+            if listen {
+                "Listening"
+            } else {
+                "Stopped listening"
+            }
+        );
+    }
+
+    let tap = (listen || teaching).then(|| start_listener(app_handle, examples, listen));
+    let tx_task = app_handle.state::<mpsc::Sender<Task>>().inner().clone();
+    spawn(async move {
+        if tx_task.send(Task::WakeTap(tap.flatten())).await.is_err() {
+            log::error!("Failed to send 'WakeTap' task to channel");
+        }
+    });
+    app_handle.state::<TranscribeIcon>().set_armed(listen);
+    stats::refresh_tooltip(app_handle.clone());
+}
+
+/// The sender goes to the audio callback
+fn start_listener(
+    app_handle: &AppHandle,
+    examples: Examples,
+    act: bool,
+) -> Option<SyncSender<Frame>> {
+    let (tx, rx) = sync_channel(QUEUE_FRAMES);
+    let app_handle = app_handle.clone();
+    let spawned = std::thread::Builder::new()
+        .name("wake-word".into())
+        .spawn(move || listen(app_handle, rx, examples, act));
+    match spawned {
+        Ok(_) => Some(tx),
+        Err(e) => {
+            log::error!("Failed to start the wake phrase listener: {e}");
+            None
+        }
+    }
+}
+
+fn listen(app_handle: AppHandle, frames: Receiver<Frame>, examples: Examples, act: bool) {
+    let mut detector = Detector::default();
+    while let Ok(frame) = frames.recv() {
+        if let Some(utterance) = detector.push(frame) {
+            heard(&app_handle, utterance, &examples, act);
+        }
+    }
+    log::debug!("Wake phrase listener ended");
+}
+
+fn heard(app_handle: &AppHandle, utterance: Vec<Frame>, examples: &Examples, act: bool) {
+    let wake = app_handle.state::<WakeWord>();
+    let teaching = wake.teaching.lock().unwrap().take();
+    if let Some((phrase, _)) = teaching {
+        learn(app_handle, phrase, utterance);
+        return;
+    }
+    if !act {
+        return;
+    }
+    // Sessions and live dictation are stopped the same way as a recording
+    let recording = matches!(
+        app_handle.state::<TranscribeIcon>().current(),
+        Icon::Starting | Icon::Recording | Icon::Session | Icon::Live
+    );
+    let (phrase, candidates) = match recording {
+        true => (Phrase::Stop, &examples.stop),
+        false => (Phrase::Start, &examples.start),
+    };
+    let best = candidates
+        .iter()
+        .map(|example| distance(&utterance, example))
+        .fold(f32::INFINITY, f32::min);
+    let sensitivity = app_handle.state::<SettingsState>().get().wake.sensitivity;
+    let max = max_distance(sensitivity);
+    log::debug!(
+        "Heard {} frames, {best:.3} from the {phrase:?} phrase (at most {max:.3})",
+        utterance.len()
+    );
+    if best > max {
+        return;
+    }
+    log::info!("Heard the {phrase:?} phrase");
+    if phrase == Phrase::Stop {
+        wake.stopped_by_phrase.store(true, Ordering::Relaxed);
+    }
+    crate::toggle_recording(app_handle.clone(), None);
+}
+
+/// The tray's "Teach start phrase…" and "Teach stop phrase…": the next thing said
+/// becomes an example of the phrase. Up to `MAX_EXAMPLES` are kept, the oldest is
+/// replaced.
+pub fn teach(app_handle: &AppHandle, phrase: Phrase) {
+    let started = Instant::now();
+    *app_handle.state::<WakeWord>().teaching.lock().unwrap() = Some((phrase, started));
+    apply(app_handle);
+    AppNotifications::new(app_handle).notify(Notification::WakeTeach {
+        phrase: phrase_text(app_handle, phrase),
+    });
+
+    let app_handle = app_handle.clone();
+    spawn(async move {
+        tokio::time::sleep(TEACH_TIMEOUT).await;
+        let wake = app_handle.state::<WakeWord>();
+        let mut teaching = wake.teaching.lock().unwrap();
+        if teaching.is_none_or(|(_, since)| since != started) {
+            return;
+        }
+        *teaching = None;
+        drop(teaching);
+        log::info!("Nothing was said while teaching the {phrase:?} phrase");
+        AppNotifications::new(&app_handle).notify(Notification::WakeTeachTimedOut);
+        apply(&app_handle);
+    });
+}
+
+fn learn(app_handle: &AppHandle, phrase: Phrase, utterance: Vec<Frame>) {
+    let mut examples = load(app_handle).unwrap_or_default();
+    let list = examples.of(phrase);
+    if list.len() >= MAX_EXAMPLES {
+        list.remove(0);
+    }
+    list.push(utterance);
+    let count = list.len();
+    if let Err(e) = save(app_handle, &examples) {
+        log::error!("Failed to save the wake phrases: {e:#}");
+        return;
+    }
+    log::info!("Learned an example of the {phrase:?} phrase, {count} kept");
+    AppNotifications::new(app_handle).notify(Notification::WakeLearned {
+        phrase: phrase_text(app_handle, phrase),
+        examples: count,
+    });
+    apply(app_handle);
+}
+
+/// The tray's "Forget wake phrases"
+pub fn forget(app_handle: &AppHandle) {
+    let removed = paths::data_file(app_handle, PHRASES_FILE).and_then(|path| {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    });
+    match removed {
+        Ok(()) => log::info!("Forgot the wake phrases"),
+        Err(e) => log::error!("Failed to forget the wake phrases: {e:#}"),
+    }
+    apply(app_handle);
+}
+
+fn phrase_text(app_handle: &AppHandle, phrase: Phrase) -> String {
+    let wake = app_handle.state::<SettingsState>().get().wake.clone();
+    match phrase {
+        Phrase::Start => wake.start_phrase,
+        Phrase::Stop => wake.stop_phrase,
+    }
+}
+
+/// Cuts the stop phrase from the end of the transcript of a recording it ended. Left
+/// alone when the transcript doesn't end with it.
+pub fn strip_stop_phrase(app_handle: &AppHandle, text: String) -> String {
+    let Some(wake) = app_handle.try_state::<WakeWord>() else {
+        return text;
+    };
+    if !wake.stopped_by_phrase.swap(false, Ordering::Relaxed) {
+        return text;
+    }
+    let phrase = app_handle.state::<SettingsState>().get().wake.stop_phrase.clone();
+    let mut rest = text.trim_end();
+    for expected in phrase.split_whitespace().rev() {
+        let start = rest
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        if normalize(&rest[start..]) != normalize(expected) {
+            log::info!("The transcript doesn't end with the stop phrase");
+            return text;
+        }
+        rest = rest[..start].trim_end();
+    }
+    rest.to_string()
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Finds utterances in the frames: speech with a pause after it, short enough to be
+/// a phrase
+#[derive(Default)]
+struct Detector {
+    /// Follows the quietest frames, dropping at once and rising slowly
+    floor: Option<f32>,
+    frames: VecDeque<Frame>,
+    speech: usize,
+    silent: usize,
+    /// Speech went on too long to be a phrase, ignored until the next pause
+    overlong: bool,
+}
+
+impl Detector {
+    fn push(&mut self, frame: Frame) -> Option<Vec<Frame>> {
+        let floor = self.floor.get_or_insert(frame.energy);
+        *floor = match frame.energy < *floor {
+            true => frame.energy,
+            false => *floor + FLOOR_RISE * (frame.energy - *floor),
+        };
+        let is_speech = frame.energy > *floor + SPEECH_ABOVE_FLOOR_DB;
+
+        if self.overlong {
+            self.silent = if is_speech { 0 } else { self.silent + 1 };
+            if self.silent >= TRAILING_SILENCE_FRAMES {
+                self.overlong = false;
+                self.silent = 0;
+            }
+            return None;
+        }
+        if !is_speech && self.speech == 0 {
+            self.frames.push_back(frame);
+            if self.frames.len() > LEAD_IN_FRAMES {
+                self.frames.pop_front();
+            }
+            return None;
+        }
+
+        self.frames.push_back(frame);
+        if is_speech {
+            self.speech += 1;
+            self.silent = 0;
+        } else {
+            self.silent += 1;
+        }
+        if self.frames.len() > MAX_UTTERANCE_FRAMES + TRAILING_SILENCE_FRAMES {
+            self.overlong = true;
+            self.reset();
+            return None;
+        }
+        if self.silent < TRAILING_SILENCE_FRAMES {
+            return None;
+        }
+        let len = self.frames.len() - self.silent;
+        let speech = self.speech;
+        let utterance = self.frames.drain(..).take(len).collect();
+        self.reset();
+        (speech >= MIN_UTTERANCE_FRAMES).then_some(utterance)
+    }
+
+    fn reset(&mut self) {
+        self.frames.clear();
+        self.speech = 0;
+        self.silent = 0;
+    }
+}
+
+/// How far apart two utterances may be to count as the same phrase
+fn max_distance(sensitivity: f32) -> f32 {
+    0.15 + 0.35 * sensitivity
+}
+
+/// Average distance between two utterances, frame by frame along the alignment that
+/// suits them best (dynamic time warping), so saying it faster or slower still
+/// matches. Loudness counts from each one's loudest frame, so does speaking louder.
+fn distance(a: &[Frame], b: &[Frame]) -> f32 {
+    // Far apart in length, they can't be the same phrase
+    if a.is_empty() || b.is_empty() || a.len() > b.len() * 2 || b.len() > a.len() * 2 {
+        return f32::INFINITY;
+    }
+    let (a, b) = (features(a), features(b));
+    let mut previous = vec![f32::INFINITY; b.len() + 1];
+    let mut current = vec![f32::INFINITY; b.len() + 1];
+    previous[0] = 0.0;
+    for x in &a {
+        current[0] = f32::INFINITY;
+        for (j, y) in b.iter().enumerate() {
+            let cost = x.iter().zip(y).map(|(x, y)| (x - y).powi(2)).sum::<f32>().sqrt();
+            current[j + 1] = cost + previous[j + 1].min(current[j]).min(previous[j]);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()] / (a.len() + b.len()) as f32
+}
+
+/// Scaled so each feature moves by about as much between different sounds
+fn features(frames: &[Frame]) -> Vec<[f32; 3]> {
+    let peak = frames
+        .iter()
+        .map(|frame| frame.energy)
+        .fold(f32::NEG_INFINITY, f32::max);
+    frames
+        .iter()
+        .map(|frame| {
+            [
+                (frame.energy - peak) / 20.0,
+                frame.zcr * 4.0,
+                frame.tilt / 10.0,
+            ]
+        })
+        .collect()
+}
@@ -0,0 +1,312 @@
+use crate::audio_file::{self, FileTranscript};
+use crate::history::{EntryKind, NewEntry};
+use crate::notifications::{AppNotifications, Notification};
+use crate::settings::{SettingsState, TranscriptFormat};
+use crate::stats;
+use crate::transcribe_client::{QuotaExceeded, TranscribeClient, Unauthorized};
+use crate::{paths, privacy, recent_menu, transcribe_app_logger};
+use anyhow::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, async_runtime::spawn};
+use tokio::sync::mpsc;
+
+const LEDGER_FILE: &str = "watch_folder.json";
+/// A file counts as fully written once its size holds still this long, synced files
+/// arrive in pieces
+const SETTLE: Duration = Duration::from_secs(3);
+const POLL: Duration = Duration::from_secs(1);
+/// Waited before each retry, times the attempts so far
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Watches `watch_folder.folder` and transcribes audio files that appear in it, writing
+/// the transcript next to each one
+#[derive(Default)]
+pub struct WatchFolder {
+    /// Dropping the watcher also ends the task working through its files
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    /// A file is being transcribed right now
+    working: AtomicBool,
+}
+
+impl WatchFolder {
+    pub fn is_working(&self) -> bool {
+        self.working.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Done,
+    Failed,
+    Skipped,
+}
+
+/// What happened to a file. A file whose modification time changed since is new.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LedgerEntry {
+    mtime: u64,
+    status: Status,
+    attempts: u32,
+}
+
+/// Files seen in the watched folder, kept across restarts so nothing is transcribed
+/// twice
+#[derive(Default, Serialize, Deserialize)]
+struct Ledger {
+    files: BTreeMap<PathBuf, LedgerEntry>,
+}
+
+impl Ledger {
+    fn path(app_handle: &AppHandle) -> Result<PathBuf> {
+        paths::data_file(app_handle, LEDGER_FILE)
+    }
+
+    fn load(app_handle: &AppHandle) -> Self {
+        let Ok(path) = Self::path(app_handle) else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::error!("Ignoring unreadable {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self, app_handle: &AppHandle) {
+        let result = Self::path(app_handle).and_then(|path| {
+            std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("Failed to save the watch folder ledger: {e:#}");
+        }
+    }
+
+    /// The entry for the file as it is now, `None` if it was never seen or changed since
+    fn get(&self, path: &Path, mtime: u64) -> Option<&LedgerEntry> {
+        self.files.get(path).filter(|entry| entry.mtime == mtime)
+    }
+}
+
+/// A file waiting to be transcribed
+struct Pending {
+    size: u64,
+    ready_at: Instant,
+}
+
+/// (Re)starts watching the configured folder, stopping any previous watcher. Files
+/// already in the folder that were never transcribed are picked up too.
+pub fn apply(app_handle: &AppHandle) {
+    let state = app_handle.state::<WatchFolder>();
+    let mut watcher = state.watcher.lock().unwrap();
+    watcher.take();
+
+    let folder = app_handle.state::<SettingsState>().get().watch_folder.folder.clone();
+    if folder.is_empty() {
+        return;
+    }
+    match watch(app_handle, Path::new(&folder)) {
+        Ok(new) => {
+            log::info!("Watching {folder:?} for audio files");
+            *watcher = Some(new);
+        }
+        Err(e) => log::error!("Failed to watch {folder:?}: {e:#}"),
+    }
+}
+
+fn watch(app_handle: &AppHandle, folder: &Path) -> Result<RecommendedWatcher> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let tx_events = tx.clone();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<Event>| {
+            match event {
+                // Synced files are often written elsewhere and renamed in
+                Ok(event)
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_)
+                    ) =>
+                {
+                    for path in event.paths {
+                        if audio_file::is_audio(&path) {
+                            _ = tx_events.send(path);
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Watch folder error: {e}"),
+            }
+        })?;
+    watcher.watch(folder, RecursiveMode::NonRecursive)?;
+
+    for entry in std::fs::read_dir(folder)?.flatten() {
+        let path = entry.path();
+        if audio_file::is_audio(&path) {
+            _ = tx.send(path);
+        }
+    }
+    spawn(run(app_handle.clone(), rx));
+    Ok(watcher)
+}
+
+/// Works through files one at a time once they stop growing, until the watcher that
+/// feeds `rx` is dropped
+async fn run(app_handle: AppHandle, mut rx: mpsc::UnboundedReceiver<PathBuf>) {
+    let mut ledger = Ledger::load(&app_handle);
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+    let mut poll = tokio::time::interval(POLL);
+
+    loop {
+        tokio::select! {
+            path = rx.recv() => {
+                let Some(path) = path else { break };
+                // Retries keep their delay, new writes restart the wait below
+                pending.entry(path).or_insert(Pending {
+                    size: 0,
+                    ready_at: Instant::now() + SETTLE,
+                });
+            }
+            _ = poll.tick() => {
+                let now = Instant::now();
+                let mut ready = Vec::new();
+                pending.retain(|path, file| {
+                    let Ok(metadata) = std::fs::metadata(path) else {
+                        return false;
+                    };
+                    if metadata.len() != file.size {
+                        file.size = metadata.len();
+                        file.ready_at = file.ready_at.max(now + SETTLE);
+                    } else if now >= file.ready_at {
+                        ready.push(path.clone());
+                        return false;
+                    }
+                    true
+                });
+                for path in ready {
+                    if let Some(retry_in) = process(&app_handle, &mut ledger, &path).await {
+                        pending.insert(path, Pending {
+                            size: 0,
+                            ready_at: Instant::now() + retry_in,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    log::info!("Stopped watching the previous folder");
+}
+
+/// Transcribes `path` unless the ledger says it's been handled. Returns how long to
+/// wait before trying again after a failure.
+async fn process(
+    app_handle: &AppHandle,
+    ledger: &mut Ledger,
+    path: &Path,
+) -> Option<Duration> {
+    let mtime = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let attempts = match ledger.get(path, mtime) {
+        Some(entry) if entry.status != Status::Failed => return None,
+        Some(entry) => entry.attempts,
+        None => 0,
+    };
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    log::info!("Transcribing watched file {name:?}");
+    let state = app_handle.state::<WatchFolder>();
+    state.working.store(true, Ordering::Relaxed);
+    let result = transcribe(app_handle, path).await;
+    state.working.store(false, Ordering::Relaxed);
+    let attempts = attempts + 1;
+    let max_attempts =
+        app_handle.state::<SettingsState>().get().watch_folder.max_attempts;
+
+    let (status, retry_in) = match result {
+        Ok(text) => {
+            let preview = match privacy::enabled(app_handle) {
+                true => transcribe_app_logger::redact(&text),
+                false => recent_menu::label(&text),
+            };
+            AppNotifications::new(app_handle)
+                .notify(Notification::WatchedFileTranscribed { name, preview });
+            (Status::Done, None)
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to transcribe watched file {name:?} (attempt {attempts}): {e:#}"
+            );
+            if e.is::<QuotaExceeded>() || e.is::<Unauthorized>() {
+                crate::notify_api_error(app_handle, &e);
+            }
+            if attempts >= max_attempts {
+                AppNotifications::new(app_handle).notify(Notification::FileFailed {
+                    name,
+                    reason: format!("Skipped after {attempts} attempts: {e}"),
+                });
+                (Status::Skipped, None)
+            } else {
+                (Status::Failed, Some(RETRY_DELAY * attempts))
+            }
+        }
+    };
+    ledger.files.insert(
+        path.to_path_buf(),
+        LedgerEntry {
+            mtime,
+            status,
+            attempts,
+        },
+    );
+    ledger.save(app_handle);
+    retry_in
+}
+
+/// Writes the transcript next to the audio file and records it in history
+async fn transcribe(app_handle: &AppHandle, path: &Path) -> Result<String> {
+    let FileTranscript {
+        text,
+        language,
+        audio_duration,
+    } = audio_file::transcribe_audio(app_handle, path).await?;
+
+    let format = app_handle.state::<SettingsState>().get().watch_folder.output;
+    let (extension, contents) = match format {
+        TranscriptFormat::Txt => ("txt", format!("{text}\n")),
+        TranscriptFormat::Md => {
+            let title = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ("md", format!("# {title}\n\n{text}\n"))
+        }
+    };
+    std::fs::write(path.with_extension(extension), contents)?;
+
+    stats::record(app_handle, &text);
+    crate::save_to_history(
+        app_handle,
+        NewEntry {
+            audio_duration_ms: Some(audio_duration.as_millis() as u64),
+            provider: Some(app_handle.state::<TranscribeClient>().provider()),
+            language,
+            ..NewEntry::new(EntryKind::Transcription, text.clone())
+        },
+    );
+    Ok(text)
+}
@@ -2,6 +2,17 @@ import { useEffect, useState } from "react";
 import MessageDisplay from "./components/MessageDisplay";
 import { getShortcuts } from "./utils/shortcuts";
 import { ShortcutInput } from "./components/ShortcutInput";
+import { MicTest } from "./components/MicTest";
+import { HookTest } from "./components/HookTest";
+import { ApiKeyInput } from "./components/ApiKeyInput";
+import { BackendCapabilities } from "./components/BackendCapabilities";
+import { AppOverrides } from "./components/AppOverrides";
+import { PinnedEntries } from "./components/PinnedEntries";
+import { HistoryTags } from "./components/HistoryTags";
+import { HistoryEncryption } from "./components/HistoryEncryption";
+import { PolishDiff } from "./components/PolishDiff";
+import { Statistics } from "./components/Statistics";
+import { SettingsTransfer } from "./components/SettingsTransfer";
 import "./App.css";
 
 function App() {
@@ -61,6 +72,17 @@ function App() {
           });
         }}
       />
+      <ApiKeyInput />
+      <BackendCapabilities />
+      <AppOverrides />
+      <MicTest />
+      <HookTest />
+      <PinnedEntries />
+      <HistoryTags />
+      <HistoryEncryption />
+      <PolishDiff />
+      <Statistics />
+      <SettingsTransfer />
       <MessageDisplay messages={messages} />
     </div>
   );
@@ -0,0 +1,60 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+export function ApiKeyInput() {
+  const [apiKey, setApiKey] = useState("");
+  const [status, setStatus] = useState("");
+  const [isRequired, setIsRequired] = useState(false);
+  const [isSaving, setIsSaving] = useState(false);
+
+  useEffect(() => {
+    const unlisten = listen("api-key-required", () => {
+      setIsRequired(true);
+      setStatus("Your API key was rejected. Please enter a new one.");
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const save = () => {
+    setIsSaving(true);
+    invoke("set_api_key", { apiKey })
+      .then(() => {
+        setApiKey("");
+        setIsRequired(false);
+        setStatus("API key saved");
+      })
+      .catch((err) => setStatus(String(err)))
+      .finally(() => setIsSaving(false));
+  };
+
+  const clear = () => {
+    invoke("clear_api_key")
+      .then(() => setStatus("API key removed"))
+      .catch((err) => setStatus(String(err)));
+  };
+
+  return (
+    <div
+      className="pt-5 space-y-3"
+      style={isRequired ? { outline: "2px solid #dc2626" } : undefined}
+    >
+      <h2 className="text-lg font-bold">API key</h2>
+      <div className="flex items-center gap-x-3">
+        <input
+          type="password"
+          placeholder="Paste your API key"
+          value={apiKey}
+          onChange={(e) => setApiKey(e.target.value)}
+        />
+        <button disabled={apiKey === "" || isSaving} onClick={save}>
+          {isSaving ? "Checking…" : "Save"}
+        </button>
+        <button onClick={clear}>Remove</button>
+      </div>
+      {status && <p className="text-sm">{status}</p>}
+    </div>
+  );
+}
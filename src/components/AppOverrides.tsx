@@ -0,0 +1,105 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+type OutputMode = "clipboard" | "paste" | "type" | "quick_note";
+type PolishPasteBack = "paste" | "undo" | "below";
+
+interface AppOverride {
+  output_mode?: OutputMode | null;
+  trailing_separator?: string | null;
+  polish_style?: string | null;
+  polish_paste_back?: PolishPasteBack | null;
+  polish_undo_count?: number | null;
+}
+
+export function AppOverrides() {
+  const [overrides, setOverrides] = useState<Record<string, AppOverride>>({});
+  const [appId, setAppId] = useState("");
+  const [outputMode, setOutputMode] = useState<OutputMode | "">("");
+  const [separator, setSeparator] = useState("");
+  const [pasteBack, setPasteBack] = useState<PolishPasteBack | "">("");
+  const [error, setError] = useState("");
+
+  const reload = () => {
+    invoke<Record<string, AppOverride>>("get_app_overrides").then(setOverrides);
+  };
+
+  useEffect(reload, []);
+
+  const add = () => {
+    const appOverride: AppOverride = {
+      output_mode: outputMode === "" ? null : outputMode,
+      trailing_separator: separator === "" ? null : separator,
+      polish_paste_back: pasteBack === "" ? null : pasteBack,
+    };
+    invoke("set_app_override", { appId, appOverride })
+      .then(() => {
+        setAppId("");
+        setError("");
+        reload();
+      })
+      .catch((err) => setError(String(err)));
+  };
+
+  const remove = (id: string) => {
+    invoke("remove_app_override", { appId: id })
+      .then(reload)
+      .catch((err) => setError(String(err)));
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Per-app behavior</h2>
+      <ul className="list-disc list-inside">
+        {Object.entries(overrides).map(([id, o]) => (
+          <li key={id} className="flex items-center gap-x-2">
+            <span>
+              {id}: {o.output_mode ?? "default"}
+              {o.trailing_separator != null &&
+                ` + ${JSON.stringify(o.trailing_separator)}`}
+              {o.polish_paste_back != null && `, polish: ${o.polish_paste_back}`}
+            </span>
+            <button onClick={() => remove(id)}>Remove</button>
+          </li>
+        ))}
+      </ul>
+      <div className="flex items-center gap-x-3">
+        <input
+          type="text"
+          placeholder="Bundle id, e.g. com.apple.Terminal"
+          value={appId}
+          onChange={(e) => setAppId(e.target.value)}
+        />
+        <select
+          value={outputMode}
+          onChange={(e) => setOutputMode(e.target.value as OutputMode | "")}
+        >
+          <option value="">Default output</option>
+          <option value="clipboard">Clipboard only</option>
+          <option value="paste">Paste</option>
+          <option value="type">Type</option>
+          <option value="quick_note">Quick note</option>
+        </select>
+        <select
+          value={pasteBack}
+          onChange={(e) => setPasteBack(e.target.value as PolishPasteBack | "")}
+        >
+          <option value="">Default polish paste</option>
+          <option value="paste">Paste at cursor</option>
+          <option value="undo">Undo, then paste</option>
+          <option value="below">Paste below</option>
+        </select>
+        <input
+          type="text"
+          placeholder="Trailing separator"
+          value={separator}
+          onChange={(e) => setSeparator(e.target.value)}
+        />
+        <button disabled={appId === ""} onClick={add}>
+          Add
+        </button>
+      </div>
+      {error && <p className="text-sm text-red-600">{error}</p>}
+    </div>
+  );
+}
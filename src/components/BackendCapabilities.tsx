@@ -0,0 +1,51 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+interface Capabilities {
+  streaming: boolean;
+  diarization: boolean;
+  language_hint: boolean;
+  prompt: boolean;
+  timestamps: boolean;
+}
+
+const LABELS: [keyof Capabilities, string][] = [
+  ["prompt", "Custom prompts"],
+  ["language_hint", "Polish in the detected language"],
+  ["timestamps", "Timestamps"],
+  ["streaming", "Streaming transcripts"],
+  ["diarization", "Speaker labels"],
+];
+
+export function BackendCapabilities() {
+  const [capabilities, setCapabilities] = useState<Capabilities | null>(null);
+
+  useEffect(() => {
+    invoke<Capabilities>("get_capabilities").then(setCapabilities);
+  }, []);
+
+  if (!capabilities) {
+    return null;
+  }
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Backend</h2>
+      <p className="text-sm">
+        What the backend set by api.upload_format supports. Greyed out options
+        have no effect with it.
+      </p>
+      <ul className="list-disc list-inside">
+        {LABELS.map(([key, label]) => (
+          <li
+            key={key}
+            className={capabilities[key] ? "" : "opacity-40"}
+            title={capabilities[key] ? undefined : "Not supported by this backend"}
+          >
+            {label}
+          </li>
+        ))}
+      </ul>
+    </div>
+  );
+}
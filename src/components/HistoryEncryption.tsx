@@ -0,0 +1,93 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+type EncryptionMode = "off" | "passphrase" | "keychain";
+
+interface EncryptionStatus {
+  mode: EncryptionMode;
+  locked: boolean;
+}
+
+export function HistoryEncryption() {
+  const [status, setStatus] = useState<EncryptionStatus | null>(null);
+  const [mode, setMode] = useState<EncryptionMode>("off");
+  const [passphrase, setPassphrase] = useState("");
+  const [message, setMessage] = useState("");
+
+  const load = () => {
+    invoke<EncryptionStatus>("get_history_encryption")
+      .then((status) => {
+        setStatus(status);
+        setMode(status.mode);
+      })
+      .catch((err) => setMessage(String(err)));
+  };
+
+  useEffect(load, []);
+
+  const unlock = () => {
+    invoke("unlock_history", { passphrase })
+      .then(() => {
+        setPassphrase("");
+        setMessage("Unlocked");
+        load();
+      })
+      .catch((err) => setMessage(String(err)));
+  };
+
+  const apply = () => {
+    setMessage("Migrating history...");
+    invoke("set_history_encryption", {
+      mode,
+      passphrase: mode === "passphrase" ? passphrase : null,
+    })
+      .then(() => {
+        setPassphrase("");
+        setMessage("Saved");
+        load();
+      })
+      .catch((err) => setMessage(String(err)));
+  };
+
+  if (!status) {
+    return null;
+  }
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">History encryption</h2>
+      {status.locked ? (
+        <div className="flex items-center gap-x-2">
+          <input
+            type="password"
+            placeholder="Passphrase"
+            value={passphrase}
+            onChange={(e) => setPassphrase(e.target.value)}
+          />
+          <button onClick={unlock}>Unlock</button>
+        </div>
+      ) : (
+        <div className="flex items-center gap-x-2">
+          <select
+            value={mode}
+            onChange={(e) => setMode(e.target.value as EncryptionMode)}
+          >
+            <option value="off">Off</option>
+            <option value="passphrase">Passphrase</option>
+            <option value="keychain">Keychain</option>
+          </select>
+          {mode === "passphrase" && (
+            <input
+              type="password"
+              placeholder="New passphrase"
+              value={passphrase}
+              onChange={(e) => setPassphrase(e.target.value)}
+            />
+          )}
+          <button onClick={apply}>Apply</button>
+        </div>
+      )}
+      {message && <p className="text-sm">{message}</p>}
+    </div>
+  );
+}
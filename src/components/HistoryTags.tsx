@@ -0,0 +1,77 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+interface HistoryEntry {
+  id: number;
+  text: string;
+  tags: string[];
+  partial: boolean;
+}
+
+export function HistoryTags() {
+  const [tags, setTags] = useState<[string, number][]>([]);
+  const [filter, setFilter] = useState("");
+  const [entries, setEntries] = useState<HistoryEntry[]>([]);
+  const [edits, setEdits] = useState<Record<number, string>>({});
+  const [status, setStatus] = useState("");
+
+  const load = () => {
+    invoke<[string, number][]>("get_history_tags")
+      .then(setTags)
+      .catch((err) => setStatus(String(err)));
+    invoke<HistoryEntry[]>("search_history", {
+      query: "",
+      tag: filter || null,
+      limit: 20,
+    })
+      .then(setEntries)
+      .catch((err) => setStatus(String(err)));
+  };
+
+  useEffect(load, [filter]);
+
+  const save = (entry: HistoryEntry) => {
+    const value = edits[entry.id] ?? entry.tags.join(", ");
+    invoke("set_history_tags", {
+      entryId: entry.id,
+      tags: value.split(","),
+    })
+      .then(() => {
+        const { [entry.id]: _, ...rest } = edits;
+        setEdits(rest);
+        load();
+      })
+      .catch((err) => setStatus(String(err)));
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Tags</h2>
+      <select value={filter} onChange={(e) => setFilter(e.target.value)}>
+        <option value="">All entries</option>
+        {tags.map(([tag, count]) => (
+          <option key={tag} value={tag}>
+            {tag} ({count})
+          </option>
+        ))}
+      </select>
+      <ul className="space-y-1">
+        {entries.map((entry) => (
+          <li key={entry.id} className="flex items-center gap-x-2">
+            {entry.partial && <span className="text-sm italic">partial</span>}
+            <span className="truncate max-w-[300px]">{entry.text}</span>
+            <input
+              placeholder="meeting, idea"
+              value={edits[entry.id] ?? entry.tags.join(", ")}
+              onChange={(e) =>
+                setEdits({ ...edits, [entry.id]: e.target.value })
+              }
+            />
+            <button onClick={() => save(entry)}>Save</button>
+          </li>
+        ))}
+      </ul>
+      {status && <p className="text-sm">{status}</p>}
+    </div>
+  );
+}
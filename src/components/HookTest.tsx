@@ -0,0 +1,33 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+export function HookTest() {
+  const [isSending, setIsSending] = useState(false);
+  const [result, setResult] = useState("");
+  const [error, setError] = useState("");
+
+  const sendTest = () => {
+    setIsSending(true);
+    setResult("");
+    setError("");
+    invoke("test_hook")
+      .then(() => setResult("The hook received the sample payload."))
+      .catch((err) => setError(String(err)))
+      .finally(() => setIsSending(false));
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Hook</h2>
+      <p className="text-sm">
+        Sends a sample transcription through the hook set up under [hook] in the
+        settings file.
+      </p>
+      <button disabled={isSending} onClick={sendTest}>
+        {isSending ? "Sending…" : "Test hook"}
+      </button>
+      {result && <p className="text-sm">{result}</p>}
+      {error && <p className="text-sm text-red-600">{error}</p>}
+    </div>
+  );
+}
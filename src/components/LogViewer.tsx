@@ -0,0 +1,116 @@
+import { useEffect, useMemo, useRef, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+interface LogChunk {
+  lines: string[];
+  offset: number;
+}
+
+const LEVELS = ["ERROR", "WARN", "INFO", "DEBUG", "TRACE"];
+const MAX_LINES = 5000;
+// e.g. [2025-01-01][10:00:00][whistle][INFO] message
+const LINE = /^\[[^\]]*\]\[[^\]]*\]\[([^\]]*)\]\[([A-Z]+)\]/;
+
+function parse(line: string) {
+  // `logging.format = "json"` writes one object per line
+  if (line.startsWith("{")) {
+    try {
+      const entry = JSON.parse(line);
+      return { module: String(entry.target ?? ""), level: String(entry.level ?? "") };
+    } catch {
+      // Fall through to the text format
+    }
+  }
+  const match = LINE.exec(line);
+  return { module: match?.[1] ?? "", level: match?.[2] ?? "" };
+}
+
+export function LogViewer() {
+  const [lines, setLines] = useState<string[]>([]);
+  const [level, setLevel] = useState("TRACE");
+  const [module, setModule] = useState("");
+  const [search, setSearch] = useState("");
+  const [follow, setFollow] = useState(true);
+  const [status, setStatus] = useState("");
+  const bottom = useRef<HTMLDivElement>(null);
+
+  useEffect(() => {
+    invoke<LogChunk>("read_log_tail", { maxLines: 1000 })
+      .then((chunk) => setLines(chunk.lines))
+      .catch((err) => setStatus(String(err)));
+    const unlisten = listen<LogChunk>("log-lines", (event) => {
+      setLines((prev) => [...prev, ...event.payload.lines].slice(-MAX_LINES));
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const modules = useMemo(
+    () => [...new Set(lines.map((line) => parse(line).module))].sort(),
+    [lines]
+  );
+
+  const visible = lines.filter((line) => {
+    const parsed = parse(line);
+    return (
+      LEVELS.indexOf(parsed.level) <= LEVELS.indexOf(level) &&
+      (!module || parsed.module === module) &&
+      (!search || line.toLowerCase().includes(search.toLowerCase()))
+    );
+  });
+
+  useEffect(() => {
+    if (follow) {
+      bottom.current?.scrollIntoView();
+    }
+  }, [visible.length, follow]);
+
+  const copyReport = () => {
+    invoke("copy_log_report")
+      .then(() => setStatus("Copied the last 200 lines"))
+      .catch((err) => setStatus(String(err)));
+  };
+
+  return (
+    <div className="p-3 space-y-2 h-screen flex flex-col">
+      <div className="flex items-center gap-x-2">
+        <select value={level} onChange={(e) => setLevel(e.target.value)}>
+          {LEVELS.map((l) => (
+            <option key={l} value={l}>
+              {l} and above
+            </option>
+          ))}
+        </select>
+        <select value={module} onChange={(e) => setModule(e.target.value)}>
+          <option value="">All modules</option>
+          {modules.map((m) => (
+            <option key={m} value={m}>
+              {m}
+            </option>
+          ))}
+        </select>
+        <input
+          placeholder="Search"
+          value={search}
+          onChange={(e) => setSearch(e.target.value)}
+        />
+        <label className="text-sm">
+          <input
+            type="checkbox"
+            checked={follow}
+            onChange={(e) => setFollow(e.target.checked)}
+          />{" "}
+          Follow
+        </label>
+        <button onClick={copyReport}>Copy last 200 lines</button>
+        {status && <span className="text-sm">{status}</span>}
+      </div>
+      <pre className="flex-1 overflow-auto text-xs select-text">
+        {visible.join("\n")}
+        <div ref={bottom} />
+      </pre>
+    </div>
+  );
+}
@@ -0,0 +1,85 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+interface MicTestReport {
+  peak: number;
+  rms: number;
+  duration_ms: number;
+}
+
+export function MicTest() {
+  const [level, setLevel] = useState(0);
+  const [isTesting, setIsTesting] = useState(false);
+  const [playback, setPlayback] = useState(true);
+  const [report, setReport] = useState<MicTestReport | null>(null);
+  const [error, setError] = useState("");
+  const [resetMessage, setResetMessage] = useState("");
+
+  useEffect(() => {
+    const unlisten = listen<number>("mic-level", (event) => {
+      setLevel(event.payload);
+    });
+    const resetAudio = () => {
+    setResetMessage("");
+    setError("");
+    invoke<string>("reset_audio")
+      .then((device) => setResetMessage(`Audio reset, recording from ${device}`))
+      .catch((err) => setError(String(err)));
+  };
+
+  return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const runTest = () => {
+    setIsTesting(true);
+    setReport(null);
+    setError("");
+    invoke<MicTestReport>("test_microphone", { durationSecs: 3, playback })
+      .then(setReport)
+      .catch((err) => setError(String(err)))
+      .finally(() => setIsTesting(false));
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Mic test</h2>
+      <p className="text-sm">
+        Records a few seconds from your microphone without transcribing it.
+      </p>
+      <div className="flex items-center gap-x-3">
+        <button disabled={isTesting} onClick={runTest}>
+          {isTesting ? "Listening…" : "Test microphone"}
+        </button>
+        <label className="flex items-center gap-x-1 text-sm">
+          <input
+            type="checkbox"
+            checked={playback}
+            onChange={(e) => setPlayback(e.target.checked)}
+          />
+          Play it back
+        </label>
+        <button disabled={isTesting} onClick={resetAudio}>
+          Reset audio input
+        </button>
+      </div>
+      <div className="h-2 w-full rounded bg-gray-200">
+        <div
+          className="h-2 rounded bg-green-500"
+          style={{ width: `${Math.min(level * 300, 100)}%` }}
+        />
+      </div>
+      {report && (
+        <p className="text-sm">
+          Peak {Math.round(report.peak * 100)}%, average{" "}
+          {Math.round(report.rms * 100)}%
+          {report.peak < 0.02 && " — that's very quiet, check your input device"}
+        </p>
+      )}
+      {resetMessage && <p className="text-sm">{resetMessage}</p>}
+      {error && <p className="text-sm text-red-600">{error}</p>}
+    </div>
+  );
+}
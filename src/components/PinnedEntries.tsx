@@ -0,0 +1,86 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+interface HistoryEntry {
+  id: number;
+  text: string;
+  pinned: boolean;
+  pin_title: string | null;
+  pin_auto_paste: boolean;
+}
+
+export function PinnedEntries() {
+  const [recent, setRecent] = useState<HistoryEntry[]>([]);
+  const [pinned, setPinned] = useState<HistoryEntry[]>([]);
+  const [titles, setTitles] = useState<Record<number, string>>({});
+  const [autoPaste, setAutoPaste] = useState<Record<number, boolean>>({});
+  const [status, setStatus] = useState("");
+
+  const load = () => {
+    invoke<HistoryEntry[]>("get_recent_transcripts", { limit: 10 })
+      .then(setRecent)
+      .catch((err) => setStatus(String(err)));
+    invoke<HistoryEntry[]>("get_pinned_history")
+      .then(setPinned)
+      .catch((err) => setStatus(String(err)));
+  };
+
+  useEffect(load, []);
+
+  const pin = (entry: HistoryEntry) => {
+    invoke("pin_history_entry", {
+      entryId: entry.id,
+      title: titles[entry.id] ?? entry.pin_title,
+      autoPaste: autoPaste[entry.id] ?? entry.pin_auto_paste,
+    })
+      .then(load)
+      .catch((err) => setStatus(String(err)));
+  };
+
+  const unpin = (entry: HistoryEntry) => {
+    invoke("unpin_history_entry", { entryId: entry.id })
+      .then(load)
+      .catch((err) => setStatus(String(err)));
+  };
+
+  const row = (entry: HistoryEntry) => (
+    <li key={entry.id} className="flex items-center gap-x-2">
+      <span className="truncate max-w-[300px]">{entry.text}</span>
+      <input
+        placeholder="Title"
+        value={titles[entry.id] ?? entry.pin_title ?? ""}
+        onChange={(e) => setTitles({ ...titles, [entry.id]: e.target.value })}
+      />
+      <label className="text-sm">
+        <input
+          type="checkbox"
+          checked={autoPaste[entry.id] ?? entry.pin_auto_paste}
+          onChange={(e) =>
+            setAutoPaste({ ...autoPaste, [entry.id]: e.target.checked })
+          }
+        />{" "}
+        Paste
+      </label>
+      <button onClick={() => pin(entry)}>
+        {entry.pinned ? "Save" : "Pin"}
+      </button>
+      {entry.pinned && <button onClick={() => unpin(entry)}>Unpin</button>}
+    </li>
+  );
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Pinned</h2>
+      {pinned.length === 0 ? (
+        <p className="text-sm">Nothing pinned yet</p>
+      ) : (
+        <ul className="space-y-1">{pinned.map(row)}</ul>
+      )}
+      <h3 className="font-bold">Recent</h3>
+      <ul className="space-y-1">
+        {recent.filter((entry) => !entry.pinned).map(row)}
+      </ul>
+      {status && <p className="text-sm">{status}</p>}
+    </div>
+  );
+}
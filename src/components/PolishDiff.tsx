@@ -0,0 +1,79 @@
+import { CSSProperties, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+interface HistoryEntry {
+  id: number;
+  kind: string;
+  source_text: string | null;
+}
+
+interface DiffOp {
+  kind: "equal" | "delete" | "insert";
+  text: string;
+}
+
+interface PolishDiff {
+  ops: DiffOp[];
+  annotated: string;
+}
+
+const styles: Record<DiffOp["kind"], CSSProperties> = {
+  equal: {},
+  delete: { color: "#dc2626", textDecoration: "line-through" },
+  insert: { color: "#16a34a" },
+};
+
+export function PolishDiff() {
+  const [entryId, setEntryId] = useState<number | null>(null);
+  const [diff, setDiff] = useState<PolishDiff | null>(null);
+  const [status, setStatus] = useState("");
+
+  const load = async () => {
+    setStatus("");
+    try {
+      const entries = await invoke<HistoryEntry[]>("get_recent_transcripts", {
+        limit: 20,
+      });
+      const polish = entries.find(
+        (entry) => entry.kind === "polish" && entry.source_text !== null,
+      );
+      if (!polish) {
+        setDiff(null);
+        setStatus("No recent polish to compare");
+        return;
+      }
+      setEntryId(polish.id);
+      setDiff(await invoke<PolishDiff>("get_polish_diff", { entryId: polish.id }));
+    } catch (err) {
+      setStatus(String(err));
+    }
+  };
+
+  const copy = () => {
+    invoke("copy_polish_diff", { entryId })
+      .then(() => setStatus("Annotated diff copied"))
+      .catch((err) => setStatus(String(err)));
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Last polish</h2>
+      <div className="flex items-center gap-x-3">
+        <button onClick={load}>Show changes</button>
+        <button disabled={entryId === null} onClick={copy}>
+          Copy annotated
+        </button>
+      </div>
+      {diff && (
+        <p>
+          {diff.ops.map((op, i) => (
+            <span key={i} style={styles[op.kind]}>
+              {op.text}{" "}
+            </span>
+          ))}
+        </p>
+      )}
+      {status && <p className="text-sm">{status}</p>}
+    </div>
+  );
+}
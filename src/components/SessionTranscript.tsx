@@ -0,0 +1,31 @@
+import { useEffect, useRef, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+// Shown in the always-on-top window while a dictation session runs
+export function SessionTranscript() {
+  const [text, setText] = useState("");
+  const bottom = useRef<HTMLDivElement>(null);
+
+  useEffect(() => {
+    invoke<string>("get_session_transcript").then(setText);
+    const unlisten = listen<string>("session-text", (event) => setText(event.payload));
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    bottom.current?.scrollIntoView();
+  }, [text]);
+
+  return (
+    <div className="p-3 space-y-2 h-screen flex flex-col">
+      <div className="flex-1 overflow-auto whitespace-pre-wrap select-text">
+        {text || <span className="text-sm text-gray-500">Listening…</span>}
+        <div ref={bottom} />
+      </div>
+      <button onClick={() => invoke("clear_session_transcript")}>Clear</button>
+    </div>
+  );
+}
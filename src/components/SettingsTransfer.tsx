@@ -0,0 +1,87 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { open, save } from "@tauri-apps/plugin-dialog";
+
+export function SettingsTransfer() {
+  const [includeKey, setIncludeKey] = useState(false);
+  const [passphrase, setPassphrase] = useState("");
+  const [status, setStatus] = useState("");
+
+  const exportSettings = async () => {
+    const path = await save({
+      defaultPath: "whistle-settings.json",
+      filters: [{ name: "JSON", extensions: ["json"] }],
+    });
+    if (!path) {
+      return;
+    }
+    invoke("export_settings", {
+      path,
+      passphrase: includeKey ? passphrase : null,
+    })
+      .then(() =>
+        setStatus(includeKey ? "Exported with your API key" : "Exported"),
+      )
+      .catch((err) => setStatus(String(err)));
+  };
+
+  const importSettings = async () => {
+    const path = await open({
+      filters: [{ name: "JSON", extensions: ["json"] }],
+    });
+    if (!path) {
+      return;
+    }
+    try {
+      const changes = await invoke<string[]>("preview_settings_import", {
+        path,
+      });
+      if (changes.length === 0) {
+        setStatus("Nothing to import, the file matches your current settings");
+        return;
+      }
+      if (!confirm(`This changes:\n\n${changes.join("\n")}`)) {
+        return;
+      }
+      const changed = await invoke<string[]>("import_settings", {
+        path,
+        passphrase: passphrase || null,
+      });
+      setStatus(`Imported ${changed.length} change(s)`);
+    } catch (err) {
+      setStatus(String(err));
+    }
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Import / export settings</h2>
+      <div className="flex items-center gap-x-3">
+        <input
+          type="password"
+          placeholder="Passphrase for the API key"
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+        />
+        <label className="text-sm">
+          <input
+            type="checkbox"
+            checked={includeKey}
+            onChange={(e) => setIncludeKey(e.target.checked)}
+          />{" "}
+          Include API key
+        </label>
+      </div>
+      <div className="flex items-center gap-x-3">
+        <button
+          disabled={includeKey && passphrase === ""}
+          onClick={exportSettings}
+        >
+          Export…
+        </button>
+        <button onClick={importSettings}>Import…</button>
+      </div>
+      {status && <p className="text-sm">{status}</p>}
+    </div>
+  );
+}
@@ -0,0 +1,90 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+interface Stats {
+  words_today: number;
+  total_words: number;
+  streak_days: number;
+}
+
+interface DiskUsage {
+  used_bytes: number;
+  budget_bytes: number;
+}
+
+interface TranscriptionTiming {
+  bytes: number;
+  encode_ms: number;
+  request_ms: number;
+  upload_ms: number | null;
+  server_ms: number | null;
+  download_ms: number;
+}
+
+const MB = 1024 * 1024;
+
+export function Statistics() {
+  const [stats, setStats] = useState<Stats | null>(null);
+  const [disk, setDisk] = useState<DiskUsage | null>(null);
+  const [timing, setTiming] = useState<TranscriptionTiming | null>(null);
+  const [status, setStatus] = useState("");
+
+  const load = () => {
+    invoke<Stats>("get_stats")
+      .then(setStats)
+      .catch((err) => setStatus(String(err)));
+    invoke<DiskUsage>("get_disk_usage")
+      .then(setDisk)
+      .catch((err) => setStatus(String(err)));
+    invoke<TranscriptionTiming | null>("get_transcription_timing").then(setTiming);
+  };
+
+  useEffect(load, []);
+
+  const reset = () => {
+    if (!confirm("Reset your word counts and streak?")) {
+      return;
+    }
+    invoke("reset_stats")
+      .then(() => {
+        setStatus("Statistics reset");
+        load();
+      })
+      .catch((err) => setStatus(String(err)));
+  };
+
+  return (
+    <div className="pt-5 space-y-3">
+      <h2 className="text-lg font-bold">Statistics</h2>
+      {stats && (
+        <ul className="list-disc list-inside">
+          <li>Words today: {stats.words_today}</li>
+          <li>Streak: {stats.streak_days} days</li>
+          <li>Words in total: {stats.total_words}</li>
+          {disk && (
+            <li>
+              Recordings on disk: {(disk.used_bytes / MB).toFixed(1)} MB
+              {disk.budget_bytes > 0 &&
+                ` of ${(disk.budget_bytes / MB).toFixed(0)} MB`}
+            </li>
+          )}
+          {timing && (
+            <li>
+              Last transcription ({(timing.bytes / MB).toFixed(1)} MB): encode{" "}
+              {timing.encode_ms} ms,{" "}
+              {timing.server_ms != null
+                ? `upload ${timing.upload_ms} ms, server ${timing.server_ms} ms`
+                : `upload and server ${timing.request_ms} ms`}
+              , download {timing.download_ms} ms
+            </li>
+          )}
+        </ul>
+      )}
+      <div className="flex items-center gap-x-3">
+        <button onClick={load}>Refresh</button>
+        <button onClick={reset}>Reset</button>
+      </div>
+      {status && <p className="text-sm">{status}</p>}
+    </div>
+  );
+}
@@ -0,0 +1,66 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+interface RunningApp {
+  id: string;
+  name: string;
+}
+
+// Shown after a recording with `behavior.pick_target` on, asking where the text goes
+export function TargetPicker() {
+  const [apps, setApps] = useState<RunningApp[]>([]);
+  const [selected, setSelected] = useState(0);
+
+  useEffect(() => {
+    invoke<RunningApp[]>("list_targets").then(setApps);
+    const unlisten = listen<RunningApp[]>("target-apps", (event) => {
+      setApps(event.payload);
+      setSelected(0);
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const choose = (id: string | null) => invoke("choose_target", { id });
+
+  useEffect(() => {
+    const onKey = (event: KeyboardEvent) => {
+      if (event.key === "ArrowDown") {
+        setSelected((n) => Math.min(n + 1, apps.length - 1));
+      } else if (event.key === "ArrowUp") {
+        setSelected((n) => Math.max(n - 1, 0));
+      } else if (event.key === "Enter" && apps[selected]) {
+        choose(apps[selected].id);
+      } else if (event.key === "Escape") {
+        choose(null);
+      } else {
+        return;
+      }
+      event.preventDefault();
+    };
+    window.addEventListener("keydown", onKey);
+    return () => window.removeEventListener("keydown", onKey);
+  }, [apps, selected]);
+
+  return (
+    <div className="p-3 space-y-2 h-screen flex flex-col">
+      <ul className="flex-1 overflow-auto">
+        {apps.map((app, n) => (
+          <li
+            key={app.id}
+            className={`px-2 py-1 rounded cursor-pointer ${
+              n === selected ? "bg-blue-500 text-white" : ""
+            }`}
+            onMouseEnter={() => setSelected(n)}
+            onClick={() => choose(app.id)}
+          >
+            {app.name}
+          </li>
+        ))}
+      </ul>
+      <button onClick={() => choose(null)}>Copy to clipboard only</button>
+    </div>
+  );
+}
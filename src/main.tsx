@@ -1,9 +1,23 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import { LogViewer } from "./components/LogViewer";
+import { SessionTranscript } from "./components/SessionTranscript";
+import { TargetPicker } from "./components/TargetPicker";
+
+// Extra windows load the same page with `?view=` picking what they show
+const view = new URLSearchParams(window.location.search).get("view");
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
-    <App />
+    {view === "logs" ? (
+      <LogViewer />
+    ) : view === "session" ? (
+      <SessionTranscript />
+    ) : view === "target" ? (
+      <TargetPicker />
+    ) : (
+      <App />
+    )}
   </React.StrictMode>
 );
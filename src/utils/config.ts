@@ -0,0 +1,16 @@
+import { invoke } from "@tauri-apps/api/core";
+
+/** The settings file as JSON, sections keyed like `settings.toml` */
+export type Config = Record<string, unknown>;
+
+export async function getConfig(): Promise<Config> {
+  return await invoke<Config>("get_settings");
+}
+
+/**
+ * Merges `partial` into the current settings and saves them. Rejects with a
+ * message naming the offending key in backticks, e.g. `hotkeys.transcribe`.
+ */
+export async function setConfig(partial: Config): Promise<Config> {
+  return await invoke<Config>("set_settings", { partial });
+}
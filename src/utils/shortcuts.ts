@@ -1,9 +1,5 @@
-import { readTextFile, BaseDirectory } from "@tauri-apps/plugin-fs";
+import { invoke } from "@tauri-apps/api/core";
 
 export async function getShortcuts(): Promise<Record<string, string>> {
-  let file = await readTextFile(".config/whistle/shortcuts.json", {
-    baseDir: BaseDirectory.Home,
-  });
-  let json = JSON.parse(file);
-  return json;
+  return await invoke<Record<string, string>>("get_shortcuts");
 }